@@ -0,0 +1,266 @@
+//! A C interface for embedding swifer in runtimes not written in Rust.
+//!
+//! Objects are blocks of raw bytes allocated with [swifer_alloc], in a heap managed by a
+//! [MarkAndSweepMem]. Instead of implementing [GcCandidate], each object is given a
+//! [SwiferTraceFn] when it is allocated, which calls a visitor with the address of every field
+//! holding a pointer to another object; the collector reads those fields while marking, and
+//! updates them when objects are moved. An optional [SwiferFinalizeFn] is called when an object
+//! is dropped.
+//!
+//! Pointers given to and from C point to the start of an object's bytes, which are aligned to
+//! 16 bytes. Fields holding pointers to objects must contain either null or such a pointer.
+//! Collections may move every object, so objects must be reachable from a root registered with
+//! [swifer_add_root] to survive [swifer_gc], and only pointers stored in roots or traced fields
+//! are updated.
+//!
+//! These functions are only available with the `ffi` feature. To link them into a C program,
+//! build this crate as a static or dynamic library, e.g. with
+//! `cargo rustc --release --features ffi --crate-type staticlib`.
+
+use std::ffi::c_void;
+use std::{mem, ptr};
+use crate::gc::{GcCandidate, ManagedMem};
+use crate::gc::mas::MarkAndSweepMem;
+use crate::heap::DynSized;
+
+/// Called with the address of a field holding a pointer to an object, and the context given to
+/// the [SwiferTraceFn].
+pub type SwiferVisitFn = unsafe extern "C" fn(slot: *mut *mut c_void, ctx: *mut c_void);
+/// Calls `visit` with the address of every field in `object` holding a pointer to another
+/// object, passing `ctx` along unchanged.
+pub type SwiferTraceFn = unsafe extern "C" fn(object: *mut c_void, visit: SwiferVisitFn, ctx: *mut c_void);
+/// Cleans up an object before its memory is reclaimed. Must not access other objects.
+pub type SwiferFinalizeFn = unsafe extern "C" fn(object: *mut c_void);
+
+/// A garbage-collected heap of objects allocated from C, and the roots used to collect it.
+pub struct SwiferHeap{
+    mem: MarkAndSweepMem<FfiObject>,
+    roots: Vec<*mut *mut c_void>
+}
+
+/// An object allocated through the C interface: a header followed by the object's bytes.
+#[repr(C)]
+pub struct FfiObject{
+    header: Header,
+    data: [Chunk]
+}
+
+// object bytes are stored in 16-byte aligned chunks
+#[repr(C, align(16))]
+#[derive(Copy, Clone)]
+struct Chunk([u8; 16]);
+
+#[repr(C, align(16))]
+struct Header{
+    trace: Option<SwiferTraceFn>,
+    finalize: Option<SwiferFinalizeFn>,
+    // the number of chunks of data
+    len: usize
+}
+
+const CHUNK_SIZE: usize = mem::size_of::<Chunk>();
+const HEADER_CHUNKS: usize = mem::size_of::<Header>() / CHUNK_SIZE;
+
+impl FfiObject{
+    // creates an object with zeroed contents of at least the given size
+    fn alloc(size: usize, trace: Option<SwiferTraceFn>, finalize: Option<SwiferFinalizeFn>) -> Box<FfiObject>{
+        let len = size.saturating_add(CHUNK_SIZE - 1) / CHUNK_SIZE;
+        let chunks: Box<[Chunk]> = vec![Chunk([0; CHUNK_SIZE]); HEADER_CHUNKS + len].into_boxed_slice();
+        unsafe{
+            let start = Box::into_raw(chunks) as *mut Chunk;
+            (start as *mut Header).write(Header{ trace, finalize, len });
+            return Box::from_raw(ptr::slice_from_raw_parts_mut(start, len) as *mut FfiObject);
+        }
+    }
+
+    // returns the address given to C for this object
+    fn data(&self) -> *mut c_void{
+        return self.data.as_ptr() as *mut c_void;
+    }
+
+    fn data_mut(&mut self) -> *mut c_void{
+        return self.data.as_mut_ptr() as *mut c_void;
+    }
+}
+
+// calls the given function with every field of the object at `data` visited by `trace`
+fn visit_slots(trace: Option<SwiferTraceFn>, data: *mut c_void, mut f: impl FnMut(*mut *mut c_void)){
+    unsafe extern "C" fn visit(slot: *mut *mut c_void, ctx: *mut c_void){
+        let f = &mut *(ctx as *mut &mut dyn FnMut(*mut *mut c_void));
+        f(slot);
+    }
+    if let Some(trace) = trace{
+        let mut f: &mut dyn FnMut(*mut *mut c_void) = &mut f;
+        unsafe{
+            trace(data, visit, &mut f as *mut _ as *mut c_void);
+        }
+    }
+}
+
+// recovers a pointer to an object from the non-null address given to C
+unsafe fn from_c(data: *mut c_void) -> *const FfiObject{
+    let start = (data as *const Chunk).sub(HEADER_CHUNKS);
+    let len = (*(start as *const Header)).len;
+    return ptr::slice_from_raw_parts(start, len) as *const FfiObject;
+}
+
+fn to_c(ptr: *const FfiObject) -> *mut c_void{
+    return unsafe{ (*ptr).data() };
+}
+
+/// Creates a heap that can hold objects with the given total size in bytes, including a 32-byte
+/// header for each object. Returns null if the heap can't be created.
+#[no_mangle]
+pub extern "C" fn swifer_heap_new(capacity: usize) -> *mut SwiferHeap{
+    if capacity == 0{
+        return ptr::null_mut();
+    }
+    return Box::into_raw(Box::new(SwiferHeap{
+        mem: MarkAndSweepMem::new(capacity),
+        roots: Vec::new()
+    }));
+}
+
+/// Drops every object in the heap, calling their finalizers, and frees the heap.
+///
+/// # Safety
+///
+/// `heap` must have been returned by [swifer_heap_new] and not yet freed, or be null.
+#[no_mangle]
+pub unsafe extern "C" fn swifer_heap_free(heap: *mut SwiferHeap){
+    if !heap.is_null(){
+        drop(Box::from_raw(heap));
+    }
+}
+
+/// Allocates an object of the given size with zeroed contents, returning a pointer to its bytes,
+/// or null if there is not enough space. Never triggers a collection.
+///
+/// `trace` may be null for objects that hold no pointers to other objects, and `finalize` may be
+/// null for objects that need no cleanup.
+///
+/// # Safety
+///
+/// `heap` must be a valid heap. `trace` must only visit fields within the object.
+#[no_mangle]
+pub unsafe extern "C" fn swifer_alloc(heap: *mut SwiferHeap, size: usize, trace: Option<SwiferTraceFn>, finalize: Option<SwiferFinalizeFn>) -> *mut c_void{
+    let heap = &mut *heap;
+    return match heap.mem.push(FfiObject::alloc(size, trace, finalize)){
+        Ok(ptr) => to_c(ptr),
+        Err(err) => {
+            // don't finalize an object that was never seen by C
+            err.into_value().header.finalize = None;
+            ptr::null_mut()
+        }
+    };
+}
+
+/// Registers a root: a location holding a pointer to an object, or null, that keeps that object
+/// alive and is updated when it is moved.
+///
+/// # Safety
+///
+/// `heap` must be a valid heap, and `slot` must remain valid until removed by [swifer_remove_root]
+/// or the heap is freed.
+#[no_mangle]
+pub unsafe extern "C" fn swifer_add_root(heap: *mut SwiferHeap, slot: *mut *mut c_void){
+    (*heap).roots.push(slot);
+}
+
+/// Unregisters a root added by [swifer_add_root]. Does nothing if it isn't registered.
+///
+/// # Safety
+///
+/// `heap` must be a valid heap.
+#[no_mangle]
+pub unsafe extern "C" fn swifer_remove_root(heap: *mut SwiferHeap, slot: *mut *mut c_void){
+    let roots = &mut (*heap).roots;
+    if let Some(idx) = roots.iter().rposition(|root| *root == slot){
+        roots.remove(idx);
+    }
+}
+
+/// Collects the heap, dropping every object unreachable from its roots, and updating roots and
+/// traced fields to point to the new locations of the remaining objects.
+///
+/// # Safety
+///
+/// `heap` must be a valid heap, and every root and traced field must be null or point to an
+/// object in it.
+#[no_mangle]
+pub unsafe extern "C" fn swifer_gc(heap: *mut SwiferHeap){
+    let heap = &mut *heap;
+    let mut ptrs: Vec<*const FfiObject> = heap.roots.iter()
+        .filter(|slot| !(***slot).is_null())
+        .map(|slot| from_c(**slot))
+        .collect();
+    heap.mem.gc(ptrs.iter_mut().map(|p| p as *mut _).collect(), Vec::new());
+    let mut updated = ptrs.into_iter();
+    for slot in &heap.roots{
+        if !(**slot).is_null(){
+            **slot = to_c(updated.next().unwrap());
+        }
+    }
+}
+
+/// Returns the number of objects in the heap.
+///
+/// # Safety
+///
+/// `heap` must be a valid heap.
+#[no_mangle]
+pub unsafe extern "C" fn swifer_heap_len(heap: *const SwiferHeap) -> usize{
+    return (*heap).mem.len();
+}
+
+/// Returns the number of bytes in use in the heap, including object headers.
+///
+/// # Safety
+///
+/// `heap` must be a valid heap.
+#[no_mangle]
+pub unsafe extern "C" fn swifer_heap_used(heap: *const SwiferHeap) -> usize{
+    return (*heap).mem.used_bytes();
+}
+
+//////////////// impls
+
+unsafe impl DynSized for FfiObject{
+    fn dyn_align() -> usize{
+        return mem::align_of::<Chunk>();
+    }
+}
+
+impl GcCandidate for FfiObject{
+    fn collect_managed_pointers(&self, this: &*const FfiObject) -> Vec<*const FfiObject>{
+        let mut ptrs = Vec::new();
+        self.trace(&mut |p: &*const FfiObject| ptrs.push(*p), this);
+        return ptrs;
+    }
+
+    fn trace(&self, visitor: &mut impl FnMut(&*const FfiObject), _this: &*const FfiObject){
+        visit_slots(self.header.trace, self.data(), |slot| unsafe{
+            if !(*slot).is_null(){
+                visitor(&from_c(*slot));
+            }
+        });
+    }
+
+    fn adjust_ptrs(&mut self, adjust: impl Fn(&*const FfiObject) -> *const FfiObject, _this: &*const FfiObject){
+        visit_slots(self.header.trace, self.data_mut(), |slot| unsafe{
+            if !(*slot).is_null(){
+                *slot = to_c(adjust(&from_c(*slot)));
+            }
+        });
+    }
+}
+
+impl Drop for FfiObject{
+    fn drop(&mut self){
+        if let Some(finalize) = self.header.finalize{
+            unsafe{
+                finalize(self.data_mut());
+            }
+        }
+    }
+}
@@ -0,0 +1,105 @@
+//! Many small independent memories, one for each actor, that only share values by copying them.
+//!
+//! Values are passed between actors with [ActorHeaps::send], which copies a value and everything
+//! reachable from it into the receiving actor's memory. Since no memory ever points into another,
+//! each can be collected on its own without looking at the others.
+
+use crate::gc::{GcCandidate, ManagedMem};
+use crate::gc::copy::{copy_in, copy_out};
+use crate::heap::{AllocError, HeapPtr};
+
+/// Identifies an actor's memory in an [ActorHeaps]. Identifiers are never reused, even after an
+/// actor is removed.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, PartialOrd, Ord)]
+pub struct ActorId(usize);
+
+/// The memories of a set of actors, which exchange values by copying them.
+pub struct ActorHeaps<M>{
+    heaps: Vec<Option<M>>
+}
+
+impl ActorId{
+    /// Returns the index of this actor, in the order actors were spawned.
+    pub fn index(&self) -> usize{
+        return self.0;
+    }
+}
+
+impl<M> ActorHeaps<M>{
+    /// Creates a set with no actors.
+    pub fn new() -> Self{
+        return ActorHeaps{ heaps: Vec::new() };
+    }
+
+    /// Adds an actor using the given memory, returning its identifier.
+    pub fn spawn(&mut self, mem: M) -> ActorId{
+        self.heaps.push(Some(mem));
+        return ActorId(self.heaps.len() - 1);
+    }
+
+    /// Removes an actor, returning its memory, or `None` if it was already removed.
+    pub fn remove(&mut self, id: ActorId) -> Option<M>{
+        return self.heaps.get_mut(id.0).and_then(Option::take);
+    }
+
+    /// Returns the memory of the given actor, or `None` if it was removed.
+    pub fn heap(&self, id: ActorId) -> Option<&M>{
+        return self.heaps.get(id.0).and_then(Option::as_ref);
+    }
+
+    /// Returns the memory of the given actor mutably, or `None` if it was removed.
+    pub fn heap_mut(&mut self, id: ActorId) -> Option<&mut M>{
+        return self.heaps.get_mut(id.0).and_then(Option::as_mut);
+    }
+
+    /// Returns the identifiers of every actor that hasn't been removed.
+    pub fn ids(&self) -> impl Iterator<Item = ActorId> + '_{
+        return self.heaps.iter().enumerate().filter(|(_, heap)| heap.is_some()).map(|(idx, _)| ActorId(idx));
+    }
+
+    /// Returns the number of actors that haven't been removed.
+    pub fn len(&self) -> usize{
+        return self.ids().count();
+    }
+
+    /// Returns whether every actor has been removed, or none were spawned.
+    pub fn is_empty(&self) -> bool{
+        return self.len() == 0;
+    }
+
+    /// Copies the value at the given pointer in the memory of `from`, and every value reachable
+    /// from it, into the memory of `to`, returning a pointer to the copy; see
+    /// [clone_graph](crate::gc::copy::clone_graph).
+    ///
+    /// # Panics
+    ///
+    /// Panics if either actor has been removed, or if `value` is not in the memory of `from`.
+    pub fn send<T, Ptr>(&mut self, value: &Ptr, from: ActorId, to: ActorId) -> Result<Ptr, AllocError<T>>
+        where T: GcCandidate<Ptr> + Clone, Ptr: HeapPtr<T>, M: ManagedMem<T, Ptr>
+    {
+        return self.send_with(value, from, to, |v| Box::new(v.clone()));
+    }
+
+    /// Copies a value and everything reachable from it between actors, as with [ActorHeaps::send],
+    /// using the given function to copy each value.
+    ///
+    /// # Panics
+    ///
+    /// Panics if either actor has been removed, or if `value` is not in the memory of `from`.
+    pub fn send_with<T, Ptr>(&mut self, value: &Ptr, from: ActorId, to: ActorId, copy: impl FnMut(&T) -> Box<T>) -> Result<Ptr, AllocError<T>>
+        where T: ?Sized + GcCandidate<Ptr>, Ptr: HeapPtr<T>, M: ManagedMem<T, Ptr>
+    {
+        let src = self.heap(from).expect("sending from a removed actor");
+        let copies = copy_out(src, value, copy);
+        let dst = self.heap_mut(to).expect("sending to a removed actor");
+        return copy_in(dst, value, copies);
+    }
+}
+
+//////////////// impls
+
+impl<M> Default for ActorHeaps<M>{
+    fn default() -> Self{
+        return ActorHeaps::new();
+    }
+}
@@ -0,0 +1,397 @@
+//! Managed memory holding values of many different types.
+//!
+//! Types are registered in an [AnyMem] with the functions used to trace, adjust, and drop their
+//! values, and are given a [TypeTag]. Every value is stored as an [AnyObject], which records its
+//! type in a header before the value itself, so any collector can manage values of all registered
+//! types in a single heap.
+//!
+//! Types with values of varying size, such as arrays, can be registered with a [SizeFn] using
+//! [TypeInfo::with_size], and have values pushed with [AnyMem::push_raw].
+
+use std::alloc::Layout;
+use std::any::TypeId;
+use std::collections::HashMap;
+use std::{io, mem};
+use std::io::Write;
+use std::ptr;
+use std::sync::Arc;
+use crate::gc::{GcCandidate, ManagedMem};
+use crate::gc::census::Census;
+use crate::gc::dump::write_dump;
+use crate::heap::{AllocError, DynSized};
+
+/// A pointer to a value in an [AnyMem].
+pub type AnyPtr = *const AnyObject;
+
+/// A function that calls the given visitor with every managed pointer in the value at the given address.
+pub type TraceFn = unsafe fn(*const u8, &mut dyn FnMut(&AnyPtr));
+/// A function that replaces every managed pointer in the value at the given address.
+pub type AdjustFn = unsafe fn(*mut u8, &dyn Fn(&AnyPtr) -> AnyPtr);
+/// A function that drops the value at the given address in place.
+pub type DropFn = unsafe fn(*mut u8);
+/// A function that returns the size in bytes of the value at the given address.
+pub type SizeFn = unsafe fn(*const u8) -> usize;
+
+/// The identifier given to a type registered in an [AnyMem].
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct TypeTag(u32);
+
+/// The functions used to manage values of a registered type.
+pub struct TypeInfo{
+    name: &'static str,
+    layout: Layout,
+    rust_type: Option<TypeId>,
+    trace: TraceFn,
+    adjust: AdjustFn,
+    drop: Option<DropFn>,
+    size: Option<SizeFn>
+}
+
+/// A Rust type that can be registered in an [AnyMem] using [TypeInfo::of].
+///
+/// This plays the role of [GcCandidate] for values that may point to values of other types.
+pub trait AnyCandidate: Sized + 'static{
+    /// Calls the given visitor with every pointer in this value to other managed values.
+    fn trace(&self, visitor: &mut dyn FnMut(&AnyPtr));
+    /// Replaces all managed pointers within this value according to the given function.
+    fn adjust_ptrs(&mut self, adjust: &dyn Fn(&AnyPtr) -> AnyPtr);
+}
+
+/// A value of any registered type, stored in an [AnyMem].
+///
+/// Values can be accessed using [AnyObject::downcast_ref] and [AnyObject::downcast_mut].
+#[repr(C)]
+pub struct AnyObject{
+    chunks: [Chunk]
+}
+
+/// Managed memory holding values of any type registered with it, backed by any [ManagedMem]
+/// implementation for [AnyObject]s.
+pub struct AnyMem<M: ManagedMem<AnyObject, AnyPtr>>{
+    mem: M,
+    // shared with every value of each type, so values can outlive this registry
+    types: Vec<Arc<TypeInfo>>,
+    rust_types: HashMap<TypeId, TypeTag>
+}
+
+// values are stored after a header, in 16-byte aligned chunks
+#[repr(C, align(16))]
+#[derive(Copy, Clone)]
+struct Chunk([u8; 16]);
+
+#[repr(C)]
+struct Header{
+    info: Arc<TypeInfo>,
+    tag: TypeTag
+}
+
+const CHUNK_SIZE: usize = mem::size_of::<Chunk>();
+const _: () = assert!(mem::size_of::<Header>() <= CHUNK_SIZE);
+
+impl TypeTag{
+    /// Returns the index of this type in the order types were registered.
+    pub fn index(&self) -> usize{
+        return self.0 as usize;
+    }
+}
+
+impl TypeInfo{
+    /// Creates type information for values with the given layout, managed by the given functions.
+    ///
+    /// Each function is given a pointer to the start of a value. `drop` may be `None` for
+    /// types that need no cleanup.
+    pub fn new(name: &'static str,
+               layout: Layout,
+               trace: TraceFn,
+               adjust: AdjustFn,
+               drop: Option<DropFn>) -> Self{
+        return TypeInfo{ name, layout, rust_type: None, trace, adjust, drop, size: None };
+    }
+
+    /// Makes values of this type variably sized, with their size given by `size`. The layout
+    /// then gives the alignment and minimum size of values.
+    pub fn with_size(mut self, size: SizeFn) -> Self{
+        self.size = Some(size);
+        return self;
+    }
+
+    /// Creates type information for a Rust type, using its [AnyCandidate] and [Drop] implementations.
+    pub fn of<U: AnyCandidate>() -> Self{
+        unsafe fn trace<U: AnyCandidate>(value: *const u8, visitor: &mut dyn FnMut(&AnyPtr)){
+            (*(value as *const U)).trace(visitor);
+        }
+        unsafe fn adjust<U: AnyCandidate>(value: *mut u8, adjust: &dyn Fn(&AnyPtr) -> AnyPtr){
+            (*(value as *mut U)).adjust_ptrs(adjust);
+        }
+        unsafe fn drop<U: AnyCandidate>(value: *mut u8){
+            (value as *mut U).drop_in_place();
+        }
+        return TypeInfo{
+            name: std::any::type_name::<U>(),
+            layout: Layout::new::<U>(),
+            rust_type: Some(TypeId::of::<U>()),
+            trace: trace::<U>,
+            adjust: adjust::<U>,
+            drop: if mem::needs_drop::<U>() { Some(drop::<U>) } else { None },
+            size: None
+        };
+    }
+
+    /// Returns the name of this type.
+    pub fn name(&self) -> &'static str{
+        return self.name;
+    }
+
+    /// Returns the layout of values of this type, or the minimum layout if it's variably sized.
+    pub fn layout(&self) -> Layout{
+        return self.layout;
+    }
+
+    /// Returns whether values of this type have varying sizes.
+    pub fn is_variably_sized(&self) -> bool{
+        return self.size.is_some();
+    }
+}
+
+impl AnyObject{
+    /// Returns the tag of this value's type.
+    pub fn tag(&self) -> TypeTag{
+        return self.header().tag;
+    }
+
+    /// Returns the information for this value's type.
+    pub fn type_info(&self) -> &TypeInfo{
+        return &self.header().info;
+    }
+
+    /// Returns a reference to this value as a `U`, or `None` if it has a different type.
+    pub fn downcast_ref<U: 'static>(&self) -> Option<&U>{
+        if self.type_info().rust_type != Some(TypeId::of::<U>()){
+            return None;
+        }
+        return unsafe{ (self.payload() as *const U).as_ref() };
+    }
+
+    /// Returns a mutable reference to this value as a `U`, or `None` if it has a different type.
+    pub fn downcast_mut<U: 'static>(&mut self) -> Option<&mut U>{
+        if self.type_info().rust_type != Some(TypeId::of::<U>()){
+            return None;
+        }
+        return unsafe{ (self.payload_mut() as *mut U).as_mut() };
+    }
+
+    /// Returns the size of this value in bytes, not including its header.
+    pub fn size(&self) -> usize{
+        let info = self.type_info();
+        return match info.size{
+            Some(size) => unsafe{ size(self.payload()) },
+            None => info.layout.size()
+        };
+    }
+
+    /// Returns a pointer to the start of this value.
+    pub fn payload(&self) -> *const u8{
+        return self.chunks[1..].as_ptr() as *const u8;
+    }
+
+    /// Returns a mutable pointer to the start of this value.
+    pub fn payload_mut(&mut self) -> *mut u8{
+        return self.chunks[1..].as_mut_ptr() as *mut u8;
+    }
+
+    fn header(&self) -> &Header{
+        return unsafe{ &*(self.chunks.as_ptr() as *const Header) };
+    }
+
+    // creates an object of the given type and size with uninitialized (zeroed) contents
+    fn alloc(info: &Arc<TypeInfo>, tag: TypeTag, size: usize) -> Box<AnyObject>{
+        let len = 1 + size.saturating_add(CHUNK_SIZE - 1) / CHUNK_SIZE;
+        let mut chunks: Box<[Chunk]> = vec![Chunk([0; CHUNK_SIZE]); len].into_boxed_slice();
+        unsafe{
+            (chunks.as_mut_ptr() as *mut Header).write(Header{ info: info.clone(), tag });
+            return Box::from_raw(Box::into_raw(chunks) as *mut AnyObject);
+        }
+    }
+}
+
+impl<M: ManagedMem<AnyObject, AnyPtr>> AnyMem<M>{
+    /// Creates a new `AnyMem` backed by the given managed memory, with no registered types.
+    pub fn new(mem: M) -> Self{
+        return AnyMem{
+            mem,
+            types: Vec::new(),
+            rust_types: HashMap::new()
+        };
+    }
+
+    /// Registers a type, returning its tag.
+    ///
+    /// Types created by [TypeInfo::of] are only registered once; registering the same Rust
+    /// type again returns the existing tag.
+    ///
+    /// Panics if the type requires an alignment greater than 16 bytes.
+    pub fn register(&mut self, info: TypeInfo) -> TypeTag{
+        assert!(info.layout.align() <= mem::align_of::<Chunk>(), "AnyMem::register: type {} is over-aligned", info.name);
+        if let Some(tag) = info.rust_type.and_then(|ty| self.rust_types.get(&ty)){
+            return *tag;
+        }
+        let tag = TypeTag(self.types.len() as u32);
+        if let Some(ty) = info.rust_type{
+            self.rust_types.insert(ty, tag);
+        }
+        self.types.push(Arc::new(info));
+        return tag;
+    }
+
+    /// Registers a Rust type using [TypeInfo::of], returning its tag.
+    pub fn register_type<U: AnyCandidate>(&mut self) -> TypeTag{
+        return self.register(TypeInfo::of::<U>());
+    }
+
+    /// Returns the tag of the given Rust type, or `None` if it hasn't been registered.
+    pub fn tag_of<U: 'static>(&self) -> Option<TypeTag>{
+        return self.rust_types.get(&TypeId::of::<U>()).copied();
+    }
+
+    /// Returns the information for the type with the given tag.
+    pub fn type_info(&self, tag: TypeTag) -> &TypeInfo{
+        return &self.types[tag.index()];
+    }
+
+    /// Pushes a value of a registered Rust type, returning a pointer to it, or an [AllocError]
+    /// containing the value if it can't be allocated.
+    ///
+    /// Panics if `U` hasn't been registered.
+    #[track_caller]
+    pub fn push<U: 'static>(&mut self, v: U) -> Result<AnyPtr, AllocError<AnyObject>>{
+        let tag = self.tag_of::<U>().unwrap_or_else(|| panic!("AnyMem::push: type {} is not registered", std::any::type_name::<U>()));
+        let info = &self.types[tag.index()];
+        let mut obj = AnyObject::alloc(info, tag, info.layout.size());
+        unsafe{
+            (obj.payload_mut() as *mut U).write(v);
+        }
+        return self.mem.push(obj);
+    }
+
+    /// Pushes a value of a registered type by copying its bytes, returning a pointer to it, or an
+    /// [AllocError] containing the value if it can't be allocated.
+    ///
+    /// # Safety
+    ///
+    /// `bytes` must be a valid value of the type with the given tag, and must be of the size
+    /// given by its layout, or by its [SizeFn] if it's variably sized.
+    #[track_caller]
+    pub unsafe fn push_raw(&mut self, tag: TypeTag, bytes: &[u8]) -> Result<AnyPtr, AllocError<AnyObject>>{
+        let info = &self.types[tag.index()];
+        if info.size.is_none(){
+            debug_assert_eq!(bytes.len(), info.layout.size(), "AnyMem::push_raw: wrong size for type {}", info.name);
+        }
+        let mut obj = AnyObject::alloc(info, tag, bytes.len());
+        ptr::copy_nonoverlapping(bytes.as_ptr(), obj.payload_mut(), bytes.len());
+        debug_assert_eq!(bytes.len(), obj.size(), "AnyMem::push_raw: wrong size for type {}", info.name);
+        return self.mem.push(obj);
+    }
+
+    /// Returns a mutable reference to the value at the given pointer as a `U`, or `None` if
+    /// that pointer does not point to a value in this memory, or if the value has a different type.
+    pub fn get<U: 'static>(&mut self, ptr: &AnyPtr) -> Option<&mut U>{
+        return self.mem.get_by(ptr)?.downcast_mut();
+    }
+
+    /// Runs the given function on the value at the given pointer as a `U`, returning its result,
+    /// or `None` if that pointer does not point to a value in this memory, or if the value has a
+    /// different type. Unlike [AnyMem::get], this notifies the collector's write barrier.
+    pub fn write<U: 'static, R>(&mut self, ptr: &AnyPtr, update: impl FnOnce(&mut U) -> R) -> Option<R>{
+        // check the type first, so that `update` is only given values of the right type
+        self.mem.get_by_ref(ptr)?.downcast_ref::<U>()?;
+        return self.mem.write_field(ptr, |obj| update(obj.downcast_mut().unwrap()));
+    }
+
+    /// Returns a reference to the value at the given pointer as a `U`, or `None` if that
+    /// pointer does not point to a value in this memory, or if the value has a different type.
+    pub fn get_ref<U: 'static>(&self, ptr: &AnyPtr) -> Option<&U>{
+        return self.mem.get_by_ref(ptr)?.downcast_ref();
+    }
+
+    /// Returns the tag of the value at the given pointer, or `None` if that pointer does not
+    /// point to a value in this memory.
+    pub fn tag_at(&self, ptr: &AnyPtr) -> Option<TypeTag>{
+        return self.mem.get_by_ref(ptr).map(|obj| obj.tag());
+    }
+
+    /// Writes a binary dump of every value stored, recording each value's [TypeTag] index as
+    /// its type id; see [dump](crate::gc::dump).
+    pub fn dump(&self, mut w: impl Write) -> io::Result<()>{
+        return write_dump(&self.mem, &mut w, |obj, _| Some(obj.tag().index() as u32));
+    }
+
+    /// Returns the number and total size of the values stored, grouped by size and by the index
+    /// of their [TypeTag]. Sizes include each value's header.
+    pub fn census(&self) -> Census{
+        let mut census = Census::new();
+        self.mem.for_each(|obj, _| census.record(mem::size_of_val(obj), Some(obj.tag().index() as u32)));
+        return census;
+    }
+
+    /// Returns the managed memory that values are stored in.
+    pub fn mem(&self) -> &M{
+        return &self.mem;
+    }
+
+    /// Returns the managed memory that values are stored in, mutably.
+    pub fn mem_mut(&mut self) -> &mut M{
+        return &mut self.mem;
+    }
+
+    /// Trigger garbage collection; see [ManagedMem::gc].
+    ///
+    /// # Safety
+    ///
+    /// See [ManagedMem::gc].
+    pub unsafe fn gc(&mut self, roots: Vec<*mut AnyPtr>, weaks: Vec<*mut AnyPtr>){
+        self.mem.gc(roots, weaks);
+    }
+}
+
+//////////////// impls
+
+unsafe impl DynSized for AnyObject{
+    fn dyn_align() -> usize{
+        return mem::align_of::<Chunk>();
+    }
+}
+
+impl GcCandidate<AnyPtr> for AnyObject{
+    fn collect_managed_pointers(&self, this: &AnyPtr) -> Vec<AnyPtr>{
+        let mut ptrs = Vec::new();
+        self.trace(&mut |p: &AnyPtr| ptrs.push(*p), this);
+        return ptrs;
+    }
+
+    fn trace(&self, visitor: &mut impl FnMut(&AnyPtr), _this: &AnyPtr){
+        unsafe{
+            (self.type_info().trace)(self.payload(), visitor);
+        }
+    }
+
+    fn adjust_ptrs(&mut self, adjust: impl Fn(&AnyPtr) -> AnyPtr, _this: &AnyPtr){
+        let adjust_fn = self.type_info().adjust;
+        unsafe{
+            adjust_fn(self.payload_mut(), &adjust);
+        }
+    }
+}
+
+impl Drop for AnyObject{
+    fn drop(&mut self){
+        if let Some(drop_fn) = self.type_info().drop{
+            unsafe{
+                drop_fn(self.payload_mut());
+            }
+        }
+        // release this value's share of its type information
+        unsafe{
+            ptr::drop_in_place(self.chunks.as_mut_ptr() as *mut Header);
+        }
+    }
+}
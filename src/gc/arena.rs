@@ -0,0 +1,154 @@
+//! A callback-scoped interface to managed memory, similar to the `Arena` and `Mutation` types of
+//! the [`gc-arena`](https://docs.rs/gc-arena) crate.
+//!
+//! An [Arena] owns a memory and a single root value. Values are only accessed inside
+//! [Arena::mutate], through a [Mutation] and [Gc] pointers branded with the invariant lifetime
+//! `'gc` of that call, so they can't be kept after it returns. Collections only happen between
+//! calls, when the root is the only thing that can be holding on to values, so nothing else needs
+//! to be registered as a root.
+//!
+//! Unlike `gc-arena`, values store plain managed pointers rather than [Gc]s, so that they can be
+//! used with any [ManagedMem]; pointers loaded from values are branded again with
+//! [Mutation::load]. Mutations are also passed by `&mut`, as pushing to a memory needs it.
+
+use std::cell::Cell;
+use std::marker::PhantomData;
+use crate::gc::{GcCandidate, ManagedMem};
+use crate::gc::incremental::{Budget, GcPhaseProgress, IncrementalMem};
+use crate::gc::report::GcReport;
+use crate::heap::{AllocError, HeapPtr};
+
+/// A memory and its root value, accessed through [Arena::mutate].
+pub struct Arena<T, Ptr, M>
+    where T: ?Sized + GcCandidate<Ptr>, Ptr: HeapPtr<T>, M: ManagedMem<T, Ptr>
+{
+    mem: M,
+    root: Ptr,
+    _phantom: PhantomData<fn(&T)>
+}
+
+/// Access to the memory of an [Arena] during a single call to [Arena::mutate].
+pub struct Mutation<'gc, T, Ptr, M>
+    where T: ?Sized + GcCandidate<Ptr>, Ptr: HeapPtr<T>, M: ManagedMem<T, Ptr>
+{
+    mem: &'gc mut M,
+    _phantom: PhantomData<fn(&T, &Ptr)>,
+    _invariant: PhantomData<Cell<&'gc ()>>
+}
+
+/// A pointer to a value in an [Arena], valid until the end of the call to [Arena::mutate] it was
+/// obtained in.
+///
+/// The lifetime `'gc` is invariant, so pointers can't be returned from that call, or stored
+/// anywhere that outlives it.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct Gc<'gc, Ptr>{
+    ptr: Ptr,
+    _phantom: PhantomData<Cell<&'gc ()>>
+}
+
+impl<T, Ptr, M> Arena<T, Ptr, M>
+    where T: ?Sized + GcCandidate<Ptr>, Ptr: HeapPtr<T>, M: ManagedMem<T, Ptr>
+{
+    /// Creates an arena around the given memory, with the root value returned by `init`, or the
+    /// error from `init` if it couldn't be allocated.
+    pub fn new<E>(mut mem: M, init: impl for<'gc> FnOnce(&mut Mutation<'gc, T, Ptr, M>) -> Result<Gc<'gc, Ptr>, E>) -> Result<Self, E>{
+        let root = init(&mut Mutation::new(&mut mem))?.ptr;
+        return Ok(Arena{
+            mem,
+            root,
+            _phantom: PhantomData
+        });
+    }
+
+    /// Runs the given function with access to the memory and the root value, returning its result.
+    pub fn mutate<R>(&mut self, f: impl for<'gc> FnOnce(&mut Mutation<'gc, T, Ptr, M>, Gc<'gc, Ptr>) -> R) -> R{
+        let root = Gc::new(self.root.clone());
+        return f(&mut Mutation::new(&mut self.mem), root);
+    }
+
+    /// Runs the given function with access to the memory and the root value, which it may
+    /// replace, returning its result.
+    pub fn mutate_root<R>(&mut self, f: impl for<'gc> FnOnce(&mut Mutation<'gc, T, Ptr, M>, &mut Gc<'gc, Ptr>) -> R) -> R{
+        let mut root = Gc::new(self.root.clone());
+        let result = f(&mut Mutation::new(&mut self.mem), &mut root);
+        self.root = root.ptr;
+        return result;
+    }
+
+    /// Collects every value unreachable from the root, returning a report of the collection.
+    pub fn collect_all(&mut self) -> GcReport{
+        // safety: the root is a valid pointer owned by this arena
+        return unsafe{ self.mem.gc_with_report(vec![&mut self.root], Vec::new()) };
+    }
+
+    /// Does up to the given budget of collection work, continuing a collection in progress or
+    /// starting a new one, for memories that can be collected incrementally.
+    pub fn collect_step(&mut self, budget: Budget) -> GcPhaseProgress where M: IncrementalMem<T, Ptr>{
+        // safety: as above; values written during mutations are reported to the write barrier
+        return unsafe{ self.mem.gc_step(vec![&mut self.root], Vec::new(), budget) };
+    }
+
+    /// Returns the memory of this arena.
+    pub fn mem(&self) -> &M{
+        return &self.mem;
+    }
+
+    /// Separates this into its memory and a pointer to its root value.
+    pub fn into_inner(self) -> (M, Ptr){
+        return (self.mem, self.root);
+    }
+}
+
+impl<'gc, T, Ptr, M> Mutation<'gc, T, Ptr, M>
+    where T: ?Sized + GcCandidate<Ptr>, Ptr: HeapPtr<T>, M: ManagedMem<T, Ptr>
+{
+    fn new(mem: &'gc mut M) -> Self{
+        return Mutation{ mem, _phantom: PhantomData, _invariant: PhantomData };
+    }
+
+    /// Pushes a value into the memory, returning a pointer to it, or an [AllocError] containing
+    /// the value if it can't be allocated. The memory is never collected to make space.
+    pub fn alloc(&mut self, v: Box<T>) -> Result<Gc<'gc, Ptr>, AllocError<T>>{
+        return self.mem.push(v).map(Gc::new);
+    }
+
+    /// Returns a reference to the value at the given pointer.
+    pub fn get(&self, gc: &Gc<'gc, Ptr>) -> &T{
+        return self.mem.get_by_ref(&gc.ptr).expect("Mutation: pointer is not in the arena");
+    }
+
+    /// Runs the given function on the value at the given pointer, reporting any pointers it
+    /// stores to the memory's write barrier as by [ManagedMem::write_field].
+    pub fn write<R>(&mut self, gc: &Gc<'gc, Ptr>, update: impl FnOnce(&mut T) -> R) -> R{
+        return self.mem.write_field(&gc.ptr, update).expect("Mutation: pointer is not in the arena");
+    }
+
+    /// Loads a managed pointer from the value at the given pointer, as by
+    /// [ManagedMem::load_field], returning it as a [Gc], or `None` if it doesn't point to a value
+    /// in the memory, e.g. if it's null.
+    pub fn load(&self, src: &Gc<'gc, Ptr>, load: impl FnOnce(&T) -> Ptr) -> Option<Gc<'gc, Ptr>>{
+        let ptr = self.mem.load_field(&src.ptr, load).expect("Mutation: pointer is not in the arena");
+        if !self.mem.contains_ptr(&ptr){
+            return None;
+        }
+        return Some(Gc::new(ptr));
+    }
+
+    /// Returns the memory of the arena.
+    pub fn mem(&self) -> &M{
+        return self.mem;
+    }
+}
+
+impl<Ptr> Gc<'_, Ptr>{
+    fn new(ptr: Ptr) -> Self{
+        return Gc{ ptr, _phantom: PhantomData };
+    }
+
+    /// Returns the managed pointer, e.g. to store in a value. It's only valid for the rest of
+    /// the call to [Arena::mutate] unless it's reachable from the root.
+    pub fn as_ptr(&self) -> &Ptr{
+        return &self.ptr;
+    }
+}
@@ -0,0 +1,452 @@
+//! Building blocks for collector barriers.
+//!
+//! Read barriers let collectors that move values while the mutator runs forward pointers to
+//! values' new locations when they are loaded; see [ManagedMem::load_barrier].
+//!
+//! Write barriers let generational and concurrent collectors find pointers stored into values
+//! they aren't otherwise scanning; see [ManagedMem::record_write]. A [CardTable] records which
+//! regions of a heap have been written to, and a [RememberedSet] records which values have.
+//!
+//! Concurrent markers instead need to know which pointers were overwritten while they were
+//! marking, so that every value reachable when marking started is marked, even if the mutator
+//! removes the only path to it. An [SatbQueue] collects these overwritten pointers from an
+//! [SatbBuffer] owned by each mutator thread, for the collector to drain and mark.
+
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::mem;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use crate::gc::{GcCandidate, ManagedMem};
+use crate::heap::{DynSized, Heap, HeapPtr, ThreadSafePtr};
+
+/// A managed pointer stored in a value, which must be loaded through the collector's read barrier.
+///
+/// Storing pointers in this wrapper makes it hard to accidentally read a stale pointer while a
+/// concurrent copying collector is relocating values.
+#[repr(transparent)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Barriered<Ptr>(Ptr);
+
+/// A table of relocated values' old and new pointers, for implementing
+/// [ManagedMem::load_barrier] in collectors that move values while the mutator runs.
+///
+/// Pointers are compared by address, ignoring metadata.
+pub struct ForwardingTable<T: ?Sized, Ptr: HeapPtr<T>>{
+    forwards: HashMap<*const (), Ptr>,
+    _phantom: PhantomData<fn(&T)>
+}
+
+/// A table of fixed-size regions ("cards") of memory, each of which is marked dirty when a value
+/// starting in it is written to.
+///
+/// Collectors can then rescan only the values in dirty cards, e.g. to find pointers from an old
+/// generation into a young one, instead of scanning every value.
+pub struct CardTable{
+    base: usize,
+    len: usize,
+    card_shift: u32,
+    cards: Vec<bool>
+}
+
+/// A set of values that have been written to, for collectors that need to rescan exactly those
+/// values, e.g. old values that may point to young values.
+///
+/// Pointers are compared by address, ignoring metadata.
+pub struct RememberedSet<T: ?Sized, Ptr: HeapPtr<T>>{
+    values: HashMap<*const (), Ptr>,
+    _phantom: PhantomData<fn(&T)>
+}
+
+/// The shared queue of a snapshot-at-the-beginning (SATB) write barrier, holding the pointers
+/// overwritten by mutators while marking is active.
+///
+/// Each mutator thread records the pointers it's about to overwrite in its own [SatbBuffer],
+/// which only takes the queue's lock once full; the collector takes every filled buffer with
+/// [SatbQueue::drain], and treats the pointers as roots. Pointers are only recorded while the
+/// queue is [active](SatbQueue::activate), so the barrier costs a single load otherwise.
+pub struct SatbQueue<T: ?Sized, Ptr: HeapPtr<T>>{
+    active: AtomicBool,
+    buffer_size: usize,
+    completed: Mutex<Vec<Vec<Ptr>>>,
+    // the number of pointers in completed buffers
+    pending: AtomicUsize,
+    _phantom: PhantomData<fn(&T)>
+}
+
+/// A single mutator thread's buffer of pointers overwritten while its [SatbQueue] is active,
+/// handed to the queue once full, when flushed, or when dropped.
+pub struct SatbBuffer<T: ?Sized, Ptr: HeapPtr<T>>{
+    queue: Arc<SatbQueue<T, Ptr>>,
+    entries: Vec<Ptr>
+}
+
+impl<Ptr> Barriered<Ptr>{
+    /// Wraps the given pointer.
+    pub fn new(ptr: Ptr) -> Self{
+        return Barriered(ptr);
+    }
+
+    /// Loads this pointer through the read barrier of the given memory.
+    pub fn load<T, M>(&self, mem: &M) -> Ptr
+        where T: ?Sized + GcCandidate<Ptr>, Ptr: HeapPtr<T>, M: ManagedMem<T, Ptr> + ?Sized
+    {
+        return mem.load_barrier(&self.0);
+    }
+
+    /// Replaces this pointer. This does not notify write barriers; see [ManagedMem::write_field].
+    pub fn store(&mut self, ptr: Ptr){
+        self.0 = ptr;
+    }
+
+    /// Returns the stored pointer without applying any barrier, e.g. for tracing.
+    pub fn raw(&self) -> &Ptr{
+        return &self.0;
+    }
+
+    /// Returns the stored pointer mutably without applying any barrier, e.g. for adjusting.
+    pub fn raw_mut(&mut self) -> &mut Ptr{
+        return &mut self.0;
+    }
+}
+
+impl<T: ?Sized, Ptr: HeapPtr<T>> ForwardingTable<T, Ptr>{
+    /// Creates an empty forwarding table.
+    pub fn new() -> Self{
+        return ForwardingTable{
+            forwards: HashMap::new(),
+            _phantom: PhantomData
+        };
+    }
+
+    /// Records that the value at `old` has been moved to `new`.
+    pub fn insert(&mut self, old: &Ptr, new: Ptr){
+        self.forwards.insert(old.to_raw_ptr().cast(), new);
+    }
+
+    /// Returns the current location of the value at the given pointer, which is the pointer
+    /// itself if the value hasn't been moved.
+    pub fn forward(&self, ptr: &Ptr) -> Ptr{
+        return match self.forwards.get(&ptr.to_raw_ptr().cast()){
+            Some(new) => new.clone(),
+            None => ptr.clone()
+        };
+    }
+
+    /// Returns whether the value at the given pointer has been moved.
+    pub fn is_forwarded(&self, ptr: &Ptr) -> bool{
+        return self.forwards.contains_key(&ptr.to_raw_ptr().cast());
+    }
+
+    /// Returns the number of forwarded values.
+    pub fn len(&self) -> usize{
+        return self.forwards.len();
+    }
+
+    /// Forgets every forwarded value, e.g. once all pointers to old locations have been updated.
+    pub fn clear(&mut self){
+        self.forwards.clear();
+    }
+}
+
+impl CardTable{
+    /// Creates a card table covering `len` bytes starting at `base`, with every card clean.
+    ///
+    /// Panics if `card_size` isn't a power of two.
+    pub fn new(base: *const u8, len: usize, card_size: usize) -> Self{
+        assert!(card_size.is_power_of_two(), "CardTable::new: card size {card_size} is not a power of two");
+        let card_shift = card_size.trailing_zeros();
+        // values may start at the very end of the covered memory if they're zero-sized
+        let count = (len >> card_shift) + 1;
+        return CardTable{
+            base: base as usize,
+            len,
+            card_shift,
+            cards: vec![false; count]
+        };
+    }
+
+    /// Creates a card table covering all of the given heap.
+    pub fn for_heap<T: ?Sized + DynSized, Ptr: HeapPtr<T>>(heap: &Heap<T, Ptr>, card_size: usize) -> Self{
+        return CardTable::new(heap.start(), heap.capacity(), card_size);
+    }
+
+    /// Marks the card containing the value at the given pointer as dirty. Returns false, and
+    /// does nothing, if the value isn't in the covered memory.
+    pub fn mark<T: ?Sized, Ptr: HeapPtr<T>>(&mut self, ptr: &Ptr) -> bool{
+        return match self.card_of(ptr){
+            Some(card) => {
+                self.cards[card] = true;
+                true
+            }
+            None => false
+        };
+    }
+
+    /// Returns whether the card containing the value at the given pointer is dirty. Values
+    /// outside the covered memory are never dirty.
+    pub fn is_dirty<T: ?Sized, Ptr: HeapPtr<T>>(&self, ptr: &Ptr) -> bool{
+        return self.card_of(ptr).map(|card| self.cards[card]).unwrap_or(false);
+    }
+
+    /// Returns the index of every dirty card, in address order.
+    pub fn dirty_cards(&self) -> impl Iterator<Item = usize> + '_{
+        return self.cards.iter().enumerate().filter(|(_, dirty)| **dirty).map(|(card, _)| card);
+    }
+
+    /// Returns the range of addresses covered by the card with the given index.
+    pub fn card_bounds(&self, card: usize) -> (*const u8, *const u8){
+        let start = self.base + (card << self.card_shift);
+        let end = (start + self.card_size()).min(self.base + self.len);
+        return (start as *const u8, end as *const u8);
+    }
+
+    /// Marks the card with the given index as clean.
+    pub fn clear_card(&mut self, card: usize){
+        self.cards[card] = false;
+    }
+
+    /// Marks every card as clean, e.g. once dirty cards have been rescanned.
+    pub fn clear(&mut self){
+        self.cards.fill(false);
+    }
+
+    /// Returns the size in bytes of each card.
+    pub fn card_size(&self) -> usize{
+        return 1 << self.card_shift;
+    }
+
+    /// Returns the number of cards in this table.
+    pub fn len(&self) -> usize{
+        return self.cards.len();
+    }
+
+    /// Returns whether this table has no cards, which is never the case.
+    pub fn is_empty(&self) -> bool{
+        return self.cards.is_empty();
+    }
+
+    // returns the index of the card containing the given pointer, if it's covered
+    fn card_of<T: ?Sized, Ptr: HeapPtr<T>>(&self, ptr: &Ptr) -> Option<usize>{
+        let offset = (ptr.to_raw_ptr().cast::<u8>() as usize).checked_sub(self.base)?;
+        if offset > self.len{
+            return None;
+        }
+        return Some(offset >> self.card_shift);
+    }
+}
+
+impl<T: ?Sized, Ptr: HeapPtr<T>> RememberedSet<T, Ptr>{
+    /// Creates an empty remembered set.
+    pub fn new() -> Self{
+        return RememberedSet{
+            values: HashMap::new(),
+            _phantom: PhantomData
+        };
+    }
+
+    /// Records that the value at the given pointer has been written to, returning whether it
+    /// wasn't already recorded.
+    pub fn insert(&mut self, ptr: &Ptr) -> bool{
+        return self.values.insert(ptr.to_raw_ptr().cast(), ptr.clone()).is_none();
+    }
+
+    /// Returns whether the value at the given pointer has been recorded.
+    pub fn contains(&self, ptr: &Ptr) -> bool{
+        return self.values.contains_key(&ptr.to_raw_ptr().cast());
+    }
+
+    /// Forgets the value at the given pointer, returning whether it was recorded.
+    pub fn remove(&mut self, ptr: &Ptr) -> bool{
+        return self.values.remove(&ptr.to_raw_ptr().cast()).is_some();
+    }
+
+    /// Returns every recorded value, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = &Ptr>{
+        return self.values.values();
+    }
+
+    /// Updates every recorded pointer after a collection, replacing it with the result of
+    /// `update`, or forgetting it if `update` returns `None`.
+    pub fn update(&mut self, mut update: impl FnMut(&Ptr) -> Option<Ptr>){
+        self.values = self.values.values()
+            .filter_map(&mut update)
+            .map(|new| (new.to_raw_ptr().cast(), new))
+            .collect();
+    }
+
+    /// Returns the number of recorded values.
+    pub fn len(&self) -> usize{
+        return self.values.len();
+    }
+
+    /// Returns whether no values have been recorded.
+    pub fn is_empty(&self) -> bool{
+        return self.values.is_empty();
+    }
+
+    /// Forgets every recorded value, e.g. once they have all been rescanned.
+    pub fn clear(&mut self){
+        self.values.clear();
+    }
+}
+
+impl<T: ?Sized, Ptr: HeapPtr<T>> SatbQueue<T, Ptr>{
+    /// Creates an inactive queue, with mutators' buffers holding up to `buffer_size` pointers
+    /// before being handed to it.
+    ///
+    /// Panics if `buffer_size` is 0.
+    pub fn new(buffer_size: usize) -> Arc<Self>{
+        assert!(buffer_size > 0, "SatbQueue::new: buffer size must be positive");
+        return Arc::new(SatbQueue{
+            active: AtomicBool::new(false),
+            buffer_size,
+            completed: Mutex::new(Vec::new()),
+            pending: AtomicUsize::new(0),
+            _phantom: PhantomData
+        });
+    }
+
+    /// Creates an empty buffer for a mutator thread, adding to this queue.
+    pub fn buffer(self: &Arc<Self>) -> SatbBuffer<T, Ptr>{
+        return SatbBuffer{
+            queue: self.clone(),
+            entries: Vec::new()
+        };
+    }
+
+    /// Starts recording overwritten pointers, e.g. when concurrent marking starts.
+    pub fn activate(&self){
+        self.active.store(true, Ordering::SeqCst);
+    }
+
+    /// Stops recording overwritten pointers, e.g. once marking is complete. Pointers already
+    /// queued are kept until drained.
+    pub fn deactivate(&self){
+        self.active.store(false, Ordering::SeqCst);
+    }
+
+    /// Returns whether overwritten pointers are being recorded.
+    pub fn is_active(&self) -> bool{
+        return self.active.load(Ordering::SeqCst);
+    }
+
+    /// Returns the number of pointers each mutator buffer holds before being handed over.
+    pub fn buffer_size(&self) -> usize{
+        return self.buffer_size;
+    }
+
+    /// Takes every pointer in buffers handed to this queue so far. Pointers still held in
+    /// mutators' buffers aren't included, so the collector must have every mutator
+    /// [flush](SatbBuffer::flush) before it finishes marking, e.g. at a safepoint.
+    pub fn drain(&self) -> Vec<Ptr>{
+        let completed = mem::take(&mut *self.completed());
+        self.pending.fetch_sub(completed.iter().map(Vec::len).sum(), Ordering::SeqCst);
+        return completed.into_iter().flatten().collect();
+    }
+
+    /// Returns the number of pointers waiting to be drained, not counting those still held in
+    /// mutators' buffers.
+    pub fn len(&self) -> usize{
+        return self.pending.load(Ordering::SeqCst);
+    }
+
+    /// Returns whether no pointers are waiting to be drained.
+    pub fn is_empty(&self) -> bool{
+        return self.len() == 0;
+    }
+
+    // hands a mutator's buffer to the queue
+    fn complete(&self, entries: Vec<Ptr>){
+        self.pending.fetch_add(entries.len(), Ordering::SeqCst);
+        self.completed().push(entries);
+    }
+
+    fn completed(&self) -> MutexGuard<'_, Vec<Vec<Ptr>>>{
+        return self.completed.lock().expect("SatbQueue: lock poisoned");
+    }
+}
+
+impl<T: ?Sized, Ptr: HeapPtr<T>> SatbBuffer<T, Ptr>{
+    /// Records a pointer that's about to be overwritten, if the queue is active and it isn't
+    /// cleared. Called by the write barrier before every store of a managed pointer.
+    pub fn enqueue(&mut self, old: &Ptr){
+        if self.queue.is_active() && !old.is_cleared(){
+            self.push(old.clone());
+        }
+    }
+
+    /// Records every pointer in the given value, if the queue is active, e.g. before a write
+    /// that may overwrite any of them, such as through [ManagedMem::write_field].
+    pub fn enqueue_edges(&mut self, value: &T, this: &Ptr) where T: GcCandidate<Ptr>{
+        if self.queue.is_active(){
+            value.trace(&mut |ptr: &Ptr| if !ptr.is_cleared(){
+                self.push(ptr.clone());
+            }, this);
+        }
+    }
+
+    /// Hands every pointer recorded so far to the queue.
+    pub fn flush(&mut self){
+        if !self.entries.is_empty(){
+            self.queue.complete(mem::take(&mut self.entries));
+        }
+    }
+
+    /// Returns the number of pointers recorded but not yet handed to the queue.
+    pub fn len(&self) -> usize{
+        return self.entries.len();
+    }
+
+    /// Returns whether every recorded pointer has been handed to the queue.
+    pub fn is_empty(&self) -> bool{
+        return self.entries.is_empty();
+    }
+
+    /// Returns the queue this buffer adds to.
+    pub fn queue(&self) -> &Arc<SatbQueue<T, Ptr>>{
+        return &self.queue;
+    }
+
+    fn push(&mut self, ptr: Ptr){
+        if self.entries.capacity() == 0{
+            self.entries.reserve_exact(self.queue.buffer_size);
+        }
+        self.entries.push(ptr);
+        if self.entries.len() >= self.queue.buffer_size{
+            self.flush();
+        }
+    }
+}
+
+impl<T: ?Sized, Ptr: HeapPtr<T>> Default for RememberedSet<T, Ptr>{
+    fn default() -> Self{
+        return RememberedSet::new();
+    }
+}
+
+impl<T: ?Sized, Ptr: HeapPtr<T>> Default for ForwardingTable<T, Ptr>{
+    fn default() -> Self{
+        return ForwardingTable::new();
+    }
+}
+
+impl<T: ?Sized, Ptr: HeapPtr<T>> Drop for SatbBuffer<T, Ptr>{
+    fn drop(&mut self){
+        // don't panic while unwinding from a panic that poisoned the lock
+        if !self.entries.is_empty(){
+            if let Ok(mut completed) = self.queue.completed.lock(){
+                self.queue.pending.fetch_add(self.entries.len(), Ordering::SeqCst);
+                completed.push(mem::take(&mut self.entries));
+            }
+        }
+    }
+}
+
+// safety: queues and buffers only store pointers and hand them back, never accessing the values
+// they point to
+unsafe impl<T: ?Sized + Send + Sync, Ptr: HeapPtr<T> + ThreadSafePtr> Send for SatbQueue<T, Ptr>{}
+
+unsafe impl<T: ?Sized + Send + Sync, Ptr: HeapPtr<T> + ThreadSafePtr> Sync for SatbQueue<T, Ptr>{}
+
+unsafe impl<T: ?Sized + Send + Sync, Ptr: HeapPtr<T> + ThreadSafePtr> Send for SatbBuffer<T, Ptr>{}
@@ -0,0 +1,151 @@
+//! Interior mutability for fields of managed values that hold managed pointers.
+//!
+//! Writing a managed pointer into a value must be reported to collectors with write barriers, such
+//! as incremental collectors that may have already scanned the value; see
+//! [ManagedMem::record_write]. [GcCell] and [GcRefCell] are written through the memory holding
+//! their value, which reports every pointer written to its barrier, if it has one, and every
+//! pointer overwritten to its [pre-write barrier](ManagedMem::record_overwrite).
+//!
+//! Since a cell is written through its memory, it's named by the pointer to the value containing
+//! it and a function returning the cell from that value, e.g. `|node: &Node| &node.next`.
+
+use std::cell::{Ref, RefCell};
+use std::fmt;
+use std::fmt::{Debug, Formatter};
+use crate::gc::{GcCandidate, ManagedMem};
+use crate::heap::HeapPtr;
+
+/// A single managed pointer in a managed value that can be replaced through a shared reference,
+/// reporting the old and new pointers to the memory's write barriers.
+pub struct GcCell<Ptr>{
+    ptr: RefCell<Ptr>
+}
+
+/// A field of a managed value that can be mutated through a shared reference, with borrows
+/// checked at runtime. Every pointer in the containing value is reported to the memory's write
+/// barriers around each mutation, as by [ManagedMem::write_field].
+pub struct GcRefCell<V>{
+    value: RefCell<V>
+}
+
+impl<Ptr: Clone> GcCell<Ptr>{
+    /// Creates a cell holding the given pointer.
+    pub fn new(ptr: Ptr) -> Self{
+        return GcCell{ ptr: RefCell::new(ptr) };
+    }
+
+    /// Returns the pointer in this cell.
+    pub fn get(&self) -> Ptr{
+        return self.ptr.borrow().clone();
+    }
+
+    /// Returns the pointer in this cell mutably, e.g. for [GcCandidate::adjust_ptrs]. No barrier
+    /// is needed, as the containing value is already borrowed mutably.
+    pub fn get_mut(&mut self) -> &mut Ptr{
+        return self.ptr.get_mut();
+    }
+
+    /// Passes the pointer in this cell to the given visitor, for [GcCandidate::trace].
+    pub fn trace(&self, visitor: &mut impl FnMut(&Ptr)){
+        visitor(&self.ptr.borrow());
+    }
+
+    /// Stores `new` in the cell given by `field` in the value at `owner`, reporting the write to
+    /// the memory's barrier, and returns the pointer it replaced, or `None` if `owner` doesn't
+    /// point to a value in the memory.
+    pub fn set<T, M>(mem: &mut M, owner: &Ptr, field: impl FnOnce(&T) -> &GcCell<Ptr>, new: Ptr) -> Option<Ptr>
+        where T: ?Sized + GcCandidate<Ptr>, Ptr: HeapPtr<T>, M: ManagedMem<T, Ptr> + ?Sized
+    {
+        let old = field(mem.get_by_ref(owner)?).ptr.replace(new.clone());
+        if mem.has_pre_write_barrier(){
+            mem.record_overwrite(owner, &old);
+        }
+        if mem.has_write_barrier(){
+            mem.record_write(owner, &new);
+        }
+        return Some(old);
+    }
+
+    /// Returns the pointer it held.
+    pub fn into_inner(self) -> Ptr{
+        return self.ptr.into_inner();
+    }
+}
+
+impl<V> GcRefCell<V>{
+    /// Creates a cell holding the given value.
+    pub fn new(value: V) -> Self{
+        return GcRefCell{ value: RefCell::new(value) };
+    }
+
+    /// Borrows the value in this cell.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the value is currently being mutated.
+    pub fn borrow(&self) -> Ref<'_, V>{
+        return self.value.borrow();
+    }
+
+    /// Borrows the value in this cell, or returns `None` if it's currently being mutated.
+    pub fn try_borrow(&self) -> Option<Ref<'_, V>>{
+        return self.value.try_borrow().ok();
+    }
+
+    /// Returns the value in this cell mutably, e.g. for [GcCandidate::adjust_ptrs]. No barrier is
+    /// needed, as the containing value is already borrowed mutably.
+    pub fn get_mut(&mut self) -> &mut V{
+        return self.value.get_mut();
+    }
+
+    /// Runs `update` on the value in the cell given by `field` in the value at `owner`, reporting
+    /// every pointer in the value at `owner` to the memory's barriers before and after, and
+    /// returns the result of `update`, or `None` if `owner` doesn't point to a value in the
+    /// memory.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the value in the cell is already borrowed.
+    pub fn update<T, Ptr, M, R>(mem: &mut M, owner: &Ptr, field: impl FnOnce(&T) -> &GcRefCell<V>, update: impl FnOnce(&mut V) -> R) -> Option<R>
+        where T: ?Sized + GcCandidate<Ptr>, Ptr: HeapPtr<T>, M: ManagedMem<T, Ptr> + ?Sized
+    {
+        if mem.has_pre_write_barrier(){
+            let mut overwritten = Vec::new();
+            mem.get_by_ref(owner)?.trace(&mut |old: &Ptr| overwritten.push(old.clone()), owner);
+            for old in &overwritten{
+                mem.record_overwrite(owner, old);
+            }
+        }
+        let result = update(&mut field(mem.get_by_ref(owner)?).value.borrow_mut());
+        if mem.has_write_barrier(){
+            let mut written = Vec::new();
+            mem.get_by_ref(owner)?.trace(&mut |dst: &Ptr| written.push(dst.clone()), owner);
+            for dst in &written{
+                mem.record_write(owner, dst);
+            }
+        }
+        return Some(result);
+    }
+
+    /// Returns the value it held.
+    pub fn into_inner(self) -> V{
+        return self.value.into_inner();
+    }
+}
+
+//////////////// impls
+
+impl<Ptr: Debug> Debug for GcCell<Ptr>{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result{
+        return f.debug_tuple("GcCell").field(&self.ptr.borrow()).finish();
+    }
+}
+
+impl<V: Debug> Debug for GcRefCell<V>{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result{
+        return match self.value.try_borrow(){
+            Ok(value) => f.debug_tuple("GcRefCell").field(&value).finish(),
+            Err(_) => f.write_str("GcRefCell(<borrowed>)")
+        };
+    }
+}
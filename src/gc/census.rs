@@ -0,0 +1,74 @@
+//! Histograms of the values stored in managed memory.
+
+use std::collections::BTreeMap;
+
+/// The number and total size of the values stored in a memory, in total and grouped by size and
+/// type, returned by [ManagedMem::census](crate::gc::ManagedMem::census).
+///
+/// Values are grouped by size into buckets keyed by powers of two: a value of `n` bytes is counted
+/// in the bucket for the smallest power of two that is at least `n`, and zero-sized values are
+/// counted in bucket 0. Values are only grouped by type if their type id is known, e.g. through
+/// [HeapPtr::type_tag](crate::heap::HeapPtr::type_tag).
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Census{
+    total: Bucket,
+    by_size: BTreeMap<usize, Bucket>,
+    by_type: BTreeMap<u32, Bucket>
+}
+
+/// The number and total size of a group of values in a [Census].
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct Bucket{
+    count: usize,
+    bytes: usize
+}
+
+impl Census{
+    /// Creates an empty census.
+    pub fn new() -> Self{
+        return Census::default();
+    }
+
+    /// Counts a value of the given size and type id.
+    pub fn record(&mut self, size: usize, type_id: Option<u32>){
+        self.total.add(size);
+        let key = if size == 0 { 0 } else { size.next_power_of_two() };
+        self.by_size.entry(key).or_default().add(size);
+        if let Some(id) = type_id{
+            self.by_type.entry(id).or_default().add(size);
+        }
+    }
+
+    /// Returns the number and total size of every value counted.
+    pub fn total(&self) -> Bucket{
+        return self.total;
+    }
+
+    /// Returns the values counted grouped by size, keyed by the power of two at least as large as
+    /// their size, in increasing order of size.
+    pub fn by_size(&self) -> &BTreeMap<usize, Bucket>{
+        return &self.by_size;
+    }
+
+    /// Returns the values counted grouped by type id, for values with a known type.
+    pub fn by_type(&self) -> &BTreeMap<u32, Bucket>{
+        return &self.by_type;
+    }
+}
+
+impl Bucket{
+    /// Returns the number of values in this group.
+    pub fn count(&self) -> usize{
+        return self.count;
+    }
+
+    /// Returns the total size of the values in this group, in bytes.
+    pub fn bytes(&self) -> usize{
+        return self.bytes;
+    }
+
+    fn add(&mut self, size: usize){
+        self.count += 1;
+        self.bytes += size;
+    }
+}
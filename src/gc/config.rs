@@ -0,0 +1,464 @@
+//! Configuration shared by managed memory implementations.
+
+use std::fmt;
+use std::fmt::{Debug, Formatter};
+use std::sync::Arc;
+use crate::gc::threads::{GcExecutor, Parallelism};
+use crate::heap::{DynSized, Heap, HeapPtr};
+
+/// Configuration for a [ManagedMem](crate::gc::ManagedMem) implementation, built by chaining
+/// `with_*` methods onto [MemConfig::new].
+///
+/// Collectors ignore options that don't apply to them; for example, [NoGcMem](crate::gc::NoGcMem)
+/// never triggers garbage collection, so its trigger thresholds have no effect.
+///
+/// A `usize` can be converted into a `MemConfig` with that heap size and default options.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MemConfig{
+    heap_size: usize,
+    growth: GrowthPolicy,
+    occupancy_trigger: Option<f64>,
+    allocation_trigger: Option<usize>,
+    threads: usize,
+    allocation_profiling: bool,
+    heap_verification: bool,
+    soft_limit: Option<usize>,
+    hard_limit: Option<usize>,
+    object_headers: bool,
+    sweep_in_place: bool,
+    foreign_pointers: ForeignPtrPolicy,
+    quarantine: Option<usize>,
+    identity_hashes: bool,
+    size_classes: bool,
+    skip_drop: bool,
+    forkable_heaps: bool,
+    evacuation_order: EvacuationOrder,
+    mark_stack_limit: Option<usize>,
+    write_barrier: WriteBarrier,
+    executor: Option<SharedExecutor>
+}
+
+/// How a heap's capacity grows when it runs out of space.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum GrowthPolicy{
+    /// Never grow; allocations fail when the heap is full.
+    Fixed,
+    /// Grow by a fixed number of bytes at a time.
+    Add(usize),
+    /// Multiply the capacity by the given factor.
+    Multiply(usize)
+}
+
+/// What collectors should do when a root or value points outside of the memory being collected,
+/// e.g. to a value in another memory, or to unmanaged memory.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ForeignPtrPolicy{
+    /// Ignore the pointer, leaving it unchanged.
+    Skip,
+    /// Ignore the pointer, counting it in the collection's [GcReport](crate::gc::report::GcReport)
+    /// and emitting a warning event with the `trace` feature.
+    Report,
+    /// Panic, as the pointer is likely a bug in a [GcCandidate](crate::gc::GcCandidate) implementation
+    /// or root set.
+    Panic
+}
+
+/// The order in which collectors that move values place surviving values in the new heap.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum EvacuationOrder{
+    /// Keep values in reverse order of allocation, as visited by [Heap::evacuate_into].
+    Allocation,
+    /// Place values in the order they were found while marking, which is approximately depth-first
+    /// from the roots, so that values are usually stored next to values they point to.
+    DepthFirst
+}
+
+/// How incremental and concurrent collectors keep track of pointers the mutator changes while
+/// they're marking.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum WriteBarrier{
+    /// Mark every pointer stored into a value ("Dijkstra" or incremental-update barrier), through
+    /// [ManagedMem::record_write](crate::gc::ManagedMem::record_write). Values that become
+    /// unreachable while marking may still be reclaimed, but every root must be rescanned before
+    /// marking can finish.
+    IncrementalUpdate,
+    /// Mark every pointer overwritten in a value ("Yuasa" or snapshot-at-the-beginning barrier),
+    /// through [ManagedMem::record_overwrite](crate::gc::ManagedMem::record_overwrite), so that
+    /// everything reachable when marking started survives. Values that become unreachable while
+    /// marking are kept until the next collection, but marking finishes once nothing is left to
+    /// scan.
+    SnapshotAtBeginning
+}
+
+impl MemConfig{
+    /// Creates a configuration with the given initial heap capacity in bytes, and default options:
+    /// a fixed-size heap, no automatic GC triggers or limits, a single GC thread, no object headers,
+    /// moving collection in allocation order, panicking on foreign pointers, an unbounded mark
+    /// stack, an incremental-update write barrier, and no allocation profiling, heap
+    /// verification, quarantine, identity hashes, or size classes. Reclaimed values are dropped.
+    pub fn new(heap_size: usize) -> Self{
+        return MemConfig{
+            heap_size,
+            growth: GrowthPolicy::Fixed,
+            occupancy_trigger: None,
+            allocation_trigger: None,
+            threads: 1,
+            allocation_profiling: false,
+            heap_verification: false,
+            soft_limit: None,
+            hard_limit: None,
+            object_headers: false,
+            sweep_in_place: false,
+            foreign_pointers: ForeignPtrPolicy::Panic,
+            quarantine: None,
+            identity_hashes: false,
+            size_classes: false,
+            skip_drop: false,
+            forkable_heaps: false,
+            evacuation_order: EvacuationOrder::Allocation,
+            mark_stack_limit: None,
+            write_barrier: WriteBarrier::IncrementalUpdate,
+            executor: None
+        };
+    }
+
+    /// Sets the initial heap capacity in bytes.
+    pub fn with_heap_size(mut self, heap_size: usize) -> Self{
+        self.heap_size = heap_size;
+        return self;
+    }
+
+    /// Sets how the heap's capacity grows when full.
+    pub fn with_growth(mut self, growth: GrowthPolicy) -> Self{
+        self.growth = growth;
+        return self;
+    }
+
+    /// Sets the fraction of the heap's capacity (between 0 and 1) that, once in use, should
+    /// trigger a collection; see [GcPolicy](crate::gc::policy::GcPolicy).
+    pub fn with_occupancy_trigger(mut self, fraction: f64) -> Self{
+        assert!(fraction > 0.0 && fraction <= 1.0, "MemConfig: occupancy trigger must be in (0, 1], got {fraction}");
+        self.occupancy_trigger = Some(fraction);
+        return self;
+    }
+
+    /// Sets the number of bytes that may be allocated after a collection before triggering another;
+    /// see [GcPolicy](crate::gc::policy::GcPolicy).
+    pub fn with_allocation_trigger(mut self, bytes: usize) -> Self{
+        self.allocation_trigger = Some(bytes);
+        return self;
+    }
+
+    /// Sets the number of threads that collectors may use, when no executor is set.
+    pub fn with_threads(mut self, threads: usize) -> Self{
+        assert!(threads > 0, "MemConfig: thread count must be positive");
+        self.threads = threads;
+        return self;
+    }
+
+    /// Sets the number of bytes in use past which a collection is triggered and the embedder is
+    /// notified; see [AutoGcMem](crate::gc::policy::AutoGcMem).
+    pub fn with_soft_limit(mut self, bytes: usize) -> Self{
+        self.soft_limit = Some(bytes);
+        return self;
+    }
+
+    /// Sets the number of bytes in use that allocations may never exceed, even if the heap has
+    /// space for them; see [AutoGcMem](crate::gc::policy::AutoGcMem).
+    pub fn with_hard_limit(mut self, bytes: usize) -> Self{
+        self.hard_limit = Some(bytes);
+        return self;
+    }
+
+    /// Sets whether to record the call site of every allocation; see [AllocProfile](crate::gc::profile::AllocProfile).
+    pub fn with_allocation_profiling(mut self, enabled: bool) -> Self{
+        self.allocation_profiling = enabled;
+        return self;
+    }
+
+    /// Sets whether collectors should check their invariants with [ManagedMem::verify](crate::gc::ManagedMem::verify)
+    /// before and after each collection, panicking if any are broken. Only has an effect in
+    /// debug builds.
+    pub fn with_heap_verification(mut self, enabled: bool) -> Self{
+        self.heap_verification = enabled;
+        return self;
+    }
+
+    /// Sets whether heaps should store an [ObjectHeader](crate::heap::ObjectHeader) before each value, which collectors may use
+    /// to keep mark bits instead of tracking them separately; see [Heap::with_headers].
+    pub fn with_object_headers(mut self, enabled: bool) -> Self{
+        self.object_headers = enabled;
+        return self;
+    }
+
+    /// Sets whether collectors that normally move surviving values should instead drop dead values
+    /// in place, reusing their space for later allocations; see [Heap::sweep]. Values then never
+    /// move, but the heap may become fragmented.
+    pub fn with_sweep_in_place(mut self, enabled: bool) -> Self{
+        self.sweep_in_place = enabled;
+        return self;
+    }
+
+    /// Sets what collectors should do with pointers outside of the memory being collected.
+    pub fn with_foreign_pointers(mut self, policy: ForeignPtrPolicy) -> Self{
+        self.foreign_pointers = policy;
+        return self;
+    }
+
+    /// Sets the number of collections for which collectors should remember values they dropped
+    /// or moved, panicking with where the value was allocated if it's accessed through a stale
+    /// pointer; see [Quarantine](crate::gc::quarantine::Quarantine). Intended for debugging, as
+    /// every allocation is tracked.
+    pub fn with_quarantine(mut self, collections: usize) -> Self{
+        self.quarantine = Some(collections);
+        return self;
+    }
+
+    /// Sets whether collectors that move values should assign every value an identity hash when
+    /// it's allocated, and keep it when the value moves; see [ManagedMem::identity_hash](crate::gc::ManagedMem::identity_hash).
+    pub fn with_identity_hashes(mut self, enabled: bool) -> Self{
+        self.identity_hashes = enabled;
+        return self;
+    }
+
+    /// Sets whether heaps should place values in slots grouped by size, so that space freed by
+    /// sweeping is reused without fragmenting the heap; see [Heap::with_size_classes]. Mostly
+    /// useful alongside [MemConfig::with_sweep_in_place].
+    pub fn with_size_classes(mut self, enabled: bool) -> Self{
+        self.size_classes = enabled;
+        return self;
+    }
+
+    /// Sets whether heaps should reuse the space of reclaimed values without dropping them, for
+    /// plain-old-data types whose drop glue does nothing; see [Heap::with_skip_drop]. Anything
+    /// owned by reclaimed values is leaked.
+    pub fn with_skip_drop(mut self, enabled: bool) -> Self{
+        self.skip_drop = enabled;
+        return self;
+    }
+
+    /// Sets whether heaps should be created with `Heap::forkable`, so that forks of them share
+    /// memory until either is written to; see [MarkAndSweepMem::fork_copy_on_write]. Only has an
+    /// effect with the `persist` feature on 64-bit Linux, and heaps that can't be mapped are
+    /// allocated as usual instead.
+    ///
+    /// [MarkAndSweepMem::fork_copy_on_write]: crate::gc::mas::MarkAndSweepMem::fork_copy_on_write
+    pub fn with_forkable_heaps(mut self, enabled: bool) -> Self{
+        self.forkable_heaps = enabled;
+        return self;
+    }
+
+    /// Sets the order in which collectors that move values place surviving values.
+    pub fn with_evacuation_order(mut self, order: EvacuationOrder) -> Self{
+        self.evacuation_order = order;
+        return self;
+    }
+
+    /// Sets the most pointers that collectors may hold on their mark stack, so that marking uses a
+    /// predictable amount of memory even for very deep or wide graphs, e.g. when collecting
+    /// because memory is short.
+    ///
+    /// Once the stack is full, values whose pointers don't fit are remembered by address, and
+    /// rescanned once the stack empties; marking then takes longer, but still finds every
+    /// reachable value.
+    pub fn with_mark_stack_limit(mut self, pointers: usize) -> Self{
+        assert!(pointers > 0, "MemConfig: mark stack limit must be positive");
+        self.mark_stack_limit = Some(pointers);
+        return self;
+    }
+
+    /// Sets the write barrier used by incremental and concurrent collectors while marking.
+    pub fn with_write_barrier(mut self, barrier: WriteBarrier) -> Self{
+        self.write_barrier = barrier;
+        return self;
+    }
+
+    /// Sets an executor, such as a [GcThreadPool](crate::gc::threads::GcThreadPool), that
+    /// collectors run their parallel work on instead of spawning threads; its thread count
+    /// replaces the one given by [MemConfig::with_threads].
+    pub fn with_executor(mut self, executor: Arc<dyn GcExecutor>) -> Self{
+        self.executor = Some(SharedExecutor(executor));
+        return self;
+    }
+
+    /// Returns the initial heap capacity in bytes.
+    pub fn heap_size(&self) -> usize{
+        return self.heap_size;
+    }
+
+    /// Returns how the heap's capacity grows when full.
+    pub fn growth(&self) -> GrowthPolicy{
+        return self.growth;
+    }
+
+    /// Returns the fraction of the heap's capacity that should trigger a collection, if any.
+    pub fn occupancy_trigger(&self) -> Option<f64>{
+        return self.occupancy_trigger;
+    }
+
+    /// Returns the number of bytes allocated after a collection that should trigger another, if any.
+    pub fn allocation_trigger(&self) -> Option<usize>{
+        return self.allocation_trigger;
+    }
+
+    /// Returns the number of threads that collectors may use.
+    pub fn threads(&self) -> usize{
+        return match &self.executor{
+            Some(executor) => executor.0.threads(),
+            None => self.threads
+        };
+    }
+
+    /// Returns the executor that collectors run their parallel work on, if any.
+    pub fn executor(&self) -> Option<&Arc<dyn GcExecutor>>{
+        return self.executor.as_ref().map(|executor| &executor.0);
+    }
+
+    /// Returns the threads that collectors should split their parallel work between.
+    pub fn parallelism(&self) -> Parallelism<'_>{
+        return match &self.executor{
+            Some(executor) => Parallelism::Executor(&*executor.0),
+            None => Parallelism::Threads(self.threads)
+        };
+    }
+
+    /// Returns the number of bytes in use past which a collection should be triggered, if any.
+    pub fn soft_limit(&self) -> Option<usize>{
+        return self.soft_limit;
+    }
+
+    /// Returns the number of bytes in use that allocations may not exceed, if any.
+    pub fn hard_limit(&self) -> Option<usize>{
+        return self.hard_limit;
+    }
+
+    /// Returns whether the call site of every allocation should be recorded.
+    pub fn allocation_profiling(&self) -> bool{
+        return self.allocation_profiling;
+    }
+
+    /// Returns whether collectors should verify their invariants around each collection.
+    pub fn heap_verification(&self) -> bool{
+        return self.heap_verification;
+    }
+
+    /// Returns whether heaps should store an [ObjectHeader](crate::heap::ObjectHeader) before each value.
+    pub fn object_headers(&self) -> bool{
+        return self.object_headers;
+    }
+
+    /// Returns whether collectors should drop dead values in place instead of moving surviving values.
+    pub fn sweep_in_place(&self) -> bool{
+        return self.sweep_in_place;
+    }
+
+    /// Returns what collectors should do with pointers outside of the memory being collected.
+    pub fn foreign_pointers(&self) -> ForeignPtrPolicy{
+        return self.foreign_pointers;
+    }
+
+    /// Returns the number of collections for which dropped or moved values should be remembered, if any.
+    pub fn quarantine(&self) -> Option<usize>{
+        return self.quarantine;
+    }
+
+    /// Returns whether values should be given identity hashes that are kept when they move.
+    pub fn identity_hashes(&self) -> bool{
+        return self.identity_hashes;
+    }
+
+    /// Returns whether heaps should place values in slots grouped by size.
+    pub fn size_classes(&self) -> bool{
+        return self.size_classes;
+    }
+
+    /// Returns whether heaps should reuse the space of reclaimed values without dropping them.
+    pub fn skip_drop(&self) -> bool{
+        return self.skip_drop;
+    }
+
+    /// Returns whether heaps should be created so that forks of them share memory.
+    pub fn forkable_heaps(&self) -> bool{
+        return self.forkable_heaps;
+    }
+
+    /// Returns the order in which collectors that move values place surviving values.
+    pub fn evacuation_order(&self) -> EvacuationOrder{
+        return self.evacuation_order;
+    }
+
+    /// Returns the most pointers that collectors may hold on their mark stack, if limited.
+    pub fn mark_stack_limit(&self) -> Option<usize>{
+        return self.mark_stack_limit;
+    }
+
+    /// Returns the write barrier used by incremental and concurrent collectors while marking.
+    pub fn write_barrier(&self) -> WriteBarrier{
+        return self.write_barrier;
+    }
+
+    // creates a heap of the given capacity, with headers, size classes, skipped drops and shared
+    // memory if configured
+    pub(crate) fn create_heap<T: ?Sized + DynSized, Ptr: HeapPtr<T>>(&self, size: usize) -> Heap<T, Ptr>{
+        #[cfg(all(feature = "persist", target_os = "linux", target_pointer_width = "64"))]
+        let forkable = match self.forkable_heaps{
+            true => Heap::forkable(size, self.object_headers).ok(),
+            false => None
+        };
+        #[cfg(not(all(feature = "persist", target_os = "linux", target_pointer_width = "64")))]
+        let forkable = None;
+        let heap = match (forkable, self.object_headers){
+            (Some(heap), _) => heap,
+            (None, true) => Heap::with_headers(size),
+            (None, false) => Heap::new(size)
+        };
+        let heap = match self.size_classes{
+            true => heap.with_size_classes(),
+            false => heap
+        };
+        return match self.skip_drop{
+            true => heap.with_skip_drop(),
+            false => heap
+        };
+    }
+}
+
+// an executor shared between configurations, equal only to clones of itself
+#[derive(Clone)]
+struct SharedExecutor(Arc<dyn GcExecutor>);
+
+impl GrowthPolicy{
+    /// Returns the new capacity for a heap of the given capacity that needs at least `needed`
+    /// bytes in total, or `None` if it may not grow.
+    pub fn grow(&self, capacity: usize, needed: usize) -> Option<usize>{
+        let mut next = capacity;
+        while next < needed{
+            next = match self{
+                GrowthPolicy::Fixed => return None,
+                GrowthPolicy::Add(n) => next.checked_add((*n).max(1))?,
+                GrowthPolicy::Multiply(n) => next.max(1).checked_mul((*n).max(2))?
+            };
+        }
+        return Some(next);
+    }
+}
+
+impl Debug for SharedExecutor{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result{
+        return f.debug_struct("GcExecutor")
+            .field("threads", &self.0.threads())
+            .finish();
+    }
+}
+
+impl PartialEq for SharedExecutor{
+    fn eq(&self, other: &Self) -> bool{
+        return Arc::ptr_eq(&self.0, &other.0);
+    }
+}
+
+impl From<usize> for MemConfig{
+    fn from(heap_size: usize) -> Self{
+        return MemConfig::new(heap_size);
+    }
+}
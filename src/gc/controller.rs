@@ -0,0 +1,162 @@
+//! Running collections on a background thread.
+
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread;
+use std::thread::JoinHandle;
+use crate::gc::{GcCandidate, ManagedMem};
+use crate::gc::safepoint::Safepoint;
+use crate::gc::shared::SharedMem;
+use crate::heap::{HeapPtr, ThreadSafePtr};
+
+/// Owns a background thread that collects a [SharedMem] on request.
+///
+/// Collections run as described in the [shared memory protocol](crate::gc::shared): they wait
+/// for in-progress accesses through handles, and block new ones until they complete, but the
+/// threads requesting collections don't need to wait for them.
+///
+/// Collections are numbered from 1 in the order they complete. Requests made while a collection
+/// is pending are merged into it. The background thread is stopped when this is dropped.
+///
+/// Its [safepoint](GcController::safepoint) is requested while a collection is pending, so that
+/// mutators can poll it and step out of long-running accesses.
+pub struct GcController{
+    shared: Arc<Control>,
+    thread: Option<JoinHandle<()>>
+}
+
+struct Control{
+    state: Mutex<ControlState>,
+    // notified when a collection is requested, or on shutdown
+    requested: Condvar,
+    // notified when a collection completes
+    completed: Condvar,
+    // requested while a collection is pending
+    safepoint: Safepoint
+}
+
+struct ControlState{
+    requested: u64,
+    completed: u64,
+    running: bool,
+    shutdown: bool,
+    callbacks: Vec<Box<dyn FnMut(u64) + Send>>
+}
+
+impl GcController{
+    /// Starts a background thread that collects the given memory on request.
+    pub fn new<T, Ptr, M>(mem: SharedMem<T, Ptr, M>) -> Self
+        where T: ?Sized + GcCandidate<Ptr> + Send + Sync + 'static, Ptr: HeapPtr<T> + ThreadSafePtr + 'static, M: ManagedMem<T, Ptr> + Send + Sync + 'static
+    {
+        let shared = Arc::new(Control{
+            state: Mutex::new(ControlState{
+                requested: 0,
+                completed: 0,
+                running: false,
+                shutdown: false,
+                callbacks: Vec::new()
+            }),
+            requested: Condvar::new(),
+            completed: Condvar::new(),
+            safepoint: Safepoint::new()
+        });
+        let control = shared.clone();
+        let thread = thread::Builder::new()
+            .name("swifer-gc".to_string())
+            .spawn(move || control.run(mem))
+            .expect("GcController: could not spawn GC thread");
+        return GcController{
+            shared,
+            thread: Some(thread)
+        };
+    }
+
+    /// Requests a collection without waiting for it, returning the number of the collection
+    /// that will satisfy this request; see [GcController::wait_for].
+    pub fn request_gc(&self) -> u64{
+        let mut state = self.shared.lock();
+        // a collection that is already running may have missed changes made before this request
+        let next = state.completed + if state.running { 2 } else { 1 };
+        state.requested = state.requested.max(next);
+        self.shared.safepoint.request();
+        self.shared.requested.notify_one();
+        return state.requested;
+    }
+
+    /// Requests a collection and waits for it to complete.
+    pub fn wait_for_gc(&self){
+        let ticket = self.request_gc();
+        self.wait_for(ticket);
+    }
+
+    /// Waits until the collection with the given number has completed.
+    pub fn wait_for(&self, collection: u64){
+        let mut state = self.shared.lock();
+        while state.completed < collection && !state.shutdown{
+            state = self.shared.completed.wait(state).expect("GcController: lock poisoned");
+        }
+    }
+
+    /// Returns the number of collections completed so far.
+    pub fn collections(&self) -> u64{
+        return self.shared.lock().completed;
+    }
+
+    /// Returns the safepoint requested while a collection is pending, and cleared once every
+    /// requested collection has completed.
+    pub fn safepoint(&self) -> Safepoint{
+        return self.shared.safepoint.clone();
+    }
+
+    /// Registers a function to be called on the GC thread with the number of each collection
+    /// after it completes. The function must not use this controller.
+    pub fn on_complete(&self, callback: impl FnMut(u64) + Send + 'static){
+        self.shared.lock().callbacks.push(Box::new(callback));
+    }
+}
+
+impl Control{
+    fn run<T, Ptr, M>(&self, mem: SharedMem<T, Ptr, M>)
+        where T: ?Sized + GcCandidate<Ptr> + Send + Sync, Ptr: HeapPtr<T>, M: ManagedMem<T, Ptr> + Send
+    {
+        loop{
+            {
+                let mut state = self.lock();
+                while state.requested <= state.completed && !state.shutdown{
+                    state = self.requested.wait(state).expect("GcController: lock poisoned");
+                }
+                if state.shutdown{
+                    return;
+                }
+                state.running = true;
+            }
+            mem.gc();
+            let mut state = self.lock();
+            state.running = false;
+            state.completed += 1;
+            let completed = state.completed;
+            if state.completed >= state.requested{
+                self.safepoint.clear();
+            }
+            for callback in &mut state.callbacks{
+                callback(completed);
+            }
+            self.completed.notify_all();
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, ControlState>{
+        return self.state.lock().expect("GcController: lock poisoned");
+    }
+}
+
+impl Drop for GcController{
+    fn drop(&mut self){
+        self.shared.lock().shutdown = true;
+        self.shared.requested.notify_all();
+        self.shared.completed.notify_all();
+        if let Some(thread) = self.thread.take(){
+            // a panic on the GC thread has already been reported
+            let _ = thread.join();
+        }
+    }
+}
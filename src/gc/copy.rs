@@ -0,0 +1,89 @@
+//! Deep copies of object graphs between managed memories.
+
+use std::collections::{HashMap, HashSet};
+use crate::gc::{GcCandidate, ManagedMem};
+use crate::heap::{AllocError, HeapPtr};
+
+/// Copies the value at `root` in `src`, and every value reachable from it, into `dst`, returning a
+/// pointer to the copy of `root`. Pointers between the copied values are updated to point to the
+/// copies, and values reachable through several paths or cycles are copied once. Pointers to
+/// values outside of `src` are copied as-is.
+///
+/// `dst` must not collect while the values are pushed. If any can't be pushed, the copies already
+/// pushed are removed and the error is returned.
+///
+/// # Panics
+///
+/// Panics if `root` is not in `src`.
+pub fn clone_graph<T, Ptr, S, D>(src: &S, root: &Ptr, dst: &mut D) -> Result<Ptr, AllocError<T>>
+    where T: GcCandidate<Ptr> + Clone, Ptr: HeapPtr<T>, S: ManagedMem<T, Ptr> + ?Sized, D: ManagedMem<T, Ptr> + ?Sized
+{
+    return clone_graph_with(src, root, dst, |v| Box::new(v.clone()));
+}
+
+/// Copies an object graph between memories, as with [clone_graph], using the given function to
+/// copy each value.
+///
+/// # Panics
+///
+/// Panics if `root` is not in `src`.
+pub fn clone_graph_with<T, Ptr, S, D>(src: &S, root: &Ptr, dst: &mut D, copy: impl FnMut(&T) -> Box<T>) -> Result<Ptr, AllocError<T>>
+    where T: ?Sized + GcCandidate<Ptr>, Ptr: HeapPtr<T>, S: ManagedMem<T, Ptr> + ?Sized, D: ManagedMem<T, Ptr> + ?Sized
+{
+    let copies = copy_out(src, root, copy);
+    return copy_in(dst, root, copies);
+}
+
+// copies the value at `root` and every value in `src` reachable from it, along with their
+// original pointers
+pub(crate) fn copy_out<T, Ptr, M>(src: &M, root: &Ptr, mut copy: impl FnMut(&T) -> Box<T>) -> Vec<(Ptr, Box<T>)>
+    where T: ?Sized + GcCandidate<Ptr>, Ptr: HeapPtr<T>, M: ManagedMem<T, Ptr> + ?Sized
+{
+    assert!(src.contains_ptr_ignoring_meta(root), "value to copy is not in the memory");
+    let mut copies = Vec::new();
+    let mut seen: HashSet<*const ()> = HashSet::new();
+    let mut pending = vec![root.clone()];
+    while let Some(ptr) = pending.pop(){
+        if !seen.insert(ptr.to_raw_ptr().cast::<()>()){
+            continue;
+        }
+        // pointers outside of the memory are copied as-is
+        if let Some(value) = src.get_by_ref_ignoring_meta(&ptr){
+            value.trace(&mut |edge: &Ptr| pending.push(edge.clone()), &ptr);
+            copies.push((ptr, copy(value)));
+        }
+    }
+    return copies;
+}
+
+// pushes copies made by `copy_out` into `dst`, pointing them to each other, and returns a pointer
+// to the copy of `root`
+pub(crate) fn copy_in<T, Ptr, M>(dst: &mut M, root: &Ptr, copies: Vec<(Ptr, Box<T>)>) -> Result<Ptr, AllocError<T>>
+    where T: ?Sized + GcCandidate<Ptr>, Ptr: HeapPtr<T>, M: ManagedMem<T, Ptr> + ?Sized
+{
+    let mut moved: HashMap<*const (), Ptr> = HashMap::with_capacity(copies.len());
+    for (old, value) in copies{
+        match dst.push(value){
+            Ok(new) => { moved.insert(old.to_raw_ptr().cast::<()>(), new); }
+            Err(err) => {
+                let pushed: HashSet<*const ()> = moved.values().map(|p| p.to_raw_ptr().cast::<()>()).collect();
+                dst.retain(|p, _| !pushed.contains(&p.to_raw_ptr().cast::<()>()));
+                return Err(err);
+            }
+        }
+    }
+    for new in moved.values(){
+        if let Some(value) = dst.get_by_ignoring_meta(new){
+            value.visit_edges(&mut |edge: &mut Ptr| {
+                if let Some(target) = moved.get(&edge.to_raw_ptr().cast::<()>()){
+                    let mut target = target.clone();
+                    target.copy_meta(edge);
+                    *edge = target;
+                }
+            }, new);
+        }
+    }
+    let mut copied = moved[&root.to_raw_ptr().cast::<()>()].clone();
+    copied.copy_meta(root);
+    return Ok(copied);
+}
@@ -0,0 +1,151 @@
+//! Tools for inspecting managed memory while debugging, enabled by the `debug` feature.
+
+use std::collections::HashMap;
+use std::io;
+use std::io::Write;
+use std::mem;
+use crate::gc::{GcCandidate, ManagedMem};
+use crate::heap::{DynSized, Heap, HeapPtr, Occupancy};
+
+// the number of buckets shown in each row of a layout map
+const ROW_BUCKETS: usize = 64;
+// the size of each bucket in a layout SVG, in pixels
+const SVG_CELL: usize = 8;
+
+/// Writes a [Graphviz](https://graphviz.org/) DOT graph of every value in the given memory and
+/// the pointers between them, as found by [GcCandidate::collect_managed_pointers].
+///
+/// Values are labelled with their index, address, and size; values pointed to by `roots` are
+/// highlighted. Pointers to values outside of the memory are drawn to a separate dashed node.
+pub fn write_dot<T, Ptr, M>(mem: &M, roots: &[Ptr], w: &mut impl Write) -> io::Result<()>
+    where T: ?Sized + GcCandidate<Ptr>, Ptr: HeapPtr<T>, M: ManagedMem<T, Ptr> + ?Sized
+{
+    // number values in order, so that edges can refer to them
+    let mut ids: HashMap<*const (), usize> = HashMap::with_capacity(mem.len());
+    let mut edges: Vec<(usize, Vec<Ptr>)> = Vec::with_capacity(mem.len());
+    let mut nodes: Vec<(*const (), usize)> = Vec::with_capacity(mem.len());
+    mem.for_each(|v, ptr| {
+        let addr = ptr.to_raw_ptr().cast::<()>();
+        ids.insert(addr, nodes.len());
+        edges.push((nodes.len(), v.collect_managed_pointers(ptr)));
+        nodes.push((addr, mem::size_of_val(v)));
+    });
+    let rooted: Vec<usize> = roots.iter().filter_map(|r| ids.get(&r.to_raw_ptr().cast::<()>()).copied()).collect();
+
+    writeln!(w, "digraph heap {{")?;
+    writeln!(w, "    node [shape=box];")?;
+    for (id, (addr, size)) in nodes.iter().enumerate(){
+        let style = if rooted.contains(&id) { ", style=filled, fillcolor=lightblue, penwidth=2" } else { "" };
+        writeln!(w, "    n{id} [label=\"#{id}\\n{addr:?}\\n{size} bytes\"{style}];")?;
+    }
+    let mut external = false;
+    for (from, targets) in &edges{
+        for target in targets{
+            match ids.get(&target.to_raw_ptr().cast::<()>()){
+                Some(to) => writeln!(w, "    n{from} -> n{to};")?,
+                None => {
+                    external = true;
+                    writeln!(w, "    n{from} -> external [style=dashed];")?
+                }
+            }
+        }
+    }
+    if external{
+        writeln!(w, "    external [label=\"(not in memory)\", style=dashed];")?;
+    }
+    writeln!(w, "}}")?;
+    return Ok(());
+}
+
+/// Writes a map of how the given heap is used, as found by [Heap::occupancy], as text with one
+/// character per bucket of `bucket_size` bytes and 64 buckets per row.
+///
+/// Each bucket is shown as whichever of its bytes are most common: `#` for live, `x` for dead,
+/// `.` for free and a space for unused. With `ansi`, buckets are also coloured by how much of
+/// them is live. Rows start with the offset of their first bucket, and the map is followed by
+/// the total of each kind of byte.
+pub fn write_layout_map<T, Ptr>(heap: &Heap<T, Ptr>, bucket_size: usize, ansi: bool, w: &mut impl Write) -> io::Result<()>
+    where T: ?Sized + DynSized, Ptr: HeapPtr<T>
+{
+    let buckets = heap.occupancy(bucket_size);
+    for row in buckets.chunks(ROW_BUCKETS){
+        write!(w, "{:#010x} |", row[0].start())?;
+        for bucket in row{
+            let symbol = match dominant(bucket){
+                Kind::Live => '#',
+                Kind::Dead => 'x',
+                Kind::Free => '.',
+                Kind::Unused => ' '
+            };
+            if ansi{
+                // green when fully live, through yellow, to red when fully dead
+                let colour = match bucket.live() * 4 / bucket.size().max(1){
+                    _ if bucket.live() == 0 && bucket.dead() == 0 => 90,
+                    4 | 3 => 32,
+                    2 | 1 => 33,
+                    _ => 31
+                };
+                write!(w, "\x1b[{colour}m{symbol}\x1b[0m")?;
+            }else{
+                write!(w, "{symbol}")?;
+            }
+        }
+        writeln!(w, "|")?;
+    }
+    let total = |count: fn(&Occupancy) -> usize| buckets.iter().map(count).sum::<usize>();
+    writeln!(w, "live {}B, dead {}B, free {}B, unused {}B",
+        total(Occupancy::live), total(Occupancy::dead), total(Occupancy::free), total(Occupancy::unused))?;
+    return Ok(());
+}
+
+/// Writes a map of how the given heap is used, as found by [Heap::occupancy], as an SVG image
+/// with one cell per bucket of `bucket_size` bytes and 64 buckets per row.
+///
+/// Each cell is split vertically into its live, dead, free and unused bytes, in that order, and
+/// titled with its offset and counts.
+pub fn write_layout_svg<T, Ptr>(heap: &Heap<T, Ptr>, bucket_size: usize, w: &mut impl Write) -> io::Result<()>
+    where T: ?Sized + DynSized, Ptr: HeapPtr<T>
+{
+    let buckets = heap.occupancy(bucket_size);
+    let width = buckets.len().min(ROW_BUCKETS) * SVG_CELL;
+    let height = buckets.chunks(ROW_BUCKETS).count() * SVG_CELL;
+    writeln!(w, "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\">")?;
+    for (idx, bucket) in buckets.iter().enumerate(){
+        let x = idx % ROW_BUCKETS * SVG_CELL;
+        let mut y = (idx / ROW_BUCKETS * SVG_CELL) as f64;
+        writeln!(w, "  <g><title>{:#x}: live {}B, dead {}B, free {}B, unused {}B</title>",
+            bucket.start(), bucket.live(), bucket.dead(), bucket.free(), bucket.unused())?;
+        for (bytes, colour) in [(bucket.live(), "#2e8b57"), (bucket.dead(), "#cd5c5c"), (bucket.free(), "#87ceeb"), (bucket.unused(), "#eeeeee")]{
+            if bytes == 0{
+                continue;
+            }
+            let part = (bytes * SVG_CELL) as f64 / bucket.size() as f64;
+            writeln!(w, "    <rect x=\"{x}\" y=\"{y:.2}\" width=\"{SVG_CELL}\" height=\"{part:.2}\" fill=\"{colour}\"/>")?;
+            y += part;
+        }
+        writeln!(w, "  </g>")?;
+    }
+    writeln!(w, "</svg>")?;
+    return Ok(());
+}
+
+// the kinds of bytes in a bucket, in order of precedence when equally common
+enum Kind{
+    Live,
+    Dead,
+    Free,
+    Unused
+}
+
+// the most common kind of byte in the given bucket
+fn dominant(bucket: &Occupancy) -> Kind{
+    let counts = [(bucket.live(), Kind::Live), (bucket.dead(), Kind::Dead), (bucket.free(), Kind::Free), (bucket.unused(), Kind::Unused)];
+    let mut best = None;
+    for (count, kind) in counts{
+        match best{
+            Some((most, _)) if most >= count => {}
+            _ => best = Some((count, kind))
+        }
+    }
+    return best.map_or(Kind::Unused, |(_, kind)| kind);
+}
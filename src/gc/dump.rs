@@ -0,0 +1,172 @@
+//! A compact binary format for capturing the contents of managed memory, to analyze offline.
+//!
+//! A dump starts with the magic bytes `SWDP` and a format version byte, followed by the number of
+//! values as a `u64`. Each value is then written as its address and size (`u64`s), a flag byte
+//! followed by a `u32` type id if the flag is 1, and the number of pointers in it (`u32`)
+//! followed by the address of each (`u64`s). All integers are little-endian.
+//!
+//! Dumps are written by [ManagedMem::dump] or [write_dump], and read by [HeapDump::read].
+
+use std::collections::HashMap;
+use std::io;
+use std::io::{Read, Write};
+use std::mem;
+use crate::gc::{GcCandidate, ManagedMem};
+use crate::heap::HeapPtr;
+
+const MAGIC: &[u8; 4] = b"SWDP";
+const VERSION: u8 = 1;
+
+/// Writes a dump of every value in the given memory, using `type_id` to find the type ids of
+/// values where available.
+pub fn write_dump<T, Ptr, M>(mem: &M, w: &mut dyn Write, mut type_id: impl FnMut(&T, &Ptr) -> Option<u32>) -> io::Result<()>
+    where T: ?Sized + GcCandidate<Ptr>, Ptr: HeapPtr<T>, M: ManagedMem<T, Ptr> + ?Sized
+{
+    w.write_all(MAGIC)?;
+    w.write_all(&[VERSION])?;
+    w.write_all(&(mem.len() as u64).to_le_bytes())?;
+    let mut result = Ok(());
+    let mut edges: Vec<u64> = Vec::new();
+    mem.for_each(|v, ptr| {
+        if result.is_err(){
+            return;
+        }
+        edges.clear();
+        v.trace(&mut |p: &Ptr| edges.push(address(p)), ptr);
+        result = write_object(w, address(ptr), mem::size_of_val(v) as u64, type_id(v, ptr), &edges);
+    });
+    return result;
+}
+
+fn write_object(w: &mut dyn Write, address: u64, size: u64, type_id: Option<u32>, edges: &[u64]) -> io::Result<()>{
+    w.write_all(&address.to_le_bytes())?;
+    w.write_all(&size.to_le_bytes())?;
+    match type_id{
+        Some(id) => {
+            w.write_all(&[1])?;
+            w.write_all(&id.to_le_bytes())?;
+        }
+        None => w.write_all(&[0])?
+    }
+    w.write_all(&(edges.len() as u32).to_le_bytes())?;
+    for edge in edges{
+        w.write_all(&edge.to_le_bytes())?;
+    }
+    return Ok(());
+}
+
+fn address<T: ?Sized, Ptr: HeapPtr<T>>(ptr: &Ptr) -> u64{
+    return ptr.to_raw_ptr().cast::<()>() as usize as u64;
+}
+
+/// A heap dump read back from the format written by [write_dump].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct HeapDump{
+    objects: Vec<DumpedObject>,
+    by_address: HashMap<u64, usize>
+}
+
+/// A single value recorded in a [HeapDump].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DumpedObject{
+    address: u64,
+    size: u64,
+    type_id: Option<u32>,
+    edges: Vec<u64>
+}
+
+impl HeapDump{
+    /// Reads a dump, returning an error of kind [io::ErrorKind::InvalidData] if it is malformed.
+    pub fn read(mut r: impl Read) -> io::Result<HeapDump>{
+        let mut magic = [0; 4];
+        r.read_exact(&mut magic)?;
+        if &magic != MAGIC{
+            return Err(invalid("not a heap dump"));
+        }
+        let version = read_u8(&mut r)?;
+        if version != VERSION{
+            return Err(invalid("unsupported heap dump version"));
+        }
+        let count = read_u64(&mut r)?;
+        let mut objects = Vec::new();
+        let mut by_address = HashMap::new();
+        for i in 0..count as usize{
+            let address = read_u64(&mut r)?;
+            let size = read_u64(&mut r)?;
+            let type_id = match read_u8(&mut r)?{
+                0 => None,
+                1 => Some(read_u32(&mut r)?),
+                _ => return Err(invalid("invalid type id flag"))
+            };
+            let edge_count = read_u32(&mut r)?;
+            let edges = (0..edge_count).map(|_| read_u64(&mut r)).collect::<io::Result<Vec<u64>>>()?;
+            objects.push(DumpedObject{ address, size, type_id, edges });
+            by_address.insert(address, i);
+        }
+        return Ok(HeapDump{ objects, by_address });
+    }
+
+    /// Returns every value in the dump, in the order they were stored.
+    pub fn objects(&self) -> &[DumpedObject]{
+        return &self.objects;
+    }
+
+    /// Returns the value at the given address, if there was one.
+    pub fn get(&self, address: u64) -> Option<&DumpedObject>{
+        return self.by_address.get(&address).map(|i| &self.objects[*i]);
+    }
+
+    /// Returns every value that points to the value at the given address.
+    pub fn referrers(&self, address: u64) -> impl Iterator<Item = &DumpedObject>{
+        return self.objects.iter().filter(move |o| o.edges.contains(&address));
+    }
+
+    /// Returns the total size of every value in the dump, in bytes.
+    pub fn total_size(&self) -> u64{
+        return self.objects.iter().map(|o| o.size).sum();
+    }
+}
+
+impl DumpedObject{
+    /// Returns the address the value was stored at.
+    pub fn address(&self) -> u64{
+        return self.address;
+    }
+
+    /// Returns the size of the value, in bytes.
+    pub fn size(&self) -> u64{
+        return self.size;
+    }
+
+    /// Returns the type id of the value, if it was known.
+    pub fn type_id(&self) -> Option<u32>{
+        return self.type_id;
+    }
+
+    /// Returns the addresses of every value this value pointed to.
+    pub fn edges(&self) -> &[u64]{
+        return &self.edges;
+    }
+}
+
+fn invalid(msg: &str) -> io::Error{
+    return io::Error::new(io::ErrorKind::InvalidData, msg);
+}
+
+fn read_u8(r: &mut impl Read) -> io::Result<u8>{
+    let mut buf = [0; 1];
+    r.read_exact(&mut buf)?;
+    return Ok(buf[0]);
+}
+
+fn read_u32(r: &mut impl Read) -> io::Result<u32>{
+    let mut buf = [0; 4];
+    r.read_exact(&mut buf)?;
+    return Ok(u32::from_le_bytes(buf));
+}
+
+fn read_u64(r: &mut impl Read) -> io::Result<u64>{
+    let mut buf = [0; 8];
+    r.read_exact(&mut buf)?;
+    return Ok(u64::from_le_bytes(buf));
+}
@@ -0,0 +1,503 @@
+//! An object-safe facade over [ManagedMem], for choosing collectors at runtime.
+
+use std::io;
+use std::io::Write;
+use crate::gc::{GcCandidate, ManagedMem, Placement};
+use crate::gc::census::Census;
+use crate::gc::hooks::{GcHooks, RelocationHooks};
+use crate::gc::log::GcLog;
+use crate::gc::pauses::PauseTimes;
+use crate::gc::profile::AllocProfile;
+use crate::gc::survival::SurvivalStats;
+use crate::gc::report::GcReport;
+use crate::gc::roots::RootProvider;
+use crate::heap::{AllocError, HeapPtr, VerifyError};
+
+/// An object-safe version of [ManagedMem], usable as `Box<dyn DynManagedMem<T, Ptr>>`.
+///
+/// Automatically implemented for every [ManagedMem]. Methods that take closures take them by
+/// `&mut dyn FnMut` instead of `impl Fn`. `Box<dyn DynManagedMem<T, Ptr>>` implements [ManagedMem]
+/// itself, so it can be used anywhere a specific collector can.
+pub trait DynManagedMem<T, Ptr = *const T>
+    where T: ?Sized + GcCandidate<Ptr>, Ptr: HeapPtr<T>
+{
+    /// See [ManagedMem::push].
+    #[track_caller]
+    fn push(&mut self, v: Box<T>) -> Result<Ptr, AllocError<T>>;
+
+    /// See [ManagedMem::push_with].
+    #[track_caller]
+    fn push_with(&mut self, v: Box<T>, with: &mut dyn FnMut(Ptr) -> Ptr) -> Result<Ptr, AllocError<T>>;
+
+    /// See [ManagedMem::push_placed].
+    #[track_caller]
+    fn push_placed(&mut self, v: Box<T>, placement: Placement, with: &mut dyn FnMut(Ptr) -> Ptr) -> Result<Ptr, AllocError<T>>;
+
+    /// See [ManagedMem::get].
+    fn get(&self, idx: usize) -> &T;
+
+    /// See [ManagedMem::get_mut].
+    fn get_mut(&mut self, idx: usize) -> &mut T;
+
+    /// See [ManagedMem::get_by].
+    fn get_by(&mut self, ptr: &Ptr) -> Option<&mut T>;
+
+    /// See [ManagedMem::get_by_ref].
+    fn get_by_ref(&self, ptr: &Ptr) -> Option<&T>;
+
+    /// See [ManagedMem::len].
+    fn len(&self) -> usize;
+
+    /// See [ManagedMem::contains_ptr].
+    fn contains_ptr(&self, ptr: &Ptr) -> bool;
+
+    /// See [ManagedMem::contains_ptr_ignoring_meta].
+    fn contains_ptr_ignoring_meta(&self, ptr: &Ptr) -> bool;
+
+    /// See [ManagedMem::get_by_ignoring_meta].
+    fn get_by_ignoring_meta(&mut self, ptr: &Ptr) -> Option<&mut T>;
+
+    /// See [ManagedMem::get_by_ref_ignoring_meta].
+    fn get_by_ref_ignoring_meta(&self, ptr: &Ptr) -> Option<&T>;
+
+    /// See [ManagedMem::to_full_ptr].
+    fn to_full_ptr(&self, ptr: &Ptr) -> Option<Ptr>;
+
+    /// See [ManagedMem::identity_hash].
+    fn identity_hash(&self, ptr: &Ptr) -> Option<u64>;
+
+    /// See [ManagedMem::for_each].
+    fn for_each(&self, cb: &mut dyn FnMut(&T, &Ptr));
+
+    /// See [ManagedMem::retain].
+    fn retain(&mut self, f: &mut dyn FnMut(&Ptr, &T) -> bool);
+
+    /// See [ManagedMem::gc].
+    ///
+    /// # Safety
+    ///
+    /// See [ManagedMem::gc].
+    unsafe fn gc(&mut self, roots: Vec<*mut Ptr>, weaks: Vec<*mut Ptr>);
+
+    /// See [ManagedMem::gc_minor].
+    ///
+    /// # Safety
+    ///
+    /// See [ManagedMem::gc].
+    unsafe fn gc_minor(&mut self, roots: Vec<*mut Ptr>, weaks: Vec<*mut Ptr>);
+
+    /// See [ManagedMem::gc_full].
+    ///
+    /// # Safety
+    ///
+    /// See [ManagedMem::gc].
+    unsafe fn gc_full(&mut self, roots: Vec<*mut Ptr>, weaks: Vec<*mut Ptr>);
+
+    /// See [ManagedMem::is_generational].
+    fn is_generational(&self) -> bool;
+
+    /// See [ManagedMem::gc_with_report].
+    ///
+    /// # Safety
+    ///
+    /// See [ManagedMem::gc].
+    unsafe fn gc_with_report(&mut self, roots: Vec<*mut Ptr>, weaks: Vec<*mut Ptr>) -> GcReport;
+
+    /// See [ManagedMem::total_size].
+    fn total_size(&self) -> usize;
+
+    /// See [ManagedMem::dump].
+    fn dump(&self, w: &mut dyn Write) -> io::Result<()>;
+
+    /// See [ManagedMem::census].
+    fn census(&self) -> Census;
+
+    /// See [ManagedMem::gc_hooks].
+    fn gc_hooks(&mut self) -> Option<&mut GcHooks>;
+
+    /// See [ManagedMem::relocation_hooks].
+    fn relocation_hooks(&mut self) -> Option<&mut RelocationHooks<Ptr>>;
+
+    /// See [ManagedMem::gc_log].
+    fn gc_log(&mut self) -> Option<&mut GcLog>;
+
+    /// See [ManagedMem::allocation_profile].
+    fn allocation_profile(&self) -> Option<&AllocProfile>;
+
+    /// See [ManagedMem::pause_times].
+    fn pause_times(&self) -> Option<&PauseTimes>;
+
+    /// See [ManagedMem::age].
+    fn age(&self, ptr: &Ptr) -> Option<u32>;
+
+    /// See [ManagedMem::survival].
+    fn survival(&self) -> Option<&SurvivalStats>;
+
+    /// See [ManagedMem::verify].
+    fn verify(&self) -> Result<(), VerifyError>;
+
+    /// See [ManagedMem::capacity].
+    fn capacity(&self) -> Option<usize>;
+
+    /// See [ManagedMem::used_bytes].
+    fn used_bytes(&self) -> usize;
+
+    /// See [ManagedMem::push_or_collect].
+    #[track_caller]
+    fn push_or_collect(&mut self, v: Box<T>, roots: &mut dyn RootProvider<Ptr>) -> Result<Ptr, AllocError<T>>;
+
+    /// See [ManagedMem::record_write].
+    fn record_write(&mut self, src: &Ptr, dst: &Ptr);
+
+    /// See [ManagedMem::has_write_barrier].
+    fn has_write_barrier(&self) -> bool;
+
+    /// See [ManagedMem::record_overwrite].
+    fn record_overwrite(&mut self, src: &Ptr, old: &Ptr);
+
+    /// See [ManagedMem::has_pre_write_barrier].
+    fn has_pre_write_barrier(&self) -> bool;
+
+    /// See [ManagedMem::load_barrier].
+    fn load_barrier(&self, ptr: &Ptr) -> Ptr;
+
+    /// See [ManagedMem::has_read_barrier].
+    fn has_read_barrier(&self) -> bool;
+}
+
+//////////////// impls
+
+impl<T, Ptr, M> DynManagedMem<T, Ptr> for M
+    where T: ?Sized + GcCandidate<Ptr>, Ptr: HeapPtr<T>, M: ManagedMem<T, Ptr>
+{
+    fn push(&mut self, v: Box<T>) -> Result<Ptr, AllocError<T>>{
+        return ManagedMem::push(self, v);
+    }
+
+    fn push_with(&mut self, v: Box<T>, with: &mut dyn FnMut(Ptr) -> Ptr) -> Result<Ptr, AllocError<T>>{
+        return ManagedMem::push_with(self, v, with);
+    }
+
+    fn push_placed(&mut self, v: Box<T>, placement: Placement, with: &mut dyn FnMut(Ptr) -> Ptr) -> Result<Ptr, AllocError<T>>{
+        return ManagedMem::push_placed(self, v, placement, with);
+    }
+
+    fn get(&self, idx: usize) -> &T{
+        return ManagedMem::get(self, idx);
+    }
+
+    fn get_mut(&mut self, idx: usize) -> &mut T{
+        return ManagedMem::get_mut(self, idx);
+    }
+
+    fn get_by(&mut self, ptr: &Ptr) -> Option<&mut T>{
+        return ManagedMem::get_by(self, ptr);
+    }
+
+    fn get_by_ref(&self, ptr: &Ptr) -> Option<&T>{
+        return ManagedMem::get_by_ref(self, ptr);
+    }
+
+    fn len(&self) -> usize{
+        return ManagedMem::len(self);
+    }
+
+    fn contains_ptr(&self, ptr: &Ptr) -> bool{
+        return ManagedMem::contains_ptr(self, ptr);
+    }
+
+    fn contains_ptr_ignoring_meta(&self, ptr: &Ptr) -> bool{
+        return ManagedMem::contains_ptr_ignoring_meta(self, ptr);
+    }
+
+    fn get_by_ignoring_meta(&mut self, ptr: &Ptr) -> Option<&mut T>{
+        return ManagedMem::get_by_ignoring_meta(self, ptr);
+    }
+
+    fn get_by_ref_ignoring_meta(&self, ptr: &Ptr) -> Option<&T>{
+        return ManagedMem::get_by_ref_ignoring_meta(self, ptr);
+    }
+
+    fn to_full_ptr(&self, ptr: &Ptr) -> Option<Ptr>{
+        return ManagedMem::to_full_ptr(self, ptr);
+    }
+
+    fn identity_hash(&self, ptr: &Ptr) -> Option<u64>{
+        return ManagedMem::identity_hash(self, ptr);
+    }
+
+    fn for_each(&self, cb: &mut dyn FnMut(&T, &Ptr)){
+        ManagedMem::for_each(self, cb);
+    }
+
+    fn retain(&mut self, f: &mut dyn FnMut(&Ptr, &T) -> bool){
+        ManagedMem::retain(self, f);
+    }
+
+    unsafe fn gc(&mut self, roots: Vec<*mut Ptr>, weaks: Vec<*mut Ptr>){
+        ManagedMem::gc(self, roots, weaks);
+    }
+
+    unsafe fn gc_minor(&mut self, roots: Vec<*mut Ptr>, weaks: Vec<*mut Ptr>){
+        ManagedMem::gc_minor(self, roots, weaks);
+    }
+
+    unsafe fn gc_full(&mut self, roots: Vec<*mut Ptr>, weaks: Vec<*mut Ptr>){
+        ManagedMem::gc_full(self, roots, weaks);
+    }
+
+    fn is_generational(&self) -> bool{
+        return ManagedMem::is_generational(self);
+    }
+
+    unsafe fn gc_with_report(&mut self, roots: Vec<*mut Ptr>, weaks: Vec<*mut Ptr>) -> GcReport{
+        return ManagedMem::gc_with_report(self, roots, weaks);
+    }
+
+    fn total_size(&self) -> usize{
+        return ManagedMem::total_size(self);
+    }
+
+    fn dump(&self, w: &mut dyn Write) -> io::Result<()>{
+        return ManagedMem::dump(self, w);
+    }
+
+    fn census(&self) -> Census{
+        return ManagedMem::census(self);
+    }
+
+    fn gc_hooks(&mut self) -> Option<&mut GcHooks>{
+        return ManagedMem::gc_hooks(self);
+    }
+
+    fn relocation_hooks(&mut self) -> Option<&mut RelocationHooks<Ptr>>{
+        return ManagedMem::relocation_hooks(self);
+    }
+
+    fn gc_log(&mut self) -> Option<&mut GcLog>{
+        return ManagedMem::gc_log(self);
+    }
+
+    fn allocation_profile(&self) -> Option<&AllocProfile>{
+        return ManagedMem::allocation_profile(self);
+    }
+
+    fn pause_times(&self) -> Option<&PauseTimes>{
+        return ManagedMem::pause_times(self);
+    }
+
+    fn age(&self, ptr: &Ptr) -> Option<u32>{
+        return ManagedMem::age(self, ptr);
+    }
+
+    fn survival(&self) -> Option<&SurvivalStats>{
+        return ManagedMem::survival(self);
+    }
+
+    fn verify(&self) -> Result<(), VerifyError>{
+        return ManagedMem::verify(self);
+    }
+
+    fn capacity(&self) -> Option<usize>{
+        return ManagedMem::capacity(self);
+    }
+
+    fn used_bytes(&self) -> usize{
+        return ManagedMem::used_bytes(self);
+    }
+
+    fn push_or_collect(&mut self, v: Box<T>, roots: &mut dyn RootProvider<Ptr>) -> Result<Ptr, AllocError<T>>{
+        return ManagedMem::push_or_collect(self, v, roots);
+    }
+
+    fn record_write(&mut self, src: &Ptr, dst: &Ptr){
+        ManagedMem::record_write(self, src, dst);
+    }
+
+    fn has_write_barrier(&self) -> bool{
+        return ManagedMem::has_write_barrier(self);
+    }
+
+    fn record_overwrite(&mut self, src: &Ptr, old: &Ptr){
+        ManagedMem::record_overwrite(self, src, old);
+    }
+
+    fn has_pre_write_barrier(&self) -> bool{
+        return ManagedMem::has_pre_write_barrier(self);
+    }
+
+    fn load_barrier(&self, ptr: &Ptr) -> Ptr{
+        return ManagedMem::load_barrier(self, ptr);
+    }
+
+    fn has_read_barrier(&self) -> bool{
+        return ManagedMem::has_read_barrier(self);
+    }
+}
+
+impl<'a, T, Ptr> ManagedMem<T, Ptr> for Box<dyn DynManagedMem<T, Ptr> + 'a>
+    where T: ?Sized + GcCandidate<Ptr>, Ptr: HeapPtr<T>
+{
+    fn push(&mut self, v: Box<T>) -> Result<Ptr, AllocError<T>>{
+        return (**self).push(v);
+    }
+
+    fn push_with(&mut self, v: Box<T>, with: impl FnOnce(Ptr) -> Ptr) -> Result<Ptr, AllocError<T>>{
+        // FnOnce can't be called through a reference, so call it at most once from an FnMut
+        let mut with = Some(with);
+        return (**self).push_with(v, &mut |p| with.take().expect("DynManagedMem::push_with: `with` called twice")(p));
+    }
+
+    fn push_placed(&mut self, v: Box<T>, placement: Placement, with: impl FnOnce(Ptr) -> Ptr) -> Result<Ptr, AllocError<T>>{
+        let mut with = Some(with);
+        return (**self).push_placed(v, placement, &mut |p| with.take().expect("DynManagedMem::push_placed: `with` called twice")(p));
+    }
+
+    fn get(&self, idx: usize) -> &T{
+        return (**self).get(idx);
+    }
+
+    fn get_mut(&mut self, idx: usize) -> &mut T{
+        return (**self).get_mut(idx);
+    }
+
+    fn get_by(&mut self, ptr: &Ptr) -> Option<&mut T>{
+        return (**self).get_by(ptr);
+    }
+
+    fn get_by_ref(&self, ptr: &Ptr) -> Option<&T>{
+        return (**self).get_by_ref(ptr);
+    }
+
+    fn len(&self) -> usize{
+        return (**self).len();
+    }
+
+    fn contains_ptr(&self, ptr: &Ptr) -> bool{
+        return (**self).contains_ptr(ptr);
+    }
+
+    fn contains_ptr_ignoring_meta(&self, ptr: &Ptr) -> bool{
+        return (**self).contains_ptr_ignoring_meta(ptr);
+    }
+
+    fn get_by_ignoring_meta(&mut self, ptr: &Ptr) -> Option<&mut T>{
+        return (**self).get_by_ignoring_meta(ptr);
+    }
+
+    fn get_by_ref_ignoring_meta(&self, ptr: &Ptr) -> Option<&T>{
+        return (**self).get_by_ref_ignoring_meta(ptr);
+    }
+
+    fn to_full_ptr(&self, ptr: &Ptr) -> Option<Ptr>{
+        return (**self).to_full_ptr(ptr);
+    }
+
+    fn identity_hash(&self, ptr: &Ptr) -> Option<u64>{
+        return (**self).identity_hash(ptr);
+    }
+
+    fn for_each(&self, mut cb: impl FnMut(&T, &Ptr)){
+        (**self).for_each(&mut cb);
+    }
+
+    fn retain(&mut self, mut f: impl FnMut(&Ptr, &T) -> bool){
+        (**self).retain(&mut f);
+    }
+
+    unsafe fn gc(&mut self, roots: Vec<*mut Ptr>, weaks: Vec<*mut Ptr>){
+        (**self).gc(roots, weaks);
+    }
+
+    unsafe fn gc_minor(&mut self, roots: Vec<*mut Ptr>, weaks: Vec<*mut Ptr>){
+        (**self).gc_minor(roots, weaks);
+    }
+
+    unsafe fn gc_full(&mut self, roots: Vec<*mut Ptr>, weaks: Vec<*mut Ptr>){
+        (**self).gc_full(roots, weaks);
+    }
+
+    fn is_generational(&self) -> bool{
+        return (**self).is_generational();
+    }
+
+    unsafe fn gc_with_report(&mut self, roots: Vec<*mut Ptr>, weaks: Vec<*mut Ptr>) -> GcReport{
+        return (**self).gc_with_report(roots, weaks);
+    }
+
+    fn total_size(&self) -> usize{
+        return (**self).total_size();
+    }
+
+    fn dump(&self, mut w: impl Write) -> io::Result<()>{
+        return (**self).dump(&mut w);
+    }
+
+    fn census(&self) -> Census{
+        return (**self).census();
+    }
+
+    fn gc_hooks(&mut self) -> Option<&mut GcHooks>{
+        return (**self).gc_hooks();
+    }
+
+    fn relocation_hooks(&mut self) -> Option<&mut RelocationHooks<Ptr>>{
+        return (**self).relocation_hooks();
+    }
+
+    fn gc_log(&mut self) -> Option<&mut GcLog>{
+        return (**self).gc_log();
+    }
+
+    fn allocation_profile(&self) -> Option<&AllocProfile>{
+        return (**self).allocation_profile();
+    }
+
+    fn pause_times(&self) -> Option<&PauseTimes>{
+        return (**self).pause_times();
+    }
+
+    fn age(&self, ptr: &Ptr) -> Option<u32>{
+        return (**self).age(ptr);
+    }
+
+    fn survival(&self) -> Option<&SurvivalStats>{
+        return (**self).survival();
+    }
+
+    fn verify(&self) -> Result<(), VerifyError>{
+        return (**self).verify();
+    }
+
+    fn capacity(&self) -> Option<usize>{
+        return (**self).capacity();
+    }
+
+    fn used_bytes(&self) -> usize{
+        return (**self).used_bytes();
+    }
+
+    fn record_write(&mut self, src: &Ptr, dst: &Ptr){
+        (**self).record_write(src, dst);
+    }
+
+    fn has_write_barrier(&self) -> bool{
+        return (**self).has_write_barrier();
+    }
+
+    fn record_overwrite(&mut self, src: &Ptr, old: &Ptr){
+        (**self).record_overwrite(src, old);
+    }
+
+    fn has_pre_write_barrier(&self) -> bool{
+        return (**self).has_pre_write_barrier();
+    }
+
+    fn load_barrier(&self, ptr: &Ptr) -> Ptr{
+        return (**self).load_barrier(ptr);
+    }
+
+    fn has_read_barrier(&self) -> bool{
+        return (**self).has_read_barrier();
+    }
+}
@@ -0,0 +1,121 @@
+//! Running incremental collections as futures, for use with async runtimes.
+//!
+//! An [AsyncCollector] pairs an [IncrementalMem] with its roots. [AsyncCollector::run_gc] returns a
+//! [GcFuture] that does one [IncrementalMem::gc_step] each time it's polled and yields back to the
+//! executor between steps, so other tasks can run while a collection is in progress.
+
+use std::future::Future;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use crate::gc::GcCandidate;
+use crate::gc::incremental::{Budget, GcPhaseProgress, IncrementalMem};
+use crate::gc::report::GcReport;
+use crate::gc::roots::RootProvider;
+use crate::heap::HeapPtr;
+
+/// An [IncrementalMem] and a [RootProvider], collected in steps of a fixed [Budget].
+pub struct AsyncCollector<M, R>{
+    mem: M,
+    roots: R,
+    budget: Budget
+}
+
+/// A collection in progress, completing with a report of the collection.
+///
+/// Each poll does one step of the collection, and wakes the task again immediately if more steps
+/// are needed. Roots are taken from the [RootProvider] at every step. Dropping this before it
+/// completes leaves the collection in progress, to be continued by the next step or finished by
+/// [ManagedMem::gc](crate::gc::ManagedMem::gc).
+#[must_use = "futures do nothing unless polled"]
+pub struct GcFuture<'a, T: ?Sized, Ptr, M, R>{
+    mem: &'a mut M,
+    roots: &'a mut R,
+    budget: Budget,
+    steps: usize,
+    _phantom: PhantomData<fn(&T, &Ptr)>
+}
+
+impl<M, R> AsyncCollector<M, R>{
+    /// Pairs the given memory with its roots, collecting with the given budget per step.
+    pub fn new(mem: M, roots: R, budget: Budget) -> Self{
+        return AsyncCollector{ mem, roots, budget };
+    }
+
+    /// Returns the memory being collected.
+    pub fn mem(&self) -> &M{
+        return &self.mem;
+    }
+
+    /// Returns the memory being collected mutably.
+    pub fn mem_mut(&mut self) -> &mut M{
+        return &mut self.mem;
+    }
+
+    /// Returns the root provider.
+    pub fn roots(&self) -> &R{
+        return &self.roots;
+    }
+
+    /// Returns the root provider mutably.
+    pub fn roots_mut(&mut self) -> &mut R{
+        return &mut self.roots;
+    }
+
+    /// Returns the budget of each step.
+    pub fn budget(&self) -> Budget{
+        return self.budget;
+    }
+
+    /// Sets the budget of each step, for collections started afterwards.
+    pub fn set_budget(&mut self, budget: Budget){
+        self.budget = budget;
+    }
+
+    /// Returns a future that runs a collection to completion, continuing one already in progress.
+    pub fn run_gc<T, Ptr>(&mut self) -> GcFuture<'_, T, Ptr, M, R>
+        where T: ?Sized + GcCandidate<Ptr>, Ptr: HeapPtr<T>, M: IncrementalMem<T, Ptr>, R: RootProvider<Ptr>
+    {
+        return GcFuture{
+            mem: &mut self.mem,
+            roots: &mut self.roots,
+            budget: self.budget,
+            steps: 0,
+            _phantom: PhantomData
+        };
+    }
+
+    /// Separates this into its memory and root provider.
+    pub fn into_inner(self) -> (M, R){
+        return (self.mem, self.roots);
+    }
+}
+
+impl<T: ?Sized, Ptr, M, R> GcFuture<'_, T, Ptr, M, R>{
+    /// Returns the number of steps done so far.
+    pub fn steps(&self) -> usize{
+        return self.steps;
+    }
+}
+
+//////////////// impls
+
+impl<T, Ptr, M, R> Future for GcFuture<'_, T, Ptr, M, R>
+    where T: ?Sized + GcCandidate<Ptr>, Ptr: HeapPtr<T>, M: IncrementalMem<T, Ptr>, R: RootProvider<Ptr>
+{
+    type Output = GcReport;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<GcReport>{
+        let this = self.get_mut();
+        this.steps += 1;
+        // safety: guaranteed by RootProvider
+        let progress = unsafe{ this.mem.gc_step(this.roots.roots(), this.roots.weaks(), this.budget) };
+        return match progress{
+            GcPhaseProgress::Complete(report) => Poll::Ready(report),
+            GcPhaseProgress::Marking{ .. } => {
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+        };
+    }
+}
@@ -0,0 +1,182 @@
+//! Collecting several memories whose values may point into each other as one.
+//!
+//! Unlike [LinkedSpaces](crate::gc::spaces::LinkedSpaces), which collects each memory on its own,
+//! a [HeapGroup] finds which values are reachable across every memory before collecting any, so
+//! that garbage cycles spanning several memories are reclaimed.
+
+use std::collections::{HashMap, HashSet};
+use crate::gc::{GcCandidate, ManagedMem};
+use crate::gc::report::GcReport;
+use crate::gc::roots::RootProvider;
+use crate::heap::HeapPtr;
+
+/// Several memories sharing a single [RootProvider], collected together by [HeapGroup::gc].
+///
+/// Values in any member may point into any other. Since each member holds pointers outside of
+/// itself, every member must be configured to skip foreign pointers; see
+/// [MemConfig::with_foreign_pointers](crate::gc::config::MemConfig::with_foreign_pointers).
+pub struct HeapGroup<M, R>{
+    members: Vec<M>,
+    roots: R
+}
+
+impl<M, R> HeapGroup<M, R>{
+    /// Creates an empty group using the given roots.
+    pub fn new(roots: R) -> Self{
+        return HeapGroup{
+            members: Vec::new(),
+            roots
+        };
+    }
+
+    /// Adds a memory to the group, returning its index.
+    pub fn add(&mut self, mem: M) -> usize{
+        self.members.push(mem);
+        return self.members.len() - 1;
+    }
+
+    /// Returns the number of memories in the group.
+    pub fn len(&self) -> usize{
+        return self.members.len();
+    }
+
+    /// Returns whether the group has no memories.
+    pub fn is_empty(&self) -> bool{
+        return self.members.is_empty();
+    }
+
+    /// Returns the memory at the given index.
+    pub fn member(&self, idx: usize) -> &M{
+        return &self.members[idx];
+    }
+
+    /// Returns the memory at the given index mutably.
+    pub fn member_mut(&mut self, idx: usize) -> &mut M{
+        return &mut self.members[idx];
+    }
+
+    /// Returns every memory in the group, in the order they were added.
+    pub fn members(&self) -> &[M]{
+        return &self.members;
+    }
+
+    /// Returns the shared root provider.
+    pub fn roots(&self) -> &R{
+        return &self.roots;
+    }
+
+    /// Returns the shared root provider mutably.
+    pub fn roots_mut(&mut self) -> &mut R{
+        return &mut self.roots;
+    }
+
+    /// Returns the index of the memory containing the value at the given pointer, ignoring metadata.
+    pub fn member_of<T, Ptr>(&self, ptr: &Ptr) -> Option<usize>
+        where T: ?Sized + GcCandidate<Ptr>, Ptr: HeapPtr<T>, M: ManagedMem<T, Ptr>
+    {
+        return self.members.iter().position(|mem| mem.contains_ptr_ignoring_meta(ptr));
+    }
+
+    /// Returns a reference to the value at the given pointer in whichever memory contains it.
+    pub fn get_by_ref<T, Ptr>(&self, ptr: &Ptr) -> Option<&T>
+        where T: ?Sized + GcCandidate<Ptr>, Ptr: HeapPtr<T>, M: ManagedMem<T, Ptr>
+    {
+        return self.members.iter().find_map(|mem| mem.get_by_ref(ptr));
+    }
+
+    /// Collects every memory in the group, returning a report for each.
+    ///
+    /// Values reachable from the shared roots are first found across every memory. Each memory is
+    /// then collected in turn, treating pointers into it from reachable values in the others as
+    /// roots, and pointers in those values are updated if their targets move.
+    pub fn gc<T, Ptr>(&mut self) -> Vec<GcReport>
+        where T: ?Sized + GcCandidate<Ptr>, Ptr: HeapPtr<T>, M: ManagedMem<T, Ptr>, R: RootProvider<Ptr>
+    {
+        // reachable values in each memory that point into another
+        let mut sources = self.find_sources();
+        let mut reports = Vec::with_capacity(self.members.len());
+        for target in 0..self.members.len(){
+            let mut cross: Vec<Ptr> = Vec::new();
+            for (idx, mem) in self.members.iter().enumerate().filter(|(idx, _)| *idx != target){
+                for src in &sources[idx]{
+                    if let Some(value) = mem.get_by_ref_ignoring_meta(src){
+                        value.trace(&mut |edge: &Ptr| {
+                            if self.members[target].contains_ptr_ignoring_meta(edge){
+                                cross.push(edge.clone());
+                            }
+                        }, src);
+                    }
+                }
+            }
+            let old_cross = cross.clone();
+            // safety: guaranteed by RootProvider
+            let report = unsafe{
+                let mut roots = self.roots.roots();
+                roots.extend(cross.iter_mut().map(|p| p as *mut Ptr));
+                // keep track of the target's sources if they move
+                let mut weaks = self.roots.weaks();
+                weaks.extend(sources[target].iter_mut().map(|p| p as *mut Ptr));
+                self.members[target].gc_with_report(roots, weaks)
+            };
+            reports.push(report);
+
+            // forward the pointers stored in other memories to where their values moved
+            let moved: HashMap<*const (), Ptr> = old_cross.iter().zip(&cross)
+                .filter(|(old, new)| !old.eq_ignoring_meta(new))
+                .map(|(old, new)| (old.to_raw_ptr().cast::<()>(), new.clone()))
+                .collect();
+            if moved.is_empty(){
+                continue;
+            }
+            for (idx, mem) in self.members.iter_mut().enumerate().filter(|(idx, _)| *idx != target){
+                for src in &sources[idx]{
+                    if let Some(value) = mem.get_by_ignoring_meta(src){
+                        value.visit_edges(&mut |edge: &mut Ptr| {
+                            if let Some(new) = moved.get(&edge.to_raw_ptr().cast::<()>()){
+                                let mut new = new.clone();
+                                new.copy_meta(edge);
+                                *edge = new;
+                            }
+                        }, src);
+                    }
+                }
+            }
+        }
+        return reports;
+    }
+
+    /// Separates the group into its memories and root provider.
+    pub fn into_inner(self) -> (Vec<M>, R){
+        return (self.members, self.roots);
+    }
+
+    // finds every value reachable from the roots, across all memories, that points into a
+    // different memory than its own, grouped by the memory containing it
+    fn find_sources<T, Ptr>(&mut self) -> Vec<Vec<Ptr>>
+        where T: ?Sized + GcCandidate<Ptr>, Ptr: HeapPtr<T>, M: ManagedMem<T, Ptr>, R: RootProvider<Ptr>
+    {
+        // safety: guaranteed by RootProvider
+        let mut pending: Vec<Ptr> = self.roots.roots().into_iter().map(|p| unsafe{ (*p).clone() }).collect();
+        let mut seen: HashSet<*const ()> = HashSet::new();
+        let mut sources = vec![Vec::new(); self.members.len()];
+        while let Some(ptr) = pending.pop(){
+            let idx = match self.member_of::<T, Ptr>(&ptr){
+                Some(idx) if seen.insert(ptr.to_raw_ptr().cast::<()>()) => idx,
+                _ => continue
+            };
+            let value = match self.members[idx].get_by_ref_ignoring_meta(&ptr){
+                Some(value) => value,
+                None => continue
+            };
+            let mut crosses = false;
+            value.trace(&mut |edge: &Ptr| {
+                crosses |= !self.members[idx].contains_ptr_ignoring_meta(edge) && self.member_of::<T, Ptr>(edge).is_some();
+                pending.push(edge.clone());
+            }, &ptr);
+            if crosses{
+                sources[idx].push(ptr);
+            }
+        }
+        return sources;
+    }
+}
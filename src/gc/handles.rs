@@ -0,0 +1,186 @@
+//! Handles to managed values for embedders, in the style of JavaScript engine embedding APIs.
+//!
+//! A [HandleTable] stores managed pointers on behalf of native code that can't keep them in managed
+//! values or on a scanned stack. [Local] handles belong to the innermost open scope and are
+//! released together when it closes, while [Persistent] handles live until released explicitly.
+//! The table is a [RootProvider] treating every live handle as a strong root, so handles always
+//! point to the current location of their value.
+
+use crate::gc::roots::RootProvider;
+
+/// A table of handles to managed pointers, used as roots for collections.
+///
+/// Handles are plain indices into the table, and must be resolved through it with
+/// [HandleTable::local] or [HandleTable::persistent] to get the current pointer. Resolving a
+/// handle that was released returns `None`.
+pub struct HandleTable<Ptr>{
+    // every local handle, with the serial of the scope it was created in
+    locals: Vec<(Ptr, u64)>,
+    // the index of the first local and the serial of each open scope, from the outermost inwards
+    scopes: Vec<(usize, u64)>,
+    next_serial: u64,
+    // every persistent handle, with the generation of its slot
+    persistents: Vec<(Option<Ptr>, u64)>,
+    free: Vec<usize>
+}
+
+/// A handle belonging to the scope it was created in, which is released when that scope closes.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct Local{
+    idx: usize,
+    scope: u64
+}
+
+/// A handle that lives until it's passed to [HandleTable::release].
+///
+/// Persistent handles can't be copied, so each is released at most once. Dropping one without
+/// releasing it keeps its value alive for as long as the table exists.
+#[derive(Debug, Eq, PartialEq, Hash)]
+pub struct Persistent{
+    idx: usize,
+    generation: u64
+}
+
+impl<Ptr: Clone> HandleTable<Ptr>{
+    /// Creates a table with no handles and no open scopes.
+    pub fn new() -> Self{
+        return HandleTable{
+            locals: Vec::new(),
+            scopes: Vec::new(),
+            next_serial: 0,
+            persistents: Vec::new(),
+            free: Vec::new()
+        };
+    }
+
+    /// Opens a new scope, which local handles are created in until it's closed.
+    pub fn open_scope(&mut self){
+        self.scopes.push((self.locals.len(), self.next_serial));
+        self.next_serial += 1;
+    }
+
+    /// Closes the innermost scope, releasing every local handle created in it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if no scope is open.
+    pub fn close_scope(&mut self){
+        let (start, _) = self.scopes.pop().expect("HandleTable: no scope is open");
+        self.locals.truncate(start);
+    }
+
+    /// Closes the innermost scope like [HandleTable::close_scope], but keeps the value of the given
+    /// handle alive in a new local handle in the enclosing scope, which is returned. Returns `None`
+    /// if the given handle was already released.
+    ///
+    /// # Panics
+    ///
+    /// Panics if fewer than two scopes are open.
+    pub fn escape(&mut self, local: Local) -> Option<Local>{
+        assert!(self.scopes.len() >= 2, "HandleTable: no enclosing scope to escape to");
+        let ptr = self.local(local).cloned();
+        self.close_scope();
+        return ptr.map(|ptr| self.new_local(ptr));
+    }
+
+    /// Runs the given function in a new scope, closing it afterwards.
+    pub fn with_scope<R>(&mut self, f: impl FnOnce(&mut Self) -> R) -> R{
+        self.open_scope();
+        let result = f(self);
+        self.close_scope();
+        return result;
+    }
+
+    /// Returns the number of open scopes.
+    pub fn scope_depth(&self) -> usize{
+        return self.scopes.len();
+    }
+
+    /// Creates a local handle to the given pointer in the innermost scope.
+    ///
+    /// # Panics
+    ///
+    /// Panics if no scope is open.
+    pub fn new_local(&mut self, ptr: Ptr) -> Local{
+        let (_, scope) = *self.scopes.last().expect("HandleTable: local handles must be created in a scope");
+        self.locals.push((ptr, scope));
+        return Local{ idx: self.locals.len() - 1, scope };
+    }
+
+    /// Returns the current pointer of the given local handle, or `None` if its scope was closed.
+    pub fn local(&self, local: Local) -> Option<&Ptr>{
+        return match self.locals.get(local.idx){
+            Some((ptr, scope)) if *scope == local.scope => Some(ptr),
+            _ => None
+        };
+    }
+
+    /// Creates a persistent handle to the given pointer.
+    pub fn persist(&mut self, ptr: Ptr) -> Persistent{
+        return match self.free.pop(){
+            Some(idx) => {
+                let slot = &mut self.persistents[idx];
+                slot.0 = Some(ptr);
+                Persistent{ idx, generation: slot.1 }
+            }
+            None => {
+                self.persistents.push((Some(ptr), 0));
+                Persistent{ idx: self.persistents.len() - 1, generation: 0 }
+            }
+        };
+    }
+
+    /// Returns the current pointer of the given persistent handle.
+    pub fn persistent(&self, handle: &Persistent) -> Option<&Ptr>{
+        return match self.persistents.get(handle.idx){
+            Some((ptr, generation)) if *generation == handle.generation => ptr.as_ref(),
+            _ => None
+        };
+    }
+
+    /// Points the given persistent handle to another pointer, returning the pointer it replaced.
+    pub fn set_persistent(&mut self, handle: &Persistent, ptr: Ptr) -> Option<Ptr>{
+        return match self.persistents.get_mut(handle.idx){
+            Some((old, generation)) if *generation == handle.generation => old.replace(ptr),
+            _ => None
+        };
+    }
+
+    /// Releases the given persistent handle, returning its current pointer.
+    pub fn release(&mut self, handle: Persistent) -> Option<Ptr>{
+        return match self.persistents.get_mut(handle.idx){
+            Some((ptr, generation)) if *generation == handle.generation => {
+                *generation += 1;
+                self.free.push(handle.idx);
+                ptr.take()
+            }
+            _ => None
+        };
+    }
+
+    /// Returns the number of live local handles, in every open scope.
+    pub fn local_count(&self) -> usize{
+        return self.locals.len();
+    }
+
+    /// Returns the number of live persistent handles.
+    pub fn persistent_count(&self) -> usize{
+        return self.persistents.len() - self.free.len();
+    }
+}
+
+//////////////// impls
+
+impl<Ptr: Clone> Default for HandleTable<Ptr>{
+    fn default() -> Self{
+        return HandleTable::new();
+    }
+}
+
+unsafe impl<Ptr> RootProvider<Ptr> for HandleTable<Ptr>{
+    fn roots(&mut self) -> Vec<*mut Ptr>{
+        let locals = self.locals.iter_mut().map(|(ptr, _)| ptr as *mut Ptr);
+        let persistents = self.persistents.iter_mut().filter_map(|(ptr, _)| ptr.as_mut().map(|p| p as *mut Ptr));
+        return locals.chain(persistents).collect();
+    }
+}
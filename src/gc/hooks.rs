@@ -0,0 +1,135 @@
+//! Callbacks fired around collections.
+
+use std::fmt::{Debug, Formatter};
+use crate::gc::report::GcReport;
+
+/// The point within a collection at which hooks are fired.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum GcPhase{
+    /// Before anything is collected; values may still be accessed through pointers.
+    Before,
+    /// After reachable values have been found, before any are moved or dropped. Only fired by
+    /// collectors with a separate marking phase.
+    Marked{
+        /// The number of reachable values found.
+        reachable: usize
+    },
+    /// After the collection has completed and every root has been updated.
+    After(GcReport)
+}
+
+/// A list of callbacks fired at each [GcPhase] of every collection, e.g. to flush caches or
+/// record metrics.
+///
+/// Collectors that support hooks return theirs from [ManagedMem::gc_hooks](crate::gc::ManagedMem::gc_hooks).
+/// Hooks are called in the order they were added, on the thread running the collection, and
+/// must not access the memory being collected.
+#[derive(Default)]
+pub struct GcHooks{
+    hooks: Vec<Hook>
+}
+
+type Hook = Box<dyn FnMut(&GcPhase) + Send>;
+
+/// A list of callbacks called with the old and new pointers of every value moved by a collection,
+/// e.g. to patch external tables keyed by address, such as JIT code caches or debugger maps.
+///
+/// Collectors that move values return theirs from
+/// [ManagedMem::relocation_hooks](crate::gc::ManagedMem::relocation_hooks). Hooks are called
+/// once every root has been updated, in the order they were added, on the thread running the
+/// collection, and must not access the memory being collected.
+pub struct RelocationHooks<Ptr>{
+    hooks: Vec<RelocationHook<Ptr>>
+}
+
+type RelocationHook<Ptr> = Box<dyn FnMut(&Ptr, &Ptr) + Send>;
+
+impl GcHooks{
+    /// Creates an empty list of hooks.
+    pub fn new() -> Self{
+        return GcHooks{ hooks: Vec::new() };
+    }
+
+    /// Adds a hook to be called at every phase of every following collection.
+    pub fn add(&mut self, hook: impl FnMut(&GcPhase) + Send + 'static){
+        self.hooks.push(Box::new(hook));
+    }
+
+    /// Calls every hook with the given phase.
+    pub fn fire(&mut self, phase: &GcPhase){
+        for hook in &mut self.hooks{
+            hook(phase);
+        }
+    }
+
+    /// Returns the number of hooks added.
+    pub fn len(&self) -> usize{
+        return self.hooks.len();
+    }
+
+    /// Removes every hook.
+    pub fn clear(&mut self){
+        self.hooks.clear();
+    }
+}
+
+impl<Ptr> RelocationHooks<Ptr>{
+    /// Creates an empty list of hooks.
+    pub fn new() -> Self{
+        return RelocationHooks{ hooks: Vec::new() };
+    }
+
+    /// Adds a hook to be called with the old and new pointer of every value moved by following
+    /// collections.
+    pub fn add(&mut self, hook: impl FnMut(&Ptr, &Ptr) + Send + 'static){
+        self.hooks.push(Box::new(hook));
+    }
+
+    /// Calls every hook with the given old and new pointers.
+    pub fn fire(&mut self, old: &Ptr, new: &Ptr){
+        for hook in &mut self.hooks{
+            hook(old, new);
+        }
+    }
+
+    /// Returns whether no hooks were added, in which case collectors needn't report moves.
+    pub fn is_empty(&self) -> bool{
+        return self.hooks.is_empty();
+    }
+
+    /// Returns the number of hooks added.
+    pub fn len(&self) -> usize{
+        return self.hooks.len();
+    }
+
+    /// Removes every hook.
+    pub fn clear(&mut self){
+        self.hooks.clear();
+    }
+}
+
+//////////////// impls
+
+impl<Ptr> Default for RelocationHooks<Ptr>{
+    fn default() -> Self{
+        return RelocationHooks::new();
+    }
+}
+
+impl Debug for GcHooks{
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result{
+        return f.debug_struct("GcHooks").field("len", &self.hooks.len()).finish();
+    }
+}
+
+impl<Ptr> Debug for RelocationHooks<Ptr>{
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result{
+        return f.debug_struct("RelocationHooks").field("len", &self.hooks.len()).finish();
+    }
+}
+
+// safety: hooks are only called through `&mut self`, so sharing a reference between threads never
+// shares the hooks themselves
+unsafe impl Sync for GcHooks{}
+
+unsafe impl<Ptr> Sync for RelocationHooks<Ptr>{}
@@ -0,0 +1,145 @@
+//! Saving the contents of managed memory with `serde`, and restoring them into a new memory.
+//!
+//! A [HeapImage] records every value in a memory, converted to a serializable form by a
+//! user-supplied function, along with the address it was stored at. Pointers between values are
+//! saved as those addresses, using [address]. When an image is restored, values are rebuilt with
+//! their old pointers, pushed into the new memory, and then have their pointers rewritten to the
+//! values' new locations with [GcCandidate::adjust_ptrs], as when a collector moves them.
+//!
+//! Only available with the `serde` feature.
+
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::{Debug, Display, Formatter};
+use std::fmt;
+use serde::{Deserialize, Serialize};
+use crate::gc::{GcCandidate, ManagedMem};
+use crate::heap::{AllocError, DynSized, HeapPtr};
+
+/// A serializable snapshot of every value in a memory, and of a set of roots.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct HeapImage<V>{
+    objects: Vec<ImageObject<V>>,
+    roots: Vec<u64>
+}
+
+/// A single value in a [HeapImage], with the address it was stored at.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct ImageObject<V>{
+    address: u64,
+    value: V
+}
+
+/// An error restoring a [HeapImage].
+pub enum RestoreError<T: ?Sized>{
+    /// A value could not be pushed into the target memory.
+    Alloc(AllocError<T>),
+    /// A value or root pointed to an address that no value in the image was stored at.
+    Dangling{
+        /// The address pointed to.
+        address: u64
+    }
+}
+
+/// Returns the address of the value at the given pointer, as used in [HeapImage]s to identify values.
+pub fn address<T: ?Sized, Ptr: HeapPtr<T>>(ptr: &Ptr) -> u64{
+    return ptr.to_raw_ptr().cast::<()>() as usize as u64;
+}
+
+impl<V> HeapImage<V>{
+    /// Captures every value in the given memory using `serialize`, and the given roots.
+    ///
+    /// `serialize` should save pointers in values using [address], so that they can be
+    /// rebuilt when restoring.
+    pub fn capture<T, Ptr, M>(mem: &M, roots: &[Ptr], mut serialize: impl FnMut(&T, &Ptr) -> V) -> Self
+        where T: ?Sized + GcCandidate<Ptr>, Ptr: HeapPtr<T>, M: ManagedMem<T, Ptr> + ?Sized
+    {
+        let mut objects = Vec::with_capacity(mem.len());
+        mem.for_each(|v, ptr| objects.push(ImageObject{ address: address(ptr), value: serialize(v, ptr) }));
+        return HeapImage{
+            objects,
+            roots: roots.iter().map(address).collect()
+        };
+    }
+
+    /// Pushes every value in this image into the given memory, rebuilding them with `deserialize`,
+    /// and returns the restored roots in the order they were captured.
+    ///
+    /// `deserialize` should give values pointers with the addresses they were saved with,
+    /// which are then updated to point to the restored values. Values already in the memory are
+    /// left untouched.
+    pub fn restore<T, Ptr, M>(self, mem: &mut M, mut deserialize: impl FnMut(V) -> Box<T>) -> Result<Vec<Ptr>, RestoreError<T>>
+        where T: ?Sized + GcCandidate<Ptr>, Ptr: HeapPtr<T>, M: ManagedMem<T, Ptr> + ?Sized
+    {
+        let mut relocated: HashMap<u64, Ptr> = HashMap::with_capacity(self.objects.len());
+        let mut pushed = Vec::with_capacity(self.objects.len());
+        for object in self.objects{
+            let ptr = mem.push(deserialize(object.value)).map_err(RestoreError::Alloc)?;
+            relocated.insert(object.address, ptr.clone());
+            pushed.push(ptr);
+        }
+        // rewrite pointers between values to their new locations
+        let dangling = Cell::new(None);
+        for ptr in &pushed{
+            let value = mem.get_by(ptr).expect("HeapImage::restore: pushed value not in memory");
+            value.adjust_ptrs(|old| match relocated.get(&address(old)){
+                Some(new) => new.clone(),
+                None => {
+                    dangling.set(Some(address(old)));
+                    old.clone()
+                }
+            }, ptr);
+            if let Some(address) = dangling.get(){
+                return Err(RestoreError::Dangling{ address });
+            }
+        }
+        return self.roots.into_iter()
+            .map(|root| relocated.get(&root).cloned().ok_or(RestoreError::Dangling{ address: root }))
+            .collect();
+    }
+
+    /// Returns every value in this image.
+    pub fn objects(&self) -> &[ImageObject<V>]{
+        return &self.objects;
+    }
+
+    /// Returns the addresses of the captured roots.
+    pub fn roots(&self) -> &[u64]{
+        return &self.roots;
+    }
+}
+
+impl<V> ImageObject<V>{
+    /// Returns the address the value was stored at.
+    pub fn address(&self) -> u64{
+        return self.address;
+    }
+
+    /// Returns the saved form of the value.
+    pub fn value(&self) -> &V{
+        return &self.value;
+    }
+}
+
+//////////////// impls
+
+impl<T: ?Sized> Debug for RestoreError<T>{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result{
+        return match self{
+            RestoreError::Alloc(err) => f.debug_tuple("Alloc").field(err).finish(),
+            RestoreError::Dangling{ address } => f.debug_struct("Dangling").field("address", address).finish()
+        };
+    }
+}
+
+impl<T: ?Sized + DynSized> Display for RestoreError<T>{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result{
+        return match self{
+            RestoreError::Alloc(err) => write!(f, "could not restore value: {err}"),
+            RestoreError::Dangling{ address } => write!(f, "pointer to {address:#x} does not point to a value in the image")
+        };
+    }
+}
+
+impl<T: ?Sized + DynSized> Error for RestoreError<T>{}
@@ -0,0 +1,101 @@
+//! Ready-made [GcCandidate] implementations for common types, and helpers for implementing it.
+//!
+//! Leaf types that cannot contain managed pointers (integers, floats, `bool`, `char`, `()`, and
+//! slices of them) implement [GcCandidate] for any pointer type, and other types without pointers
+//! can be given the same impl with [pointer_free_candidate](crate::pointer_free_candidate). For values that contain
+//! pointers in `Option`s, slices, or `Vec`s, the functions in this module can be used to
+//! implement [GcCandidate::trace] and [GcCandidate::adjust_ptrs] for those fields.
+
+use crate::gc::GcCandidate;
+use crate::heap::HeapPtr;
+
+macro_rules! leaf_candidate{
+    ($($t:ty),*) => {
+        $(
+            impl<Ptr: HeapPtr<$t>> GcCandidate<Ptr> for $t{
+                fn collect_managed_pointers(&self, _this: &Ptr) -> Vec<Ptr>{
+                    return Vec::new();
+                }
+
+                fn trace(&self, _visitor: &mut impl FnMut(&Ptr), _this: &Ptr){}
+
+                fn adjust_ptrs(&mut self, _adjust: impl Fn(&Ptr) -> Ptr, _this: &Ptr){}
+            }
+
+            impl<Ptr: HeapPtr<[$t]>> GcCandidate<Ptr> for [$t]{
+                fn collect_managed_pointers(&self, _this: &Ptr) -> Vec<Ptr>{
+                    return Vec::new();
+                }
+
+                fn trace(&self, _visitor: &mut impl FnMut(&Ptr), _this: &Ptr){}
+
+                fn adjust_ptrs(&mut self, _adjust: impl Fn(&Ptr) -> Ptr, _this: &Ptr){}
+            }
+        )*
+    };
+}
+
+leaf_candidate!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, f32, f64, bool, char, ());
+
+/// Implements [GcCandidate] for each of the given types as values that hold no pointers to
+/// other values in the same memory, like the leaf types in [gc::impls](crate::gc::impls).
+///
+/// Any such pointers would never be traced, and their targets would be dropped while still
+/// reachable. Pointers managed elsewhere are fine; e.g. the `gc` crate's `Gc` pointers stay rooted
+/// in that crate's heap for as long as the value holding them is kept.
+#[macro_export]
+macro_rules! pointer_free_candidate{
+    ($($t:ty),* $(,)?) => {
+        $(
+            impl<Ptr: $crate::heap::HeapPtr<$t>> $crate::gc::GcCandidate<Ptr> for $t{
+                fn collect_managed_pointers(&self, _this: &Ptr) -> Vec<Ptr>{
+                    return Vec::new();
+                }
+
+                fn trace(&self, _visitor: &mut impl FnMut(&Ptr), _this: &Ptr){}
+
+                fn adjust_ptrs(&mut self, _adjust: impl Fn(&Ptr) -> Ptr, _this: &Ptr){}
+            }
+        )*
+    };
+}
+
+/// Calls the given visitor with the pointer in `field`, if present.
+pub fn trace_option<Ptr>(field: &Option<Ptr>, visitor: &mut impl FnMut(&Ptr)){
+    if let Some(ptr) = field{
+        visitor(ptr);
+    }
+}
+
+/// Replaces the pointer in `field`, if present, according to the given function.
+pub fn adjust_option<Ptr>(field: &mut Option<Ptr>, adjust: impl Fn(&Ptr) -> Ptr){
+    if let Some(ptr) = field{
+        *ptr = adjust(ptr);
+    }
+}
+
+/// Calls the given visitor with every pointer in `field`.
+///
+/// `Vec`s of pointers can be passed directly, as they dereference to slices.
+pub fn trace_slice<Ptr>(field: &[Ptr], visitor: &mut impl FnMut(&Ptr)){
+    for ptr in field{
+        visitor(ptr);
+    }
+}
+
+/// Replaces every pointer in `field` according to the given function.
+///
+/// `Vec`s of pointers can be passed directly, as they dereference to slices.
+pub fn adjust_slice<Ptr>(field: &mut [Ptr], adjust: impl Fn(&Ptr) -> Ptr){
+    for ptr in field{
+        *ptr = adjust(ptr);
+    }
+}
+
+/// Clones every pointer yielded by `fields` into a new `Vec`, for implementing
+/// [GcCandidate::collect_managed_pointers].
+///
+/// `Option`s, slices, and `Vec`s of pointers can all be iterated and combined with [Iterator::chain].
+pub fn collect_all<'a, Ptr: Clone + 'a>(fields: impl IntoIterator<Item = &'a Ptr>) -> Vec<Ptr>{
+    return fields.into_iter().cloned().collect();
+}
@@ -0,0 +1,91 @@
+//! Collecting memory in small steps, spreading pauses out over time.
+
+use std::time::{Duration, Instant};
+use crate::gc::{GcCandidate, ManagedMem};
+use crate::gc::report::GcReport;
+use crate::heap::HeapPtr;
+
+/// How much work a single [IncrementalMem::gc_step] may do before returning.
+///
+/// Budgets are checked after each value is marked, so a step always marks at least one value if
+/// any are pending, and may slightly overrun its budget. The final step of a collection, which
+/// moves or drops values, is not limited by the budget.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Budget{
+    /// Stop after the given amount of time has passed.
+    Time(Duration),
+    /// Stop after marking the given number of values.
+    Objects(usize),
+    /// Stop after marking values with the given total size, in bytes.
+    Bytes(usize)
+}
+
+/// The state of a collection after an [IncrementalMem::gc_step].
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum GcPhaseProgress{
+    /// Reachable values are still being found; more steps are needed.
+    Marking{
+        /// The number of values found to be reachable so far.
+        marked: usize,
+        /// The number of values waiting to be scanned.
+        pending: usize
+    },
+    /// The collection has completed, and every root has been updated.
+    Complete(GcReport)
+}
+
+/// A [ManagedMem] that can collect incrementally, interleaving collection work with the mutator.
+///
+/// Between steps, the mutator may keep pushing and accessing values, but must notify the
+/// collector of every managed pointer it stores into a value through [ManagedMem::write_field] or
+/// [ManagedMem::record_write], and of every pointer it overwrites through
+/// [ManagedMem::record_overwrite] if the collector has a pre-write barrier. Values pushed while
+/// collecting survive that collection.
+pub trait IncrementalMem<T, Ptr = *const T>: ManagedMem<T, Ptr>
+    where T: ?Sized + GcCandidate<Ptr>, Ptr: HeapPtr<T>
+{
+    /// Does up to the given budget of collection work, starting a new collection if none is in
+    /// progress. Collections started this way can be finished early with [ManagedMem::gc].
+    ///
+    /// # Safety
+    ///
+    /// See [ManagedMem::gc]. The roots given must include every root of the mutator at the time
+    /// of the call; they may differ between steps.
+    unsafe fn gc_step(&mut self, roots: Vec<*mut Ptr>, weaks: Vec<*mut Ptr>, budget: Budget) -> GcPhaseProgress;
+
+    /// Returns whether a collection has been started by [IncrementalMem::gc_step] and not yet
+    /// completed.
+    fn is_collecting(&self) -> bool;
+}
+
+// tracks the work done by a step against its budget
+pub(crate) struct BudgetTracker{
+    budget: Budget,
+    start: Instant,
+    spent: usize
+}
+
+impl BudgetTracker{
+    pub(crate) fn new(budget: Budget) -> Self{
+        return BudgetTracker{
+            budget,
+            start: Instant::now(),
+            spent: 0
+        };
+    }
+
+    // records that a value of the given size was marked, returning whether any budget remains
+    pub(crate) fn spend(&mut self, bytes: usize) -> bool{
+        return match self.budget{
+            Budget::Time(limit) => self.start.elapsed() < limit,
+            Budget::Objects(limit) => {
+                self.spent += 1;
+                self.spent < limit
+            }
+            Budget::Bytes(limit) => {
+                self.spent = self.spent.saturating_add(bytes);
+                self.spent < limit
+            }
+        };
+    }
+}
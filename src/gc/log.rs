@@ -0,0 +1,273 @@
+//! Structured logs of collections, written to pluggable sinks.
+//!
+//! Collectors that support logging return their [GcLog] from
+//! [ManagedMem::gc_log](crate::gc::ManagedMem::gc_log), and emit one [GcLogRecord] per collection
+//! to every sink added to it. How much each record contains is chosen by its [GcLogLevel]; records
+//! are formatted as a single line per collection, plus one line per value at
+//! [GcLogLevel::Objects], similar to the JVM's unified GC logging.
+
+use std::fmt;
+use std::fmt::{Debug, Display, Formatter};
+use std::io::Write;
+use std::time::Duration;
+use crate::gc::report::GcReport;
+
+/// How much detail is recorded for each collection, from least to most.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum GcLogLevel{
+    /// Only the [GcReport] of each collection.
+    #[default]
+    Summary,
+    /// The report, and the time spent in each phase.
+    Phases,
+    /// The report, phases, and the fate of every value stored when the collection started.
+    Objects
+}
+
+/// What a collection did with a single value, at [GcLogLevel::Objects].
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum ObjectFate{
+    /// The value was kept where it was.
+    Survived,
+    /// The value was kept, and moved to the given address.
+    Moved(usize),
+    /// The value was unreachable, and dropped.
+    Reclaimed
+}
+
+/// A value stored when a collection started, and what that collection did with it.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct ObjectRecord{
+    address: usize,
+    size: usize,
+    fate: ObjectFate
+}
+
+/// Everything logged about a single collection.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct GcLogRecord{
+    collection: u64,
+    collector: &'static str,
+    report: GcReport,
+    phases: Vec<(&'static str, Duration)>,
+    objects: Vec<ObjectRecord>
+}
+
+/// A destination for [GcLogRecord]s, such as a file or a metrics pipeline.
+///
+/// Implemented for closures taking records, and for [WriterSink].
+pub trait GcLogSink: Send{
+    /// Receives the record of a collection that just completed.
+    fn write(&mut self, record: &GcLogRecord);
+}
+
+/// A [GcLogSink] formatting every record to an [io::Write](std::io::Write), ignoring errors.
+pub struct WriterSink<W>{
+    writer: W
+}
+
+/// The log of a collector, holding its level and sinks.
+///
+/// Logging is disabled until a sink is added, so collectors only gather records when they'll be
+/// written somewhere.
+#[derive(Default)]
+pub struct GcLog{
+    level: GcLogLevel,
+    sinks: Vec<Box<dyn GcLogSink>>,
+    collections: u64
+}
+
+impl ObjectRecord{
+    /// Records the fate of the value of the given size that was at the given address.
+    pub fn new(address: usize, size: usize, fate: ObjectFate) -> Self{
+        return ObjectRecord{ address, size, fate };
+    }
+
+    /// Returns the address the value was at when the collection started.
+    pub fn address(&self) -> usize{
+        return self.address;
+    }
+
+    /// Returns the size of the value, in bytes.
+    pub fn size(&self) -> usize{
+        return self.size;
+    }
+
+    /// Returns what the collection did with the value.
+    pub fn fate(&self) -> ObjectFate{
+        return self.fate;
+    }
+}
+
+impl GcLogRecord{
+    /// Creates a record of the given collection by the named collector, with no phases or values.
+    pub fn new(collection: u64, collector: &'static str, report: GcReport) -> Self{
+        return GcLogRecord{
+            collection,
+            collector,
+            report,
+            phases: Vec::new(),
+            objects: Vec::new()
+        };
+    }
+
+    /// Adds a phase and the time spent in it.
+    pub fn with_phase(mut self, name: &'static str, time: Duration) -> Self{
+        self.phases.push((name, time));
+        return self;
+    }
+
+    /// Sets the fate of every value stored when the collection started.
+    pub fn with_objects(mut self, objects: Vec<ObjectRecord>) -> Self{
+        self.objects = objects;
+        return self;
+    }
+
+    /// Returns the number of this collection in its log, counting from 1.
+    pub fn collection(&self) -> u64{
+        return self.collection;
+    }
+
+    /// Returns the name of the collector.
+    pub fn collector(&self) -> &'static str{
+        return self.collector;
+    }
+
+    /// Returns the report of the collection.
+    pub fn report(&self) -> &GcReport{
+        return &self.report;
+    }
+
+    /// Returns every phase of the collection and the time spent in it, in order, if logged at
+    /// [GcLogLevel::Phases] or above.
+    pub fn phases(&self) -> &[(&'static str, Duration)]{
+        return &self.phases;
+    }
+
+    /// Returns the fate of every value stored when the collection started, if logged at
+    /// [GcLogLevel::Objects].
+    pub fn objects(&self) -> &[ObjectRecord]{
+        return &self.objects;
+    }
+}
+
+impl<W: Write + Send> WriterSink<W>{
+    /// Creates a sink writing to the given writer.
+    pub fn new(writer: W) -> Self{
+        return WriterSink{ writer };
+    }
+
+    /// Returns the writer.
+    pub fn into_inner(self) -> W{
+        return self.writer;
+    }
+}
+
+impl GcLog{
+    /// Creates a log at [GcLogLevel::Summary] with no sinks.
+    pub fn new() -> Self{
+        return GcLog::default();
+    }
+
+    /// Returns the level of detail recorded.
+    pub fn level(&self) -> GcLogLevel{
+        return self.level;
+    }
+
+    /// Sets the level of detail recorded for following collections.
+    pub fn set_level(&mut self, level: GcLogLevel){
+        self.level = level;
+    }
+
+    /// Adds a sink receiving every following record.
+    pub fn add_sink(&mut self, sink: impl GcLogSink + 'static){
+        self.sinks.push(Box::new(sink));
+    }
+
+    /// Removes every sink, disabling logging.
+    pub fn clear_sinks(&mut self){
+        self.sinks.clear();
+    }
+
+    /// Returns whether records at the given level should be gathered, i.e. whether any sink was
+    /// added and the log's level is at least the given level.
+    pub fn logs(&self, level: GcLogLevel) -> bool{
+        return !self.sinks.is_empty() && level <= self.level;
+    }
+
+    /// Returns the number of collections logged so far.
+    pub fn collections(&self) -> u64{
+        return self.collections;
+    }
+
+    /// Creates a record for the next collection, numbering it.
+    pub fn next_record(&mut self, collector: &'static str, report: GcReport) -> GcLogRecord{
+        self.collections += 1;
+        return GcLogRecord::new(self.collections, collector, report);
+    }
+
+    /// Writes the given record to every sink, leaving out any details above the log's level.
+    pub fn emit(&mut self, mut record: GcLogRecord){
+        if self.level < GcLogLevel::Phases{
+            record.phases.clear();
+        }
+        if self.level < GcLogLevel::Objects{
+            record.objects.clear();
+        }
+        for sink in &mut self.sinks{
+            sink.write(&record);
+        }
+    }
+}
+
+//////////////// impls
+
+impl<F: FnMut(&GcLogRecord) + Send> GcLogSink for F{
+    fn write(&mut self, record: &GcLogRecord){
+        self(record);
+    }
+}
+
+impl<W: Write + Send> GcLogSink for WriterSink<W>{
+    fn write(&mut self, record: &GcLogRecord){
+        // logging must never fail a collection
+        let _ = write!(self.writer, "{record}");
+    }
+}
+
+impl Display for GcLogRecord{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result{
+        let report = &self.report;
+        write!(f, "[gc] GC({}) {}: {} scanned, {} reclaimed ({}B), {}B surviving, pause {:?}",
+            self.collection, self.collector,
+            report.objects_scanned(), report.objects_reclaimed(), report.bytes_reclaimed(),
+            report.bytes_surviving(), report.pause())?;
+        for (name, time) in &self.phases{
+            write!(f, ", {name} {time:?}")?;
+        }
+        writeln!(f)?;
+        for object in &self.objects{
+            write!(f, "[gc] GC({}) {:#x} {}B ", self.collection, object.address, object.size)?;
+            match object.fate{
+                ObjectFate::Survived => writeln!(f, "survived")?,
+                ObjectFate::Moved(to) => writeln!(f, "moved to {to:#x}")?,
+                ObjectFate::Reclaimed => writeln!(f, "reclaimed")?
+            }
+        }
+        return Ok(());
+    }
+}
+
+impl Debug for GcLog{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result{
+        return f.debug_struct("GcLog")
+            .field("level", &self.level)
+            .field("sinks", &self.sinks.len())
+            .field("collections", &self.collections)
+            .finish();
+    }
+}
+
+// safety: sinks are only written to through `&mut self`, so sharing a reference between threads
+// never shares the sinks themselves
+unsafe impl Sync for GcLog{}
@@ -2,40 +2,723 @@
 
 use std::collections::{HashMap, HashSet};
 use std::fmt::{Debug, Formatter};
+use std::alloc::Layout;
 use std::hash::{Hash, Hasher};
 use std::marker::PhantomData;
-use std::mem::swap;
-use crate::gc::{GcCandidate, ManagedMem};
-use crate::heap::{Heap, HeapPtr};
+use std::mem;
+use std::mem::{swap, MaybeUninit};
+use std::ops::RangeInclusive;
+use std::io;
+#[cfg(all(feature = "persist", unix, target_pointer_width = "64"))]
+use std::path::Path;
+use std::panic;
+use std::panic::Location;
+use std::thread;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use crate::gc::{dedup_roots, mix_hash, verify_edges, GcCandidate, ManagedMem, Placement};
+use crate::gc::config::{EvacuationOrder, ForeignPtrPolicy, MemConfig, WriteBarrier};
+use crate::gc::hooks::{GcHooks, GcPhase, RelocationHooks};
+use crate::gc::incremental::{Budget, BudgetTracker, GcPhaseProgress, IncrementalMem};
+use crate::gc::log::{GcLog, GcLogLevel, ObjectFate, ObjectRecord};
+use crate::gc::pauses::PauseTimes;
+use crate::gc::profile::{record_caller, AllocProfile};
+use crate::gc::quarantine::Quarantine;
+use crate::gc::report::GcReport;
+use crate::gc::survival::SurvivalStats;
+use crate::heap::{header_of, AllocError, AllocErrorKind, Heap, HeapCheckpoint, HeapPtr, ObjectHeader, ThreadSafePtr, VerifyError};
 
 /// A memory space managed by a mark-and-sweep garbage collector.
 ///
 /// When garbage collection is triggered, all objects reachable from roots are
 /// marked; then all marked objects are moved to a new heap, and unmarked objects dropped.
+/// Moving objects is split between the threads given by [MemConfig::parallelism], and
+/// objects are placed in the order given by [MemConfig::evacuation_order].
+/// If surviving objects don't fit in a heap of the same capacity, e.g. because of padding between
+/// objects placed in a different order, the new heap is grown as allowed by
+/// [MemConfig::growth], or by just enough if that's fixed; see [GcReport::resize].
+/// If configured with [MemConfig::with_sweep_in_place], unmarked objects are instead dropped in
+/// place, and marked objects never move.
+///
+/// If configured with [MemConfig::with_object_headers], the number of collections each value has
+/// survived is tracked, and available through [ManagedMem::age] and [ManagedMem::survival].
+///
+/// Values have identity hashes that are kept when they move if configured with
+/// [MemConfig::with_identity_hashes], or when sweeping in place.
+///
+/// If configured with [MemConfig::with_quarantine], accessing a value through a pointer to where
+/// a recently dropped or moved value was panics, naming where that value was allocated.
+///
+/// Objects pushed with [ManagedMem::push_placed] as [Placement::Immortal] are never dropped, and
+/// keep everything they point to reachable; they're still moved by collections like any other
+/// object. When moving objects, immortal objects are placed first, followed by other objects,
+/// and then [Placement::Cold] objects.
+///
+/// Roots and objects may point outside of the heap if allowed by [MemConfig::with_foreign_pointers];
+/// such pointers are never followed or updated.
+///
+/// Marking can also be done incrementally with [IncrementalMem::gc_step]; objects are then only
+/// moved once marking is complete. Pointers changed between steps are tracked by the barrier
+/// given by [MemConfig::with_write_barrier].
+///
+/// The length of every collection is recorded in [ManagedMem::pause_times], along with each call
+/// spent marking as the `"mark"` phase and the time spent dropping or moving values as `"sweep"`.
+/// Every collection is also written to [ManagedMem::gc_log], with the same phases.
+///
+/// Values reclaimed by collections that move objects can be dropped on a background thread after
+/// the collection returns; see [MarkAndSweepMem::set_background_sweep]. If configured with
+/// [MemConfig::with_skip_drop], they're not dropped at all, and their space is just reused.
 pub struct MarkAndSweepMem<T, Ptr = *const T>
     where T: ?Sized + GcCandidate<Ptr>, Ptr: HeapPtr<T>
 {
-    active: Heap<T, Ptr>
+    active: Heap<T, Ptr>,
+    config: MemConfig,
+    hooks: GcHooks,
+    relocations: RelocationHooks<Ptr>,
+    log: GcLog,
+    profile: Option<AllocProfile>,
+    quarantine: Option<Quarantine>,
+    // identity hashes of values by address, and the number of hashes assigned, if configured
+    identities: Option<HashMap<usize, u64>>,
+    hashes_assigned: u64,
+    // the placement of every value not pushed as hot, by address
+    placements: HashMap<usize, Placement>,
+    pauses: PauseTimes,
+    survival: SurvivalStats,
+    marking: Option<MarkState<T, Ptr>>,
+    sweeper: Sweeper<T, Ptr>
+}
+
+/// The state of a [MarkAndSweepMem] saved by [MarkAndSweepMem::checkpoint], which it can be
+/// restored to with [MarkAndSweepMem::rollback].
+pub struct Checkpoint<T: ?Sized, Ptr>{
+    heap: HeapCheckpoint<T, Ptr>,
+    quarantine: Option<Quarantine>,
+    identities: Option<HashMap<usize, u64>>,
+    hashes_assigned: u64,
+    placements: HashMap<usize, Placement>
+}
+
+// the objects marked reachable in a collection, kept in object headers if the heap has them
+enum Marks{
+    // one bit for every byte a value can start at, indexed by offset into the heap
+    Bitmap{
+        bits: Vec<u64>,
+        base: usize,
+        count: usize
+    },
+    // mark bits are stored in headers, so only their number is kept here
+    Headers(usize)
+}
+
+// the progress of a collection that has started marking
+struct MarkState<T, Ptr>
+    where T: ?Sized + GcCandidate<Ptr>, Ptr: HeapPtr<T>
+{
+    marked: Marks,
+    // reachable objects that haven't been scanned yet
+    grey: Vec<Ptr>,
+    // the most pointers pushed to `grey` while scanning objects
+    limit: usize,
+    // the lowest and highest addresses of marked objects with pointers that didn't fit in `grey`,
+    // which must be scanned again
+    overflow: Option<(usize, usize)>,
+    // every object marked, in the order they were marked, if evacuating in that order
+    visited: Option<Vec<Ptr>>,
+    scanned: usize,
+    // pointers found outside the heap, if reported
+    foreign: usize,
+    // objects and bytes stored during this collection, including those pushed since it started
+    objects: usize,
+    bytes: usize,
+    elapsed: Duration,
+    _phantom: PhantomData<T>
+}
+
+// spawns a thread dropping the given heap
+type SpawnSweep<T, Ptr> = fn(Heap<T, Ptr>) -> JoinHandle<()>;
+
+// drops the old heaps left by collections on a background thread, one at a time
+struct Sweeper<T, Ptr>
+    where T: ?Sized + GcCandidate<Ptr>, Ptr: HeapPtr<T>
+{
+    // spawns a thread dropping the given heap, if enabled; only set where heaps can be sent
+    spawn: Option<SpawnSweep<T, Ptr>>,
+    running: Option<JoinHandle<()>>
 }
 
 impl<T: ?Sized + GcCandidate<Ptr>, Ptr: HeapPtr<T>> MarkAndSweepMem<T, Ptr>{
-    /// Creates a new `MarkAndSweepMem` instance with the given capacity in bytes.
-    pub fn new(size: usize) -> Self{
+    /// Creates a new `MarkAndSweepMem` instance with the given configuration, or capacity in bytes.
+    pub fn new(config: impl Into<MemConfig>) -> Self{
+        let config = config.into();
+        return MarkAndSweepMem::with_heap(config.create_heap(config.heap_size()), config);
+    }
+
+    // creates a memory using the given heap, which must match the configuration
+    fn with_heap(active: Heap<T, Ptr>, config: MemConfig) -> Self{
+        return MarkAndSweepMem{
+            active,
+            profile: config.allocation_profiling().then(AllocProfile::new),
+            quarantine: config.quarantine().map(Quarantine::new),
+            identities: config.identity_hashes().then(HashMap::new),
+            hashes_assigned: 0,
+            placements: HashMap::new(),
+            config,
+            hooks: GcHooks::new(),
+            relocations: RelocationHooks::new(),
+            log: GcLog::new(),
+            pauses: PauseTimes::new(),
+            survival: SurvivalStats::new(),
+            marking: None,
+            sweeper: Sweeper{ spawn: None, running: None }
+        };
+    }
+
+    /// Returns the configuration this was created with.
+    pub fn config(&self) -> &MemConfig{
+        return &self.config;
+    }
+
+    /// Sets whether values reclaimed by each following collection are dropped on a background
+    /// thread, rather than before the collection returns, shortening pauses when dropping values
+    /// is expensive. Each collection first waits for the values reclaimed by the last one to be
+    /// dropped, so at most one old heap is kept waiting; see [MarkAndSweepMem::wait_for_sweep].
+    ///
+    /// Only collections that move values sweep in the background; with
+    /// [MemConfig::with_sweep_in_place], values are still dropped during collections. Values
+    /// dropped in the background must not access other managed values when dropped.
+    pub fn set_background_sweep(&mut self, enabled: bool) where T: Send + 'static, Ptr: ThreadSafePtr + 'static{
+        self.sweeper.spawn = enabled.then_some(spawn_sweep::<T, Ptr> as SpawnSweep<T, Ptr>);
+    }
+
+    /// Returns whether reclaimed values are dropped on a background thread.
+    pub fn background_sweep(&self) -> bool{
+        return self.sweeper.spawn.is_some();
+    }
+
+    /// Waits for the values reclaimed by the last collection to be dropped, if they're being
+    /// dropped on a background thread.
+    ///
+    /// # Panics
+    ///
+    /// Resumes the panic of any value that panicked when dropped in the background.
+    pub fn wait_for_sweep(&mut self){
+        self.sweeper.wait();
+    }
+
+    /// Returns the allocation sites and recently freed values recorded, if configured with
+    /// [MemConfig::with_quarantine].
+    pub fn quarantine(&self) -> Option<&Quarantine>{
+        return self.quarantine.as_ref();
+    }
+
+    /// Returns the header of the value at the given pointer, if configured to store headers;
+    /// see [Heap::header]. Mark bits are used by the collector while marking.
+    pub fn header(&self, ptr: &Ptr) -> Option<&ObjectHeader>{
+        return self.active.header(ptr);
+    }
+
+    /// Returns the header of the value at the given pointer mutably, if configured to store
+    /// headers; see [Heap::header_mut].
+    pub fn header_mut(&mut self, ptr: &Ptr) -> Option<&mut ObjectHeader>{
+        return self.active.header_mut(ptr);
+    }
+
+    /// Copies every value in this memory, so that it can later be restored to its current state
+    /// with [MarkAndSweepMem::rollback], e.g. to undo the effects of speculative execution.
+    ///
+    /// # Panics
+    ///
+    /// Panics if an incremental collection is in progress.
+    pub fn checkpoint(&self) -> Checkpoint<T, Ptr> where T: Clone{
+        return self.checkpoint_with(|v| Box::new(v.clone()));
+    }
+
+    /// Makes a checkpoint of this memory, as with [MarkAndSweepMem::checkpoint], using the given
+    /// function to copy each value.
+    ///
+    /// # Panics
+    ///
+    /// Panics if an incremental collection is in progress.
+    pub fn checkpoint_with(&self, copy: impl FnMut(&T) -> Box<T>) -> Checkpoint<T, Ptr>{
+        assert!(self.marking.is_none(), "Mark and Sweep: can't make a checkpoint while collecting");
+        return Checkpoint{
+            heap: self.active.checkpoint_with(copy),
+            quarantine: self.quarantine.clone(),
+            identities: self.identities.clone(),
+            hashes_assigned: self.hashes_assigned,
+            placements: self.placements.clone()
+        };
+    }
+
+    /// Drops every value in this memory and restores the values saved by the given checkpoint, at
+    /// the same addresses, along with their headers and identity hashes. Any incremental
+    /// collection in progress is abandoned.
+    ///
+    /// Values can only be restored to where they were if they haven't been moved since, so any
+    /// collections in between must sweep in place; see [MemConfig::with_sweep_in_place].
+    ///
+    /// # Panics
+    ///
+    /// Panics if a collection has moved values since the checkpoint was made, or if it was made by
+    /// a different memory.
+    pub fn rollback(&mut self, checkpoint: Checkpoint<T, Ptr>){
+        self.marking = None;
+        self.active.rollback(checkpoint.heap);
+        self.quarantine = checkpoint.quarantine;
+        self.identities = checkpoint.identities;
+        self.hashes_assigned = checkpoint.hashes_assigned;
+        self.placements = checkpoint.placements;
+    }
+
+    /// Creates an independent copy of this memory, with the same configuration and a copy of every
+    /// value at the same offset in a new heap, e.g. for snapshots of an interpreter's state.
+    /// Pointers between values are updated to point to the copies, and pointers held elsewhere can
+    /// be translated with [MarkAndSweepMem::ptr_in_fork]. Identity hashes are kept.
+    ///
+    /// Values are copied eagerly; see [MarkAndSweepMem::fork_copy_on_write] to share memory with
+    /// the copy instead.
+    ///
+    /// # Panics
+    ///
+    /// Panics if an incremental collection is in progress.
+    pub fn fork(&self) -> Self where T: Clone{
+        return self.fork_with(|v| Box::new(v.clone()));
+    }
+
+    /// Creates a copy of this memory, as with [MarkAndSweepMem::fork], using the given function to
+    /// copy each value.
+    ///
+    /// # Panics
+    ///
+    /// Panics if an incremental collection is in progress.
+    pub fn fork_with(&self, copy: impl FnMut(&T) -> Box<T>) -> Self{
+        assert!(self.marking.is_none(), "Mark and Sweep: can't fork while collecting");
+        return self.forked(self.active.fork_with(copy));
+    }
+
+    /// Creates a copy of this memory, as with [MarkAndSweepMem::fork], sharing memory with it until
+    /// either is written to if its heap is forkable; see [MemConfig::with_forkable_heaps].
+    /// Otherwise, values are copied eagerly. Pages holding values with pointers are copied anyway,
+    /// as those pointers are updated to point to the copies.
+    ///
+    /// Returns an error if memory can't be mapped for the copy.
+    ///
+    /// # Panics
+    ///
+    /// Panics if an incremental collection is in progress.
+    pub fn fork_copy_on_write(&mut self) -> io::Result<Self> where T: Copy{
+        assert!(self.marking.is_none(), "Mark and Sweep: can't fork while collecting");
+        let active = self.active.fork()?;
+        return Ok(self.forked(active));
+    }
+
+    // wraps a fork of this memory's heap, updating pointers between values to point into it
+    fn forked(&self, mut active: Heap<T, Ptr>) -> Self{
+        let start = active.start();
+        active.for_each_mut(|value, this| value.visit_edges(&mut |edge: &mut Ptr| {
+            if let Some(rebased) = self.active.rebase(edge, start){
+                *edge = rebased;
+            }
+        }, this));
+        let offset = |addr: usize| addr - self.active.start() as usize + start as usize;
         return MarkAndSweepMem{
-            active: Heap::new(size)
+            active,
+            profile: self.config.allocation_profiling().then(AllocProfile::new),
+            quarantine: self.config.quarantine().map(Quarantine::new),
+            identities: self.identities.as_ref().map(|ids| ids.iter().map(|(addr, hash)| (offset(*addr), *hash)).collect()),
+            hashes_assigned: self.hashes_assigned,
+            placements: self.placements.iter().map(|(addr, placement)| (offset(*addr), *placement)).collect(),
+            config: self.config.clone(),
+            hooks: GcHooks::new(),
+            relocations: RelocationHooks::new(),
+            log: GcLog::new(),
+            pauses: PauseTimes::new(),
+            survival: SurvivalStats::new(),
+            marking: None,
+            sweeper: Sweeper{ spawn: None, running: None }
         };
     }
+
+    /// Returns a pointer to the copy in the given fork of the value at the given pointer, or
+    /// `None` if either memory has moved its values since the fork was made.
+    pub fn ptr_in_fork(&self, ptr: &Ptr, fork: &Self) -> Option<Ptr>{
+        if !self.active.contains_ptr_ignoring_meta(ptr){
+            return None;
+        }
+        return self.active.ptr_in_fork(ptr, &fork.active).filter(|p| fork.active.contains_ptr_ignoring_meta(p));
+    }
+
+    // starts a new collection, without marking anything
+    fn begin(&mut self) -> MarkState<T, Ptr>{
+        self.sweeper.wait();
+        self.hooks.fire(&GcPhase::Before);
+        self.debug_verify("before");
+        let (objects, bytes) = (self.len(), self.total_size());
+        gc_event!(DEBUG, collector = "mark-and-sweep", objects, bytes, "collection started");
+        // a collection that panicked while marking leaves marks in headers, which would stop
+        // those values being traced again
+        self.active.clear_marks();
+        return MarkState{
+            marked: Marks::new(&self.active),
+            grey: Vec::new(),
+            limit: self.config.mark_stack_limit().unwrap_or(usize::MAX),
+            overflow: None,
+            visited: (self.config.evacuation_order() == EvacuationOrder::DepthFirst && !self.config.sweep_in_place()).then(Vec::new),
+            scanned: 0,
+            foreign: 0,
+            objects,
+            bytes,
+            elapsed: Duration::ZERO,
+            _phantom: PhantomData
+        };
+    }
+
+    // moves every marked object to a new heap, or sweeps in place if configured to, and drops
+    // the rest, updating roots and weaks
+    unsafe fn finish(&mut self, mut state: MarkState<T, Ptr>, mut roots: Vec<*mut Ptr>, mut weaks: Vec<*mut Ptr>, start: Instant) -> GcReport{
+        // locations given more than once must only be updated once
+        dedup_roots(&mut roots, &mut weaks);
+        self.hooks.fire(&GcPhase::Marked{ reachable: state.marked.len() });
+        let mark_time = state.elapsed + start.elapsed();
+        let sweep_start = Instant::now();
+        let marked = state.marked;
+        // every value stored and whether it survives, if logged
+        let mut logged = Vec::new();
+        if self.log.logs(GcLogLevel::Objects){
+            self.active.for_each(|v, p| logged.push((address(p), mem::size_of_val(v), marked.is_marked(p))));
+        }
+        // clear weak pointers to unmarked objects, leaving any pointing outside of the heap
+        let mut weaks_cleared = 0;
+        for weak in &weaks{
+            let weak = &mut **weak;
+            if !weak.is_cleared() && self.active.contains_ptr_ignoring_meta(weak) && !marked.is_marked(weak){
+                weak.set_cleared();
+                weaks_cleared += 1;
+            }
+        }
+        // ages are kept in headers
+        if self.active.has_headers(){
+            let survival = &mut self.survival;
+            self.active.for_each(|_, p| survival.record((*header_of(p)).age(), marked.is_marked(p)));
+        }
+        let capacity_before = self.active.capacity();
+        let rel = match self.config.sweep_in_place(){
+            true => {
+                self.active.sweep(|p| marked.is_marked(p));
+                HashMap::new()
+            }
+            false => self.relocate(&marked, state.visited.unwrap_or_default(), roots, weaks)
+        };
+        let capacity_after = self.active.capacity();
+        if self.quarantine.is_some() || self.identities.is_some() || !self.placements.is_empty(){
+            let moved: HashMap<usize, usize> = rel.iter().map(|(old, new)| (address(&old.ptr), address(&new.ptr))).collect();
+            let mut survivors = HashSet::with_capacity(self.active.len());
+            self.active.for_each(|_, p| { survivors.insert(address(p)); });
+            let new_address = |old: usize| moved.get(&old).copied().or(survivors.contains(&old).then_some(old));
+            if let Some(quarantine) = &mut self.quarantine{
+                quarantine.collected(new_address);
+            }
+            if let Some(identities) = &mut self.identities{
+                *identities = identities.drain().filter_map(|(old, hash)| Some((new_address(old)?, hash))).collect();
+            }
+            self.placements = self.placements.drain().filter_map(|(old, placement)| Some((new_address(old)?, placement))).collect();
+        }
+        self.debug_verify("after");
+        state.elapsed += start.elapsed();
+        let sweep_time = sweep_start.elapsed();
+        self.pauses.record_phase("sweep", sweep_time);
+        self.pauses.record_collection(state.elapsed);
+        let bytes_after = self.total_size();
+        let report = GcReport::new(state.scanned, state.objects - self.len(), state.bytes - bytes_after, bytes_after, state.elapsed)
+            .with_foreign_pointers(state.foreign)
+            .with_weaks_cleared(weaks_cleared);
+        let report = match capacity_before == capacity_after{
+            true => report,
+            false => report.with_resize(capacity_before, capacity_after)
+        };
+        gc_event!(DEBUG,
+            collector = "mark-and-sweep",
+            objects_scanned = state.scanned,
+            objects_reclaimed = report.objects_reclaimed(),
+            bytes_reclaimed = report.bytes_reclaimed(),
+            bytes_surviving = bytes_after,
+            relocated = rel.len(),
+            weaks_cleared,
+            pause_us = report.pause().as_micros() as u64,
+            "collection finished");
+        self.hooks.fire(&GcPhase::After(report));
+        if self.log.logs(GcLogLevel::Summary){
+            let moved: HashMap<usize, usize> = rel.iter().map(|(old, new)| (address(&old.ptr), address(&new.ptr))).collect();
+            let objects = logged.into_iter().map(|(addr, size, survived)| {
+                let fate = match moved.get(&addr){
+                    Some(to) if *to != addr => ObjectFate::Moved(*to),
+                    _ if survived => ObjectFate::Survived,
+                    _ => ObjectFate::Reclaimed
+                };
+                ObjectRecord::new(addr, size, fate)
+            }).collect();
+            let record = self.log.next_record("mark-and-sweep", report)
+                .with_phase("mark", mark_time)
+                .with_phase("sweep", sweep_time)
+                .with_objects(objects);
+            self.log.emit(record);
+        }
+        return report;
+    }
+
+    // moves every marked object to a new heap, starting with those in `first`, and drops the rest,
+    // possibly in the background, updating roots and weaks, and returns the old and new pointers
+    // of every object moved
+    unsafe fn relocate(&mut self, marked: &Marks, first: Vec<Ptr>, roots: Vec<*mut Ptr>, weaks: Vec<*mut Ptr>) -> HashMap<HashWrap<T, Ptr>, HashWrap<T, Ptr>>{
+        // new target heap
+        let mut next: Heap<T, Ptr> = self.config.create_heap(self.active.capacity());
+        let first = self.order_by_placement(first);
+        // sweep phase: copy marked objects to new heap (in parallel if configured) and update pointers
+        let moved = loop{
+            let evacuated = match self.sweeper.spawn{
+                Some(_) => self.active.try_evacuate_kept_into_ordered(&mut next, &first, |p| marked.is_marked(p), self.config.parallelism()),
+                None => self.active.try_evacuate_into_ordered(&mut next, &first, |p| marked.is_marked(p), self.config.parallelism())
+            };
+            match evacuated{
+                Ok(moved) => break moved,
+                // survivors may need more space than they took up, e.g. when placed in a different
+                // order; grow as allowed by the growth policy, or by just enough if it's fixed
+                Err(needed) => {
+                    let capacity = self.config.growth().grow(next.capacity(), needed).unwrap_or(needed);
+                    gc_event!(DEBUG, from = next.capacity(), to = capacity, "growing heap to fit survivors");
+                    next = self.config.create_heap(capacity);
+                }
+            }
+        };
+        let rel: HashMap<HashWrap<T, Ptr>, HashWrap<T, Ptr>> = moved.into_iter()
+            .map(|(old, new)| (HashWrap::new(old), HashWrap::new(new)))
+            .collect();
+        // every pointer into the heap was marked, so any others point outside of it
+        let find = |p: &Ptr| match rel.get(&HashWrap::new(p.clone())){
+            Some(new) => new.ptr.clone(),
+            None => p.clone()
+        };
+        next.for_each_mut(|o: &mut T, this: &Ptr| o.visit_edges(&mut |p: &mut Ptr| *p = find(p), this));
+        // swap them, and drop the old heap along with any unmarked objects left in it
+        swap(&mut self.active, &mut next);
+        self.sweeper.sweep(next);
+        // update root pointers
+        for root in roots{
+            *root = find(&*root);
+        }
+        for weak in weaks{
+            match rel.get(&HashWrap::new((*weak).clone())) {
+                None => {}
+                Some(p) => *weak = p.ptr.clone()
+            }
+        }
+        if !self.relocations.is_empty(){
+            for (old, new) in &rel{
+                self.relocations.fire(&old.ptr, &new.ptr);
+            }
+        }
+        return rel;
+    }
+
+    // sorts the given objects, or every object in reverse order if none are given, so that
+    // immortal objects come first and cold objects last
+    fn order_by_placement(&self, mut order: Vec<Ptr>) -> Vec<Ptr>{
+        if self.placements.is_empty(){
+            return order;
+        }
+        if order.is_empty(){
+            self.active.for_each(|_, p| order.push(p.clone()));
+            order.reverse();
+        }
+        order.sort_by_key(|p| match self.placements.get(&address(p)){
+            Some(Placement::Immortal) => 0,
+            None | Some(Placement::Hot) => 1,
+            Some(Placement::Cold) => 2
+        });
+        return order;
+    }
+
+    // adds every immortal object to the grey stack
+    fn mark_immortals(&self, state: &mut MarkState<T, Ptr>){
+        if self.placements.values().any(|placement| *placement == Placement::Immortal){
+            self.active.for_each(|_, p| if self.placements.get(&address(p)) == Some(&Placement::Immortal){
+                state.grey.push(p.clone());
+            });
+        }
+    }
+
+    // records the caller as the allocation site of the value at the given pointer, if quarantining,
+    // assigns the value an identity hash, if configured to, and greys it if marking is in progress
+    #[track_caller]
+    fn record_allocation(&mut self, ptr: &Ptr){
+        // the space may have been used by a dropped value with a different placement
+        self.placements.remove(&address(ptr));
+        if let Some(state) = &mut self.marking{
+            // values pushed while marking survive, along with anything they point to
+            state.grey.push(ptr.clone());
+            state.objects += 1;
+            state.bytes += unsafe{ mem::size_of_val(&*ptr.to_raw_ptr()) };
+        }
+        if let Some(quarantine) = &mut self.quarantine{
+            quarantine.allocated(address(ptr), Location::caller());
+        }
+        if let Some(identities) = &mut self.identities{
+            self.hashes_assigned += 1;
+            identities.insert(address(ptr), mix_hash(self.hashes_assigned));
+        }
+    }
+
+    // panics if the given pointer points to a value that was dropped or moved, if quarantining
+    fn check_stale(&self, ptr: &Ptr){
+        if let Some(freed) = self.quarantine.as_ref().and_then(|q| q.get(address(ptr))){
+            panic!("Mark and Sweep: access through stale pointer {:?}: {freed}", ptr.to_raw_ptr());
+        }
+    }
+
+    // verifies this memory in debug builds if configured to, panicking if it's broken
+    fn debug_verify(&self, when: &str){
+        if cfg!(debug_assertions) && self.config.heap_verification(){
+            if let Err(err) = self.verify(){
+                panic!("Mark and Sweep: heap verification failed {when} collection: {err}");
+            }
+        }
+    }
+}
+
+#[cfg(all(feature = "persist", unix, target_pointer_width = "64"))]
+impl<T: GcCandidate<Ptr> + Copy, Ptr: HeapPtr<T>> MarkAndSweepMem<T, Ptr>{
+    /// Opens a memory stored in the given file, creating it with the capacity and headers of the
+    /// given configuration if it doesn't exist; see [Heap::open_file]. Collections always sweep in
+    /// place, so that values stay in the file.
+    ///
+    /// If the file is mapped at a different address than when it was last synced, pointers between
+    /// values are updated to match.
+    pub fn open_file(config: impl Into<MemConfig>, path: impl AsRef<Path>) -> io::Result<Self>{
+        let config = config.into().with_sweep_in_place(true);
+        let mut active: Heap<T, Ptr> = Heap::open_file(path, config.heap_size(), config.object_headers())?;
+        if config.skip_drop(){
+            active = active.with_skip_drop();
+        }
+        if let Some(old) = active.moved_from(){
+            let (old, new, used) = (old as usize, active.start() as usize, active.used());
+            active.for_each_mut(|value, this| value.visit_edges(&mut |edge: &mut Ptr| {
+                let addr = edge.to_raw_ptr() as usize;
+                if addr >= old && addr - old <= used{
+                    let mut moved = Ptr::from_raw_ptr((addr - old + new) as *const T);
+                    moved.copy_meta(edge);
+                    *edge = moved;
+                }
+            }, this));
+            active.sync()?;
+        }
+        return Ok(MarkAndSweepMem::with_heap(active, config));
+    }
+
+    /// Writes this memory to the file it was opened from; see [Heap::sync].
+    pub fn sync(&self) -> io::Result<()>{
+        return self.active.sync();
+    }
+}
+
+impl<T: ?Sized + GcCandidate<Ptr>, Ptr: HeapPtr<T>> MarkAndSweepMem<T, Ptr>{
+    /// Pushes a value built in zero-filled space by `init`; see [Heap::push_zeroed].
+    ///
+    /// # Safety
+    ///
+    /// As for [Heap::push_zeroed].
+    #[track_caller]
+    pub unsafe fn push_zeroed(&mut self, layout: Layout, init: impl FnOnce(*mut u8) -> *mut T) -> Result<Ptr, AllocErrorKind>{
+        let ptr = self.active.push_zeroed(layout, init)?;
+        record_caller(&mut self.profile, layout.size());
+        self.record_allocation(&ptr);
+        return Ok(ptr);
+    }
+
+    /// Prepares the next `n` bytes of the heap for allocation; see [Heap::reserve_bytes].
+    pub fn reserve_bytes(&mut self, n: usize){
+        self.active.reserve_bytes(n);
+    }
+
+    /// Reserves space for indexing `n` more values; see [Heap::reserve_objects].
+    pub fn reserve_objects(&mut self, n: usize){
+        self.active.reserve_objects(n);
+    }
+
+    /// Reserves space for a value to be built byte by byte; see [Heap::alloc_uninit].
+    #[track_caller]
+    pub fn alloc_uninit(&mut self, size: usize, align: usize, meta: *const T) -> Option<(Ptr, &mut [MaybeUninit<u8>])>{
+        let reserved = self.active.alloc_uninit(size, align, meta);
+        if reserved.is_some(){
+            record_caller(&mut self.profile, size);
+        }
+        return reserved;
+    }
+
+    /// Adds a value reserved by [MarkAndSweepMem::alloc_uninit] to this memory, once its bytes
+    /// have been written.
+    ///
+    /// # Safety
+    ///
+    /// As for [Heap::commit_uninit]; this memory must not have been collected since the value was
+    /// reserved.
+    #[track_caller]
+    pub unsafe fn commit_uninit(&mut self, ptr: Ptr){
+        self.active.commit_uninit(ptr.clone());
+        self.record_allocation(&ptr);
+    }
+
+    /// Pushes a value initialized from all-zero bytes by `init`; see [Heap::alloc_zeroed].
+    ///
+    /// # Safety
+    ///
+    /// As for [Heap::alloc_zeroed].
+    #[track_caller]
+    pub unsafe fn alloc_zeroed(&mut self, init: impl FnOnce(&mut T)) -> Result<Ptr, AllocErrorKind> where T: Sized{
+        let ptr = self.active.alloc_zeroed(init)?;
+        record_caller(&mut self.profile, mem::size_of::<T>());
+        self.record_allocation(&ptr);
+        return Ok(ptr);
+    }
+}
+
+impl<T, Ptr: HeapPtr<[T]>> MarkAndSweepMem<[T], Ptr> where [T]: GcCandidate<Ptr>{
+    /// Pushes a slice containing clones of the given values; see [Heap::push_slice].
+    #[track_caller]
+    pub fn push_slice(&mut self, data: &[T]) -> Result<Ptr, AllocErrorKind> where T: Clone{
+        return self.push_slice_with_len(data.len(), |i| data[i].clone());
+    }
+
+    /// Pushes a slice built by calling `fill` for each index; see [Heap::push_slice_with_len].
+    #[track_caller]
+    pub fn push_slice_with_len(&mut self, len: usize, fill: impl FnMut(usize) -> T) -> Result<Ptr, AllocErrorKind>{
+        let ptr = self.active.push_slice_with_len(len, fill)?;
+        record_caller(&mut self.profile, len * mem::size_of::<T>());
+        self.record_allocation(&ptr);
+        return Ok(ptr);
+    }
 }
 
 //////////////// impls
 
 impl<T: ?Sized + GcCandidate<Ptr>, Ptr: HeapPtr<T>> ManagedMem<T, Ptr> for MarkAndSweepMem<T, Ptr>{
-    fn push(&mut self, v: Box<T>) -> Option<Ptr>{
-        return self.active.push(v);
+    fn push(&mut self, v: Box<T>) -> Result<Ptr, AllocError<T>>{
+        return self.push_with(v, |x| x);
     }
 
-    fn push_with(&mut self, v: Box<T>, with: impl FnOnce(Ptr) -> Ptr) -> Option<Ptr> {
-        return self.active.push_with(v, with);
+    fn push_with(&mut self, v: Box<T>, with: impl FnOnce(Ptr) -> Ptr) -> Result<Ptr, AllocError<T>>{
+        return self.push_placed(v, Placement::Hot, with);
+    }
+
+    fn push_placed(&mut self, v: Box<T>, placement: Placement, with: impl FnOnce(Ptr) -> Ptr) -> Result<Ptr, AllocError<T>>{
+        let size = mem::size_of_val(v.as_ref());
+        let ptr = self.active.push_with(v, with)?;
+        record_caller(&mut self.profile, size);
+        self.record_allocation(&ptr);
+        if placement != Placement::Hot{
+            self.placements.insert(address(&ptr), placement);
+        }
+        return Ok(ptr);
     }
 
     fn get(&self, idx: usize) -> &T{
@@ -47,9 +730,20 @@ impl<T: ?Sized + GcCandidate<Ptr>, Ptr: HeapPtr<T>> ManagedMem<T, Ptr> for MarkA
     }
 
     fn get_by(&mut self, ptr: &Ptr) -> Option<&mut T>{
+        if self.quarantine.is_some() && !self.active.contains_ptr(ptr){
+            self.check_stale(ptr);
+        }
         return self.active.get_by(ptr);
     }
 
+    fn get_by_ref(&self, ptr: &Ptr) -> Option<&T>{
+        let value = self.active.get_by_ref(ptr);
+        if value.is_none(){
+            self.check_stale(ptr);
+        }
+        return value;
+    }
+
     fn len(&self) -> usize{
         return self.active.len();
     }
@@ -58,81 +752,385 @@ impl<T: ?Sized + GcCandidate<Ptr>, Ptr: HeapPtr<T>> ManagedMem<T, Ptr> for MarkA
         return self.active.contains_ptr(ptr);
     }
 
+    fn contains_ptr_ignoring_meta(&self, ptr: &Ptr) -> bool{
+        return self.active.contains_ptr_ignoring_meta(ptr);
+    }
+
+    fn to_full_ptr(&self, ptr: &Ptr) -> Option<Ptr>{
+        return self.active.to_full_ptr(ptr);
+    }
+
+    fn identity_hash(&self, ptr: &Ptr) -> Option<u64>{
+        if !self.active.contains_ptr_ignoring_meta(ptr){
+            return None;
+        }
+        return match &self.identities{
+            Some(identities) => identities.get(&address(ptr)).copied(),
+            // values never move when swept in place, so their addresses are stable
+            None if self.config.sweep_in_place() => Some(mix_hash(address(ptr) as u64)),
+            None => None
+        };
+    }
+
     fn for_each(&self, cb: impl FnMut(&T, &Ptr)){
         self.active.for_each(cb);
     }
 
+    fn retain(&mut self, mut f: impl FnMut(&Ptr, &T) -> bool){
+        match &mut self.marking{
+            None => self.active.retain(f),
+            Some(state) => self.active.retain(|ptr, v| {
+                let keep = f(ptr, v);
+                if !keep{
+                    state.marked.unmark(ptr);
+                    state.grey.retain(|p| !p.eq_ignoring_meta(ptr));
+                    state.objects -= 1;
+                    state.bytes -= mem::size_of_val(v);
+                }
+                keep
+            })
+        }
+    }
+
     unsafe fn gc(&mut self, roots: Vec<*mut Ptr>, weaks: Vec<*mut Ptr>){
-        // new target heap
-        let mut next: Heap<T, Ptr> = Heap::new(self.active.capacity());
+        self.gc_with_report(roots, weaks);
+    }
+
+    unsafe fn gc_with_report(&mut self, roots: Vec<*mut Ptr>, weaks: Vec<*mut Ptr>) -> GcReport{
+        let start = Instant::now();
+        // finish any incremental collection in progress
+        let mut state = match self.marking.take(){
+            Some(state) => state,
+            None => self.begin()
+        };
         // mark phase: mark every reachable object
-        let mut marked: HashSet<HashWrap<T, Ptr>> = HashSet::with_capacity(5);
-        for root in &roots{
-            mark_reachable(&mut self.active, &**root, &mut marked);
-        }
-        // sweep phase: copy marked objects to new heap and update pointers
-        let mut rel: HashMap<HashWrap<T, Ptr>, HashWrap<T, Ptr>> = HashMap::with_capacity(marked.len());
-        for i in (0..self.active.len()).rev(){
-            let (obj, old_ptr): (Box<T>, Ptr) = self.active.take(i);
-            if marked.contains(&HashWrap::new(old_ptr.clone())){
-                match next.push_with(obj, |mut x| {x.copy_meta(&old_ptr); x}){
-                    Some(new_ptr) => rel.insert(HashWrap::new(old_ptr), HashWrap::new(new_ptr)),
-                    None => panic!("Mark and Sweep: could not allocate space in inactive heap for object")
-                };
-            }else{
-                drop(obj);
+        state.grey.extend(roots.iter().map(|root| (**root).clone()));
+        self.mark_immortals(&mut state);
+        mark(&mut self.active, &mut state, self.config.foreign_pointers(), |_| true);
+        self.pauses.record_phase("mark", start.elapsed());
+        return self.finish(state, roots, weaks, start);
+    }
+
+    fn gc_hooks(&mut self) -> Option<&mut GcHooks>{
+        return Some(&mut self.hooks);
+    }
+
+    fn relocation_hooks(&mut self) -> Option<&mut RelocationHooks<Ptr>>{
+        return Some(&mut self.relocations);
+    }
+
+    fn gc_log(&mut self) -> Option<&mut GcLog>{
+        return Some(&mut self.log);
+    }
+
+    fn allocation_profile(&self) -> Option<&AllocProfile>{
+        return self.profile.as_ref();
+    }
+
+    fn pause_times(&self) -> Option<&PauseTimes>{
+        return Some(&self.pauses);
+    }
+
+    fn age(&self, ptr: &Ptr) -> Option<u32>{
+        return self.active.header(ptr).map(ObjectHeader::age);
+    }
+
+    fn survival(&self) -> Option<&SurvivalStats>{
+        return self.active.has_headers().then_some(&self.survival);
+    }
+
+    fn verify(&self) -> Result<(), VerifyError>{
+        self.active.verify()?;
+        return verify_edges(self);
+    }
+
+    fn capacity(&self) -> Option<usize>{
+        return Some(self.active.capacity());
+    }
+
+    fn used_bytes(&self) -> usize{
+        return self.active.used();
+    }
+
+    fn record_write(&mut self, _src: &Ptr, dst: &Ptr){
+        // an already-scanned object may now point to an unmarked one
+        if let Some(state) = &mut self.marking{
+            if self.config.write_barrier() == WriteBarrier::IncrementalUpdate{
+                state.grey.push(dst.clone());
             }
         }
-        let find = |p: &Ptr| {
-            rel.get(&HashWrap::new(p.clone()))
-                .expect(format!("Could not find updated pointer for {:?} in table {rel:?}!", p.to_raw_ptr()).as_str())
-                .ptr
-                .clone()
+    }
+
+    fn has_write_barrier(&self) -> bool{
+        return self.marking.is_some() && self.config.write_barrier() == WriteBarrier::IncrementalUpdate;
+    }
+
+    fn record_overwrite(&mut self, _src: &Ptr, old: &Ptr){
+        // the only path to an unmarked object may be about to be removed
+        if let Some(state) = &mut self.marking{
+            if self.config.write_barrier() == WriteBarrier::SnapshotAtBeginning && !old.is_cleared(){
+                state.grey.push(old.clone());
+            }
+        }
+    }
+
+    fn has_pre_write_barrier(&self) -> bool{
+        return self.marking.is_some() && self.config.write_barrier() == WriteBarrier::SnapshotAtBeginning;
+    }
+}
+
+impl<T: ?Sized + GcCandidate<Ptr>, Ptr: HeapPtr<T>> IncrementalMem<T, Ptr> for MarkAndSweepMem<T, Ptr>{
+    unsafe fn gc_step(&mut self, roots: Vec<*mut Ptr>, weaks: Vec<*mut Ptr>, budget: Budget) -> GcPhaseProgress{
+        let start = Instant::now();
+        let (mut state, started) = match self.marking.take(){
+            Some(state) => (state, false),
+            None => (self.begin(), true)
         };
-        next.for_each_mut(|o: &mut T, this: &Ptr| o.adjust_ptrs(find, this));
-        // reset the active heap - should not drop anything, since everything has been moved
-        self.active.reset();
-        // and swap them
-        swap(&mut self.active, &mut next);
-        // update root pointers
-        for root in roots{
-            *root = find(&*root);
+        // roots may have changed since the last step, so rescan them every time, unless only
+        // what was reachable when marking started needs to be kept
+        if started || self.config.write_barrier() == WriteBarrier::IncrementalUpdate{
+            state.grey.extend(roots.iter().map(|root| (**root).clone()));
         }
-        for weak in weaks{
-            match rel.get(&HashWrap::new((*weak).clone())) {
-                None => {}
-                Some(p) => *weak = p.ptr.clone()
-            }
+        self.mark_immortals(&mut state);
+        let mut tracker = BudgetTracker::new(budget);
+        mark(&mut self.active, &mut state, self.config.foreign_pointers(), |size| tracker.spend(size));
+        self.pauses.record_phase("mark", start.elapsed());
+        if state.grey.is_empty() && state.overflow.is_none(){
+            return GcPhaseProgress::Complete(self.finish(state, roots, weaks, start));
         }
+        state.elapsed += start.elapsed();
+        let progress = GcPhaseProgress::Marking{ marked: state.marked.len(), pending: state.grey.len() };
+        self.marking = Some(state);
+        return progress;
+    }
+
+    fn is_collecting(&self) -> bool{
+        return self.marking.is_some();
     }
 }
 
-fn mark_reachable<T: ?Sized + GcCandidate<Ptr>, Ptr: HeapPtr<T>>(heap: &mut Heap<T, Ptr>, root: &Ptr, marked: &mut HashSet<HashWrap<T, Ptr>>) -> usize{
-    let mut count = 0;
-    // unprocessed objects
-    let mut stack: Vec<Ptr> = Vec::with_capacity(5);
-    stack.push(root.clone());
-    while let Some(current) = stack.pop(){
-        if let Some(obj) = heap.get_by(&current){
-            // if not already marked,
-            let marker = HashWrap::new(current.clone());
-            if !marked.contains(&marker){
-                // mark the object
-                marked.insert(marker);
-                count += 1;
-                // schedule every pointee for marking
-                for mut ptr in obj.collect_managed_pointers(&current){
-                    if Ptr::has_significant_meta(){
-                        ptr = heap.to_full_ptr(&ptr);
+// the pointers in a mark state only point into the memory's own heap, like the heap's own indexes
+unsafe impl<T: ?Sized + GcCandidate<Ptr> + Send, Ptr: HeapPtr<T> + ThreadSafePtr> Send for MarkState<T, Ptr>{}
+
+unsafe impl<T: ?Sized + GcCandidate<Ptr> + Sync, Ptr: HeapPtr<T> + ThreadSafePtr> Sync for MarkState<T, Ptr>{}
+
+fn address<T: ?Sized, Ptr: HeapPtr<T>>(ptr: &Ptr) -> usize{
+    return ptr.to_raw_ptr().cast::<u8>() as usize;
+}
+
+// marks every object reachable from the grey stack, stopping early when `more` returns false
+// after marking an object, and counts the objects marked and foreign pointers found
+fn mark<T: ?Sized + GcCandidate<Ptr>, Ptr: HeapPtr<T>>(heap: &mut Heap<T, Ptr>, state: &mut MarkState<T, Ptr>, foreign: ForeignPtrPolicy, mut more: impl FnMut(usize) -> bool){
+    let bounds = heap_bounds(heap);
+    loop{
+        while let Some(current) = state.grey.pop(){
+            // edges may not have all of the metadata the object was pushed with
+            if let Some((current, obj)) = heap.find_ignoring_meta(&current){
+                // mark the object, if not already marked
+                if state.marked.mark(&current){
+                    state.scanned += 1;
+                    if let Some(visited) = &mut state.visited{
+                        visited.push(current.clone());
+                    }
+                    // schedule every pointee for marking, remembering this object if they don't all fit
+                    let mut overflowed = false;
+                    obj.trace(&mut |ptr: &Ptr| {
+                        if state.grey.len() < state.limit{
+                            state.grey.push(ptr.clone());
+                        }else if bounds.contains(&address(ptr)){
+                            overflowed = true;
+                        }else{
+                            foreign_pointer(ptr.clone(), foreign, &mut state.foreign);
+                        }
+                    }, &current);
+                    if overflowed{
+                        widen(&mut state.overflow, address(&current));
+                    }
+                    if !more(mem::size_of_val(obj)){
+                        return;
                     }
-                    stack.push(ptr);
                 }
+            }else{
+                foreign_pointer(current, foreign, &mut state.foreign);
+            }
+        }
+        match state.overflow.take(){
+            Some(region) => rescan(heap, state, region),
+            None => return
+        }
+    }
+}
+
+// scans every marked object within the given addresses again, pushing pointers to objects that
+// aren't marked yet, after the grey stack overflowed
+fn rescan<T: ?Sized + GcCandidate<Ptr>, Ptr: HeapPtr<T>>(heap: &Heap<T, Ptr>, state: &mut MarkState<T, Ptr>, (low, high): (usize, usize)){
+    gc_event!(DEBUG, collector = "mark-and-sweep", low, high, "rescanning after mark stack overflow");
+    // the heap's lock can't be held while checking pointers, so objects are visited by index
+    for idx in 0..heap.len(){
+        let this = heap.ptr_at(idx);
+        let addr = address(&this);
+        if addr < low || addr > high || !state.marked.is_marked(&this){
+            continue;
+        }
+        let mut overflowed = false;
+        heap.get(idx).trace(&mut |ptr: &Ptr| {
+            // pointers outside of the heap were already handled when they didn't fit
+            let unmarked = heap.contains_ptr_ignoring_meta(ptr) && !state.marked.is_marked(ptr);
+            if unmarked && state.grey.len() < state.limit{
+                state.grey.push(ptr.clone());
+            }else if unmarked{
+                overflowed = true;
             }
-        }else{
-            panic!("Managed pointer {:?} not in heap!", HashWrap::new(current));
+        }, &this);
+        if overflowed{
+            widen(&mut state.overflow, addr);
         }
     }
-    return count;
+}
+
+// the addresses that values in the given heap may start at
+fn heap_bounds<T: ?Sized + GcCandidate<Ptr>, Ptr: HeapPtr<T>>(heap: &Heap<T, Ptr>) -> RangeInclusive<usize>{
+    let start = heap.start() as usize;
+    return start..=start + heap.capacity();
+}
+
+// extends a range of addresses to include the given address
+fn widen(region: &mut Option<(usize, usize)>, addr: usize){
+    *region = Some(match *region{
+        Some((low, high)) => (low.min(addr), high.max(addr)),
+        None => (addr, addr)
+    });
+}
+
+// handles a pointer found while marking that doesn't point to an object in the heap
+fn foreign_pointer<T: ?Sized + GcCandidate<Ptr>, Ptr: HeapPtr<T>>(ptr: Ptr, policy: ForeignPtrPolicy, count: &mut usize){
+    match policy{
+        ForeignPtrPolicy::Skip => {}
+        ForeignPtrPolicy::Report => {
+            gc_event!(WARN, collector = "mark-and-sweep", address = ?ptr.to_raw_ptr(), "skipped pointer outside of heap");
+            *count += 1;
+        }
+        ForeignPtrPolicy::Panic => panic!("Managed pointer {:?} not in heap!", HashWrap::new(ptr))
+    }
+}
+
+impl<T: ?Sized + GcCandidate<Ptr>, Ptr: HeapPtr<T>> Sweeper<T, Ptr>{
+    // drops the given old heap, in the background if enabled
+    fn sweep(&mut self, heap: Heap<T, Ptr>){
+        match self.spawn{
+            Some(spawn) => self.running = Some(spawn(heap)),
+            None => drop(heap)
+        }
+    }
+
+    // waits for the heap being dropped in the background, if any
+    fn wait(&mut self){
+        if let Some(running) = self.running.take(){
+            if let Err(payload) = running.join(){
+                panic::resume_unwind(payload);
+            }
+        }
+    }
+}
+
+impl<T: ?Sized + GcCandidate<Ptr>, Ptr: HeapPtr<T>> Drop for Sweeper<T, Ptr>{
+    fn drop(&mut self){
+        if let Some(running) = self.running.take(){
+            // a panic on the sweep thread has already been reported
+            let _ = running.join();
+        }
+    }
+}
+
+fn spawn_sweep<T: ?Sized + GcCandidate<Ptr> + Send + 'static, Ptr: HeapPtr<T> + ThreadSafePtr + 'static>(heap: Heap<T, Ptr>) -> JoinHandle<()>{
+    return thread::Builder::new()
+        .name("swifer-sweep".to_string())
+        .spawn(move || drop(heap))
+        .expect("Mark and Sweep: could not spawn sweep thread");
+}
+
+impl Marks{
+    fn new<T: ?Sized + GcCandidate<Ptr>, Ptr: HeapPtr<T>>(heap: &Heap<T, Ptr>) -> Self{
+        if heap.has_headers(){
+            return Marks::Headers(0);
+        }
+        // values of different types may have any alignment, so any byte may start one
+        let positions = heap.capacity();
+        return Marks::Bitmap{
+            bits: vec![0; positions.saturating_add(63) / 64],
+            base: heap.start() as usize,
+            count: 0
+        };
+    }
+
+    // returns the word and bit for the object at the given pointer in a bitmap
+    fn locate<T: ?Sized, Ptr: HeapPtr<T>>(ptr: &Ptr, base: usize) -> (usize, u64){
+        let position = ptr.to_raw_ptr().cast::<u8>() as usize - base;
+        return (position / 64, 1 << (position % 64));
+    }
+
+    // marks the object at the given pointer, which must be in the heap, returning whether it
+    // wasn't already marked
+    fn mark<T: ?Sized, Ptr: HeapPtr<T>>(&mut self, ptr: &Ptr) -> bool{
+        return match self{
+            Marks::Bitmap{ bits, base, count } => {
+                let (word, bit) = Self::locate(ptr, *base);
+                if bits[word] & bit != 0{
+                    return false;
+                }
+                bits[word] |= bit;
+                *count += 1;
+                true
+            }
+            Marks::Headers(count) => {
+                // safety: the heap has headers, and the object is in it
+                let header = unsafe{ &mut *header_of(ptr) };
+                if header.is_marked(){
+                    return false;
+                }
+                header.set_marked(true);
+                *count += 1;
+                true
+            }
+        };
+    }
+
+    fn is_marked<T: ?Sized, Ptr: HeapPtr<T>>(&self, ptr: &Ptr) -> bool{
+        return match self{
+            Marks::Bitmap{ bits, base, .. } => {
+                let (word, bit) = Self::locate(ptr, *base);
+                bits[word] & bit != 0
+            }
+            Marks::Headers(_) => unsafe{ (*header_of(ptr)).is_marked() }
+        };
+    }
+
+    // forgets the object at the given pointer before it's dropped
+    fn unmark<T: ?Sized, Ptr: HeapPtr<T>>(&mut self, ptr: &Ptr){
+        match self{
+            Marks::Bitmap{ bits, base, count } => {
+                let (word, bit) = Self::locate(ptr, *base);
+                if bits[word] & bit != 0{
+                    bits[word] &= !bit;
+                    *count -= 1;
+                }
+            }
+            Marks::Headers(count) => {
+                let header = unsafe{ &mut *header_of(ptr) };
+                if header.is_marked(){
+                    header.set_marked(false);
+                    *count -= 1;
+                }
+            }
+        }
+    }
+
+    fn len(&self) -> usize{
+        return match self{
+            Marks::Bitmap{ count, .. } => *count,
+            Marks::Headers(count) => *count
+        };
+    }
 }
 
 // allow using HashMap/Debug over !Hash/!Debug Ptr
@@ -155,7 +1153,8 @@ impl<T: ?Sized + GcCandidate<Ptr>, Ptr: HeapPtr<T>> HashWrap<T, Ptr>{
 
 impl<T: ?Sized + GcCandidate<Ptr>, Ptr: HeapPtr<T>> Hash for HashWrap<T, Ptr>{
     fn hash<H: Hasher>(&self, state: &mut H){
-        self.ptr.to_raw_ptr().hash(state)
+        // must be consistent with `eq_ignoring_meta`
+        self.ptr.to_raw_ptr().cast::<()>().hash(state)
     }
 }
 
@@ -0,0 +1,97 @@
+//! Exporting collection statistics through the [`metrics`](https://docs.rs/metrics) facade, so
+//! that they can be graphed by any exporter, such as Prometheus.
+//!
+//! A [GcMetrics] installed into a memory's [GcHooks] records every collection as it completes.
+//! Occupancy can't be read from hooks, so it's recorded separately by [GcMetrics::record_heap],
+//! e.g. whenever the embedder's metrics are scraped. Every metric is labelled with `memory` set to
+//! the name given to the [GcMetrics], to tell apart several memories in one process:
+//!
+//! - `swifer_gc_collections_total`: the number of collections completed.
+//! - `swifer_gc_objects_reclaimed_total` and `swifer_gc_bytes_reclaimed_total`: the values dropped
+//!   by collections.
+//! - `swifer_gc_allocated_bytes_total`: the bytes allocated between collections, counting from
+//!   the first collection recorded.
+//! - `swifer_gc_allocation_rate`: the bytes allocated per second between the last two collections.
+//! - `swifer_gc_pause_seconds`: a histogram of the pause of each collection.
+//! - `swifer_heap_live_bytes`: the bytes surviving the last collection.
+//! - `swifer_heap_used_bytes`, `swifer_heap_capacity_bytes` and `swifer_heap_objects`: the
+//!   occupancy of the memory when last given to [GcMetrics::record_heap].
+
+use std::time::Instant;
+use ::metrics::{counter, describe_counter, describe_gauge, describe_histogram, gauge, histogram, Unit};
+use crate::gc::{GcCandidate, ManagedMem};
+use crate::gc::hooks::{GcHooks, GcPhase};
+use crate::gc::report::GcReport;
+use crate::heap::HeapPtr;
+
+/// Records the statistics of a single memory's collections to the installed `metrics` recorder.
+#[derive(Clone, Debug)]
+pub struct GcMetrics{
+    memory: &'static str,
+    // when the last collection completed, and the bytes surviving it
+    last: Option<(Instant, usize)>
+}
+
+impl GcMetrics{
+    /// Creates a recorder labelling its metrics with the given memory name.
+    pub fn new(memory: &'static str) -> Self{
+        return GcMetrics{ memory, last: None };
+    }
+
+    /// Registers a description and unit for every metric with the installed recorder.
+    pub fn describe(){
+        describe_counter!("swifer_gc_collections_total", "Collections completed");
+        describe_counter!("swifer_gc_objects_reclaimed_total", "Values dropped by collections");
+        describe_counter!("swifer_gc_bytes_reclaimed_total", Unit::Bytes, "Bytes dropped by collections");
+        describe_counter!("swifer_gc_allocated_bytes_total", Unit::Bytes, "Bytes allocated between collections");
+        describe_gauge!("swifer_gc_allocation_rate", "Bytes allocated per second between the last two collections");
+        describe_histogram!("swifer_gc_pause_seconds", Unit::Seconds, "Pause of each collection");
+        describe_gauge!("swifer_heap_live_bytes", Unit::Bytes, "Bytes surviving the last collection");
+        describe_gauge!("swifer_heap_used_bytes", Unit::Bytes, "Bytes used by the heap");
+        describe_gauge!("swifer_heap_capacity_bytes", Unit::Bytes, "Capacity of the heap");
+        describe_gauge!("swifer_heap_objects", "Values stored");
+    }
+
+    /// Adds a hook recording every following collection to the given hooks, e.g. those returned
+    /// by [ManagedMem::gc_hooks].
+    pub fn install(mut self, hooks: &mut GcHooks){
+        hooks.add(move |phase| {
+            if let GcPhase::After(report) = phase{
+                self.record_collection(report);
+            }
+        });
+    }
+
+    /// Records a collection that just completed.
+    pub fn record_collection(&mut self, report: &GcReport){
+        let memory = self.memory;
+        counter!("swifer_gc_collections_total", 1, "memory" => memory);
+        counter!("swifer_gc_objects_reclaimed_total", report.objects_reclaimed() as u64, "memory" => memory);
+        counter!("swifer_gc_bytes_reclaimed_total", report.bytes_reclaimed() as u64, "memory" => memory);
+        histogram!("swifer_gc_pause_seconds", report.pause().as_secs_f64(), "memory" => memory);
+        gauge!("swifer_heap_live_bytes", report.bytes_surviving() as f64, "memory" => memory);
+
+        let now = Instant::now();
+        if let Some((then, surviving)) = self.last.replace((now, report.bytes_surviving())){
+            // everything stored before this collection, except what survived the last one
+            let allocated = (report.bytes_surviving() + report.bytes_reclaimed()).saturating_sub(surviving);
+            counter!("swifer_gc_allocated_bytes_total", allocated as u64, "memory" => memory);
+            let elapsed = now.duration_since(then).as_secs_f64();
+            if elapsed > 0.0{
+                gauge!("swifer_gc_allocation_rate", allocated as f64 / elapsed, "memory" => memory);
+            }
+        }
+    }
+
+    /// Records the current occupancy of the given memory.
+    pub fn record_heap<T, Ptr, M>(&self, mem: &M)
+        where T: ?Sized + GcCandidate<Ptr>, Ptr: HeapPtr<T>, M: ManagedMem<T, Ptr> + ?Sized
+    {
+        let memory = self.memory;
+        gauge!("swifer_heap_used_bytes", mem.used_bytes() as f64, "memory" => memory);
+        gauge!("swifer_heap_objects", mem.len() as f64, "memory" => memory);
+        if let Some(capacity) = mem.capacity(){
+            gauge!("swifer_heap_capacity_bytes", capacity as f64, "memory" => memory);
+        }
+    }
+}
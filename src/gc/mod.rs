@@ -1,8 +1,62 @@
 //! Garbage collectors and GC-managed memory.
 
-use crate::heap::{DynSized, Heap, HeapPtr};
+use std::{io, mem};
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::io::Write;
+use std::time::Instant;
+use crate::gc::census::Census;
+use crate::gc::config::MemConfig;
+use crate::gc::hooks::{GcHooks, GcPhase, RelocationHooks};
+use crate::gc::log::GcLog;
+use crate::gc::pauses::PauseTimes;
+use crate::gc::survival::SurvivalStats;
+use crate::gc::profile::{record_caller, AllocProfile};
+use crate::gc::report::{GcReport, UnreachableReport};
+use crate::gc::roots::RootProvider;
+use crate::heap::{AllocError, AllocErrorKind, DynSized, Heap, HeapPtr, ObjectHeader, VerifyError};
 
 pub mod mas;
+pub mod impls;
+pub mod config;
+pub mod roots;
+pub mod stack;
+pub mod any;
+pub mod dyn_mem;
+pub mod shared;
+pub mod barriers;
+pub mod controller;
+pub mod report;
+pub mod hooks;
+pub mod log;
+pub mod dump;
+pub mod profile;
+pub mod census;
+pub mod policy;
+pub mod incremental;
+pub mod quarantine;
+pub mod pauses;
+pub mod survival;
+pub mod spaces;
+pub mod group;
+pub mod actors;
+pub mod copy;
+pub mod raw_image;
+pub mod regions;
+pub mod scoped;
+pub mod arena;
+pub mod cell;
+pub mod handles;
+pub mod future;
+pub mod safepoint;
+pub mod parallel;
+pub mod threads;
+#[cfg(feature = "debug")]
+pub mod debug;
+#[cfg(feature = "serde")]
+pub mod image;
+#[cfg(feature = "metrics")]
+pub mod metrics;
 
 /// A memory space managed by a garbage collector.
 ///
@@ -17,14 +71,28 @@ pub mod mas;
 pub trait ManagedMem<T, Ptr = *const T>
     where T: ?Sized + GcCandidate<Ptr>, Ptr: HeapPtr<T>
 {
-    /// Pushes an object onto the end, returning a pointer to it, or `None` if this is full.
-    fn push(&mut self, v: Box<T>) -> Option<Ptr>;
+    /// Pushes an object onto the end, returning a pointer to it, or an [AllocError] containing
+    /// the object if it can't be allocated.
+    #[track_caller]
+    fn push(&mut self, v: Box<T>) -> Result<Ptr, AllocError<T>>;
 
-    /// Pushes an object onto the end, returning a pointer to it, or `None` if this is full.
+    /// Pushes an object onto the end, returning a pointer to it, or an [AllocError] containing
+    /// the object if it can't be allocated.
     ///
     /// The given `with` function is applied to the pointer before saving, for e.g.
     /// adding extra metadata.
-    fn push_with(&mut self, v: Box<T>, with: impl FnOnce(Ptr) -> Ptr) -> Option<Ptr>;
+    #[track_caller]
+    fn push_with(&mut self, v: Box<T>, with: impl FnOnce(Ptr) -> Ptr) -> Result<Ptr, AllocError<T>>;
+
+    /// Pushes an object as with [ManagedMem::push_with], with a hint about how it will be used,
+    /// which collectors may use to decide where to place it; see [Placement].
+    ///
+    /// By default, the hint is ignored.
+    #[track_caller]
+    fn push_placed(&mut self, v: Box<T>, placement: Placement, with: impl FnOnce(Ptr) -> Ptr) -> Result<Ptr, AllocError<T>>{
+        let _ = placement;
+        return self.push_with(v, with);
+    }
 
     /// Returns a reference to the value at the given index.
     fn get(&self, idx: usize) -> &T;
@@ -36,20 +104,98 @@ pub trait ManagedMem<T, Ptr = *const T>
     /// if that pointer does not point to a value in this memory.
     fn get_by(&mut self, ptr: &Ptr) -> Option<&mut T>;
 
+    /// Returns a reference to the value at the given pointer, or `None`
+    /// if that pointer does not point to a value in this memory.
+    ///
+    /// By default, this checks the pointer with [ManagedMem::contains_ptr], then searches every
+    /// value by index for one at the same address.
+    fn get_by_ref(&self, ptr: &Ptr) -> Option<&T>{
+        if !self.contains_ptr(ptr){
+            return None;
+        }
+        let addr = ptr.to_raw_ptr().cast::<u8>();
+        return (0..self.len()).map(|idx| self.get(idx)).find(|value| (*value as *const T).cast::<u8>() == addr);
+    }
+
+    /// Returns a mutable reference to the value at the given pointer, or `None` if that pointer
+    /// does not point to a value in this memory, ignoring any metadata the pointer doesn't share
+    /// with the pointer returned when the value was pushed.
+    ///
+    /// By default, this finds the full pointer with [ManagedMem::to_full_ptr].
+    fn get_by_ignoring_meta(&mut self, ptr: &Ptr) -> Option<&mut T>{
+        let full = self.to_full_ptr(ptr)?;
+        return self.get_by(&full);
+    }
+
+    /// Returns a reference to the value at the given pointer, ignoring metadata as with
+    /// [ManagedMem::get_by_ignoring_meta].
+    ///
+    /// By default, this finds the full pointer with [ManagedMem::to_full_ptr].
+    fn get_by_ref_ignoring_meta(&self, ptr: &Ptr) -> Option<&T>{
+        let full = self.to_full_ptr(ptr)?;
+        return self.get_by_ref(&full);
+    }
+
+    /// Returns a pointer to the same value as the one given, with the metadata it was given when
+    /// pushed, using [HeapPtr::eq_ignoring_meta]; or `None` if there is no such value.
+    ///
+    /// By default, this searches every value with [ManagedMem::for_each].
+    fn to_full_ptr(&self, ptr: &Ptr) -> Option<Ptr>{
+        let mut full = None;
+        self.for_each(|_, p| {
+            if full.is_none() && p.eq_ignoring_meta(ptr){
+                full = Some(p.clone());
+            }
+        });
+        return full;
+    }
+
+    /// Returns a hash of the value at the given pointer that stays the same for as long as the
+    /// value is stored, even if it's moved by a collection, or `None` if the pointer doesn't point
+    /// to a value in this memory or this memory can't provide stable hashes.
+    ///
+    /// By default, this hashes the value's address, which is only stable for memories that never
+    /// move values; collectors that move values must override this.
+    fn identity_hash(&self, ptr: &Ptr) -> Option<u64>{
+        return self.contains_ptr_ignoring_meta(ptr).then(|| mix_hash(ptr.to_raw_ptr().cast::<u8>() as usize as u64));
+    }
+
     /// Returns the number of values stored.
     fn len(&self) -> usize;
 
     /// Returns whether the given pointer points to a value in this memory.
     fn contains_ptr(&self, ptr: &Ptr) -> bool;
 
+    /// Returns whether the given pointer points to a value in this memory, ignoring any metadata
+    /// it doesn't share with the pointer returned when the value was pushed.
+    ///
+    /// By default, this checks for a full pointer with [ManagedMem::to_full_ptr].
+    fn contains_ptr_ignoring_meta(&self, ptr: &Ptr) -> bool{
+        return self.to_full_ptr(ptr).is_some();
+    }
+
     /// Runs the given function over every value.
     fn for_each(&self, cb: impl FnMut(&T, &Ptr));
 
+    /// Drops every value for which the given predicate returns false, regardless of reachability.
+    ///
+    /// Their space is reclaimed no later than the next collection. Remaining values must not
+    /// point to dropped values.
+    fn retain(&mut self, f: impl FnMut(&Ptr, &T) -> bool);
+
     // TODO: is this the right representation of roots?
     /// Trigger garbage collection, removing any values unreachable from the given `roots`.
     ///
     /// Values in both `roots` and `weaks` are updated if the value they point to are moved,
-    /// but only values in `roots` can cause another value to become reachable.
+    /// but only values in `roots` can cause another value to become reachable. Values in `weaks`
+    /// whose value is dropped are cleared with [HeapPtr::set_cleared], counted in
+    /// [GcReport::weaks_cleared], and listed by [ManagedMem::gc_reporting_cleared].
+    ///
+    /// The same location may be given more than once, in which case it's only updated once; a
+    /// location given in both `roots` and `weaks` is treated as a root.
+    ///
+    /// Collectors that separate values into generations may choose whether to do a minor or full
+    /// collection; see [ManagedMem::gc_minor] and [ManagedMem::gc_full].
     ///
     /// # Safety
     ///
@@ -57,18 +203,447 @@ pub trait ManagedMem<T, Ptr = *const T>
     /// and pointing to initialized memory. Effectively, they must be valid `&mut` references, except
     /// that they may alias.
     unsafe fn gc(&mut self, roots: Vec<*mut Ptr>, weaks: Vec<*mut Ptr>);
+
+    /// Triggers a cheap collection that may only remove recently allocated values, for collectors
+    /// that separate values into generations; see [ManagedMem::is_generational].
+    ///
+    /// By default, this collects everything with [ManagedMem::gc].
+    ///
+    /// # Safety
+    ///
+    /// See [ManagedMem::gc].
+    unsafe fn gc_minor(&mut self, roots: Vec<*mut Ptr>, weaks: Vec<*mut Ptr>){
+        self.gc(roots, weaks);
+    }
+
+    /// Triggers a collection that removes every unreachable value, regardless of generation.
+    ///
+    /// By default, this is the same as [ManagedMem::gc]; generational collectors whose `gc` may do
+    /// a minor collection should override this.
+    ///
+    /// # Safety
+    ///
+    /// See [ManagedMem::gc].
+    unsafe fn gc_full(&mut self, roots: Vec<*mut Ptr>, weaks: Vec<*mut Ptr>){
+        self.gc(roots, weaks);
+    }
+
+    /// Whether [ManagedMem::gc_minor] can be cheaper than [ManagedMem::gc_full]. If false, there is
+    /// no point trying a minor collection before a full one.
+    fn is_generational(&self) -> bool{
+        return false;
+    }
+
+    /// Triggers garbage collection as with [ManagedMem::gc], returning statistics about it.
+    ///
+    /// By default, this compares the values stored before and after collecting, counting every
+    /// value stored before as scanned, fires any [ManagedMem::gc_hooks] before and after, and
+    /// writes a summary to any [ManagedMem::gc_log].
+    /// Collectors that know more should override this.
+    ///
+    /// # Safety
+    ///
+    /// See [ManagedMem::gc].
+    unsafe fn gc_with_report(&mut self, roots: Vec<*mut Ptr>, weaks: Vec<*mut Ptr>) -> GcReport{
+        if let Some(hooks) = self.gc_hooks(){
+            hooks.fire(&GcPhase::Before);
+        }
+        let (objects_before, bytes_before) = (self.len(), self.total_size());
+        gc_event!(DEBUG, objects = objects_before, bytes = bytes_before, "collection started");
+        let start = Instant::now();
+        self.gc(roots, weaks);
+        let pause = start.elapsed();
+        let (objects_after, bytes_after) = (self.len(), self.total_size());
+        let report = GcReport::new(
+            objects_before,
+            objects_before.saturating_sub(objects_after),
+            bytes_before.saturating_sub(bytes_after),
+            bytes_after,
+            pause
+        );
+        gc_event!(DEBUG,
+            objects_reclaimed = report.objects_reclaimed(),
+            bytes_reclaimed = report.bytes_reclaimed(),
+            bytes_surviving = bytes_after,
+            pause_us = pause.as_micros() as u64,
+            "collection finished");
+        if let Some(hooks) = self.gc_hooks(){
+            hooks.fire(&GcPhase::After(report));
+        }
+        if let Some(log) = self.gc_log(){
+            let record = log.next_record("managed-mem", report);
+            log.emit(record);
+        }
+        return report;
+    }
+
+    /// Triggers garbage collection as with [ManagedMem::gc_with_report], also returning the index
+    /// in `weaks` of every weak pointer cleared by it, in order, so that e.g. entries for dropped
+    /// values can be removed from tables without checking every weak pointer.
+    ///
+    /// # Safety
+    ///
+    /// See [ManagedMem::gc].
+    unsafe fn gc_reporting_cleared(&mut self, roots: Vec<*mut Ptr>, weaks: Vec<*mut Ptr>) -> (GcReport, Vec<usize>){
+        let live: Vec<bool> = weaks.iter().map(|weak| !(**weak).is_cleared()).collect();
+        let report = self.gc_with_report(roots, weaks.clone());
+        let cleared = weaks.iter().zip(live).enumerate()
+            .filter(|(_, (weak, live))| *live && (***weak).is_cleared())
+            .map(|(idx, _)| idx)
+            .collect();
+        return (report, cleared);
+    }
+
+    /// Returns the allocations made by each call site, or `None` if this collector isn't recording
+    /// them; see [MemConfig::with_allocation_profiling].
+    ///
+    /// Returns `None` by default.
+    fn allocation_profile(&self) -> Option<&AllocProfile>{
+        return None;
+    }
+
+    /// Returns statistics about how long each collection and each of its phases took, or `None` if
+    /// this collector doesn't record them.
+    ///
+    /// Returns `None` by default.
+    fn pause_times(&self) -> Option<&PauseTimes>{
+        return None;
+    }
+
+    /// Returns the number of collections the value at the given pointer has survived, or `None`
+    /// if it isn't in this memory or this collector doesn't track ages.
+    ///
+    /// Returns `None` by default.
+    fn age(&self, _ptr: &Ptr) -> Option<u32>{
+        return None;
+    }
+
+    /// Returns how many values of each age survived or were dropped by collections so far, or
+    /// `None` if this collector doesn't track ages.
+    ///
+    /// Returns `None` by default.
+    fn survival(&self) -> Option<&SurvivalStats>{
+        return None;
+    }
+
+    /// Returns the hooks fired around each collection, or `None` if this collector doesn't
+    /// support hooks.
+    ///
+    /// Returns `None` by default.
+    fn gc_hooks(&mut self) -> Option<&mut GcHooks>{
+        return None;
+    }
+
+    /// Returns the hooks called for every value moved by a collection, or `None` if this
+    /// collector never moves values or doesn't support relocation hooks.
+    ///
+    /// Returns `None` by default.
+    fn relocation_hooks(&mut self) -> Option<&mut RelocationHooks<Ptr>>{
+        return None;
+    }
+
+    /// Returns the log written to after each collection, or `None` if this collector doesn't
+    /// support logging.
+    ///
+    /// Returns `None` by default.
+    fn gc_log(&mut self) -> Option<&mut GcLog>{
+        return None;
+    }
+
+    /// Returns the number and total size of the values stored, grouped by size and by the type ids
+    /// given by [HeapPtr::type_tag].
+    fn census(&self) -> Census{
+        let mut census = Census::new();
+        self.for_each(|v, ptr| census.record(mem::size_of_val(v), ptr.type_tag()));
+        return census;
+    }
+
+    /// Calls the given visitor with every value stored, its pointer, its size in bytes, and the
+    /// managed pointers it holds, in the order given by [ManagedMem::for_each].
+    fn walk(&self, visitor: &mut impl HeapVisitor<T, Ptr>){
+        let mut edges = Vec::new();
+        self.for_each(|v, ptr| {
+            edges.clear();
+            v.trace(&mut |edge: &Ptr| edges.push(edge.clone()), ptr);
+            visitor.visit_value(v, ptr, mem::size_of_val(v), &edges);
+        });
+    }
+
+    /// Writes a binary dump of every value stored and the pointers between them; see [dump](crate::gc::dump).
+    fn dump(&self, mut w: impl Write) -> io::Result<()>{
+        return dump::write_dump(self, &mut w, |_, ptr| ptr.type_tag());
+    }
+
+    /// Checks that every managed pointer in every value points to a value in this memory,
+    /// returning the first broken invariant found. Collectors that store values in a [Heap] also
+    /// check it with [Heap::verify].
+    ///
+    /// Intended for debugging; collectors may call this before and after each collection when
+    /// created with [MemConfig::with_heap_verification] in debug builds.
+    fn verify(&self) -> Result<(), VerifyError>{
+        return verify_edges(self);
+    }
+
+    /// Returns the number of bytes this memory can hold before it must be collected, or `None`
+    /// if that is unknown.
+    ///
+    /// Returns `None` by default.
+    fn capacity(&self) -> Option<usize>{
+        return None;
+    }
+
+    /// Returns the number of bytes in use, including any space not yet reclaimed.
+    ///
+    /// Returns [ManagedMem::total_size] by default.
+    fn used_bytes(&self) -> usize{
+        return self.total_size();
+    }
+
+    /// Returns the total size of every value stored, in bytes.
+    fn total_size(&self) -> usize{
+        let mut total = 0;
+        self.for_each(|v, _| total += mem::size_of_val(v));
+        return total;
+    }
+
+    /// Runs the given function on the value at the given pointer, returning its result, or `None`
+    /// if that pointer does not point to a value in this memory.
+    ///
+    /// Unlike [ManagedMem::get_by], this notifies collectors with write barriers of any pointers
+    /// stored in the value through [ManagedMem::record_write], and of any pointers it held
+    /// beforehand through [ManagedMem::record_overwrite]. Mutations that may store managed
+    /// pointers should use this method.
+    fn write_field<R>(&mut self, ptr: &Ptr, update: impl FnOnce(&mut T) -> R) -> Option<R>{
+        if self.has_pre_write_barrier(){
+            let mut overwritten = Vec::new();
+            self.get_by_ref(ptr)?.trace(&mut |old: &Ptr| overwritten.push(old.clone()), ptr);
+            for old in &overwritten{
+                self.record_overwrite(ptr, old);
+            }
+        }
+        let value = self.get_by(ptr)?;
+        let result = update(value);
+        if self.has_write_barrier(){
+            let mut written = Vec::new();
+            self.get_by_ref(ptr)?.trace(&mut |dst: &Ptr| written.push(dst.clone()), ptr);
+            for dst in &written{
+                self.record_write(ptr, dst);
+            }
+        }
+        return Some(result);
+    }
+
+    /// Records that a pointer to `dst` was written into the value at `src`, for collectors that
+    /// maintain remembered sets or other barrier invariants.
+    ///
+    /// Does nothing by default.
+    fn record_write(&mut self, _src: &Ptr, _dst: &Ptr){
+        // no-op
+    }
+
+    /// Whether this collector uses [ManagedMem::record_write]. If false, [ManagedMem::write_field]
+    /// does not trace written values.
+    fn has_write_barrier(&self) -> bool{
+        return false;
+    }
+
+    /// Records that a pointer to `old` in the value at `src` is about to be overwritten, for
+    /// collectors with snapshot-at-the-beginning barriers, which must keep everything reachable
+    /// when marking started.
+    ///
+    /// Does nothing by default.
+    fn record_overwrite(&mut self, _src: &Ptr, _old: &Ptr){
+        // no-op
+    }
+
+    /// Whether this collector uses [ManagedMem::record_overwrite]. If false,
+    /// [ManagedMem::write_field] does not trace values before writing to them.
+    fn has_pre_write_barrier(&self) -> bool{
+        return false;
+    }
+
+    /// Returns the current location of the value at the given pointer, for collectors that move
+    /// values while the mutator runs. Pointers loaded from values should be passed through this
+    /// before use, e.g. with [ManagedMem::load_field] or [Barriered](barriers::Barriered).
+    ///
+    /// Returns the pointer unchanged by default, as collectors that stop the world update every
+    /// pointer before returning.
+    fn load_barrier(&self, ptr: &Ptr) -> Ptr{
+        return ptr.clone();
+    }
+
+    /// Whether this collector uses [ManagedMem::load_barrier].
+    fn has_read_barrier(&self) -> bool{
+        return false;
+    }
+
+    /// Loads a pointer from the value at `src` using the given function, then applies the read
+    /// barrier to it, or returns `None` if `src` does not point to a value in this memory.
+    fn load_field(&self, src: &Ptr, load: impl FnOnce(&T) -> Ptr) -> Option<Ptr>{
+        let loaded = load(self.get_by_ref(&self.load_barrier(src))?);
+        return Some(self.load_barrier(&loaded));
+    }
+
+    /// Pushes an object onto the end, returning a pointer to it. If there is not enough space,
+    /// triggers garbage collection using the given roots and tries again once, returning an
+    /// [AllocError] containing the object if it still can't be allocated.
+    ///
+    /// Generational collectors first try a minor collection, and only do a full collection if
+    /// that doesn't free enough space.
+    #[track_caller]
+    fn push_or_collect(&mut self, v: Box<T>, roots: &mut (impl RootProvider<Ptr> + ?Sized)) -> Result<Ptr, AllocError<T>>{
+        let mut err = match self.push(v){
+            Err(err) if err.kind() == AllocErrorKind::OutOfSpace => err,
+            result => return result
+        };
+        if self.is_generational(){
+            // safety: guaranteed by RootProvider
+            unsafe{
+                self.gc_minor(roots.roots(), roots.weaks());
+            }
+            err = match self.push(err.into_value()){
+                Err(err) if err.kind() == AllocErrorKind::OutOfSpace => err,
+                result => return result
+            };
+        }
+        // safety: guaranteed by RootProvider
+        unsafe{
+            self.gc_full(roots.roots(), roots.weaks());
+        }
+        return self.push(err.into_value());
+    }
+
+    /// Collects garbage using every root from the given provider, such as [RootArrays](roots::RootArrays)
+    /// holding a VM's operand stack and registers, returning a report of the collection.
+    fn collect_from(&mut self, roots: &mut (impl RootProvider<Ptr> + ?Sized)) -> GcReport{
+        // safety: guaranteed by RootProvider
+        return unsafe{ self.gc_with_report(roots.roots(), roots.weaks()) };
+    }
 }
 
 /// A value in managed memory that may point to other managed values, keeping them reachable.
+///
+/// Pointers are read with [GcCandidate::trace] and replaced with [GcCandidate::visit_edges], which
+/// collectors use for marking and relocation respectively. Each has a counterpart defined in terms
+/// of it, so implementors must provide one of `trace` and [GcCandidate::collect_managed_pointers],
+/// and one of `visit_edges` and [GcCandidate::adjust_ptrs].
 pub trait GcCandidate<Ptr = *const Self>: DynSized
     where Ptr: HeapPtr<Self>
 {
     /// Collects all pointers in this value to other garbage-collected objects.
     /// Pointers to unmanaged memory must not be included.
-    fn collect_managed_pointers(&self, this: &Ptr) -> Vec<Ptr>;
+    ///
+    /// By default, this collects the pointers given by [GcCandidate::trace].
+    fn collect_managed_pointers(&self, this: &Ptr) -> Vec<Ptr>{
+        let mut ptrs = Vec::new();
+        self.trace(&mut |ptr: &Ptr| ptrs.push(ptr.clone()), this);
+        return ptrs;
+    }
+    /// Calls the given visitor with every pointer in this value to other garbage-collected objects.
+    ///
+    /// By default, this delegates to [GcCandidate::collect_managed_pointers]; implement it directly
+    /// to avoid allocating while marking.
+    fn trace(&self, visitor: &mut impl FnMut(&Ptr), this: &Ptr){
+        for ptr in self.collect_managed_pointers(this){
+            visitor(&ptr);
+        }
+    }
     /// Replaces all managed pointers within this value according to the given function
     /// (e.g. after this value's pointees have been moved).
-    fn adjust_ptrs(&mut self, adjust: impl Fn(&Ptr) -> Ptr, this: &Ptr);
+    ///
+    /// By default, this replaces every pointer given to the visitor by [GcCandidate::visit_edges].
+    fn adjust_ptrs(&mut self, adjust: impl Fn(&Ptr) -> Ptr, this: &Ptr){
+        self.visit_edges(&mut |edge: &mut Ptr| *edge = adjust(edge), this);
+    }
+    /// Calls the given visitor with every pointer in this value to other garbage-collected objects,
+    /// allowing the visitor to replace them. Collectors use this to relocate pointers.
+    ///
+    /// By default, this visits every pointer given to the function by [GcCandidate::adjust_ptrs],
+    /// which must call it exactly once per pointer.
+    fn visit_edges(&mut self, v: &mut impl EdgeVisitor<Ptr>, this: &Ptr){
+        let v = RefCell::new(v);
+        self.adjust_ptrs(|old| {
+            let mut new = old.clone();
+            v.borrow_mut().visit_edge(&mut new);
+            new
+        }, this);
+    }
+}
+
+/// A visitor over the managed pointers, or edges, within a value; see [GcCandidate::visit_edges].
+///
+/// Implemented for all `FnMut(&mut Ptr)` closures.
+pub trait EdgeVisitor<Ptr>{
+    /// Visits a single edge, possibly replacing it.
+    fn visit_edge(&mut self, edge: &mut Ptr);
+}
+
+impl<Ptr, F: FnMut(&mut Ptr)> EdgeVisitor<Ptr> for F{
+    fn visit_edge(&mut self, edge: &mut Ptr){
+        self(edge);
+    }
+}
+
+/// A visitor over every value in a memory, for tools such as debuggers, serializers, and leak
+/// detectors; see [ManagedMem::walk].
+///
+/// Implemented for all `FnMut(&T, &Ptr, usize, &[Ptr])` closures.
+pub trait HeapVisitor<T: ?Sized, Ptr>{
+    /// Visits a single value, given its pointer, its size in bytes, and the managed pointers it holds.
+    fn visit_value(&mut self, value: &T, ptr: &Ptr, size: usize, edges: &[Ptr]);
+}
+
+impl<T: ?Sized, Ptr, F: FnMut(&T, &Ptr, usize, &[Ptr])> HeapVisitor<T, Ptr> for F{
+    fn visit_value(&mut self, value: &T, ptr: &Ptr, size: usize, edges: &[Ptr]){
+        self(value, ptr, size, edges);
+    }
+}
+
+/// A hint given to [ManagedMem::push_placed] about how a value will be used.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub enum Placement{
+    /// The value is accessed often; the default for values pushed without a hint.
+    #[default]
+    Hot,
+    /// The value is rarely accessed, e.g. debug information, so may be kept away from others.
+    Cold,
+    /// The value should never be reclaimed, and keeps everything it points to reachable.
+    Immortal
+}
+
+// checks that every edge of every value in the memory points to a value in it, ignoring metadata
+pub(crate) fn verify_edges<T, Ptr, M>(mem: &M) -> Result<(), VerifyError>
+    where T: ?Sized + GcCandidate<Ptr>, Ptr: HeapPtr<T>, M: ManagedMem<T, Ptr> + ?Sized
+{
+    let mut addresses: HashSet<*const ()> = HashSet::with_capacity(mem.len());
+    mem.for_each(|_, ptr| { addresses.insert(ptr.to_raw_ptr().cast::<()>()); });
+    let mut result = Ok(());
+    let mut index = 0;
+    mem.for_each(|v, ptr| {
+        v.trace(&mut |edge: &Ptr| {
+            let target = edge.to_raw_ptr().cast::<()>();
+            if result.is_ok() && !addresses.contains(&target){
+                result = Err(VerifyError::DanglingEdge{ index, target });
+            }
+        }, ptr);
+        index += 1;
+    });
+    return result;
+}
+
+// scrambles the bits of the given number, for hashes of addresses or counters (using the
+// finalizer of splitmix64)
+pub(crate) fn mix_hash(mut x: u64) -> u64{
+    x = (x ^ (x >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94D049BB133111EB);
+    return x ^ (x >> 31);
+}
+
+// removes repeated locations from roots and weaks, and weaks that are also roots, keeping their order
+pub(crate) fn dedup_roots<Ptr>(roots: &mut Vec<*mut Ptr>, weaks: &mut Vec<*mut Ptr>){
+    let mut seen: HashSet<*mut Ptr> = HashSet::with_capacity(roots.len() + weaks.len());
+    roots.retain(|root| seen.insert(*root));
+    weaks.retain(|weak| seen.insert(*weak));
 }
 
 // No-GC memory, delegates directly to the (single) heap.
@@ -79,25 +654,93 @@ pub trait GcCandidate<Ptr = *const Self>: DynSized
 pub struct NoGcMem<T, Ptr = *const T>
     where T: ?Sized + GcCandidate<Ptr>, Ptr: HeapPtr<T>
 {
-    heap: Heap<T, Ptr>
+    heap: Heap<T, Ptr>,
+    config: MemConfig,
+    profile: Option<AllocProfile>
 }
 
 impl<T: ?Sized + GcCandidate<Ptr>, Ptr: HeapPtr<T>> NoGcMem<T, Ptr>{
-    /// Creates a new `NoGcMem` with the given capacity in bytes.
-    pub fn new(size: usize) -> Self{
+    /// Creates a new `NoGcMem` with the given configuration, or capacity in bytes.
+    pub fn new(config: impl Into<MemConfig>) -> Self{
+        let config = config.into();
         return NoGcMem{
-            heap: Heap::new(size)
+            heap: config.create_heap(config.heap_size()),
+            profile: config.allocation_profiling().then(AllocProfile::new),
+            config
         };
     }
+
+    /// Returns the configuration this was created with.
+    pub fn config(&self) -> &MemConfig{
+        return &self.config;
+    }
+
+    /// Returns the header of the value at the given pointer, if configured to store headers;
+    /// see [Heap::header].
+    pub fn header(&self, ptr: &Ptr) -> Option<&ObjectHeader>{
+        return self.heap.header(ptr);
+    }
+
+    /// Returns the header of the value at the given pointer mutably, if configured to store
+    /// headers; see [Heap::header_mut].
+    pub fn header_mut(&mut self, ptr: &Ptr) -> Option<&mut ObjectHeader>{
+        return self.heap.header_mut(ptr);
+    }
+
+    /// Finds every value that isn't reachable from the given roots, i.e. that a collector would
+    /// drop, without dropping anything. Pointers to values outside of this memory are ignored.
+    ///
+    /// Useful for measuring how much garbage accumulates before switching to a real collector.
+    pub fn report_unreachable(&self, roots: &[Ptr]) -> UnreachableReport<Ptr>{
+        let mut marked: HashSet<*const ()> = HashSet::new();
+        let mut grey: Vec<Ptr> = roots.to_vec();
+        while let Some(ptr) = grey.pop(){
+            // edges may lack metadata that the pointer the value was pushed with has
+            if let Some(full) = self.heap.to_full_ptr(&ptr){
+                if marked.insert(full.to_raw_ptr().cast()){
+                    let value = self.heap.get_by_ref(&full).expect("NoGcMem: value not found by its own pointer");
+                    value.trace(&mut |edge: &Ptr| grey.push(edge.clone()), &full);
+                }
+            }
+        }
+        let mut objects = Vec::new();
+        let mut bytes = 0;
+        self.heap.for_each(|value, ptr| {
+            if !marked.contains(&ptr.to_raw_ptr().cast()){
+                objects.push(ptr.clone());
+                bytes += mem::size_of_val(value);
+            }
+        });
+        return UnreachableReport::new(objects, bytes);
+    }
+}
+
+impl<T, Ptr: HeapPtr<[T]>> NoGcMem<[T], Ptr> where [T]: GcCandidate<Ptr>{
+    /// Pushes a slice containing clones of the given values; see [Heap::push_slice].
+    #[track_caller]
+    pub fn push_slice(&mut self, data: &[T]) -> Result<Ptr, AllocErrorKind> where T: Clone{
+        return self.push_slice_with_len(data.len(), |i| data[i].clone());
+    }
+
+    /// Pushes a slice built by calling `fill` for each index; see [Heap::push_slice_with_len].
+    #[track_caller]
+    pub fn push_slice_with_len(&mut self, len: usize, fill: impl FnMut(usize) -> T) -> Result<Ptr, AllocErrorKind>{
+        let ptr = self.heap.push_slice_with_len(len, fill)?;
+        record_caller(&mut self.profile, len * mem::size_of::<T>());
+        return Ok(ptr);
+    }
 }
 
 impl<T: ?Sized + GcCandidate<Ptr>, Ptr: HeapPtr<T>> ManagedMem<T, Ptr> for NoGcMem<T, Ptr>{
-    fn push(&mut self, v: Box<T>) -> Option<Ptr>{
-        return self.heap.push(v);
+    fn push(&mut self, v: Box<T>) -> Result<Ptr, AllocError<T>>{
+        return self.push_with(v, |x| x);
     }
 
-    fn push_with(&mut self, v: Box<T>, with: impl FnOnce(Ptr) -> Ptr) -> Option<Ptr> {
-        return self.heap.push_with(v, with);
+    fn push_with(&mut self, v: Box<T>, with: impl FnOnce(Ptr) -> Ptr) -> Result<Ptr, AllocError<T>>{
+        let size = mem::size_of_val(v.as_ref());
+        let ptr = self.heap.push_with(v, with)?;
+        record_caller(&mut self.profile, size);
+        return Ok(ptr);
     }
 
     fn get(&self, idx: usize) -> &T{
@@ -112,6 +755,10 @@ impl<T: ?Sized + GcCandidate<Ptr>, Ptr: HeapPtr<T>> ManagedMem<T, Ptr> for NoGcM
         return self.heap.get_by(ptr);
     }
 
+    fn get_by_ref(&self, ptr: &Ptr) -> Option<&T>{
+        return self.heap.get_by_ref(ptr);
+    }
+
     fn len(&self) -> usize{
         return self.heap.len();
     }
@@ -120,11 +767,48 @@ impl<T: ?Sized + GcCandidate<Ptr>, Ptr: HeapPtr<T>> ManagedMem<T, Ptr> for NoGcM
         return self.heap.contains_ptr(ptr);
     }
 
+    fn contains_ptr_ignoring_meta(&self, ptr: &Ptr) -> bool{
+        return self.heap.contains_ptr_ignoring_meta(ptr);
+    }
+
+    fn get_by_ignoring_meta(&mut self, ptr: &Ptr) -> Option<&mut T>{
+        return self.heap.get_by_ignoring_meta(ptr);
+    }
+
+    fn get_by_ref_ignoring_meta(&self, ptr: &Ptr) -> Option<&T>{
+        return self.heap.get_by_ref_ignoring_meta(ptr);
+    }
+
+    fn to_full_ptr(&self, ptr: &Ptr) -> Option<Ptr>{
+        return self.heap.to_full_ptr(ptr);
+    }
+
     fn for_each(&self, cb: impl FnMut(&T, &Ptr)){
         self.heap.for_each(cb);
     }
 
+    fn retain(&mut self, f: impl FnMut(&Ptr, &T) -> bool){
+        self.heap.retain(f);
+    }
+
     unsafe fn gc(&mut self, _roots: Vec<*mut Ptr>, _weaks: Vec<*mut Ptr>){
         // no-op
     }
+
+    fn allocation_profile(&self) -> Option<&AllocProfile>{
+        return self.profile.as_ref();
+    }
+
+    fn verify(&self) -> Result<(), VerifyError>{
+        self.heap.verify()?;
+        return verify_edges(self);
+    }
+
+    fn capacity(&self) -> Option<usize>{
+        return Some(self.heap.capacity());
+    }
+
+    fn used_bytes(&self) -> usize{
+        return self.heap.used();
+    }
 }
\ No newline at end of file
@@ -0,0 +1,214 @@
+//! Work-stealing queues for collectors that split work between threads, such as marking or
+//! evacuating values in parallel.
+//!
+//! A [WorkPool] keeps one deque of work items per worker thread. Each worker takes work from the
+//! back of its own deque, pushing any new work it finds there, and steals from the front of other
+//! workers' deques once its own is empty. A run ends once every deque is empty and no worker is
+//! still processing an item, as that's the only way new work can appear.
+//!
+//! Workers run on threads spawned for each run, or on an existing [GcExecutor] with
+//! [WorkPool::run_on].
+
+use std::collections::VecDeque;
+use std::{hint, panic};
+use std::sync::{Mutex, MutexGuard};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread;
+use crate::gc::threads::GcExecutor;
+
+/// A set of work-stealing deques, processed by a fixed number of worker threads.
+pub struct WorkPool<W>{
+    queues: Vec<Mutex<VecDeque<W>>>,
+    // the number of items in every queue; raised before an item is queued and lowered after one
+    // is taken, so it's never lower than the number of items that can be taken
+    queued: AtomicUsize,
+    // the number of workers processing an item or looking for one, rather than waiting to stop
+    active: AtomicUsize,
+    // set if a worker panicked, so that the rest stop waiting for it
+    panicked: AtomicBool
+}
+
+/// A single worker of a running [WorkPool], given to the function processing each item so that it
+/// can queue more work.
+pub struct Worker<'a, W>{
+    pool: &'a WorkPool<W>,
+    index: usize
+}
+
+impl<W: Send> WorkPool<W>{
+    /// Creates a pool for the given number of workers, with no work queued.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `workers` is 0.
+    pub fn new(workers: usize) -> Self{
+        assert!(workers > 0, "WorkPool: worker count must be positive");
+        return WorkPool{
+            queues: (0..workers).map(|_| Mutex::new(VecDeque::new())).collect(),
+            queued: AtomicUsize::new(0),
+            active: AtomicUsize::new(0),
+            panicked: AtomicBool::new(false)
+        };
+    }
+
+    /// Returns the number of workers.
+    pub fn workers(&self) -> usize{
+        return self.queues.len();
+    }
+
+    /// Queues an item for the given worker, wrapping around if the index is past the last worker,
+    /// e.g. to spread initial work between workers before a run.
+    pub fn push(&self, worker: usize, work: W){
+        let idx = worker % self.queues.len();
+        self.queued.fetch_add(1, Ordering::SeqCst);
+        self.queue(idx).push_back(work);
+    }
+
+    /// Returns the number of items queued.
+    pub fn len(&self) -> usize{
+        return self.queued.load(Ordering::SeqCst);
+    }
+
+    /// Returns whether no items are queued.
+    pub fn is_empty(&self) -> bool{
+        return self.len() == 0;
+    }
+
+    /// Processes every queued item, and every item queued while processing, with one thread per
+    /// worker, returning once all of them are done.
+    pub fn run(&mut self, process: impl Fn(&Worker<'_, W>, W) + Sync){
+        self.run_with(|_| (), |_, worker, work| process(worker, work));
+    }
+
+    /// Processes every queued item as by [WorkPool::run], giving each worker the state created
+    /// for it by `init`, and returns the final state of every worker in order, e.g. to sum the
+    /// values each worker marked.
+    ///
+    /// With a single worker, items are processed on the current thread.
+    ///
+    /// # Panics
+    ///
+    /// Resumes the panic of any worker whose `process` panicked, once every worker has stopped.
+    /// Items left queued by the other workers are kept for the next run.
+    pub fn run_with<S: Send>(&mut self, init: impl Fn(usize) -> S + Sync, process: impl Fn(&mut S, &Worker<'_, W>, W) + Sync) -> Vec<S>{
+        return self.run_on(&ScopedThreads, init, process);
+    }
+
+    /// Processes every queued item as by [WorkPool::run_with], running the workers on the given
+    /// executor rather than spawning threads for them.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the executor has fewer threads than this pool has workers, and resumes the panic
+    /// of any worker as by [WorkPool::run_with].
+    pub fn run_on<S: Send>(&mut self, executor: &dyn GcExecutor, init: impl Fn(usize) -> S + Sync, process: impl Fn(&mut S, &Worker<'_, W>, W) + Sync) -> Vec<S>{
+        let pool = &*self;
+        pool.active.store(pool.workers(), Ordering::SeqCst);
+        pool.panicked.store(false, Ordering::SeqCst);
+        if pool.workers() == 1{
+            return vec![pool.work(0, init(0), &process)];
+        }
+        assert!(executor.threads() >= pool.workers(), "WorkPool: executor has fewer threads than workers");
+        let states: Vec<Mutex<Option<S>>> = (0..pool.workers()).map(|_| Mutex::new(None)).collect();
+        executor.run(pool.workers(), &|index| {
+            let state = pool.work(index, init(index), &process);
+            *states[index].lock().expect("WorkPool: state lock poisoned") = Some(state);
+        });
+        return states.into_iter()
+            .map(|state| state.into_inner().expect("WorkPool: state lock poisoned").expect("WorkPool: worker didn't finish"))
+            .collect();
+    }
+
+    // processes items on a single worker until there are none left anywhere
+    fn work<S>(&self, index: usize, mut state: S, process: &impl Fn(&mut S, &Worker<'_, W>, W)) -> S{
+        let _guard = PanicGuard(self);
+        let worker = Worker{ pool: self, index };
+        loop{
+            match self.take(index){
+                Some(work) => process(&mut state, &worker, work),
+                None if self.offer_termination() => return state,
+                None => {}
+            }
+        }
+    }
+
+    // takes an item from the back of the given worker's queue, or steals one from the front of
+    // another worker's queue
+    fn take(&self, index: usize) -> Option<W>{
+        let count = self.queues.len();
+        // the worker's own queue must be unlocked before stealing, so that two workers stealing
+        // from each other can't deadlock
+        let own = self.queue(index).pop_back();
+        let work = own.or_else(|| (1..count).find_map(|offset| self.queue((index + offset) % count).pop_front()))?;
+        self.queued.fetch_sub(1, Ordering::SeqCst);
+        return Some(work);
+    }
+
+    // waits until either more work is queued, returning false, or every worker is waiting, in
+    // which case no more work can be queued, returning true
+    fn offer_termination(&self) -> bool{
+        self.active.fetch_sub(1, Ordering::SeqCst);
+        loop{
+            if self.panicked.load(Ordering::SeqCst){
+                return true;
+            }
+            if self.queued.load(Ordering::SeqCst) > 0{
+                self.active.fetch_add(1, Ordering::SeqCst);
+                return false;
+            }
+            if self.active.load(Ordering::SeqCst) == 0{
+                return true;
+            }
+            hint::spin_loop();
+            thread::yield_now();
+        }
+    }
+
+    fn queue(&self, index: usize) -> MutexGuard<'_, VecDeque<W>>{
+        return self.queues[index].lock().expect("WorkPool: queue lock poisoned");
+    }
+}
+
+impl<W: Send> Worker<'_, W>{
+    /// Returns the index of this worker, from 0 to one less than [WorkPool::workers].
+    pub fn index(&self) -> usize{
+        return self.index;
+    }
+
+    /// Queues an item on this worker's deque, to be processed by this worker next, unless another
+    /// worker steals it first.
+    pub fn push(&self, work: W){
+        self.pool.push(self.index, work);
+    }
+}
+
+// stops the other workers of a pool from waiting on one that panicked
+struct PanicGuard<'a, W>(&'a WorkPool<W>);
+
+// spawns a scoped thread for every worker of each run
+struct ScopedThreads;
+
+//////////////// impls
+
+impl GcExecutor for ScopedThreads{
+    fn threads(&self) -> usize{
+        return usize::MAX;
+    }
+
+    fn run(&self, count: usize, work: &(dyn Fn(usize) + Sync)){
+        thread::scope(|scope| {
+            let handles: Vec<_> = (0..count).map(|index| scope.spawn(move || work(index))).collect();
+            for handle in handles{
+                handle.join().unwrap_or_else(|payload| panic::resume_unwind(payload));
+            }
+        });
+    }
+}
+
+impl<W> Drop for PanicGuard<'_, W>{
+    fn drop(&mut self){
+        if thread::panicking(){
+            self.0.panicked.store(true, Ordering::SeqCst);
+        }
+    }
+}
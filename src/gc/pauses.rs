@@ -0,0 +1,175 @@
+//! Tracking how long collections pause the program for.
+
+use std::time::Duration;
+
+// each power of two of nanoseconds is split into this many buckets, giving percentiles within 12.5%
+const SUB_BUCKETS: u32 = 8;
+const SUB_BITS: u32 = SUB_BUCKETS.trailing_zeros();
+
+/// Running statistics about a series of pauses: their count, total, maximum, and approximate
+/// percentiles.
+///
+/// Percentiles are estimated from a histogram, and may be up to 12.5% above the true value,
+/// but never above the maximum.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct PauseStats{
+    count: u64,
+    total: Duration,
+    max: Duration,
+    buckets: Vec<u64>
+}
+
+/// Pause statistics for every collection done by a memory, and for each phase of them, returned
+/// by [ManagedMem::pause_times](crate::gc::ManagedMem::pause_times).
+///
+/// Phases are named by the collector; e.g. [MarkAndSweepMem](crate::gc::mas::MarkAndSweepMem)
+/// records each period of marking as `"mark"`, and each sweep as `"sweep"`.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct PauseTimes{
+    collections: PauseStats,
+    phases: Vec<(&'static str, PauseStats)>
+}
+
+impl PauseStats{
+    /// Creates statistics with no pauses recorded.
+    pub fn new() -> Self{
+        return PauseStats::default();
+    }
+
+    /// Records a pause of the given length.
+    pub fn record(&mut self, pause: Duration){
+        self.count += 1;
+        self.total += pause;
+        self.max = self.max.max(pause);
+        let bucket = bucket_of(pause);
+        if self.buckets.len() <= bucket{
+            self.buckets.resize(bucket + 1, 0);
+        }
+        self.buckets[bucket] += 1;
+    }
+
+    /// Returns the number of pauses recorded.
+    pub fn count(&self) -> u64{
+        return self.count;
+    }
+
+    /// Returns the total length of every pause recorded.
+    pub fn total(&self) -> Duration{
+        return self.total;
+    }
+
+    /// Returns the longest pause recorded, or zero if none were.
+    pub fn max(&self) -> Duration{
+        return self.max;
+    }
+
+    /// Returns the average length of the pauses recorded, or zero if none were.
+    pub fn mean(&self) -> Duration{
+        if self.count == 0{
+            return Duration::ZERO;
+        }
+        return Duration::from_nanos((self.total.as_nanos() / self.count as u128) as u64);
+    }
+
+    /// Returns the approximate length that the given fraction of pauses, between 0 and 1, were
+    /// no longer than, or zero if no pauses were recorded.
+    pub fn percentile(&self, fraction: f64) -> Duration{
+        if self.count == 0{
+            return Duration::ZERO;
+        }
+        let rank = ((fraction.clamp(0.0, 1.0) * self.count as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (bucket, count) in self.buckets.iter().enumerate(){
+            seen += count;
+            if seen >= rank{
+                return upper_bound(bucket).min(self.max);
+            }
+        }
+        return self.max;
+    }
+
+    /// Returns the approximate median pause length.
+    pub fn p50(&self) -> Duration{
+        return self.percentile(0.5);
+    }
+
+    /// Returns the approximate length that 99% of pauses were no longer than.
+    pub fn p99(&self) -> Duration{
+        return self.percentile(0.99);
+    }
+
+    /// Forgets every recorded pause.
+    pub fn clear(&mut self){
+        *self = PauseStats::default();
+    }
+}
+
+impl PauseTimes{
+    /// Creates pause times with no collections recorded.
+    pub fn new() -> Self{
+        return PauseTimes::default();
+    }
+
+    /// Records a collection that paused for the given total length, for use by collector implementations.
+    pub fn record_collection(&mut self, pause: Duration){
+        self.collections.record(pause);
+    }
+
+    /// Records a pause for the given phase of a collection, for use by collector implementations.
+    pub fn record_phase(&mut self, phase: &'static str, pause: Duration){
+        match self.phases.iter_mut().find(|(name, _)| *name == phase){
+            Some((_, stats)) => stats.record(pause),
+            None => {
+                let mut stats = PauseStats::new();
+                stats.record(pause);
+                self.phases.push((phase, stats));
+            }
+        }
+    }
+
+    /// Returns statistics about the total pause of each collection. Collections done in several
+    /// steps, such as incremental collections, count as a single pause of their combined length.
+    pub fn collections(&self) -> &PauseStats{
+        return &self.collections;
+    }
+
+    /// Returns statistics about the pauses for the given phase, if any were recorded.
+    pub fn phase(&self, phase: &str) -> Option<&PauseStats>{
+        return self.phases.iter().find(|(name, _)| *name == phase).map(|(_, stats)| stats);
+    }
+
+    /// Returns every phase and its statistics, in the order they were first recorded.
+    pub fn phases(&self) -> impl Iterator<Item = (&'static str, &PauseStats)>{
+        return self.phases.iter().map(|(name, stats)| (*name, stats));
+    }
+
+    /// Forgets every recorded pause.
+    pub fn clear(&mut self){
+        self.collections.clear();
+        self.phases.clear();
+    }
+}
+
+// the histogram bucket for a pause; lengths below `SUB_BUCKETS` nanoseconds get their own bucket,
+// and every power of two above is split evenly
+fn bucket_of(pause: Duration) -> usize{
+    let nanos = u64::try_from(pause.as_nanos()).unwrap_or(u64::MAX);
+    if nanos < SUB_BUCKETS as u64{
+        return nanos as usize;
+    }
+    let exp = 63 - nanos.leading_zeros();
+    let sub = (nanos >> (exp - SUB_BITS)) & (SUB_BUCKETS as u64 - 1);
+    return ((exp - SUB_BITS + 1) * SUB_BUCKETS) as usize + sub as usize;
+}
+
+// the longest pause that falls in the given bucket
+fn upper_bound(bucket: usize) -> Duration{
+    let bucket = bucket as u32;
+    if bucket < SUB_BUCKETS{
+        return Duration::from_nanos(bucket as u64);
+    }
+    let shift = bucket / SUB_BUCKETS - 1;
+    let sub = (bucket % SUB_BUCKETS) as u128;
+    let bound = ((SUB_BUCKETS as u128 + sub + 1) << shift) - 1;
+    return Duration::from_nanos(u64::try_from(bound).unwrap_or(u64::MAX));
+}
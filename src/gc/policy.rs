@@ -0,0 +1,392 @@
+//! Triggering collections automatically as values are allocated.
+
+use std::mem;
+use crate::gc::{GcCandidate, ManagedMem, Placement};
+use crate::gc::config::MemConfig;
+use crate::gc::hooks::{GcHooks, RelocationHooks};
+use crate::gc::log::GcLog;
+use crate::gc::pauses::PauseTimes;
+use crate::gc::profile::AllocProfile;
+use crate::gc::survival::SurvivalStats;
+use crate::gc::report::GcReport;
+use crate::gc::roots::RootProvider;
+use crate::heap::{AllocError, AllocErrorKind, HeapPtr, VerifyError};
+
+/// Decides when a collection should be triggered, based on how full a memory is and how much
+/// has been allocated since the last collection, and whether allocations are within the memory's
+/// limits.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct GcPolicy{
+    occupancy_trigger: Option<f64>,
+    allocation_trigger: Option<usize>,
+    soft_limit: Option<usize>,
+    hard_limit: Option<usize>,
+    allocated: usize
+}
+
+type LimitCallback = Box<dyn FnMut(usize) + Send>;
+
+/// A wrapper around a [ManagedMem] that triggers collections automatically when its [GcPolicy]
+/// asks for one, using its own [RootProvider].
+///
+/// Before each value is pushed, the memory is collected if it is fuller than the occupancy trigger,
+/// or if more bytes than the allocation trigger have been pushed since the last collection.
+/// Values pushed with [ManagedMem::push] also trigger a collection and are retried once if there
+/// isn't enough space for them, like [ManagedMem::push_or_collect].
+///
+/// If a value would take the number of bytes in use past the soft limit, the callback given to
+/// [AutoGcMem::on_soft_limit] is called and the memory is collected first. If it would still take
+/// it past the hard limit, the callback given to [AutoGcMem::on_hard_limit] is called and the
+/// push fails with [AllocErrorKind::LimitExceeded]. Limits are independent of the capacity of
+/// the wrapped memory.
+///
+/// Every pointer to a value in the memory that must survive an allocation must be reachable from
+/// the root provider, which can be accessed with [AutoGcMem::roots_mut].
+pub struct AutoGcMem<M, R>{
+    mem: M,
+    roots: R,
+    policy: GcPolicy,
+    on_soft_limit: Option<LimitCallback>,
+    on_hard_limit: Option<LimitCallback>
+}
+
+impl GcPolicy{
+    /// Creates a policy that never triggers collections and has no limits.
+    pub fn new() -> Self{
+        return GcPolicy{
+            occupancy_trigger: None,
+            allocation_trigger: None,
+            soft_limit: None,
+            hard_limit: None,
+            allocated: 0
+        };
+    }
+
+    /// Creates a policy using the occupancy and allocation triggers, and soft and hard limits, of
+    /// the given configuration.
+    pub fn from_config(config: &MemConfig) -> Self{
+        return GcPolicy{
+            occupancy_trigger: config.occupancy_trigger(),
+            allocation_trigger: config.allocation_trigger(),
+            soft_limit: config.soft_limit(),
+            hard_limit: config.hard_limit(),
+            allocated: 0
+        };
+    }
+
+    /// Records that a value of the given size was allocated.
+    pub fn record_allocation(&mut self, bytes: usize){
+        self.allocated = self.allocated.saturating_add(bytes);
+    }
+
+    /// Records that a collection has happened, resetting the allocation count.
+    pub fn record_collection(&mut self){
+        self.allocated = 0;
+    }
+
+    /// Returns the number of bytes allocated since the last collection.
+    pub fn allocated(&self) -> usize{
+        return self.allocated;
+    }
+
+    /// Returns whether a memory with the given number of bytes in use, out of its capacity if
+    /// known, should be collected.
+    pub fn should_collect(&self, used: usize, capacity: Option<usize>) -> bool{
+        if let (Some(fraction), Some(capacity)) = (self.occupancy_trigger, capacity){
+            if capacity > 0 && used as f64 >= fraction * capacity as f64{
+                return true;
+            }
+        }
+        return matches!(self.allocation_trigger, Some(limit) if self.allocated >= limit);
+    }
+
+    /// Returns whether the given number of bytes in use is past the soft limit.
+    pub fn exceeds_soft_limit(&self, used: usize) -> bool{
+        return matches!(self.soft_limit, Some(limit) if used > limit);
+    }
+
+    /// Returns whether the given number of bytes in use is past the hard limit.
+    pub fn exceeds_hard_limit(&self, used: usize) -> bool{
+        return matches!(self.hard_limit, Some(limit) if used > limit);
+    }
+}
+
+impl<M, R> AutoGcMem<M, R>{
+    /// Wraps the given memory, collecting it using the given roots when the policy asks.
+    pub fn new(mem: M, roots: R, policy: GcPolicy) -> Self{
+        return AutoGcMem{
+            mem,
+            roots,
+            policy,
+            on_soft_limit: None,
+            on_hard_limit: None
+        };
+    }
+
+    /// Sets the function called with the number of bytes that would be in use when an allocation
+    /// passes the soft limit, before collecting, replacing any previous one.
+    pub fn on_soft_limit(&mut self, callback: impl FnMut(usize) + Send + 'static){
+        self.on_soft_limit = Some(Box::new(callback));
+    }
+
+    /// Sets the function called with the number of bytes that would be in use when an allocation
+    /// fails because of the hard limit, replacing any previous one.
+    pub fn on_hard_limit(&mut self, callback: impl FnMut(usize) + Send + 'static){
+        self.on_hard_limit = Some(Box::new(callback));
+    }
+
+    /// Returns the root provider used for automatic collections.
+    pub fn roots(&self) -> &R{
+        return &self.roots;
+    }
+
+    /// Returns the root provider used for automatic collections, mutably.
+    pub fn roots_mut(&mut self) -> &mut R{
+        return &mut self.roots;
+    }
+
+    /// Returns the policy deciding when to collect.
+    pub fn policy(&self) -> &GcPolicy{
+        return &self.policy;
+    }
+
+    /// Returns the wrapped memory.
+    pub fn mem(&self) -> &M{
+        return &self.mem;
+    }
+
+    /// Returns the wrapped memory and root provider.
+    pub fn into_inner(self) -> (M, R){
+        return (self.mem, self.roots);
+    }
+
+    // collects the memory using the root provider
+    fn collect<T, Ptr>(&mut self) -> GcReport
+        where T: ?Sized + GcCandidate<Ptr>, Ptr: HeapPtr<T>, M: ManagedMem<T, Ptr>, R: RootProvider<Ptr>
+    {
+        self.policy.record_collection();
+        // safety: guaranteed by RootProvider
+        unsafe{
+            return self.mem.gc_with_report(self.roots.roots(), self.roots.weaks());
+        }
+    }
+
+    // collects if the policy asks to before allocating `size` bytes, then checks the hard limit
+    fn prepare<T, Ptr>(&mut self, size: usize) -> Result<(), AllocErrorKind>
+        where T: ?Sized + GcCandidate<Ptr>, Ptr: HeapPtr<T>, M: ManagedMem<T, Ptr>, R: RootProvider<Ptr>
+    {
+        let used = self.mem.used_bytes();
+        let needed = used.saturating_add(size);
+        let mut collected = false;
+        if self.policy.exceeds_soft_limit(needed){
+            if let Some(callback) = &mut self.on_soft_limit{
+                callback(needed);
+            }
+        }
+        if self.policy.exceeds_soft_limit(needed) || self.policy.should_collect(used, self.mem.capacity()){
+            self.collect();
+            collected = true;
+        }
+        if self.policy.exceeds_hard_limit(needed){
+            if !collected{
+                self.collect();
+            }
+            let needed = self.mem.used_bytes().saturating_add(size);
+            if self.policy.exceeds_hard_limit(needed){
+                if let Some(callback) = &mut self.on_hard_limit{
+                    callback(needed);
+                }
+                return Err(AllocErrorKind::LimitExceeded);
+            }
+        }
+        return Ok(());
+    }
+}
+
+//////////////// impls
+
+impl Default for GcPolicy{
+    fn default() -> Self{
+        return GcPolicy::new();
+    }
+}
+
+impl<T, Ptr, M, R> ManagedMem<T, Ptr> for AutoGcMem<M, R>
+    where T: ?Sized + GcCandidate<Ptr>, Ptr: HeapPtr<T>, M: ManagedMem<T, Ptr>, R: RootProvider<Ptr>
+{
+    fn push(&mut self, v: Box<T>) -> Result<Ptr, AllocError<T>>{
+        let size = mem::size_of_val(v.as_ref());
+        if let Err(kind) = self.prepare(size){
+            return Err(AllocError::new(v, kind));
+        }
+        let ptr = match self.mem.push(v){
+            Err(err) if err.kind() == AllocErrorKind::OutOfSpace => {
+                self.collect();
+                self.mem.push(err.into_value())?
+            }
+            result => result?
+        };
+        self.policy.record_allocation(size);
+        return Ok(ptr);
+    }
+
+    fn push_with(&mut self, v: Box<T>, with: impl FnOnce(Ptr) -> Ptr) -> Result<Ptr, AllocError<T>>{
+        let size = mem::size_of_val(v.as_ref());
+        if let Err(kind) = self.prepare(size){
+            return Err(AllocError::new(v, kind));
+        }
+        let ptr = self.mem.push_with(v, with)?;
+        self.policy.record_allocation(size);
+        return Ok(ptr);
+    }
+
+    fn push_placed(&mut self, v: Box<T>, placement: Placement, with: impl FnOnce(Ptr) -> Ptr) -> Result<Ptr, AllocError<T>>{
+        let size = mem::size_of_val(v.as_ref());
+        if let Err(kind) = self.prepare(size){
+            return Err(AllocError::new(v, kind));
+        }
+        let ptr = self.mem.push_placed(v, placement, with)?;
+        self.policy.record_allocation(size);
+        return Ok(ptr);
+    }
+
+    fn get(&self, idx: usize) -> &T{
+        return self.mem.get(idx);
+    }
+
+    fn get_mut(&mut self, idx: usize) -> &mut T{
+        return self.mem.get_mut(idx);
+    }
+
+    fn get_by(&mut self, ptr: &Ptr) -> Option<&mut T>{
+        return self.mem.get_by(ptr);
+    }
+
+    fn get_by_ref(&self, ptr: &Ptr) -> Option<&T>{
+        return self.mem.get_by_ref(ptr);
+    }
+
+    fn len(&self) -> usize{
+        return self.mem.len();
+    }
+
+    fn contains_ptr(&self, ptr: &Ptr) -> bool{
+        return self.mem.contains_ptr(ptr);
+    }
+
+    fn contains_ptr_ignoring_meta(&self, ptr: &Ptr) -> bool{
+        return self.mem.contains_ptr_ignoring_meta(ptr);
+    }
+
+    fn get_by_ignoring_meta(&mut self, ptr: &Ptr) -> Option<&mut T>{
+        return self.mem.get_by_ignoring_meta(ptr);
+    }
+
+    fn get_by_ref_ignoring_meta(&self, ptr: &Ptr) -> Option<&T>{
+        return self.mem.get_by_ref_ignoring_meta(ptr);
+    }
+
+    fn to_full_ptr(&self, ptr: &Ptr) -> Option<Ptr>{
+        return self.mem.to_full_ptr(ptr);
+    }
+
+    fn identity_hash(&self, ptr: &Ptr) -> Option<u64>{
+        return self.mem.identity_hash(ptr);
+    }
+
+    fn for_each(&self, cb: impl FnMut(&T, &Ptr)){
+        self.mem.for_each(cb);
+    }
+
+    fn retain(&mut self, f: impl FnMut(&Ptr, &T) -> bool){
+        self.mem.retain(f);
+    }
+
+    unsafe fn gc(&mut self, roots: Vec<*mut Ptr>, weaks: Vec<*mut Ptr>){
+        self.policy.record_collection();
+        self.mem.gc(roots, weaks);
+    }
+
+    unsafe fn gc_minor(&mut self, roots: Vec<*mut Ptr>, weaks: Vec<*mut Ptr>){
+        self.policy.record_collection();
+        self.mem.gc_minor(roots, weaks);
+    }
+
+    unsafe fn gc_full(&mut self, roots: Vec<*mut Ptr>, weaks: Vec<*mut Ptr>){
+        self.policy.record_collection();
+        self.mem.gc_full(roots, weaks);
+    }
+
+    fn is_generational(&self) -> bool{
+        return self.mem.is_generational();
+    }
+
+    unsafe fn gc_with_report(&mut self, roots: Vec<*mut Ptr>, weaks: Vec<*mut Ptr>) -> GcReport{
+        self.policy.record_collection();
+        return self.mem.gc_with_report(roots, weaks);
+    }
+
+    fn gc_hooks(&mut self) -> Option<&mut GcHooks>{
+        return self.mem.gc_hooks();
+    }
+
+    fn relocation_hooks(&mut self) -> Option<&mut RelocationHooks<Ptr>>{
+        return self.mem.relocation_hooks();
+    }
+
+    fn gc_log(&mut self) -> Option<&mut GcLog>{
+        return self.mem.gc_log();
+    }
+
+    fn allocation_profile(&self) -> Option<&AllocProfile>{
+        return self.mem.allocation_profile();
+    }
+
+    fn pause_times(&self) -> Option<&PauseTimes>{
+        return self.mem.pause_times();
+    }
+
+    fn age(&self, ptr: &Ptr) -> Option<u32>{
+        return self.mem.age(ptr);
+    }
+
+    fn survival(&self) -> Option<&SurvivalStats>{
+        return self.mem.survival();
+    }
+
+    fn verify(&self) -> Result<(), VerifyError>{
+        return self.mem.verify();
+    }
+
+    fn capacity(&self) -> Option<usize>{
+        return self.mem.capacity();
+    }
+
+    fn used_bytes(&self) -> usize{
+        return self.mem.used_bytes();
+    }
+
+    fn record_write(&mut self, src: &Ptr, dst: &Ptr){
+        self.mem.record_write(src, dst);
+    }
+
+    fn has_write_barrier(&self) -> bool{
+        return self.mem.has_write_barrier();
+    }
+
+    fn record_overwrite(&mut self, src: &Ptr, old: &Ptr){
+        self.mem.record_overwrite(src, old);
+    }
+
+    fn has_pre_write_barrier(&self) -> bool{
+        return self.mem.has_pre_write_barrier();
+    }
+
+    fn load_barrier(&self, ptr: &Ptr) -> Ptr{
+        return self.mem.load_barrier(ptr);
+    }
+
+    fn has_read_barrier(&self) -> bool{
+        return self.mem.has_read_barrier();
+    }
+}
@@ -0,0 +1,74 @@
+//! Counting allocations by the source location that made them.
+
+use std::collections::HashMap;
+use std::panic::Location;
+
+/// Allocation counts and sizes for each call site that pushed values into a memory.
+///
+/// Collectors record allocations when created with [MemConfig::with_allocation_profiling](crate::gc::config::MemConfig::with_allocation_profiling),
+/// and return their profile from [ManagedMem::allocation_profile](crate::gc::ManagedMem::allocation_profile).
+/// Call sites are found using `#[track_caller]`, so wrappers around pushing methods should be
+/// marked `#[track_caller]` too, to attribute allocations to their callers instead.
+#[derive(Clone, Debug, Default)]
+pub struct AllocProfile{
+    sites: HashMap<&'static Location<'static>, SiteStats>
+}
+
+/// The allocations made by a single call site.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct SiteStats{
+    count: usize,
+    bytes: usize
+}
+
+impl AllocProfile{
+    /// Creates an empty profile.
+    pub fn new() -> Self{
+        return AllocProfile{ sites: HashMap::new() };
+    }
+
+    /// Records an allocation of the given size by the given call site.
+    pub fn record(&mut self, site: &'static Location<'static>, bytes: usize){
+        let stats = self.sites.entry(site).or_default();
+        stats.count += 1;
+        stats.bytes += bytes;
+    }
+
+    /// Returns the allocations made by the given call site, if it made any.
+    pub fn get(&self, site: &'static Location<'static>) -> Option<SiteStats>{
+        return self.sites.get(site).copied();
+    }
+
+    /// Returns every call site and its allocations, ordered by the number of bytes allocated,
+    /// largest first.
+    pub fn report(&self) -> Vec<(&'static Location<'static>, SiteStats)>{
+        let mut sites: Vec<_> = self.sites.iter().map(|(site, stats)| (*site, *stats)).collect();
+        sites.sort_by(|(l, a), (r, b)| b.bytes.cmp(&a.bytes).then_with(|| l.to_string().cmp(&r.to_string())));
+        return sites;
+    }
+
+    /// Forgets every recorded allocation.
+    pub fn clear(&mut self){
+        self.sites.clear();
+    }
+}
+
+// records an allocation by the caller of the (tracked) calling function, if profiling
+#[track_caller]
+pub(crate) fn record_caller(profile: &mut Option<AllocProfile>, bytes: usize){
+    if let Some(profile) = profile{
+        profile.record(Location::caller(), bytes);
+    }
+}
+
+impl SiteStats{
+    /// Returns the number of values allocated.
+    pub fn count(&self) -> usize{
+        return self.count;
+    }
+
+    /// Returns the total size of the values allocated, in bytes.
+    pub fn bytes(&self) -> usize{
+        return self.bytes;
+    }
+}
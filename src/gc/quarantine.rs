@@ -0,0 +1,114 @@
+//! Detecting uses of pointers to values that have been dropped or moved.
+//!
+//! A [Quarantine] remembers where every value was allocated, and, for a number of collections
+//! after a value is dropped or moved, the address it used to be at. Collectors configured with
+//! [MemConfig::with_quarantine](crate::gc::config::MemConfig::with_quarantine) use this to report
+//! accesses through stale pointers, e.g. pointers that weren't passed as roots, instead of
+//! returning `None` or reading unrelated values.
+
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::fmt;
+use std::panic::Location;
+
+/// The allocation sites of live values, and the values recently dropped or moved by collections.
+///
+/// Values are identified by address.
+#[derive(Clone, Debug)]
+pub struct Quarantine{
+    collections: usize,
+    collection: usize,
+    live: HashMap<usize, &'static Location<'static>>,
+    freed: HashMap<usize, Freed>
+}
+
+/// A value that was dropped or moved by a collection, as remembered by a [Quarantine].
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Freed{
+    allocated_at: &'static Location<'static>,
+    collection: usize,
+    moved_to: Option<usize>
+}
+
+impl Quarantine{
+    /// Creates an empty quarantine that remembers freed values for the given number of collections.
+    pub fn new(collections: usize) -> Self{
+        return Quarantine{
+            collections,
+            collection: 0,
+            live: HashMap::new(),
+            freed: HashMap::new()
+        };
+    }
+
+    /// Records that a value was allocated at the given address by the given call site.
+    pub fn allocated(&mut self, address: usize, site: &'static Location<'static>){
+        self.freed.remove(&address);
+        self.live.insert(address, site);
+    }
+
+    /// Records that a collection has finished, given the new address of every value that
+    /// survived it, or `None` for values that were dropped.
+    pub fn collected(&mut self, mut new_address: impl FnMut(usize) -> Option<usize>){
+        self.collection += 1;
+        let collection = self.collection;
+        let mut live = HashMap::with_capacity(self.live.len());
+        for (address, site) in self.live.drain(){
+            let moved_to = new_address(address);
+            if let Some(new) = moved_to{
+                live.insert(new, site);
+                if new == address{
+                    continue;
+                }
+            }
+            self.freed.insert(address, Freed{ allocated_at: site, collection, moved_to });
+        }
+        let oldest = collection.saturating_sub(self.collections);
+        // values may have been moved to addresses that were freed
+        self.freed.retain(|address, freed| freed.collection > oldest && !live.contains_key(address));
+        self.live = live;
+    }
+
+    /// Returns the value that was dropped or moved from the given address, if it's still remembered.
+    pub fn get(&self, address: usize) -> Option<Freed>{
+        return self.freed.get(&address).copied();
+    }
+
+    /// Returns where the value at the given address was allocated, if it's live.
+    pub fn allocation_site(&self, address: usize) -> Option<&'static Location<'static>>{
+        return self.live.get(&address).copied();
+    }
+
+    /// Returns the number of collections recorded so far.
+    pub fn collection(&self) -> usize{
+        return self.collection;
+    }
+}
+
+impl Freed{
+    /// Returns where the value was allocated.
+    pub fn allocated_at(&self) -> &'static Location<'static>{
+        return self.allocated_at;
+    }
+
+    /// Returns the number of the collection that dropped or moved the value, counting from 1.
+    pub fn collection(&self) -> usize{
+        return self.collection;
+    }
+
+    /// Returns the address the value was moved to, or `None` if it was dropped.
+    pub fn moved_to(&self) -> Option<usize>{
+        return self.moved_to;
+    }
+}
+
+//////////////// impls
+
+impl Display for Freed{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result{
+        return match self.moved_to{
+            Some(to) => write!(f, "value allocated at {} was moved to {to:#x} by collection {}", self.allocated_at, self.collection),
+            None => write!(f, "value allocated at {} was dropped by collection {}", self.allocated_at, self.collection)
+        };
+    }
+}
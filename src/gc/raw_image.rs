@@ -0,0 +1,143 @@
+//! Saving the contents of managed memory as raw bytes, and restoring them into a new memory.
+//!
+//! Unlike [HeapImage](crate::gc::image::HeapImage)s, raw images store each value as its bytes,
+//! which makes saving and loading large memories fast, but only works for [Copy] values. Pointers
+//! between values are saved as the position of their target in the image, and are rewritten with
+//! [GcCandidate::adjust_ptrs] when loaded, as when a collector moves values.
+//!
+//! Images use the native byte order and layout, so they can only be loaded on the same platform,
+//! by a program using the same value type.
+
+use std::cell::Cell;
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::io::{ErrorKind, Read, Write};
+use std::mem;
+use std::slice;
+use crate::gc::{GcCandidate, ManagedMem};
+use crate::heap::HeapPtr;
+
+const MAGIC: &[u8; 8] = b"swiferim";
+const VERSION: u64 = 1;
+
+/// Writes every value in the given memory, and the given roots, to `w` as a raw image.
+///
+/// Every pointer given to [GcCandidate::adjust_ptrs] by a value, and every root, must point to a
+/// value in the memory; otherwise, an error with the kind [ErrorKind::InvalidInput] is returned.
+/// Padding bytes within values are written as-is.
+pub fn save_image<T, Ptr, M>(mem: &M, roots: &[Ptr], mut w: impl Write) -> io::Result<()>
+    where T: GcCandidate<Ptr> + Copy, Ptr: HeapPtr<T>, M: ManagedMem<T, Ptr> + ?Sized
+{
+    // values are identified by their position in the image, starting from 1
+    let mut positions: HashMap<*const (), usize> = HashMap::with_capacity(mem.len());
+    mem.for_each(|_, ptr| {
+        positions.insert(ptr.to_raw_ptr().cast::<()>(), positions.len() + 1);
+    });
+    let dangling = Cell::new(false);
+    let encode = |ptr: &Ptr| match positions.get(&ptr.to_raw_ptr().cast::<()>()){
+        Some(pos) => *pos,
+        None => {
+            dangling.set(true);
+            0
+        }
+    };
+    w.write_all(MAGIC)?;
+    for word in [VERSION, mem::size_of::<T>() as u64, mem.len() as u64, roots.len() as u64]{
+        w.write_all(&word.to_ne_bytes())?;
+    }
+    for root in roots{
+        w.write_all(&(encode(root) as u64).to_ne_bytes())?;
+    }
+    let mut result = Ok(());
+    mem.for_each(|value, ptr| {
+        if result.is_err(){
+            return;
+        }
+        let mut copy = *value;
+        copy.adjust_ptrs(|target| Ptr::from_raw_ptr(encode(target) as *const T), ptr);
+        result = w.write_all(bytes_of(&copy));
+    });
+    result?;
+    if dangling.get(){
+        return Err(io::Error::new(ErrorKind::InvalidInput, "raw image: pointer to a value outside of the memory"));
+    }
+    return Ok(());
+}
+
+/// Pushes every value in the raw image read from `r` into the given memory, rewriting pointers
+/// between them to their new locations, and returns the restored roots in the order they were
+/// saved. Values already in the memory are left untouched.
+///
+/// Returns an error with the kind [ErrorKind::InvalidData] if the image is not valid, or
+/// [ErrorKind::OutOfMemory] if a value can't be pushed; values already pushed are then removed.
+/// The memory must not collect while values are pushed.
+///
+/// # Safety
+///
+/// The image must have been written by [save_image] for the same value type, on the same platform.
+pub unsafe fn load_image<T, Ptr, M>(mem: &mut M, mut r: impl Read) -> io::Result<Vec<Ptr>>
+    where T: GcCandidate<Ptr> + Copy, Ptr: HeapPtr<T>, M: ManagedMem<T, Ptr> + ?Sized
+{
+    let mut magic = [0; 8];
+    r.read_exact(&mut magic)?;
+    let [version, size, count, root_count] = [read_word(&mut r)?, read_word(&mut r)?, read_word(&mut r)?, read_word(&mut r)?];
+    if &magic != MAGIC || version != VERSION || size != mem::size_of::<T>() as u64{
+        return Err(invalid("not an image of this type"));
+    }
+    let roots = (0..root_count).map(|_| read_word(&mut r)).collect::<io::Result<Vec<u64>>>()?;
+    let mut pushed: Vec<Ptr> = Vec::new();
+    let result = read_values(mem, &mut r, count, &mut pushed).and_then(|()| {
+        let dangling = Cell::new(false);
+        let decode = |pos: u64| match pos.checked_sub(1).and_then(|idx| pushed.get(idx as usize)){
+            Some(ptr) => Some(ptr.clone()),
+            None => {
+                dangling.set(true);
+                None
+            }
+        };
+        for ptr in &pushed{
+            if let Some(value) = mem.get_by(ptr){
+                value.adjust_ptrs(|target| decode(target.to_raw_ptr() as usize as u64).unwrap_or_else(|| target.clone()), ptr);
+            }
+        }
+        let roots = roots.iter().filter_map(|pos| decode(*pos)).collect();
+        return match dangling.get(){
+            true => Err(invalid("pointer to a value outside of the image")),
+            false => Ok(roots)
+        };
+    });
+    if result.is_err(){
+        let pushed: HashSet<*const ()> = pushed.iter().map(|p| p.to_raw_ptr().cast::<()>()).collect();
+        mem.retain(|ptr, _| !pushed.contains(&ptr.to_raw_ptr().cast::<()>()));
+    }
+    return result;
+}
+
+// reads `count` values from an image and pushes them, recording their pointers in `pushed`
+unsafe fn read_values<T, Ptr, M>(mem: &mut M, r: &mut impl Read, count: u64, pushed: &mut Vec<Ptr>) -> io::Result<()>
+    where T: GcCandidate<Ptr> + Copy, Ptr: HeapPtr<T>, M: ManagedMem<T, Ptr> + ?Sized
+{
+    let mut bytes = vec![0; mem::size_of::<T>()];
+    for _ in 0..count{
+        r.read_exact(&mut bytes)?;
+        let value = bytes.as_ptr().cast::<T>().read_unaligned();
+        let ptr = mem.push(Box::new(value))
+            .map_err(|err| io::Error::new(ErrorKind::OutOfMemory, format!("raw image: {err}")))?;
+        pushed.push(ptr);
+    }
+    return Ok(());
+}
+
+fn read_word(r: &mut impl Read) -> io::Result<u64>{
+    let mut word = [0; 8];
+    r.read_exact(&mut word)?;
+    return Ok(u64::from_ne_bytes(word));
+}
+
+fn bytes_of<T: Copy>(value: &T) -> &[u8]{
+    return unsafe{ slice::from_raw_parts((value as *const T).cast::<u8>(), mem::size_of::<T>()) };
+}
+
+fn invalid(reason: &str) -> io::Error{
+    return io::Error::new(ErrorKind::InvalidData, format!("raw image: {reason}"));
+}
@@ -0,0 +1,149 @@
+//! Separate memories for values of particular types, so that values of the same type are stored
+//! together and can be visited without walking every other value.
+//!
+//! [TypedRegions] keeps a memory for each declared type tag, and a default memory for every other
+//! value. Regions are collected together as a [HeapGroup], so values may point between them.
+
+use std::collections::HashMap;
+use crate::gc::{GcCandidate, ManagedMem};
+use crate::gc::group::HeapGroup;
+use crate::gc::report::GcReport;
+use crate::gc::roots::RootProvider;
+use crate::heap::{AllocError, HeapPtr};
+
+/// A default memory, and a memory for each declared type tag, sharing a single [RootProvider].
+///
+/// Values are pushed into the region for the type tag they're pushed with, and tags are matched
+/// against [HeapPtr::type_tag] when visiting values by type. As with [HeapGroup], every region must
+/// be configured to skip foreign pointers; see
+/// [MemConfig::with_foreign_pointers](crate::gc::config::MemConfig::with_foreign_pointers).
+pub struct TypedRegions<M, R>{
+    group: HeapGroup<M, R>,
+    // the index in the group of the region for each declared type tag
+    regions: HashMap<u32, usize>,
+    // the index of the region for values of undeclared types
+    default: usize
+}
+
+impl<M, R> TypedRegions<M, R>{
+    /// Creates a set of regions with only the given default region, using the given roots.
+    pub fn new(default: M, roots: R) -> Self{
+        let mut group = HeapGroup::new(roots);
+        let default = group.add(default);
+        return TypedRegions{
+            group,
+            regions: HashMap::new(),
+            default
+        };
+    }
+
+    /// Declares a region for values with the given type tag, using the given memory.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a region was already declared for that type tag.
+    pub fn add_region(&mut self, tag: u32, mem: M){
+        assert!(!self.regions.contains_key(&tag), "TypedRegions: a region for type tag {tag} was already declared");
+        let idx = self.group.add(mem);
+        self.regions.insert(tag, idx);
+    }
+
+    /// Returns whether a region was declared for the given type tag.
+    pub fn has_region(&self, tag: u32) -> bool{
+        return self.regions.contains_key(&tag);
+    }
+
+    /// Returns the region storing values with the given type tag, which is the default region if
+    /// none was declared for it.
+    pub fn region(&self, tag: u32) -> &M{
+        return self.group.member(self.region_idx(tag));
+    }
+
+    /// Returns the region storing values with the given type tag mutably; see [TypedRegions::region].
+    pub fn region_mut(&mut self, tag: u32) -> &mut M{
+        return self.group.member_mut(self.region_idx(tag));
+    }
+
+    /// Returns the region storing values of undeclared types.
+    pub fn default_region(&self) -> &M{
+        return self.group.member(self.default);
+    }
+
+    /// Returns the region storing values of undeclared types mutably.
+    pub fn default_region_mut(&mut self) -> &mut M{
+        return self.group.member_mut(self.default);
+    }
+
+    /// Returns the shared root provider.
+    pub fn roots(&self) -> &R{
+        return self.group.roots();
+    }
+
+    /// Returns the shared root provider mutably.
+    pub fn roots_mut(&mut self) -> &mut R{
+        return self.group.roots_mut();
+    }
+
+    /// Returns the group of every region, in which the default region is first, followed by the
+    /// others in the order they were declared.
+    pub fn group(&self) -> &HeapGroup<M, R>{
+        return &self.group;
+    }
+
+    /// Pushes a value into the region for the given type tag, returning a pointer to it.
+    pub fn push<T, Ptr>(&mut self, tag: u32, v: Box<T>) -> Result<Ptr, AllocError<T>>
+        where T: ?Sized + GcCandidate<Ptr>, Ptr: HeapPtr<T>, M: ManagedMem<T, Ptr>
+    {
+        return self.region_mut(tag).push(v);
+    }
+
+    /// Pushes a value into the region for the given type tag, applying `with` to its pointer as
+    /// with [ManagedMem::push_with], e.g. to record the tag in its metadata.
+    pub fn push_with<T, Ptr>(&mut self, tag: u32, v: Box<T>, with: impl FnOnce(Ptr) -> Ptr) -> Result<Ptr, AllocError<T>>
+        where T: ?Sized + GcCandidate<Ptr>, Ptr: HeapPtr<T>, M: ManagedMem<T, Ptr>
+    {
+        return self.region_mut(tag).push_with(v, with);
+    }
+
+    /// Returns a reference to the value at the given pointer in whichever region contains it.
+    pub fn get_by_ref<T, Ptr>(&self, ptr: &Ptr) -> Option<&T>
+        where T: ?Sized + GcCandidate<Ptr>, Ptr: HeapPtr<T>, M: ManagedMem<T, Ptr>
+    {
+        return self.group.get_by_ref(ptr);
+    }
+
+    /// Calls the given function on every value with the given type tag.
+    ///
+    /// If a region was declared for the tag, only that region is visited. Otherwise, values in the
+    /// default region are visited if their pointers' [HeapPtr::type_tag] matches.
+    pub fn for_each_of_type<T, Ptr>(&self, tag: u32, mut cb: impl FnMut(&T, &Ptr))
+        where T: ?Sized + GcCandidate<Ptr>, Ptr: HeapPtr<T>, M: ManagedMem<T, Ptr>
+    {
+        match self.regions.get(&tag){
+            Some(idx) => self.group.member(*idx).for_each(cb),
+            None => self.default_region().for_each(|v, ptr| {
+                if ptr.type_tag() == Some(tag){
+                    cb(v, ptr);
+                }
+            })
+        }
+    }
+
+    /// Collects every region together, returning a report for each, in the same order as
+    /// [TypedRegions::group]; see [HeapGroup::gc].
+    pub fn gc<T, Ptr>(&mut self) -> Vec<GcReport>
+        where T: ?Sized + GcCandidate<Ptr>, Ptr: HeapPtr<T>, M: ManagedMem<T, Ptr>, R: RootProvider<Ptr>
+    {
+        return self.group.gc();
+    }
+
+    /// Separates the regions into their group, with the default region first, and the index of
+    /// each declared region in it.
+    pub fn into_inner(self) -> (HeapGroup<M, R>, HashMap<u32, usize>){
+        return (self.group, self.regions);
+    }
+
+    fn region_idx(&self, tag: u32) -> usize{
+        return self.regions.get(&tag).copied().unwrap_or(self.default);
+    }
+}
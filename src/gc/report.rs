@@ -0,0 +1,130 @@
+//! Statistics about completed collections, and about values that a collection would drop.
+
+use std::time::Duration;
+
+/// A summary of a single collection, returned by [ManagedMem::gc_with_report](crate::gc::ManagedMem::gc_with_report).
+///
+/// Byte counts include only the sizes of values, not any padding between them.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct GcReport{
+    objects_scanned: usize,
+    objects_reclaimed: usize,
+    bytes_reclaimed: usize,
+    bytes_surviving: usize,
+    pause: Duration,
+    foreign_pointers: usize,
+    weaks_cleared: usize,
+    resize: Option<(usize, usize)>
+}
+
+/// The values in a memory that are unreachable from a set of roots, and so would be dropped by a
+/// collection; returned by [NoGcMem::report_unreachable](crate::gc::NoGcMem::report_unreachable).
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct UnreachableReport<Ptr>{
+    objects: Vec<Ptr>,
+    bytes: usize
+}
+
+impl GcReport{
+    /// Creates a report with the given statistics, for use by collector implementations.
+    pub fn new(objects_scanned: usize, objects_reclaimed: usize, bytes_reclaimed: usize, bytes_surviving: usize, pause: Duration) -> Self{
+        return GcReport{
+            objects_scanned,
+            objects_reclaimed,
+            bytes_reclaimed,
+            bytes_surviving,
+            pause,
+            foreign_pointers: 0,
+            weaks_cleared: 0,
+            resize: None
+        };
+    }
+
+    /// Sets the number of pointers outside of the collected memory that were skipped.
+    pub fn with_foreign_pointers(mut self, count: usize) -> Self{
+        self.foreign_pointers = count;
+        return self;
+    }
+
+    /// Returns the number of values the collector examined, e.g. every reachable value when marking.
+    pub fn objects_scanned(&self) -> usize{
+        return self.objects_scanned;
+    }
+
+    /// Returns the number of values that were dropped.
+    pub fn objects_reclaimed(&self) -> usize{
+        return self.objects_reclaimed;
+    }
+
+    /// Returns the total size of the values that were dropped, in bytes.
+    pub fn bytes_reclaimed(&self) -> usize{
+        return self.bytes_reclaimed;
+    }
+
+    /// Returns the total size of the values that survived, in bytes.
+    pub fn bytes_surviving(&self) -> usize{
+        return self.bytes_surviving;
+    }
+
+    /// Sets the number of weak pointers that were cleared.
+    pub fn with_weaks_cleared(mut self, count: usize) -> Self{
+        self.weaks_cleared = count;
+        return self;
+    }
+
+    /// Sets the capacity of the heap before and after the collection, if it changed.
+    pub fn with_resize(mut self, from: usize, to: usize) -> Self{
+        self.resize = Some((from, to));
+        return self;
+    }
+
+    /// Returns how long the collection took.
+    pub fn pause(&self) -> Duration{
+        return self.pause;
+    }
+
+    /// Returns the number of pointers outside of the collected memory that were found and skipped,
+    /// if the collector was configured to report them; see [ForeignPtrPolicy](crate::gc::config::ForeignPtrPolicy).
+    pub fn foreign_pointers(&self) -> usize{
+        return self.foreign_pointers;
+    }
+
+    /// Returns the number of weak pointers that were cleared because their values were dropped.
+    /// Cleared pointers can be found with [HeapPtr::is_cleared](crate::heap::HeapPtr::is_cleared).
+    pub fn weaks_cleared(&self) -> usize{
+        return self.weaks_cleared;
+    }
+
+    /// Returns the capacity of the heap in bytes before and after the collection, if the collector
+    /// resized it, e.g. to fit every surviving value.
+    pub fn resize(&self) -> Option<(usize, usize)>{
+        return self.resize;
+    }
+}
+
+impl<Ptr> UnreachableReport<Ptr>{
+    /// Creates a report of the given unreachable values, with the given total size in bytes.
+    pub fn new(objects: Vec<Ptr>, bytes: usize) -> Self{
+        return UnreachableReport{ objects, bytes };
+    }
+
+    /// Returns pointers to every unreachable value, in the order they're stored.
+    pub fn objects(&self) -> &[Ptr]{
+        return &self.objects;
+    }
+
+    /// Returns the number of unreachable values.
+    pub fn len(&self) -> usize{
+        return self.objects.len();
+    }
+
+    /// Returns whether every value is reachable.
+    pub fn is_empty(&self) -> bool{
+        return self.objects.is_empty();
+    }
+
+    /// Returns the total size of the unreachable values, in bytes.
+    pub fn bytes(&self) -> usize{
+        return self.bytes;
+    }
+}
@@ -0,0 +1,102 @@
+//! Sources of GC roots, for collections triggered by managed memory itself.
+
+/// A source of root pointers, used when a [ManagedMem](crate::gc::ManagedMem) triggers a
+/// collection itself, e.g. in [ManagedMem::push_or_collect](crate::gc::ManagedMem::push_or_collect).
+///
+/// Implemented for `Vec`s and slices of pointers, which provide all of their elements as strong
+/// roots; several arrays can be combined with [RootArrays].
+/// Interpreters can provide the pointers on their call stacks through [StackRoots](crate::gc::stack::StackRoots).
+///
+/// # Safety
+///
+/// All pointers returned by [RootProvider::roots] and [RootProvider::weaks] must be valid to pass
+/// to [ManagedMem::gc](crate::gc::ManagedMem::gc) until this provider is next used.
+pub unsafe trait RootProvider<Ptr>{
+    /// Returns pointers to every strong root, which are updated if their targets are moved.
+    fn roots(&mut self) -> Vec<*mut Ptr>;
+
+    /// Returns pointers to every weak root, which are updated if their targets are moved.
+    fn weaks(&mut self) -> Vec<*mut Ptr>{
+        return Vec::new();
+    }
+}
+
+/// A [RootProvider] made of whole arrays of pointers, such as a VM's operand stack and register
+/// file, whose elements are used as roots and updated in place by collections.
+///
+/// Only the part of an array holding live pointers should be added, e.g. `&mut stack[..top]`;
+/// every element must point to a value in the memory being collected, unless that memory skips
+/// pointers outside of it.
+pub struct RootArrays<'a, Ptr>{
+    strong: Vec<&'a mut [Ptr]>,
+    weak: Vec<&'a mut [Ptr]>
+}
+
+impl<'a, Ptr> RootArrays<'a, Ptr>{
+    /// Creates a provider with no arrays.
+    pub fn new() -> Self{
+        return RootArrays{ strong: Vec::new(), weak: Vec::new() };
+    }
+
+    /// Adds an array whose elements are strong roots.
+    pub fn with_array(mut self, array: &'a mut [Ptr]) -> Self{
+        self.add_array(array);
+        return self;
+    }
+
+    /// Adds an array whose elements are weak roots.
+    pub fn with_weak_array(mut self, array: &'a mut [Ptr]) -> Self{
+        self.add_weak_array(array);
+        return self;
+    }
+
+    /// Adds an array whose elements are strong roots.
+    pub fn add_array(&mut self, array: &'a mut [Ptr]){
+        self.strong.push(array);
+    }
+
+    /// Adds an array whose elements are weak roots.
+    pub fn add_weak_array(&mut self, array: &'a mut [Ptr]){
+        self.weak.push(array);
+    }
+
+    /// Returns the number of roots in every array, strong and weak.
+    pub fn len(&self) -> usize{
+        return self.strong.iter().chain(&self.weak).map(|array| array.len()).sum();
+    }
+
+    /// Returns whether there are no roots in any array.
+    pub fn is_empty(&self) -> bool{
+        return self.len() == 0;
+    }
+}
+
+//////////////// impls
+
+unsafe impl<Ptr> RootProvider<Ptr> for RootArrays<'_, Ptr>{
+    fn roots(&mut self) -> Vec<*mut Ptr>{
+        return self.strong.iter_mut().flat_map(|array| array.iter_mut().map(|p| p as *mut Ptr)).collect();
+    }
+
+    fn weaks(&mut self) -> Vec<*mut Ptr>{
+        return self.weak.iter_mut().flat_map(|array| array.iter_mut().map(|p| p as *mut Ptr)).collect();
+    }
+}
+
+unsafe impl<Ptr> RootProvider<Ptr> for [Ptr]{
+    fn roots(&mut self) -> Vec<*mut Ptr>{
+        return self.iter_mut().map(|p| p as *mut Ptr).collect();
+    }
+}
+
+unsafe impl<Ptr> RootProvider<Ptr> for Vec<Ptr>{
+    fn roots(&mut self) -> Vec<*mut Ptr>{
+        return self.as_mut_slice().roots();
+    }
+}
+
+impl<Ptr> Default for RootArrays<'_, Ptr>{
+    fn default() -> Self{
+        return RootArrays::new();
+    }
+}
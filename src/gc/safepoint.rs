@@ -0,0 +1,71 @@
+//! Cheap checks for pending collections, for placement in interpreter loops.
+//!
+//! A [Safepoint] is a flag shared between a mutator and whatever decides when to collect, such as
+//! an allocation policy, a timer, or a [GcController](crate::gc::controller::GcController). The
+//! mutator polls it at points where its roots are all visible, such as the top of an interpreter's
+//! dispatch loop, and collects (or waits for a collection) when it's requested. Polling is a
+//! single relaxed load and branch, so a long-running loop can be interrupted quickly without
+//! slowing it down.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Polls a [Safepoint], running the given block if a collection was requested.
+///
+/// Unlike [Safepoint::poll], the block is not a closure, so it may `return`, `break` or `continue`
+/// out of the surrounding loop. The safepoint is not cleared automatically.
+#[macro_export]
+macro_rules! poll_safepoint{
+    ($safepoint:expr, $block:block) => {
+        if $crate::gc::safepoint::Safepoint::is_requested(&$safepoint){
+            $block
+        }
+    };
+}
+
+/// A flag requesting that a mutator reach a safepoint, cloned cheaply to share between threads.
+#[derive(Clone, Debug, Default)]
+pub struct Safepoint{
+    requested: Arc<AtomicBool>
+}
+
+impl Safepoint{
+    /// Creates a safepoint that isn't requested.
+    pub fn new() -> Self{
+        return Safepoint{ requested: Arc::new(AtomicBool::new(false)) };
+    }
+
+    /// Requests that mutators polling this safepoint stop at their next poll.
+    pub fn request(&self){
+        self.requested.store(true, Ordering::Release);
+    }
+
+    /// Withdraws a request, e.g. once the mutator has collected.
+    pub fn clear(&self){
+        self.requested.store(false, Ordering::Release);
+    }
+
+    /// Returns whether a collection was requested.
+    #[inline(always)]
+    pub fn is_requested(&self) -> bool{
+        return self.requested.load(Ordering::Relaxed);
+    }
+
+    /// Runs the given function if a collection was requested, returning its result, and clears
+    /// the request beforehand.
+    #[inline(always)]
+    pub fn poll<R>(&self, at_safepoint: impl FnOnce() -> R) -> Option<R>{
+        if self.is_requested(){
+            return Some(self.reached(at_safepoint));
+        }
+        return None;
+    }
+
+    // kept out of line so that polls stay small
+    #[cold]
+    #[inline(never)]
+    fn reached<R>(&self, at_safepoint: impl FnOnce() -> R) -> R{
+        self.clear();
+        return at_safepoint();
+    }
+}
@@ -0,0 +1,213 @@
+//! Named regions of values allocated alongside a collected memory, and freed all at once.
+//!
+//! [ScopedRegions] keeps a main memory, collected as usual, and any number of named regions, each
+//! a [NoGcMem] that is never collected. A region can be closed once nothing outside of it points
+//! into it, freeing every value in it at once, e.g. for values only needed while compiling a
+//! single function or serving a single request.
+
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::marker::PhantomData;
+use crate::gc::{GcCandidate, ManagedMem, NoGcMem};
+use crate::gc::config::MemConfig;
+use crate::gc::report::GcReport;
+use crate::gc::roots::RootProvider;
+use crate::heap::{AllocError, HeapPtr};
+
+/// A main memory and named regions, sharing a single [RootProvider].
+///
+/// Every value in an open region is kept alive, along with every value in the main memory it
+/// points to. Since values in the main memory may point into regions, the main memory must be
+/// configured to skip foreign pointers; see
+/// [MemConfig::with_foreign_pointers](crate::gc::config::MemConfig::with_foreign_pointers).
+pub struct ScopedRegions<T, Ptr, M, R>
+    where T: ?Sized + GcCandidate<Ptr>, Ptr: HeapPtr<T>, M: ManagedMem<T, Ptr>
+{
+    main: M,
+    regions: HashMap<String, NoGcMem<T, Ptr>>,
+    roots: R,
+    _phantom: PhantomData<fn(&T)>
+}
+
+/// The reason a region of a [ScopedRegions] couldn't be closed.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum RegionError{
+    /// No region with the given name is open.
+    NotOpen,
+    /// A root, a value reachable from the roots, or a value in another region points into the region.
+    InUse
+}
+
+impl<T, Ptr, M, R> ScopedRegions<T, Ptr, M, R>
+    where T: ?Sized + GcCandidate<Ptr>, Ptr: HeapPtr<T>, M: ManagedMem<T, Ptr>
+{
+    /// Creates a set of regions around the given main memory, with no regions open.
+    pub fn new(main: M, roots: R) -> Self{
+        return ScopedRegions{
+            main,
+            regions: HashMap::new(),
+            roots,
+            _phantom: PhantomData
+        };
+    }
+
+    /// Returns the main memory.
+    pub fn main(&self) -> &M{
+        return &self.main;
+    }
+
+    /// Returns the main memory mutably.
+    pub fn main_mut(&mut self) -> &mut M{
+        return &mut self.main;
+    }
+
+    /// Returns the shared root provider.
+    pub fn roots(&self) -> &R{
+        return &self.roots;
+    }
+
+    /// Returns the shared root provider mutably.
+    pub fn roots_mut(&mut self) -> &mut R{
+        return &mut self.roots;
+    }
+
+    /// Opens a region with the given name, using the given configuration, or capacity in bytes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a region with that name is already open.
+    pub fn open(&mut self, name: &str, config: impl Into<MemConfig>){
+        assert!(!self.regions.contains_key(name), "ScopedRegions: region {name} is already open");
+        self.regions.insert(name.to_string(), NoGcMem::new(config));
+    }
+
+    /// Returns the open region with the given name, if any.
+    pub fn region(&self, name: &str) -> Option<&NoGcMem<T, Ptr>>{
+        return self.regions.get(name);
+    }
+
+    /// Returns the open region with the given name mutably, if any.
+    pub fn region_mut(&mut self, name: &str) -> Option<&mut NoGcMem<T, Ptr>>{
+        return self.regions.get_mut(name);
+    }
+
+    /// Returns the names of every open region, in no particular order.
+    pub fn region_names(&self) -> impl Iterator<Item = &str> + '_{
+        return self.regions.keys().map(String::as_str);
+    }
+
+    /// Pushes a value into the main memory, returning a pointer to it.
+    pub fn push(&mut self, v: Box<T>) -> Result<Ptr, AllocError<T>>{
+        return self.main.push(v);
+    }
+
+    /// Pushes a value into the open region with the given name, returning a pointer to it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if no region with that name is open.
+    pub fn push_in(&mut self, name: &str, v: Box<T>) -> Result<Ptr, AllocError<T>>{
+        let region = self.regions.get_mut(name).unwrap_or_else(|| panic!("ScopedRegions: region {name} is not open"));
+        return region.push(v);
+    }
+
+    /// Returns a reference to the value at the given pointer, in the main memory or any region.
+    pub fn get_by_ref(&self, ptr: &Ptr) -> Option<&T>{
+        return self.main.get_by_ref(ptr).or_else(|| self.regions.values().find_map(|region| region.get_by_ref(ptr)));
+    }
+
+    /// Collects the main memory, treating pointers into it from every value in an open region as
+    /// roots, and updating them if their values move.
+    pub fn gc(&mut self) -> GcReport where R: RootProvider<Ptr>{
+        let mut cross: Vec<Ptr> = Vec::new();
+        for region in self.regions.values(){
+            region.for_each(|value, this| value.trace(&mut |edge: &Ptr| {
+                if self.main.contains_ptr_ignoring_meta(edge){
+                    cross.push(edge.clone());
+                }
+            }, this));
+        }
+        let old_cross = cross.clone();
+        // safety: guaranteed by RootProvider
+        let report = unsafe{
+            let mut roots = self.roots.roots();
+            roots.extend(cross.iter_mut().map(|p| p as *mut Ptr));
+            self.main.gc_with_report(roots, self.roots.weaks())
+        };
+
+        // forward the pointers stored in regions to where their values moved
+        let moved: HashMap<*const (), Ptr> = old_cross.iter().zip(&cross)
+            .filter(|(old, new)| !old.eq_ignoring_meta(new))
+            .map(|(old, new)| (old.to_raw_ptr().cast::<()>(), new.clone()))
+            .collect();
+        if !moved.is_empty(){
+            for region in self.regions.values_mut(){
+                let mut values = Vec::with_capacity(region.len());
+                region.for_each(|_, this| values.push(this.clone()));
+                for this in values{
+                    if let Some(value) = region.get_by(&this){
+                        value.visit_edges(&mut |edge: &mut Ptr| {
+                            if let Some(new) = moved.get(&edge.to_raw_ptr().cast::<()>()){
+                                let mut new = new.clone();
+                                new.copy_meta(edge);
+                                *edge = new;
+                            }
+                        }, &this);
+                    }
+                }
+            }
+        }
+        return report;
+    }
+
+    /// Closes the region with the given name, dropping every value in it at once.
+    ///
+    /// The region is only closed if nothing outside of it points into it: values reachable from
+    /// the roots in the main memory and values in other regions are checked first, without
+    /// scanning values in the region. Unreachable values in the main memory may still point into
+    /// it, and are reclaimed as usual by the next collection.
+    pub fn close(&mut self, name: &str) -> Result<(), RegionError> where R: RootProvider<Ptr>{
+        let region = self.regions.get(name).ok_or(RegionError::NotOpen)?;
+        // safety: guaranteed by RootProvider
+        let mut pending: Vec<Ptr> = self.roots.roots().into_iter().map(|p| unsafe{ (*p).clone() }).collect();
+        for (other, mem) in &self.regions{
+            if other != name{
+                mem.for_each(|value, this| value.trace(&mut |edge: &Ptr| pending.push(edge.clone()), this));
+            }
+        }
+        let mut seen: HashSet<*const ()> = HashSet::new();
+        while let Some(ptr) = pending.pop(){
+            if region.contains_ptr_ignoring_meta(&ptr){
+                return Err(RegionError::InUse);
+            }
+            if !seen.insert(ptr.to_raw_ptr().cast::<()>()){
+                continue;
+            }
+            if let Some(value) = self.main.get_by_ref_ignoring_meta(&ptr){
+                value.trace(&mut |edge: &Ptr| pending.push(edge.clone()), &ptr);
+            }
+        }
+        self.regions.remove(name);
+        return Ok(());
+    }
+
+    /// Separates this into the main memory, every open region, and the root provider.
+    pub fn into_inner(self) -> (M, HashMap<String, NoGcMem<T, Ptr>>, R){
+        return (self.main, self.regions, self.roots);
+    }
+}
+
+//////////////// impls
+
+impl Display for RegionError{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result{
+        return match self{
+            RegionError::NotOpen => write!(f, "no region with that name is open"),
+            RegionError::InUse => write!(f, "region is still referenced from outside of it")
+        };
+    }
+}
+
+impl Error for RegionError{}
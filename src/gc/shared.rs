@@ -0,0 +1,485 @@
+//! Managed memory shared between threads.
+//!
+//! A [SharedMem] wraps any [ManagedMem] behind a lock. Values pushed into it are accessed through
+//! [Handle]s, which are `Send + Sync`, act as roots, and are updated whenever values are moved.
+//!
+//! # Collection protocol
+//!
+//! [SharedMem::gc] takes the lock exclusively, so it waits for every in-progress access through
+//! a handle to finish, and blocks new accesses until it completes. Every live handle is treated as
+//! a root, so any value reachable from a handle survives, and handles always point to the current
+//! location of their value. References given to [Handle::read] and [Handle::write] can't escape
+//! their closures, so they can't be held across a collection. Raw pointers obtained through
+//! [Handle::ptr] or [SharedMem::with_mem] are only valid until the next collection, and must be
+//! stored in values reachable from handles (or re-rooted with [SharedMem::root]) to survive.
+//!
+//! # Mutator threads
+//!
+//! Threads may register with [SharedMem::register_mutator], getting a [MutatorHandle] that owns
+//! the [MutatorState] kept for that thread, such as its [HandleTable] of scoped handles, until it's
+//! unregistered. Every handle in a registered mutator's table is also treated as a root.
+//!
+//! Each mutator also has a pool of local handles, used through [MutatorHandle::with_locals], for
+//! temporarily rooting values without taking the memory's lock. Only the mutator and collections
+//! use the pool, so creating and releasing local handles is uncontended; collections add every
+//! handle in every pool to their roots.
+//!
+//! Per-thread work, such as flushing a mutator's barrier buffers, is done through ragged
+//! handshakes: [SharedMem::handshake] asks every registered mutator to run a function at its next
+//! safepoint, and each does so in its own time with [MutatorHandle::process_handshakes], rather
+//! than all of them stopping at once. Mutators should poll their [MutatorHandle::safepoint]
+//! regularly, e.g. with [poll_safepoint](crate::poll_safepoint), so that handshakes complete.
+
+use std::cell::Cell;
+use std::marker::PhantomData;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use crate::gc::{GcCandidate, ManagedMem};
+use crate::gc::handles::HandleTable;
+use crate::gc::roots::RootProvider;
+use crate::gc::safepoint::Safepoint;
+use crate::gc::report::GcReport;
+use crate::heap::{AllocError, AllocErrorKind, HeapPtr, ThreadSafePtr};
+
+/// A thread-safe wrapper around a [ManagedMem], cloned cheaply to share between threads.
+///
+/// See the [module documentation](crate::gc::shared) for how collection interacts with handles.
+pub struct SharedMem<T, Ptr, M>
+    where T: ?Sized + GcCandidate<Ptr>, Ptr: HeapPtr<T>, M: ManagedMem<T, Ptr>
+{
+    inner: Arc<RwLock<State<M, Ptr>>>,
+    _phantom: PhantomData<fn(Box<T>)>
+}
+
+/// A rooted reference to a value in a [SharedMem], kept up to date as values are moved.
+///
+/// The value, and everything reachable from it, is kept alive at least until this is dropped.
+pub struct Handle<T, Ptr, M>
+    where T: ?Sized + GcCandidate<Ptr>, Ptr: HeapPtr<T>, M: ManagedMem<T, Ptr>
+{
+    mem: SharedMem<T, Ptr, M>,
+    slot: usize
+}
+
+/// A thread's registration with a [SharedMem], owning the state kept for that thread until it's
+/// unregistered, either with [SharedMem::unregister_mutator] or by dropping it.
+///
+/// Mutator handles can be sent to other threads, but not shared between them.
+pub struct MutatorHandle<T, Ptr, M>
+    where T: ?Sized + GcCandidate<Ptr>, Ptr: HeapPtr<T>, M: ManagedMem<T, Ptr>
+{
+    mem: SharedMem<T, Ptr, M>,
+    id: usize,
+    safepoint: Safepoint,
+    locals: Arc<Mutex<HandleTable<Ptr>>>,
+    _not_sync: PhantomData<Cell<()>>
+}
+
+/// The state a [SharedMem] keeps for a single registered mutator thread.
+pub struct MutatorState<Ptr>{
+    handles: HandleTable<Ptr>
+}
+
+/// A function given to [SharedMem::handshake], which can be waited on until every mutator it was
+/// given to has run it or been unregistered.
+#[derive(Clone)]
+pub struct Handshake{
+    progress: Arc<HandshakeProgress>
+}
+
+// a function run by each mutator during a handshake
+type HandshakeFn<M, Ptr> = dyn Fn(&mut M, &mut MutatorState<Ptr>) + Send + Sync;
+
+struct HandshakeOp<M, Ptr>{
+    op: Box<HandshakeFn<M, Ptr>>,
+    progress: Arc<HandshakeProgress>
+}
+
+struct HandshakeProgress{
+    // the number of mutators that haven't run the handshake yet
+    remaining: Mutex<usize>,
+    // notified when the last mutator runs it
+    done: Condvar
+}
+
+// a registered mutator, and the handshakes it hasn't run yet
+struct Mutator<M, Ptr>{
+    state: MutatorState<Ptr>,
+    safepoint: Safepoint,
+    locals: Arc<Mutex<HandleTable<Ptr>>>,
+    pending: Vec<Arc<HandshakeOp<M, Ptr>>>
+}
+
+struct State<M, Ptr>{
+    mem: M,
+    // the current pointers of every handle, indexed by slot
+    slots: Vec<Option<Ptr>>,
+    free: Vec<usize>,
+    // every registered mutator, indexed by id
+    mutators: Vec<Option<Mutator<M, Ptr>>>
+}
+
+impl<T, Ptr, M> SharedMem<T, Ptr, M>
+    where T: ?Sized + GcCandidate<Ptr>, Ptr: HeapPtr<T>, M: ManagedMem<T, Ptr>
+{
+    /// Wraps the given managed memory for use by multiple threads.
+    pub fn new(mem: M) -> Self{
+        return SharedMem{
+            inner: Arc::new(RwLock::new(State{
+                mem,
+                slots: Vec::new(),
+                free: Vec::new(),
+                mutators: Vec::new()
+            })),
+            _phantom: PhantomData
+        };
+    }
+
+    /// Pushes an object, returning a handle to it, or an [AllocError] containing the object
+    /// if it can't be allocated.
+    #[track_caller]
+    pub fn push(&self, v: Box<T>) -> Result<Handle<T, Ptr, M>, AllocError<T>>{
+        let mut state = self.write();
+        let ptr = state.mem.push(v)?;
+        return Ok(self.new_handle(&mut state, ptr));
+    }
+
+    /// Pushes an object, returning a handle to it. If there is not enough space, triggers
+    /// garbage collection and tries again once, returning an [AllocError] containing the object
+    /// if it still can't be allocated.
+    #[track_caller]
+    pub fn push_or_collect(&self, v: Box<T>) -> Result<Handle<T, Ptr, M>, AllocError<T>>{
+        return match self.push(v){
+            Err(err) if err.kind() == AllocErrorKind::OutOfSpace => {
+                self.gc();
+                self.push(err.into_value())
+            }
+            result => result
+        };
+    }
+
+    /// Creates a new handle for the given pointer, or `None` if it does not point to a value
+    /// in this memory.
+    pub fn root(&self, ptr: &Ptr) -> Option<Handle<T, Ptr, M>>{
+        let mut state = self.write();
+        if !state.mem.contains_ptr(ptr){
+            return None;
+        }
+        return Some(self.new_handle(&mut state, ptr.clone()));
+    }
+
+    /// Triggers garbage collection, using every live handle, and every handle of a registered
+    /// mutator, as a root, and returns statistics about it.
+    ///
+    /// Waits until no other thread is accessing this memory.
+    pub fn gc(&self) -> GcReport{
+        let mut state = self.write();
+        let State{ mem, slots, mutators, .. } = &mut *state;
+        let mut roots: Vec<*mut Ptr> = slots.iter_mut().flatten().map(|p| p as *mut Ptr).collect();
+        for mutator in mutators.iter_mut().flatten(){
+            roots.extend(mutator.state.handles.roots());
+        }
+        // local pools stay locked until the collection is done updating them
+        let pools: Vec<_> = mutators.iter().flatten().map(|mutator| mutator.locals.clone()).collect();
+        let mut locals: Vec<_> = pools.iter().map(|pool| pool.lock().expect("SharedMem: local handles lock poisoned")).collect();
+        for pool in &mut locals{
+            roots.extend(pool.roots());
+        }
+        // safety: the pointers are to slots we have exclusive access to
+        unsafe{
+            return mem.gc_with_report(roots, Vec::new());
+        }
+    }
+
+    /// Returns the number of values stored.
+    pub fn len(&self) -> usize{
+        return self.read().mem.len();
+    }
+
+    /// Registers the current thread as a mutator, returning a handle owning its state.
+    pub fn register_mutator(&self) -> MutatorHandle<T, Ptr, M>{
+        let mut state = self.write();
+        let safepoint = Safepoint::new();
+        let locals = Arc::new(Mutex::new(HandleTable::new()));
+        let mutator = Some(Mutator{
+            state: MutatorState{ handles: HandleTable::new() },
+            safepoint: safepoint.clone(),
+            locals: locals.clone(),
+            pending: Vec::new()
+        });
+        let id = match state.mutators.iter().position(Option::is_none){
+            Some(id) => {
+                state.mutators[id] = mutator;
+                id
+            }
+            None => {
+                state.mutators.push(mutator);
+                state.mutators.len() - 1
+            }
+        };
+        return MutatorHandle{ mem: self.clone(), id, safepoint, locals, _not_sync: PhantomData };
+    }
+
+    /// Unregisters a mutator, dropping its state; values only reachable from its handles may then
+    /// be collected, and handshakes it hasn't run are counted as done. Equivalent to dropping the
+    /// handle.
+    pub fn unregister_mutator(&self, mutator: MutatorHandle<T, Ptr, M>){
+        drop(mutator);
+    }
+
+    /// Returns the number of registered mutators.
+    pub fn mutators(&self) -> usize{
+        return self.read().mutators.iter().flatten().count();
+    }
+
+    /// Asks every registered mutator to run the given function with the memory and its state at
+    /// its next safepoint, requesting their safepoints, and returns a [Handshake] to wait on.
+    /// Mutators registered afterwards don't run it.
+    ///
+    /// Must not be waited on by a registered mutator, unless it processes its own handshakes first.
+    pub fn handshake(&self, op: impl Fn(&mut M, &mut MutatorState<Ptr>) + Send + Sync + 'static) -> Handshake{
+        let mut state = self.write();
+        let count = state.mutators.iter().flatten().count();
+        let progress = Arc::new(HandshakeProgress{
+            remaining: Mutex::new(count),
+            done: Condvar::new()
+        });
+        let op = Arc::new(HandshakeOp{ op: Box::new(op), progress: progress.clone() });
+        for mutator in state.mutators.iter_mut().flatten(){
+            mutator.pending.push(op.clone());
+            mutator.safepoint.request();
+        }
+        return Handshake{ progress };
+    }
+
+    /// Runs the given function with exclusive access to the underlying memory.
+    pub fn with_mem<R>(&self, f: impl FnOnce(&mut M) -> R) -> R{
+        return f(&mut self.write().mem);
+    }
+
+    fn new_handle(&self, state: &mut State<M, Ptr>, ptr: Ptr) -> Handle<T, Ptr, M>{
+        let slot = match state.free.pop(){
+            Some(slot) => {
+                state.slots[slot] = Some(ptr);
+                slot
+            }
+            None => {
+                state.slots.push(Some(ptr));
+                state.slots.len() - 1
+            }
+        };
+        return Handle{ mem: self.clone(), slot };
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, State<M, Ptr>>{
+        return self.inner.read().expect("SharedMem: lock poisoned");
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, State<M, Ptr>>{
+        return self.inner.write().expect("SharedMem: lock poisoned");
+    }
+}
+
+impl<T, Ptr, M> Handle<T, Ptr, M>
+    where T: ?Sized + GcCandidate<Ptr>, Ptr: HeapPtr<T>, M: ManagedMem<T, Ptr>
+{
+    /// Runs the given function with a reference to this handle's value.
+    ///
+    /// Other threads may read values at the same time, but not write or collect.
+    pub fn read<R>(&self, f: impl FnOnce(&T) -> R) -> R{
+        let state = self.mem.read();
+        let ptr = state.slots[self.slot].as_ref().unwrap();
+        return f(state.mem.get_by_ref(ptr).expect("Handle::read: value not in memory"));
+    }
+
+    /// Runs the given function with a mutable reference to this handle's value.
+    ///
+    /// No other thread may access values at the same time.
+    pub fn write<R>(&self, f: impl FnOnce(&mut T) -> R) -> R{
+        let mut state = self.mem.write();
+        let State{ mem, slots, .. } = &mut *state;
+        let ptr = slots[self.slot].as_ref().unwrap();
+        return mem.write_field(ptr, f).expect("Handle::write: value not in memory");
+    }
+
+    /// Returns the current pointer to this handle's value, which is only valid until the next
+    /// collection.
+    pub fn ptr(&self) -> Ptr{
+        return self.mem.read().slots[self.slot].clone().unwrap();
+    }
+
+    /// Returns the memory this handle's value is stored in.
+    pub fn mem(&self) -> &SharedMem<T, Ptr, M>{
+        return &self.mem;
+    }
+}
+
+impl<T, Ptr, M> MutatorHandle<T, Ptr, M>
+    where T: ?Sized + GcCandidate<Ptr>, Ptr: HeapPtr<T>, M: ManagedMem<T, Ptr>
+{
+    /// Runs the given function with exclusive access to the underlying memory and this mutator's
+    /// state.
+    pub fn with_mem<R>(&self, f: impl FnOnce(&mut M, &mut MutatorState<Ptr>) -> R) -> R{
+        let mut state = self.mem.write();
+        let State{ mem, mutators, .. } = &mut *state;
+        return f(mem, &mut mutators[self.id].as_mut().unwrap().state);
+    }
+
+    /// Runs the given function with this mutator's pool of local handles, which are roots of
+    /// every collection, without taking the memory's lock.
+    ///
+    /// The function must not access the memory, since a collection may be holding the memory's
+    /// lock while waiting for the pool; read pointers from the pool inside it, and use them after.
+    /// It may be called within [SharedMem::with_mem], e.g. to root a value as soon as it's pushed.
+    pub fn with_locals<R>(&self, f: impl FnOnce(&mut HandleTable<Ptr>) -> R) -> R{
+        return f(&mut self.locals.lock().expect("MutatorHandle: local handles lock poisoned"));
+    }
+
+    /// Returns the safepoint requested when a handshake is waiting for this mutator, which should
+    /// be polled regularly, calling [MutatorHandle::process_handshakes] when requested.
+    pub fn safepoint(&self) -> &Safepoint{
+        return &self.safepoint;
+    }
+
+    /// Runs every handshake waiting for this mutator, in the order they were requested, and
+    /// returns how many were run. Must be called where the mutator holds no pointers that aren't
+    /// rooted, as with any safepoint.
+    pub fn process_handshakes(&self) -> usize{
+        let mut state = self.mem.write();
+        let State{ mem, mutators, .. } = &mut *state;
+        let mutator = mutators[self.id].as_mut().unwrap();
+        mutator.safepoint.clear();
+        let pending: Vec<_> = mutator.pending.drain(..).collect();
+        for handshake in &pending{
+            (handshake.op)(mem, &mut mutator.state);
+            handshake.progress.finish();
+        }
+        return pending.len();
+    }
+
+    /// Returns the id of this mutator, which is reused once it's unregistered.
+    pub fn id(&self) -> usize{
+        return self.id;
+    }
+
+    /// Returns the memory this mutator is registered with.
+    pub fn mem(&self) -> &SharedMem<T, Ptr, M>{
+        return &self.mem;
+    }
+}
+
+impl<Ptr> MutatorState<Ptr>{
+    /// Returns the mutator's table of handles, which are roots of every collection.
+    pub fn handles(&self) -> &HandleTable<Ptr>{
+        return &self.handles;
+    }
+
+    /// Returns the mutator's table of handles mutably, e.g. to open a scope or create handles.
+    pub fn handles_mut(&mut self) -> &mut HandleTable<Ptr>{
+        return &mut self.handles;
+    }
+}
+
+impl Handshake{
+    /// Waits until every mutator has run this handshake, or been unregistered.
+    pub fn wait(&self){
+        let mut remaining = self.progress.remaining();
+        while *remaining > 0{
+            remaining = self.progress.done.wait(remaining).expect("Handshake: lock poisoned");
+        }
+    }
+
+    /// Returns the number of mutators that haven't run this handshake yet.
+    pub fn remaining(&self) -> usize{
+        return *self.progress.remaining();
+    }
+
+    /// Returns whether every mutator has run this handshake, or been unregistered.
+    pub fn is_complete(&self) -> bool{
+        return self.remaining() == 0;
+    }
+}
+
+impl HandshakeProgress{
+    // records that one more mutator has run the handshake
+    fn finish(&self){
+        let mut remaining = self.remaining();
+        *remaining -= 1;
+        if *remaining == 0{
+            self.done.notify_all();
+        }
+    }
+
+    fn remaining(&self) -> MutexGuard<'_, usize>{
+        return self.remaining.lock().expect("Handshake: lock poisoned");
+    }
+}
+
+//////////////// impls
+
+impl<T, Ptr, M> Clone for SharedMem<T, Ptr, M>
+    where T: ?Sized + GcCandidate<Ptr>, Ptr: HeapPtr<T>, M: ManagedMem<T, Ptr>
+{
+    fn clone(&self) -> Self{
+        return SharedMem{
+            inner: self.inner.clone(),
+            _phantom: PhantomData
+        };
+    }
+}
+
+impl<T, Ptr, M> Clone for Handle<T, Ptr, M>
+    where T: ?Sized + GcCandidate<Ptr>, Ptr: HeapPtr<T>, M: ManagedMem<T, Ptr>
+{
+    fn clone(&self) -> Self{
+        let mut state = self.mem.write();
+        let ptr = state.slots[self.slot].clone().unwrap();
+        return self.mem.new_handle(&mut state, ptr);
+    }
+}
+
+impl<T, Ptr, M> Drop for Handle<T, Ptr, M>
+    where T: ?Sized + GcCandidate<Ptr>, Ptr: HeapPtr<T>, M: ManagedMem<T, Ptr>
+{
+    fn drop(&mut self){
+        // don't panic while unwinding from a panic that poisoned the lock
+        if let Ok(mut state) = self.mem.inner.write(){
+            state.slots[self.slot] = None;
+            state.free.push(self.slot);
+        }
+    }
+}
+
+impl<T, Ptr, M> Drop for MutatorHandle<T, Ptr, M>
+    where T: ?Sized + GcCandidate<Ptr>, Ptr: HeapPtr<T>, M: ManagedMem<T, Ptr>
+{
+    fn drop(&mut self){
+        // don't panic while unwinding from a panic that poisoned the lock
+        if let Ok(mut state) = self.mem.inner.write(){
+            if let Some(mutator) = state.mutators[self.id].take(){
+                for handshake in mutator.pending{
+                    handshake.progress.finish();
+                }
+            }
+        }
+    }
+}
+
+// safety: all access to the memory and the pointers in handles is synchronized by the lock, and
+// values and the memory are only ever shared (through `read`) or exclusively borrowed (through
+// `write`)
+unsafe impl<T, Ptr, M> Send for SharedMem<T, Ptr, M>
+    where T: ?Sized + GcCandidate<Ptr> + Send + Sync, Ptr: HeapPtr<T> + ThreadSafePtr, M: ManagedMem<T, Ptr> + Send + Sync {}
+
+unsafe impl<T, Ptr, M> Sync for SharedMem<T, Ptr, M>
+    where T: ?Sized + GcCandidate<Ptr> + Send + Sync, Ptr: HeapPtr<T> + ThreadSafePtr, M: ManagedMem<T, Ptr> + Send + Sync {}
+
+unsafe impl<T, Ptr, M> Send for Handle<T, Ptr, M>
+    where T: ?Sized + GcCandidate<Ptr> + Send + Sync, Ptr: HeapPtr<T> + ThreadSafePtr, M: ManagedMem<T, Ptr> + Send + Sync {}
+
+unsafe impl<T, Ptr, M> Sync for Handle<T, Ptr, M>
+    where T: ?Sized + GcCandidate<Ptr> + Send + Sync, Ptr: HeapPtr<T> + ThreadSafePtr, M: ManagedMem<T, Ptr> + Send + Sync {}
+
+// safety: the pointers in a mutator's local handles are only accessed by it and by collections,
+// synchronized by the pool's lock
+unsafe impl<T, Ptr, M> Send for MutatorHandle<T, Ptr, M>
+    where T: ?Sized + GcCandidate<Ptr> + Send + Sync, Ptr: HeapPtr<T> + ThreadSafePtr, M: ManagedMem<T, Ptr> + Send + Sync {}
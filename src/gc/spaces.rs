@@ -0,0 +1,181 @@
+//! References between separately collected memories.
+//!
+//! [LinkedSpaces] holds two memories whose values may point into each other, such as a code heap
+//! and a data heap, and remembers which values point into the other memory. Either memory can
+//! then be collected on its own, using the remembered values as extra roots, without scanning
+//! every value of the other.
+
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use crate::gc::{GcCandidate, ManagedMem};
+use crate::gc::barriers::RememberedSet;
+use crate::gc::report::GcReport;
+use crate::heap::HeapPtr;
+
+/// Two memories whose values may point into each other, along with the values in each that point
+/// into the other.
+///
+/// Cross-space pointers must be reported with [LinkedSpaces::record_write] whenever they're
+/// stored, or [LinkedSpaces::record_value] for values pushed already containing them. Since each
+/// memory holds pointers outside of itself, both must be configured to skip foreign pointers; see
+/// [MemConfig::with_foreign_pointers](crate::gc::config::MemConfig::with_foreign_pointers).
+pub struct LinkedSpaces<T, Ptr, A, B>
+    where T: ?Sized + GcCandidate<Ptr>, Ptr: HeapPtr<T>, A: ManagedMem<T, Ptr>, B: ManagedMem<T, Ptr>
+{
+    first: A,
+    second: B,
+    // values in the second memory that point into the first, and vice versa
+    into_first: RememberedSet<T, Ptr>,
+    into_second: RememberedSet<T, Ptr>,
+    _phantom: PhantomData<fn(&T)>
+}
+
+impl<T, Ptr, A, B> LinkedSpaces<T, Ptr, A, B>
+    where T: ?Sized + GcCandidate<Ptr>, Ptr: HeapPtr<T>, A: ManagedMem<T, Ptr>, B: ManagedMem<T, Ptr>
+{
+    /// Links the given memories, which must not yet contain any pointers into each other.
+    pub fn new(first: A, second: B) -> Self{
+        return LinkedSpaces{
+            first,
+            second,
+            into_first: RememberedSet::new(),
+            into_second: RememberedSet::new(),
+            _phantom: PhantomData
+        };
+    }
+
+    /// Returns the first memory.
+    pub fn first(&self) -> &A{
+        return &self.first;
+    }
+
+    /// Returns the first memory mutably. Pointers into the second memory stored through this must
+    /// be reported with [LinkedSpaces::record_write].
+    pub fn first_mut(&mut self) -> &mut A{
+        return &mut self.first;
+    }
+
+    /// Returns the second memory.
+    pub fn second(&self) -> &B{
+        return &self.second;
+    }
+
+    /// Returns the second memory mutably. Pointers into the first memory stored through this must
+    /// be reported with [LinkedSpaces::record_write].
+    pub fn second_mut(&mut self) -> &mut B{
+        return &mut self.second;
+    }
+
+    /// Records that a pointer to `dst` has been stored in the value at `src`, passing the write on
+    /// to the barrier of the memory containing `src`.
+    pub fn record_write(&mut self, src: &Ptr, dst: &Ptr){
+        if self.first.contains_ptr_ignoring_meta(src){
+            self.first.record_write(src, dst);
+            if self.second.contains_ptr_ignoring_meta(dst){
+                self.into_second.insert(src);
+            }
+        }else if self.second.contains_ptr_ignoring_meta(src){
+            self.second.record_write(src, dst);
+            if self.first.contains_ptr_ignoring_meta(dst){
+                self.into_first.insert(src);
+            }
+        }
+    }
+
+    /// Records every pointer into the other memory held by the value at the given pointer, e.g.
+    /// after pushing a value that already contains some.
+    pub fn record_value(&mut self, src: &Ptr){
+        let mut edges = Vec::new();
+        if let Some(value) = self.first.get_by_ref_ignoring_meta(src){
+            value.trace(&mut |edge: &Ptr| edges.push(edge.clone()), src);
+        }else if let Some(value) = self.second.get_by_ref_ignoring_meta(src){
+            value.trace(&mut |edge: &Ptr| edges.push(edge.clone()), src);
+        }
+        for edge in edges{
+            self.record_write(src, &edge);
+        }
+    }
+
+    /// Returns the values in the second memory remembered as pointing into the first.
+    pub fn refs_into_first(&self) -> &RememberedSet<T, Ptr>{
+        return &self.into_first;
+    }
+
+    /// Returns the values in the first memory remembered as pointing into the second.
+    pub fn refs_into_second(&self) -> &RememberedSet<T, Ptr>{
+        return &self.into_second;
+    }
+
+    /// Collects the first memory, as with [ManagedMem::gc_with_report], treating pointers into it
+    /// from remembered values in the second memory as roots, and updating them if their values move.
+    ///
+    /// # Safety
+    ///
+    /// See [ManagedMem::gc].
+    pub unsafe fn gc_first(&mut self, roots: Vec<*mut Ptr>, weaks: Vec<*mut Ptr>) -> GcReport{
+        return collect(&mut self.first, &mut self.second, &mut self.into_first, &mut self.into_second, roots, weaks);
+    }
+
+    /// Collects the second memory, as with [LinkedSpaces::gc_first].
+    ///
+    /// # Safety
+    ///
+    /// See [ManagedMem::gc].
+    pub unsafe fn gc_second(&mut self, roots: Vec<*mut Ptr>, weaks: Vec<*mut Ptr>) -> GcReport{
+        return collect(&mut self.second, &mut self.first, &mut self.into_second, &mut self.into_first, roots, weaks);
+    }
+
+    /// Separates the linked memories.
+    pub fn into_inner(self) -> (A, B){
+        return (self.first, self.second);
+    }
+}
+
+// collects `target`, using pointers into it from the values in `other` remembered by `incoming`
+// as roots, and keeping the values in `target` remembered by `outgoing` up to date
+unsafe fn collect<T, Ptr, M, O>(target: &mut M, other: &mut O, incoming: &mut RememberedSet<T, Ptr>, outgoing: &mut RememberedSet<T, Ptr>, mut roots: Vec<*mut Ptr>, mut weaks: Vec<*mut Ptr>) -> GcReport
+    where T: ?Sized + GcCandidate<Ptr>, Ptr: HeapPtr<T>, M: ManagedMem<T, Ptr>, O: ManagedMem<T, Ptr>
+{
+    // copy out every pointer into the target from the other memory, to be used as roots
+    let mut cross: Vec<Ptr> = Vec::new();
+    for src in incoming.iter(){
+        if let Some(value) = other.get_by_ref_ignoring_meta(src){
+            value.trace(&mut |edge: &Ptr| {
+                if target.contains_ptr_ignoring_meta(edge){
+                    cross.push(edge.clone());
+                }
+            }, src);
+        }
+    }
+    let old_cross = cross.clone();
+    roots.extend(cross.iter_mut().map(|p| p as *mut Ptr));
+    // values pointing out of the target are only weakly held by it
+    let mut sources: Vec<Ptr> = outgoing.iter().cloned().collect();
+    weaks.extend(sources.iter_mut().map(|p| p as *mut Ptr));
+    let report = target.gc_with_report(roots, weaks);
+
+    // forward the pointers stored in the other memory to where their values moved
+    let moved: HashMap<*const (), Ptr> = old_cross.iter().zip(&cross)
+        .filter(|(old, new)| !old.eq_ignoring_meta(new))
+        .map(|(old, new)| (old.to_raw_ptr().cast::<()>(), new.clone()))
+        .collect();
+    incoming.update(|src| {
+        let value = other.get_by_ignoring_meta(src)?;
+        let mut points_in = false;
+        value.visit_edges(&mut |edge: &mut Ptr| {
+            if let Some(new) = moved.get(&edge.to_raw_ptr().cast::<()>()){
+                let mut new = new.clone();
+                new.copy_meta(edge);
+                *edge = new;
+            }
+            points_in |= target.contains_ptr_ignoring_meta(edge);
+        }, src);
+        // forget values that no longer point into the target
+        points_in.then(|| src.clone())
+    });
+    outgoing.clear();
+    for src in sources.iter().filter(|p| !p.is_cleared()){
+        outgoing.insert(src);
+    }
+    return report;
+}
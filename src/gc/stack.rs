@@ -0,0 +1,67 @@
+//! Finding roots by walking a mutator's call stack.
+
+use crate::gc::roots::RootProvider;
+
+/// A mutator's call stack, such as an interpreter's, that can enumerate its frames and the slots
+/// in each that hold managed pointers.
+///
+/// Frames are numbered by depth, starting from 0 for the innermost (most recently entered) frame.
+/// Wrap a stack in [StackRoots] to use every pointer in it as a root for collections.
+///
+/// # Safety
+///
+/// Every slot passed to the visitor by [MutatorStack::visit_frame] must be valid to pass to
+/// [ManagedMem::gc](crate::gc::ManagedMem::gc) as a root until this stack is next used.
+pub unsafe trait MutatorStack<Ptr>{
+    /// Returns the number of frames on this stack.
+    fn depth(&self) -> usize;
+
+    /// Calls the given visitor with every slot holding a managed pointer in the frame at the given
+    /// depth.
+    fn visit_frame(&mut self, depth: usize, visitor: &mut dyn FnMut(*mut Ptr));
+
+    /// Calls the given visitor with every slot holding a managed pointer in every frame, from the
+    /// innermost frame outwards.
+    fn visit_all(&mut self, visitor: &mut dyn FnMut(*mut Ptr)){
+        for depth in 0..self.depth(){
+            self.visit_frame(depth, visitor);
+        }
+    }
+}
+
+/// A [RootProvider] using every managed pointer on a [MutatorStack] as a strong root.
+pub struct StackRoots<S>{
+    stack: S
+}
+
+impl<S> StackRoots<S>{
+    /// Wraps the given stack.
+    pub fn new(stack: S) -> Self{
+        return StackRoots{ stack };
+    }
+
+    /// Returns the wrapped stack.
+    pub fn stack(&self) -> &S{
+        return &self.stack;
+    }
+
+    /// Returns the wrapped stack mutably, e.g. to push or pop frames.
+    pub fn stack_mut(&mut self) -> &mut S{
+        return &mut self.stack;
+    }
+
+    /// Returns the wrapped stack.
+    pub fn into_inner(self) -> S{
+        return self.stack;
+    }
+}
+
+//////////////// impls
+
+unsafe impl<Ptr, S: MutatorStack<Ptr>> RootProvider<Ptr> for StackRoots<S>{
+    fn roots(&mut self) -> Vec<*mut Ptr>{
+        let mut roots = Vec::new();
+        self.stack.visit_all(&mut |slot| roots.push(slot));
+        return roots;
+    }
+}
@@ -0,0 +1,87 @@
+//! Statistics about how long values live, measured in collections survived.
+
+/// The number of values of each age that survived or were dropped by collections, where a
+/// value's age is the number of collections it had already survived; returned by
+/// [ManagedMem::survival](crate::gc::ManagedMem::survival).
+///
+/// This gives the survival curve of values in a memory, e.g. for choosing how large a nursery
+/// should be: [SurvivalStats::survival_rate] for age 0 is the fraction of new values that
+/// survive their first collection.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct SurvivalStats{
+    // indexed by age
+    ages: Vec<AgeStats>
+}
+
+/// The values of a single age seen by collections, in [SurvivalStats].
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct AgeStats{
+    survived: u64,
+    dropped: u64
+}
+
+impl SurvivalStats{
+    /// Creates statistics with no values recorded.
+    pub fn new() -> Self{
+        return SurvivalStats::default();
+    }
+
+    /// Records that a collection found a value of the given age, and whether it survived, for use by
+    /// collector implementations.
+    pub fn record(&mut self, age: u32, survived: bool){
+        let age = age as usize;
+        if self.ages.len() <= age{
+            self.ages.resize(age + 1, AgeStats::default());
+        }
+        match survived{
+            true => self.ages[age].survived += 1,
+            false => self.ages[age].dropped += 1
+        }
+    }
+
+    /// Returns the values of the given age seen by collections.
+    pub fn at(&self, age: u32) -> AgeStats{
+        return self.ages.get(age as usize).copied().unwrap_or_default();
+    }
+
+    /// Returns the values of every age seen by collections, indexed by age.
+    pub fn ages(&self) -> &[AgeStats]{
+        return &self.ages;
+    }
+
+    /// Returns the fraction of values of the given age that survived collections, or `None` if
+    /// none were seen.
+    pub fn survival_rate(&self, age: u32) -> Option<f64>{
+        return self.at(age).survival_rate();
+    }
+
+    /// Returns the estimated fraction of values that survive more than the given number of
+    /// collections, i.e. the product of the survival rates of every age up to it. Ages that no
+    /// values were seen at are assumed to have no survivors.
+    pub fn survival_curve(&self, age: u32) -> f64{
+        return (0..=age).map(|a| self.survival_rate(a).unwrap_or(0.0)).product();
+    }
+
+    /// Forgets every recorded value.
+    pub fn clear(&mut self){
+        self.ages.clear();
+    }
+}
+
+impl AgeStats{
+    /// Returns the number of values that survived.
+    pub fn survived(&self) -> u64{
+        return self.survived;
+    }
+
+    /// Returns the number of values that were dropped.
+    pub fn dropped(&self) -> u64{
+        return self.dropped;
+    }
+
+    /// Returns the fraction of values that survived, or `None` if there were none.
+    pub fn survival_rate(&self) -> Option<f64>{
+        let total = self.survived + self.dropped;
+        return (total > 0).then(|| self.survived as f64 / total as f64);
+    }
+}
@@ -0,0 +1,329 @@
+//! Threads that parallel and concurrent collectors run their work on.
+//!
+//! Collectors split work between the workers of a [GcExecutor], given through
+//! [MemConfig::with_executor](crate::gc::config::MemConfig::with_executor). A [GcThreadPool] keeps
+//! a fixed set of dedicated threads for this, configured with a [ThreadPoolConfig]; embedders that
+//! already have a thread pool, such as `rayon`'s, can run collection work on it instead by
+//! implementing [GcExecutor] for it. Without an executor, collectors spawn the number of threads
+//! given by [MemConfig::with_threads](crate::gc::config::MemConfig::with_threads) for each piece of
+//! work.
+
+use std::any::Any;
+use std::fmt;
+use std::fmt::{Debug, Formatter};
+use std::mem;
+use std::panic;
+use std::panic::AssertUnwindSafe;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread;
+use std::thread::JoinHandle;
+
+/// Runs the workers of parallel collection work on threads.
+///
+/// Workers of a single piece of work wait for each other, so every call given to
+/// [GcExecutor::run] must be running at the same time; an executor running them one after another
+/// would never finish.
+pub trait GcExecutor: Send + Sync{
+    /// Returns the most workers that can run at the same time.
+    fn threads(&self) -> usize;
+
+    /// Calls `work` with every index from 0 up to `count`, each on its own thread and all at the
+    /// same time, and returns once every call has returned. `count` is never more than
+    /// [GcExecutor::threads].
+    ///
+    /// # Panics
+    ///
+    /// Resumes the panic of any call that panicked, once every call has returned.
+    fn run(&self, count: usize, work: &(dyn Fn(usize) + Sync));
+}
+
+/// The threads a piece of collection work is split between.
+#[derive(Copy, Clone)]
+pub enum Parallelism<'a>{
+    /// Spawn the given number of threads for the work, or run it on the current thread if 1.
+    Threads(usize),
+    /// Run the work on every thread of an existing executor.
+    Executor(&'a dyn GcExecutor)
+}
+
+/// Options for the threads of a [GcThreadPool], built by chaining `with_*` methods onto
+/// [ThreadPoolConfig::new].
+///
+/// A `usize` can be converted into a `ThreadPoolConfig` with that many threads and default options.
+#[derive(Clone)]
+pub struct ThreadPoolConfig{
+    threads: usize,
+    name: String,
+    stack_size: Option<usize>,
+    on_start: Option<Arc<dyn Fn(usize) + Send + Sync>>
+}
+
+/// A fixed set of dedicated threads that collection work is run on, stopped when this is dropped.
+pub struct GcThreadPool{
+    shared: Arc<PoolShared>,
+    handles: Vec<JoinHandle<()>>,
+    // held while running work, so that only one piece of work is given to the threads at a time
+    running: Mutex<()>
+}
+
+struct PoolShared{
+    state: Mutex<PoolState>,
+    // notified when work is given to the threads, or on shutdown
+    ready: Condvar,
+    // notified when the last worker of a piece of work returns
+    done: Condvar
+}
+
+struct PoolState{
+    job: Option<Job>,
+    // incremented every time work is given to the threads
+    generation: u64,
+    // the number of workers of the current work that haven't returned yet
+    remaining: usize,
+    panic: Option<Box<dyn Any + Send>>,
+    shutdown: bool
+}
+
+// work given to the threads, which is only called while `GcThreadPool::run` waits for it; the
+// lifetime is erased, so it's cleared before `run` returns and never used after a worker returns
+#[derive(Copy, Clone)]
+struct Job{
+    work: &'static (dyn Fn(usize) + Sync),
+    count: usize
+}
+
+impl Parallelism<'_>{
+    /// Returns the number of workers the work is split between.
+    pub fn workers(&self) -> usize{
+        return match self{
+            Parallelism::Threads(threads) => *threads,
+            Parallelism::Executor(executor) => executor.threads()
+        };
+    }
+}
+
+impl ThreadPoolConfig{
+    /// Creates a configuration for the given number of threads, named `swifer-gc-worker` and
+    /// with the default stack size.
+    ///
+    /// Panics if `threads` is 0.
+    pub fn new(threads: usize) -> Self{
+        assert!(threads > 0, "ThreadPoolConfig: thread count must be positive");
+        return ThreadPoolConfig{
+            threads,
+            name: "swifer-gc-worker".to_string(),
+            stack_size: None,
+            on_start: None
+        };
+    }
+
+    /// Sets the name given to every thread, followed by its index.
+    pub fn with_name(mut self, name: impl Into<String>) -> Self{
+        self.name = name.into();
+        return self;
+    }
+
+    /// Sets the stack size of every thread, in bytes.
+    pub fn with_stack_size(mut self, bytes: usize) -> Self{
+        self.stack_size = Some(bytes);
+        return self;
+    }
+
+    /// Sets a function called on every thread with its index when it starts, before running any
+    /// work, e.g. to pin it to a core or change its priority through the platform's APIs.
+    pub fn with_start_handler(mut self, on_start: impl Fn(usize) + Send + Sync + 'static) -> Self{
+        self.on_start = Some(Arc::new(on_start));
+        return self;
+    }
+
+    /// Returns the number of threads.
+    pub fn threads(&self) -> usize{
+        return self.threads;
+    }
+
+    /// Returns the name given to every thread.
+    pub fn name(&self) -> &str{
+        return &self.name;
+    }
+
+    /// Returns the stack size of every thread, if set.
+    pub fn stack_size(&self) -> Option<usize>{
+        return self.stack_size;
+    }
+}
+
+impl GcThreadPool{
+    /// Starts a pool of threads with the given configuration, or number of threads.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a thread can't be spawned.
+    pub fn new(config: impl Into<ThreadPoolConfig>) -> Self{
+        let config = config.into();
+        let shared = Arc::new(PoolShared{
+            state: Mutex::new(PoolState{
+                job: None,
+                generation: 0,
+                remaining: 0,
+                panic: None,
+                shutdown: false
+            }),
+            ready: Condvar::new(),
+            done: Condvar::new()
+        });
+        let handles = (0..config.threads).map(|index| {
+            let mut builder = thread::Builder::new().name(format!("{}-{index}", config.name));
+            if let Some(stack_size) = config.stack_size{
+                builder = builder.stack_size(stack_size);
+            }
+            let shared = shared.clone();
+            let on_start = config.on_start.clone();
+            builder
+                .spawn(move || {
+                    if let Some(on_start) = on_start{
+                        on_start(index);
+                    }
+                    shared.work(index);
+                })
+                .expect("GcThreadPool: could not spawn worker thread")
+        }).collect();
+        return GcThreadPool{
+            shared,
+            handles,
+            running: Mutex::new(())
+        };
+    }
+}
+
+impl PoolShared{
+    // runs every piece of work given to the pool on a single thread, until shutdown
+    fn work(&self, index: usize){
+        let mut seen = 0;
+        loop{
+            let job = {
+                let mut state = self.lock();
+                while !state.shutdown && state.generation == seen{
+                    state = self.ready.wait(state).expect("GcThreadPool: lock poisoned");
+                }
+                if state.shutdown{
+                    return;
+                }
+                seen = state.generation;
+                state.job
+            };
+            match job{
+                Some(job) if index < job.count => {
+                    // the work outlives the call to `run` that gave it, which waits for every
+                    // worker to return
+                    let result = panic::catch_unwind(AssertUnwindSafe(|| (job.work)(index)));
+                    let mut state = self.lock();
+                    if let Err(payload) = result{
+                        state.panic.get_or_insert(payload);
+                    }
+                    state.remaining -= 1;
+                    if state.remaining == 0{
+                        self.done.notify_all();
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, PoolState>{
+        return self.state.lock().expect("GcThreadPool: lock poisoned");
+    }
+}
+
+//////////////// impls
+
+impl GcExecutor for GcThreadPool{
+    fn threads(&self) -> usize{
+        return self.handles.len();
+    }
+
+    fn run(&self, count: usize, work: &(dyn Fn(usize) + Sync)){
+        assert!(count <= self.threads(), "GcThreadPool: more workers than threads");
+        if count == 0{
+            return;
+        }
+        let payload = {
+            let _running = self.running.lock().expect("GcThreadPool: lock poisoned");
+            let mut state = self.shared.lock();
+            // safety: only the lifetime is erased; the work is forgotten before this returns
+            let work = unsafe{ mem::transmute::<&(dyn Fn(usize) + Sync), &'static (dyn Fn(usize) + Sync)>(work) };
+            state.job = Some(Job{ work, count });
+            state.generation += 1;
+            state.remaining = count;
+            self.shared.ready.notify_all();
+            while state.remaining > 0{
+                state = self.shared.done.wait(state).expect("GcThreadPool: lock poisoned");
+            }
+            state.job = None;
+            state.panic.take()
+        };
+        // resumed once the locks are released, so that the pool can still be used afterwards
+        if let Some(payload) = payload{
+            panic::resume_unwind(payload);
+        }
+    }
+}
+
+impl Drop for GcThreadPool{
+    fn drop(&mut self){
+        // don't panic while unwinding from a panic that poisoned the lock
+        if let Ok(mut state) = self.shared.state.lock(){
+            state.shutdown = true;
+        }
+        self.shared.ready.notify_all();
+        for handle in self.handles.drain(..){
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Debug for GcThreadPool{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result{
+        return f.debug_struct("GcThreadPool")
+            .field("threads", &self.handles.len())
+            .finish();
+    }
+}
+
+impl Debug for ThreadPoolConfig{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result{
+        return f.debug_struct("ThreadPoolConfig")
+            .field("threads", &self.threads)
+            .field("name", &self.name)
+            .field("stack_size", &self.stack_size)
+            .field("on_start", &self.on_start.is_some())
+            .finish();
+    }
+}
+
+impl Debug for Parallelism<'_>{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result{
+        return match self{
+            Parallelism::Threads(threads) => f.debug_tuple("Threads").field(threads).finish(),
+            Parallelism::Executor(executor) => f.debug_tuple("Executor").field(&executor.threads()).finish()
+        };
+    }
+}
+
+impl From<usize> for ThreadPoolConfig{
+    fn from(threads: usize) -> Self{
+        return ThreadPoolConfig::new(threads);
+    }
+}
+
+impl From<usize> for Parallelism<'_>{
+    fn from(threads: usize) -> Self{
+        return Parallelism::Threads(threads);
+    }
+}
+
+impl<'a> From<&'a dyn GcExecutor> for Parallelism<'a>{
+    fn from(executor: &'a dyn GcExecutor) -> Self{
+        return Parallelism::Executor(executor);
+    }
+}
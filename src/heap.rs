@@ -1,26 +1,177 @@
 //! The heap data structure, alongside basic traits used by garbage collectors.
 
-use std::{alloc, mem};
+use std::{alloc, fmt, mem};
+use std::borrow::Cow;
+use std::error::Error;
+use std::io;
+use std::fmt::{Debug, Display, Formatter};
 use std::marker::PhantomData;
+use std::mem::MaybeUninit;
+use std::{ptr, slice};
 use std::ptr::NonNull;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use classes::SizeClasses;
+use crate::gc::parallel::{Worker, WorkPool};
+use crate::gc::threads::Parallelism;
+
+pub mod classes;
+mod sanitize;
+#[cfg(all(feature = "persist", unix, target_pointer_width = "64"))]
+pub mod mapped;
+#[cfg(all(feature = "persist", target_os = "linux", target_pointer_width = "64"))]
+pub mod cow;
 
 /// A fixed-capacity contiguous vector of possibly-unsized data.
+///
+/// Values can be pushed by multiple threads at once using [Heap::push_concurrent]; space is
+/// reserved without locking, and a lock is only taken to record the new value.
+///
+/// Zero-sized values, such as `()` or empty slices, take a byte of space each, so that every
+/// value has its own address to be found and moved by.
+///
+/// Heaps created with [Heap::with_headers] store an [ObjectHeader] immediately before each value.
+/// Heaps using [Heap::with_size_classes] place values in blocks of equally-sized slots instead of
+/// one after another.
+///
+/// In debug builds, memory is overwritten with [POISON] once the value stored there is dropped or
+/// moved, so that uses of stale pointers are easy to recognise. With the `sanitize` feature, such
+/// memory is also reported as inaccessible to AddressSanitizer and Valgrind.
 pub struct Heap<T, Ptr = *const T>
     where T: ?Sized + DynSized, Ptr: HeapPtr<T>
 {
     head: NonNull<u8>, // T is ?Sized, so NonNull<T> would need metadata that doesn't exist yet
     cap: usize,
-    used: usize,
+    used: AtomicUsize,
     indexes: Vec<Ptr>,
+    // indexes of values sorted by address, for finding values by pointer
+    order: Vec<usize>,
+    // values pushed by `push_concurrent`, which follow `indexes` until a mutable method records them
+    pending: Mutex<Vec<Ptr>>,
+    pending_len: AtomicUsize,
+    headers: bool,
+    // (start, end) offsets of space freed by `sweep` within the used space, sorted and disjoint
+    free: Vec<(usize, usize)>,
+    // the blocks and free slots of a heap using size classes
+    classes: Option<SizeClasses>,
+    // whether values are left undropped when removed; see `Heap::with_skip_drop`
+    skip_drop: bool,
+    backing: Backing,
     _phantom: PhantomData<T>
 }
 
+// where the memory of a heap comes from
+enum Backing{
+    // the global allocator
+    Alloc,
+    // a memory-mapped file, which outlives the heap
+    #[cfg(all(feature = "persist", unix, target_pointer_width = "64"))]
+    File(mapped::MappedFile),
+    // an anonymous file that can be mapped privately by forks, which is unmapped when dropped
+    #[cfg(all(feature = "persist", target_os = "linux", target_pointer_width = "64"))]
+    Cow(cow::CowMap)
+}
+
+/// Information stored in a heap immediately before a value, for use by collectors.
+///
+/// Headers are created when values are pushed, and carried along (with their age incremented and
+/// mark bits cleared) when values are moved by [Heap::evacuate_into].
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct ObjectHeader{
+    size: usize,
+    user: usize,
+    marks: u32,
+    age: u32
+}
+
+/// A copy of every value in a [Heap] and where it was stored, made by [Heap::checkpoint] and
+/// restored by [Heap::rollback].
+pub struct HeapCheckpoint<T: ?Sized, Ptr>{
+    // the address and capacity of the heap it was made from
+    head: usize,
+    cap: usize,
+    values: Vec<(Ptr, Option<ObjectHeader>, Box<T>)>,
+    used: usize,
+    free: Vec<(usize, usize)>,
+    classes: Option<SizeClasses>
+}
+
+/// The error returned when a value can't be allocated in a heap, giving back the value.
+pub struct AllocError<T: ?Sized>{
+    value: Box<T>,
+    kind: AllocErrorKind
+}
+
+/// The reason a value could not be allocated.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum AllocErrorKind{
+    /// There is not enough free space left for the value, but it may fit after a collection.
+    OutOfSpace,
+    /// The value is larger than the capacity of the heap, and can never fit.
+    TooLarge,
+    /// The value requires a greater alignment than the heap provides.
+    Alignment,
+    /// The value would take the memory over its configured hard limit, even after a collection.
+    LimitExceeded
+}
+
+/// An invariant of a heap or managed memory found to be broken by a verifier, such as [Heap::verify].
+///
+/// Values are identified by their index.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum VerifyError{
+    /// The value is not entirely within the used space of the heap.
+    OutOfBounds{ index: usize },
+    /// The value is not aligned as its type requires.
+    Misaligned{ index: usize },
+    /// The two values occupy some of the same memory.
+    Overlap{ first: usize, second: usize },
+    /// The value contains a managed pointer to the given address, which is not a value in the
+    /// same memory.
+    DanglingEdge{ index: usize, target: *const () },
+    /// The value's [ObjectHeader] records a different size than the value's.
+    HeaderMismatch{ index: usize }
+}
+
+/// How the bytes in a range of a heap are used, as found by [Heap::occupancy].
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub struct Occupancy{
+    start: usize,
+    live: usize,
+    dead: usize,
+    free: usize,
+    unused: usize
+}
+
 /// A (possibly-unsized) value that provides certain information about its memory layout.
 ///
-/// Automatically implemented for sized types and slices.
+/// Automatically implemented for sized types, slices and `str`. Trait objects and other custom
+/// DSTs need an implementation, which can be empty, as the layout of each value is found from the
+/// metadata of its pointer, which heaps keep when values are pushed. Without specialization, a
+/// blanket implementation for them would leave no way to override the alignments below.
+///
+/// # Safety
+///
+/// `dyn_align` and `dyn_align_of_val` must return powers of two.
 pub unsafe trait DynSized{
-    /// Returns the alignment of values of this type.
-    fn dyn_align() -> usize;
+    /// Returns the alignment of the memory of heaps holding values of this type, and so the
+    /// greatest alignment of values they can store; others are rejected with
+    /// [AllocErrorKind::Alignment].
+    ///
+    /// By default, this is a page (4096 bytes), so that values of any type aligned to at most a page
+    /// can be stored.
+    fn dyn_align() -> usize{
+        return PAGE_SIZE;
+    }
+    /// Returns the alignment that this value is placed at in a heap, for values that should be
+    /// aligned to more than they require. Values are always placed at least at the alignment they
+    /// require.
+    ///
+    /// By default, this is the alignment the value requires, from [mem::align_of_val].
+    fn dyn_align_of_val(&self) -> usize{
+        return mem::align_of_val(self);
+    }
 }
 
 /// A pointer to a value in managed memory, usable by heaps.
@@ -30,7 +181,9 @@ pub unsafe trait DynSized{
 ///  - You want to store additional metadata, e.g. types, that are relevant for garbage collection.
 ///
 /// In the latter case, additionally implement [HeapPtr::copy_meta], [HeapPtr::has_significant_meta],
-/// and [HeapPtr::eq_ignoring_meta].
+/// and [HeapPtr::eq_ignoring_meta]; if the metadata identifies types, also implement [HeapPtr::type_tag].
+///
+/// Heaps are only `Send` and `Sync` if their pointer type implements [ThreadSafePtr].
 pub trait HeapPtr<T: ?Sized>: Eq + Clone{
     /// Create an instance of this pointer type with the target and size information given.
     fn from_raw_ptr(raw: *const T) -> Self;
@@ -52,13 +205,51 @@ pub trait HeapPtr<T: ?Sized>: Eq + Clone{
     fn eq_ignoring_meta(&self, other: &Self) -> bool{
         return self == other;
     }
+    /// Returns an identifier for the type of the value this points to, if this pointer's metadata
+    /// records one. Used by diagnostics such as heap dumps and censuses.
+    fn type_tag(&self) -> Option<u32>{
+        return None;
+    }
+    /// Makes this pointer point to no value, keeping its metadata. Collectors use this to clear
+    /// weak pointers whose values were dropped.
+    ///
+    /// By default, this sets the pointer's address to null.
+    fn set_cleared(&mut self){
+        let mut cleared = Self::from_raw_ptr(with_metadata_of(ptr::null_mut(), self.to_raw_ptr()));
+        cleared.copy_meta(self);
+        *self = cleared;
+    }
+    /// Returns whether this pointer has been cleared by [HeapPtr::set_cleared].
+    fn is_cleared(&self) -> bool{
+        return self.to_raw_ptr().cast::<()>().is_null();
+    }
 }
 
+/// A [HeapPtr] that can be sent and shared between threads along with the heap holding the values
+/// it points to, making heaps and the memories built on them `Send` and `Sync` when their values
+/// are.
+///
+/// Implemented for raw pointers, which only address values; implement it for pointer types that
+/// are `Send` and `Sync` apart from the raw pointers they hold.
+///
+/// # Safety
+///
+/// Pointers of this type, and their clones, must be safe to move to and use from other threads,
+/// ignoring that they point into a heap.
+pub unsafe trait ThreadSafePtr{}
+
 //////////////// impls
 
+unsafe impl<T: ?Sized> ThreadSafePtr for *const T{}
+
 impl<T: ?Sized> HeapPtr<T> for *const T{
     fn from_raw_ptr(raw: *const T) -> Self { raw }
     fn to_raw_ptr(&self) -> *const T { *self }
+
+    fn eq_ignoring_meta(&self, other: &Self) -> bool{
+        // vtables for the same type aren't guaranteed to be unique, so only compare addresses
+        return self.cast::<()>() == other.cast::<()>();
+    }
 }
 
 unsafe impl<T: Sized> DynSized for T{
@@ -68,168 +259,1579 @@ unsafe impl<T: Sized> DynSized for T{
 }
 
 unsafe impl<T: Sized> DynSized for [T]{
-    fn dyn_align() -> usize {
+    fn dyn_align() -> usize{
         return mem::align_of::<T>();
     }
 }
 
+unsafe impl DynSized for str{
+    fn dyn_align() -> usize{
+        return 1;
+    }
+}
+
 impl<T: ?Sized + DynSized, Ptr: HeapPtr<T>> Heap<T, Ptr>{
 
     /// Creates a new heap with the given capacity in bytes.
     pub fn new(size: usize) -> Heap<T, Ptr>{
-        let layout = alloc::Layout::from_size_align(size, T::dyn_align()).expect("Invalid layout for new Heap");
+        return Heap::create(size, false);
+    }
+
+    /// Creates a new heap with the given capacity in bytes, which stores an [ObjectHeader] before
+    /// each value. Headers take up space in the heap.
+    pub fn with_headers(size: usize) -> Heap<T, Ptr>{
+        return Heap::create(size, true);
+    }
+
+    /// Makes this heap place values in slots of a fixed set of sizes, each in blocks of
+    /// [BLOCK_SIZE](classes::BLOCK_SIZE) bytes holding slots of one size, with larger values
+    /// taking whole blocks. Space freed by [Heap::sweep] or [Heap::retain] is reused by values of
+    /// the same size class, and blocks left empty are reused by any; see [classes].
+    ///
+    /// Any space after the last whole block is unused, and values can't be pushed with
+    /// [Heap::push_concurrent].
+    ///
+    /// # Panics
+    ///
+    /// Panics if this heap is not empty.
+    pub fn with_size_classes(mut self) -> Self{
+        assert!(self.len() == 0 && *self.used.get_mut() == 0, "Heap::with_size_classes: heap is not empty");
+        self.classes = Some(SizeClasses::new(self.cap));
+        return self;
+    }
+
+    /// Returns whether this heap places values in size-class slots; see [Heap::with_size_classes].
+    pub fn has_size_classes(&self) -> bool{
+        return self.classes.is_some();
+    }
+
+    /// Makes this heap reuse the space of values it removes without dropping them, e.g. for
+    /// plain-old-data types where calling `drop_in_place` on each value is pure overhead.
+    ///
+    /// This applies to every value removed by [Heap::sweep], [Heap::retain], [Heap::reset],
+    /// evacuation, and dropping this heap. Anything owned by those values, such as other
+    /// allocations, is leaked.
+    pub fn with_skip_drop(mut self) -> Self{
+        self.skip_drop = true;
+        return self;
+    }
+
+    /// Returns whether this heap removes values without dropping them; see [Heap::with_skip_drop].
+    pub fn skips_drop(&self) -> bool{
+        return self.skip_drop;
+    }
+
+    fn create(size: usize, headers: bool) -> Heap<T, Ptr>{
+        let layout = Heap::<T, Ptr>::layout(size);
         let head = unsafe{ alloc::alloc(layout) };
         let nn_head = match NonNull::new(head){
             None => alloc::handle_alloc_error(layout),
             Some(p) => p
         };
+        unsafe{
+            sanitize::make_noaccess(head, size);
+        }
         return Heap{
             head: nn_head,
             cap: size,
-            used: 0,
+            used: AtomicUsize::new(0),
             indexes: vec![],
+            order: vec![],
+            pending: Mutex::new(vec![]),
+            pending_len: AtomicUsize::new(0),
+            headers,
+            free: Vec::new(),
+            classes: None,
+            skip_drop: false,
+            backing: Backing::Alloc,
             _phantom: PhantomData
         };
     }
 
-    /// Pushes an object onto the end of this heap, returning a pointer to it,
-    /// or `None` if this heap is full.
+    // the layout of the memory backing a heap, aligned for values and headers
+    fn layout(size: usize) -> alloc::Layout{
+        return alloc::Layout::from_size_align(size, Self::align()).expect("Invalid layout for new Heap");
+    }
+
+    // the alignment of the memory backing a heap, and so the greatest alignment of any value
+    fn align() -> usize{
+        return T::dyn_align().max(mem::align_of::<ObjectHeader>());
+    }
+
+    /// Returns whether this heap stores an [ObjectHeader] before each value.
+    pub fn has_headers(&self) -> bool{
+        return self.headers;
+    }
+
+    /// Returns the header of the value at the given pointer, or `None` if this heap doesn't have
+    /// headers or that pointer does not point to a value in this heap.
+    pub fn header(&self, ptr: &Ptr) -> Option<&ObjectHeader>{
+        if !self.headers || !self.contains_ptr(ptr){
+            return None;
+        }
+        return unsafe{ header_of(ptr).as_ref() };
+    }
+
+    /// Returns the header of the value at the given pointer mutably, or `None` if this heap
+    /// doesn't have headers or that pointer does not point to a value in this heap.
+    pub fn header_mut(&mut self, ptr: &Ptr) -> Option<&mut ObjectHeader>{
+        if !self.headers || !self.contains_ptr(ptr){
+            return None;
+        }
+        return unsafe{ header_of(ptr).as_mut() };
+    }
+
+    /// Pushes an object into this heap, returning a pointer to it,
+    /// or an [AllocError] containing the object if it can't be allocated.
     ///
+    /// The object is placed in space freed by [Heap::sweep] if any fits, and otherwise at the end.
     /// The given `with` function is applied to the pointer before saving, for e.g.
     /// adding extra metadata.
-    pub fn push_with(&mut self, v: Box<T>, with: impl FnOnce(Ptr) -> Ptr) -> Option<Ptr>{
+    #[inline]
+    pub fn push_with(&mut self, v: Box<T>, with: impl FnOnce(Ptr) -> Ptr) -> Result<Ptr, AllocError<T>>{
+        let size = mem::size_of_val(v.as_ref());
+        let align = align_of(v.as_ref());
+        // fast path: bump the end of the used space, if there are no headers or free spaces to consider
+        if !self.headers && self.free.is_empty() && self.classes.is_none() && align <= Self::align(){
+            let used = self.used.get_mut();
+            let start = (*used + align - 1) & !(align - 1);
+            if start <= self.cap && self.cap - start >= footprint(size){
+                *used = start + footprint(size);
+                let raw = unsafe{ self.move_into(v, start) };
+                return Ok(self.record_push(raw, with));
+            }
+        }
+        return self.push_slow(v, with);
+    }
+
+    // pushes a value that doesn't fit the fast path of `push_with`, e.g. because it must be given
+    // a header or placed in free space, or because it doesn't fit
+    #[cold]
+    #[inline(never)]
+    fn push_slow(&mut self, v: Box<T>, with: impl FnOnce(Ptr) -> Ptr) -> Result<Ptr, AllocError<T>>{
         let size = mem::size_of_val(v.as_ref());
         // check we can allocate
-        if self.cap - self.used < size{
-            return None;
+        let start = match self.reserve(size, align_of(v.as_ref())){
+            Ok(start) => start,
+            Err(kind) => return Err(self.alloc_failed(v, kind))
+        };
+        let raw = unsafe{
+            self.write_header(start, size);
+            self.move_into(v, start)
+        };
+        return Ok(self.record_push(raw, with));
+    }
+
+    // applies the `with` function given to `push_with` to a new value's pointer, and records it
+    #[inline(never)]
+    fn record_push(&mut self, raw: Ptr, with: impl FnOnce(Ptr) -> Ptr) -> Ptr{
+        let new_ptr = with(raw);
+        self.record_index(new_ptr.clone());
+        return new_ptr;
+    }
+
+    /// Pushes an object onto the end of this heap from any thread, returning a pointer to it,
+    /// or an [AllocError] containing the object if it can't be allocated.
+    ///
+    /// Space is reserved with an atomic compare-and-swap, so concurrent pushes don't wait for
+    /// each other to copy their values. Values pushed concurrently may be stored in a different
+    /// order than their addresses.
+    ///
+    /// # Panics
+    ///
+    /// Panics if this heap uses size classes; see [Heap::with_size_classes].
+    pub fn push_concurrent(&self, v: Box<T>) -> Result<Ptr, AllocError<T>> where T: Send{
+        assert!(self.classes.is_none(), "Heap::push_concurrent: heap uses size classes");
+        let size = mem::size_of_val(v.as_ref());
+        let align = align_of(v.as_ref());
+        // reserve space by bumping the allocation pointer, retrying if another thread got there first
+        let mut used = self.used.load(Ordering::Relaxed);
+        let start = loop{
+            let start = match self.next_start(used, size, align){
+                Ok(start) => start,
+                Err(kind) => return Err(self.alloc_failed(v, kind))
+            };
+            match self.used.compare_exchange_weak(used, start + footprint(size), Ordering::Relaxed, Ordering::Relaxed){
+                Ok(_) => break start,
+                Err(current) => used = current
+            }
+        };
+        // the reserved space is only visible to this thread until it's recorded
+        let new_ptr = unsafe{
+            self.write_header(start, size);
+            self.move_into(v, start)
+        };
+        let mut pending = self.pending.lock().expect("Heap: pending lock poisoned");
+        pending.push(new_ptr.clone());
+        self.pending_len.store(pending.len(), Ordering::Release);
+        return Ok(new_ptr);
+    }
+
+    // moves the value into this heap at the given offset, which must have been reserved for it
+    #[inline]
+    unsafe fn move_into(&self, v: Box<T>, start: usize) -> Ptr{
+        let size = mem::size_of_val(v.as_ref());
+        // get the raw source pointer (with size metadata)
+        let raw = Box::into_raw(v);
+        // find the destination location
+        let dest_ptr: *mut u8 = self.head.as_ptr().add(start);
+        sanitize::make_accessible(dest_ptr, size);
+        // add the metadata of the source pointer (e.g. object size) to get the fat target pointer
+        let dest_ptr: *mut T = with_metadata_of(dest_ptr, raw);
+        // copy the bytes of the source to the target
+        // *const u8 is required as we specify size in bytes
+        (dest_ptr as *mut u8).copy_from(raw as *const u8, size);
+        // deallocate the box's memory; boxes of zero-sized values don't allocate any
+        let layout = layout_of_raw(raw);
+        if layout.size() != 0{
+            alloc::dealloc(raw as *mut u8, layout);
         }
-        let new_ptr: Ptr;
-        unsafe{
-            // get the raw source pointer (with size metadata)
-            let raw = Box::into_raw(v);
-            // find the destination location
-            let dest_ptr: *mut u8 = self.head.as_ptr().offset(self.used as isize);
-            // add the metadata of the source pointer (e.g. object size) to get the fat target pointer
-            let dest_ptr: *mut T = dest_ptr.with_metadata_of(raw);
-            // copy the bytes of the source to the target
-            // *const u8 is required as we specify size in bytes
-            (dest_ptr as *mut u8).copy_from(raw as *const u8, size);
-            // deallocate the box's memory
-            alloc::dealloc(raw as *mut u8, alloc::Layout::for_value_raw(raw));
-            // keep track of the new entry
-            new_ptr = with(Ptr::from_raw_ptr(dest_ptr));
-            self.indexes.push(new_ptr.clone());
-        }
-        self.used += size;
-        return Some(new_ptr);
-    }
-
-    /// Pushes an object onto the end of this heap, returning a pointer to it,
-    /// or `None` if this heap is full.
-    pub fn push(&mut self, v: Box<T>) -> Option<Ptr>{
+        return Ptr::from_raw_ptr(dest_ptr);
+    }
+
+    /// Pushes an object into this heap, returning a pointer to it,
+    /// or an [AllocError] containing the object if it can't be allocated; see [Heap::push_with].
+    pub fn push(&mut self, v: Box<T>) -> Result<Ptr, AllocError<T>>{
         return self.push_with(v, |x| x);
     }
 
+    /// Pushes a value with the given layout into this heap, built directly in zero-filled space by
+    /// `init`, returning a pointer to it, or the reason it can't be allocated.
+    ///
+    /// `init` is given a pointer to the space, and returns a pointer to the value it initialized
+    /// there, e.g. by casting it, or adding a slice length. The value is placed as by
+    /// [Heap::push_with].
+    ///
+    /// # Safety
+    ///
+    /// `init` must return a pointer to the address it was given, to a valid value of the size
+    /// given by `layout`. If `init` panics, the space reserved is leaked.
+    pub unsafe fn push_zeroed(&mut self, layout: alloc::Layout, init: impl FnOnce(*mut u8) -> *mut T) -> Result<Ptr, AllocErrorKind>{
+        let size = layout.size();
+        let start = match self.reserve(size, layout.align()){
+            Ok(start) => start,
+            Err(kind) => {
+                gc_event!(DEBUG, ?kind, size, capacity = self.cap, "allocation failed");
+                return Err(kind);
+            }
+        };
+        self.write_header(start, size);
+        let dest = self.head.as_ptr().add(start);
+        sanitize::make_accessible(dest, size);
+        // space reused from dropped values may hold their bytes, or poison
+        dest.write_bytes(0, size);
+        let raw = init(dest);
+        debug_assert_eq!(raw as *mut u8, dest, "Heap::push_zeroed: initializer returned a different address");
+        let new_ptr = Ptr::from_raw_ptr(raw);
+        self.record_index(new_ptr.clone());
+        return Ok(new_ptr);
+    }
+
+    /// Reserves space for a value of the given size and alignment in this heap, returning a
+    /// pointer to it with the metadata (e.g. slice length) of `meta`, and the uninitialized bytes
+    /// of that space, or `None` if it can't be allocated or the alignment isn't a power of two.
+    ///
+    /// The value isn't part of this heap until its bytes are written and it's committed with
+    /// [Heap::commit_uninit]; until then, it's ignored by every other method. The space is placed
+    /// as by [Heap::push_with], and is leaked if the value is never committed.
+    pub fn alloc_uninit(&mut self, size: usize, align: usize, meta: *const T) -> Option<(Ptr, &mut [MaybeUninit<u8>])>{
+        alloc::Layout::from_size_align(size, align).ok()?;
+        let start = match self.reserve(size, align){
+            Ok(start) => start,
+            Err(_kind) => {
+                gc_event!(DEBUG, kind = ?_kind, size, capacity = self.cap, "allocation failed");
+                return None;
+            }
+        };
+        unsafe{
+            self.write_header(start, size);
+            let dest = self.head.as_ptr().add(start);
+            sanitize::make_accessible(dest, size);
+            let ptr = Ptr::from_raw_ptr(with_metadata_of(dest, meta));
+            return Some((ptr, slice::from_raw_parts_mut(dest as *mut MaybeUninit<u8>, size)));
+        }
+    }
+
+    /// Adds a value reserved by [Heap::alloc_uninit] to this heap, after its bytes have been
+    /// written, so that it can be accessed, moved and dropped like any other value.
+    ///
+    /// # Safety
+    ///
+    /// The pointer must have been returned by [Heap::alloc_uninit] on this heap, and not already
+    /// committed; its bytes must be a valid value of the size that was reserved. Values of this
+    /// heap must not have been swept, evacuated, retained or reset since it was reserved, as its
+    /// space may have been reused.
+    pub unsafe fn commit_uninit(&mut self, ptr: Ptr){
+        debug_assert!(self.position(&ptr, |p| p.eq_ignoring_meta(&ptr)).is_none(), "Heap::commit_uninit: value is already committed");
+        self.record_index(ptr);
+    }
+
+    /// Pushes a value into this heap, initialized by `init` from all-zero bytes, returning a
+    /// pointer to it, or the reason it can't be allocated; see [Heap::push_zeroed].
+    ///
+    /// # Safety
+    ///
+    /// All-zero bytes must be a valid `T`, e.g. if it only holds integers and raw pointers.
+    pub unsafe fn alloc_zeroed(&mut self, init: impl FnOnce(&mut T)) -> Result<Ptr, AllocErrorKind> where T: Sized{
+        return self.push_zeroed(alloc::Layout::new::<T>(), |dest| {
+            let value = dest as *mut T;
+            init(&mut *value);
+            value
+        });
+    }
+
+    fn alloc_failed(&self, v: Box<T>, kind: AllocErrorKind) -> AllocError<T>{
+        gc_event!(DEBUG, ?kind, size = mem::size_of_val(v.as_ref()), capacity = self.cap, "allocation failed");
+        return AllocError::new(v, kind);
+    }
+
+    // finds the offset that a new value of the given size and alignment would be placed at,
+    // given the number of bytes used so far
+    fn next_start(&self, used: usize, size: usize, align: usize) -> Result<usize, AllocErrorKind>{
+        let size = footprint(size);
+        if align > Self::align(){
+            return Err(AllocErrorKind::Alignment);
+        }
+        if size > self.cap || (self.headers && size > self.cap - HEADER_SIZE.min(self.cap)){
+            return Err(AllocErrorKind::TooLarge);
+        }
+        let start = self.place(used, align);
+        if self.cap < start || self.cap - start < size{
+            return Err(AllocErrorKind::OutOfSpace);
+        }
+        return Ok(start);
+    }
+
+    // finds the offset that a new value of the given size and alignment would be placed at,
+    // preferring the first free space it fits in
+    fn find_space(&self, size: usize, align: usize) -> Result<usize, AllocErrorKind>{
+        let end = self.next_start(self.used.load(Ordering::Relaxed), size, align);
+        if let Err(AllocErrorKind::Alignment | AllocErrorKind::TooLarge) = end{
+            return end;
+        }
+        for &(free_start, free_end) in &self.free{
+            let start = self.place(free_start, align);
+            if start <= free_end && free_end - start >= size{
+                return Ok(start);
+            }
+        }
+        return end;
+    }
+
+    // finds space for a value of the given size and alignment and marks it as used, returning the
+    // offset it should be placed at
+    fn reserve(&mut self, size: usize, align: usize) -> Result<usize, AllocErrorKind>{
+        let size = footprint(size);
+        if self.classes.is_none(){
+            let start = self.find_space(size, align)?;
+            self.claim(start, size);
+            return Ok(start);
+        }
+        // slots are aligned to every class, so the value starts the same distance into any slot
+        let offset = self.next_start(0, size, align)?;
+        let classes = self.classes.as_mut().unwrap();
+        let slot = classes.alloc(offset + size, align).ok_or(AllocErrorKind::OutOfSpace)?;
+        *self.used.get_mut() = classes.end();
+        return Ok(slot + offset);
+    }
+
+    // marks the space found by `find_space` for a value of the given size as used
+    fn claim(&mut self, start: usize, size: usize){
+        let used = self.used.get_mut();
+        if start >= *used{
+            *used = start + size;
+            return;
+        }
+        let from = if self.headers { start - HEADER_SIZE } else { start };
+        let idx = self.free.iter().position(|&(free_start, free_end)| free_start <= from && start + size <= free_end)
+            .expect("Heap: claimed space is not free");
+        let (free_start, free_end) = self.free.remove(idx);
+        // keep the space left on either side of the value
+        let mut insert = idx;
+        for (left_start, left_end) in [(free_start, from), (start + size, free_end)]{
+            if left_start < left_end{
+                self.free.insert(insert, (left_start, left_end));
+                insert += 1;
+            }
+        }
+    }
+
+    // finds the offset after `used` that a value with the given alignment can be placed at,
+    // leaving space for its header if needed
+    fn place(&self, used: usize, align: usize) -> usize{
+        // values of different types (e.g. trait objects) may need padding between them
+        if self.headers{
+            let align = align.max(mem::align_of::<ObjectHeader>());
+            return (used + HEADER_SIZE + align - 1) & !(align - 1);
+        }
+        return (used + align - 1) & !(align - 1);
+    }
+
+    // writes a new header for a value of the given size at the given offset, if this heap has headers
+    unsafe fn write_header(&self, start: usize, size: usize){
+        self.restore_header(start, ObjectHeader::new(size));
+    }
+
+    // writes the given header for the value at the given offset, if this heap has headers
+    unsafe fn restore_header(&self, start: usize, header: ObjectHeader){
+        if self.headers{
+            let at = self.head.as_ptr().add(start - HEADER_SIZE);
+            sanitize::make_accessible(at, HEADER_SIZE);
+            (at as *mut ObjectHeader).write(header);
+        }
+    }
+
+    // overwrites the space used by the value at the given pointer, and its header, with `POISON`
+    // in debug builds, once the value has been dropped or moved
+    unsafe fn poison_value(&self, ptr: &Ptr, size: usize){
+        let start = ptr.to_raw_ptr() as *const u8 as usize - self.head.as_ptr() as usize;
+        match self.headers{
+            true => self.poison(start - HEADER_SIZE, HEADER_SIZE + size),
+            false => self.poison(start, size)
+        }
+    }
+
+    // overwrites the given range of this heap with `POISON` in debug builds, and marks it as
+    // inaccessible to memory checkers
+    unsafe fn poison(&self, start: usize, len: usize){
+        if cfg!(debug_assertions){
+            // padding between values was never made accessible
+            sanitize::make_accessible(self.head.as_ptr().add(start), len);
+            self.head.as_ptr().add(start).write_bytes(POISON, len);
+        }
+        sanitize::make_noaccess(self.head.as_ptr().add(start), len);
+    }
+
     /// Returns a reference to the value at the given index.
     pub fn get(&self, idx: usize) -> &T{
+        let raw = self.ptr_at(idx).to_raw_ptr();
         unsafe{
-            return self.indexes[idx].to_raw_ptr().as_ref().expect("Heap::get: GcPtr returned null");
+            return raw.as_ref().expect("Heap::get: GcPtr returned null");
         }
     }
 
+    // the pointer to the value at the given index
+    pub(crate) fn ptr_at(&self, idx: usize) -> Ptr{
+        if idx < self.indexes.len(){
+            return self.indexes[idx].clone();
+        }
+        return self.pending.lock().expect("Heap: pending lock poisoned")[idx - self.indexes.len()].clone();
+    }
+
     /// Returns a mutable reference to the value at the given index.
     pub fn get_mut(&mut self, idx: usize) -> &mut T{
         unsafe{
-            return (self.indexes[idx].to_raw_ptr() as *mut T).as_mut().expect("Heap::get_mut: GcPtr returned null");
+            return (self.indexes_mut()[idx].to_raw_ptr() as *mut T).as_mut().expect("Heap::get_mut: GcPtr returned null");
         }
     }
 
     /// Returns a mutable reference to the value at the given pointer, or `None`
     /// if that pointer does not point to a value in this heap.
     pub fn get_by(&mut self, ptr: &Ptr) -> Option<&mut T>{
-        return self.indexes.iter().position(|p| p == ptr).map(|x| self.get_mut(x));
+        return self.position(ptr, |p| p == ptr).map(|x| self.get_mut(x));
+    }
+
+    /// Returns a reference to the value at the given pointer, or `None`
+    /// if that pointer does not point to a value in this heap.
+    pub fn get_by_ref(&self, ptr: &Ptr) -> Option<&T>{
+        let idx = self.position(ptr, |p| p == ptr)?;
+        return Some(self.get(idx));
+    }
+
+    /// Returns a mutable reference to the value at the given pointer, ignoring any metadata the
+    /// pointer doesn't share with the pointer returned when the value was pushed, as with
+    /// [HeapPtr::eq_ignoring_meta].
+    pub fn get_by_ignoring_meta(&mut self, ptr: &Ptr) -> Option<&mut T>{
+        return self.position(ptr, |p| p.eq_ignoring_meta(ptr)).map(|x| self.get_mut(x));
+    }
+
+    /// Returns a reference to the value at the given pointer, ignoring metadata as with
+    /// [Heap::get_by_ignoring_meta].
+    pub fn get_by_ref_ignoring_meta(&self, ptr: &Ptr) -> Option<&T>{
+        return self.position(ptr, |p| p.eq_ignoring_meta(ptr)).map(|x| self.get(x));
+    }
+
+    // like `get_by_ignoring_meta`, but also returns the full pointer to the value
+    pub(crate) fn find_ignoring_meta(&mut self, ptr: &Ptr) -> Option<(Ptr, &mut T)>{
+        let idx = self.position(ptr, |p| p.eq_ignoring_meta(ptr))?;
+        let full = self.indexes_mut()[idx].clone();
+        return Some((full, self.get_mut(idx)));
     }
 
     /// Moves the element at the given index out of this heap, returning it (contained in a box)
     /// and its former pointer.
     ///
+    /// Later values are shifted down an index, so this takes time proportional to the number of
+    /// values after it; use [Heap::take_where] to take many values at once.
+    ///
     /// Note that this does not allow new values to be allocated in their place; use
     /// [Heap::reset] if that is necessary.
     pub fn take(&mut self, idx: usize) -> (Box<T>, Ptr){
         // need to preserve order because this might be called in a (reversed) loop
-        let ptr = self.indexes.remove(idx);
-        unsafe{
-            // get the raw source pointer with size metadata
-            let src: *const T = ptr.to_raw_ptr();
-            // find the size
-            let size = mem::size_of_val_raw(src);
-            // allocate the target memory
-            let dest: *mut u8 = alloc::alloc(alloc::Layout::for_value_raw(src));
-            // add size info to the destination pointer
-            let dest: *mut T = dest.with_metadata_of(src);
-            // copy the object's data into the destination
-            (dest as *mut u8).copy_from(src as *const u8, size);
-            // convert to a box and return
-            return (Box::from_raw(dest), ptr);
+        let ptr = self.indexes_mut().remove(idx);
+        self.rebuild_order();
+        return (unsafe{ self.move_out(&ptr) }, ptr);
+    }
+
+    /// Moves every value for which the given predicate returns true out of this heap, returning
+    /// them (contained in boxes) and their former pointers, in index order. Remaining values keep
+    /// their order.
+    ///
+    /// This takes time proportional to the number of values stored. As with [Heap::take], new
+    /// values are not allocated in the space of values taken.
+    pub fn take_where(&mut self, mut f: impl FnMut(&Ptr, &T) -> bool) -> Vec<(Box<T>, Ptr)>{
+        let indexes = mem::take(self.indexes_mut());
+        let mut kept = Vec::with_capacity(indexes.len());
+        let mut taken = Vec::new();
+        for ptr in indexes{
+            unsafe{
+                if f(&ptr, &*ptr.to_raw_ptr()){
+                    taken.push((self.move_out(&ptr), ptr));
+                }else{
+                    kept.push(ptr);
+                }
+            }
         }
+        *self.indexes_mut() = kept;
+        self.rebuild_order();
+        return taken;
+    }
+
+    // copies the value at the given pointer into a new box, poisoning its old space; the pointer
+    // must already have been removed from the indexes
+    unsafe fn move_out(&self, ptr: &Ptr) -> Box<T>{
+        // get the raw source pointer with size metadata
+        let src: *const T = ptr.to_raw_ptr();
+        // find the size
+        let layout = layout_of_raw(src);
+        let size = layout.size();
+        // allocate the target memory, or use a dangling pointer for zero-sized values as boxes do
+        let dest: *mut u8 = match size{
+            0 => layout.align() as *mut u8,
+            _ => alloc::alloc(layout)
+        };
+        // add size info to the destination pointer
+        let dest: *mut T = with_metadata_of(dest, src);
+        // copy the object's data into the destination
+        (dest as *mut u8).copy_from(src as *const u8, size);
+        self.poison_value(ptr, size);
+        // convert to a box
+        return Box::from_raw(dest);
     }
 
     /// Returns the number of values stored in this heap.
     pub fn len(&self) -> usize{
-        return self.indexes.len();
+        return self.indexes.len() + self.pending_len.load(Ordering::Acquire);
+    }
+
+    /// Returns the address of the start of this heap's memory.
+    pub fn start(&self) -> *const u8{
+        return self.head.as_ptr();
     }
 
     /// Returns whether the given pointer points to a value in this heap.
+    ///
+    /// Pointers outside of the used space are rejected immediately, and others are looked up by
+    /// address in logarithmic time.
     pub fn contains_ptr(&self, ptr: &Ptr) -> bool{
-        return self.indexes.contains(ptr);
+        return self.position(ptr, |p| p == ptr).is_some();
+    }
+
+    /// Returns whether the given pointer points to a value in this heap, ignoring any metadata
+    /// it doesn't share with the pointer returned when the value was pushed, as with
+    /// [HeapPtr::eq_ignoring_meta].
+    pub fn contains_ptr_ignoring_meta(&self, ptr: &Ptr) -> bool{
+        return self.position(ptr, |p| p.eq_ignoring_meta(ptr)).is_some();
     }
 
     /// Returns a pointer equivalent to the one given, but with any additional metadata
-    /// know by this heap, using [HeapPtr::eq_ignoring_meta].
-    pub fn to_full_ptr(&self, ptr: &Ptr) -> Ptr{
-        return self.indexes.iter().filter(|x| x.eq_ignoring_meta(&ptr)).next().clone().unwrap().clone();
+    /// known by this heap, using [HeapPtr::eq_ignoring_meta], or `None` if the given pointer
+    /// does not point to a value in this heap.
+    pub fn to_full_ptr(&self, ptr: &Ptr) -> Option<Ptr>{
+        let idx = self.position(ptr, |p| p.eq_ignoring_meta(ptr))?;
+        return Some(self.ptr_at(idx));
+    }
+
+    // finds the index of a value at the same address as the given pointer, for which `eq` returns true
+    fn position(&self, ptr: &Ptr, eq: impl Fn(&Ptr) -> bool) -> Option<usize>{
+        let addr = address_of(ptr);
+        let head = self.head.as_ptr() as usize;
+        if addr < head || addr - head >= self.used.load(Ordering::Relaxed){
+            return None;
+        }
+        let (indexes, order) = (&self.indexes, &self.order);
+        let first = order.partition_point(|idx| address_of(&indexes[*idx]) < addr);
+        let found = order[first..].iter()
+            .take_while(|idx| address_of(&indexes[**idx]) == addr)
+            .find(|idx| eq(&indexes[**idx]));
+        if let Some(idx) = found{
+            return Some(*idx);
+        }
+        if self.pending_len.load(Ordering::Acquire) == 0{
+            return None;
+        }
+        let pending = self.pending.lock().expect("Heap: pending lock poisoned");
+        return pending.iter()
+            .position(|p| address_of(p) == addr && eq(p))
+            .map(|idx| indexes.len() + idx);
+    }
+
+    // records a new value's pointer, after every other value
+    fn record_index(&mut self, ptr: Ptr){
+        self.record_pending();
+        self.indexes.push(ptr);
+        insert_ordered(&mut self.order, &self.indexes, self.indexes.len() - 1);
+    }
+
+    // records the values pushed concurrently since the last mutable method, after every other value
+    fn record_pending(&mut self){
+        if *self.pending_len.get_mut() == 0{
+            return;
+        }
+        let pending = mem::take(self.pending.get_mut().expect("Heap: pending lock poisoned"));
+        *self.pending_len.get_mut() = 0;
+        for ptr in pending{
+            self.indexes.push(ptr);
+            insert_ordered(&mut self.order, &self.indexes, self.indexes.len() - 1);
+        }
+    }
+
+    // re-sorts the indexes of values by address, after values have been removed
+    fn rebuild_order(&mut self){
+        self.record_pending();
+        let (indexes, order) = (&self.indexes, &mut self.order);
+        order.clear();
+        order.extend(0..indexes.len());
+        // values are usually already in address order, which a stable sort handles in linear time
+        order.sort_by_key(|idx| address_of(&indexes[*idx]));
+    }
+
+    // clears the lowest mark bit in the header of every value, if this heap has headers
+    pub(crate) fn clear_marks(&mut self){
+        if !self.headers{
+            return;
+        }
+        for ptr in self.indexes_mut().iter(){
+            unsafe{
+                (*header_of(ptr)).set_marked(false);
+            }
+        }
     }
 
     /// Runs the given function over every value in this heap.
+    ///
+    /// No locks are held while the function runs, so it may look up or push values. Values pushed
+    /// concurrently while this runs may or may not be visited.
     pub fn for_each(&self, mut cb: impl FnMut(&T, &Ptr)){
-        for i in 0..self.len(){
-            cb(self.get(i), &self.indexes[i]);
+        for ptr in self.ptrs().iter(){
+            unsafe{
+                cb(&*ptr.to_raw_ptr(), ptr);
+            }
         }
     }
 
     /// Runs the given function over every value in this heap, allowing mutation.
     pub fn for_each_mut(&mut self, mut cb: impl FnMut(&mut T, &Ptr)){
-        for i in 0..self.len(){
-            let ptr = &self.indexes[i].clone();
-            cb(self.get_mut(i), ptr);
+        for ptr in self.indexes_mut().iter(){
+            unsafe{
+                cb(&mut *(ptr.to_raw_ptr() as *mut T), ptr);
+            }
+        }
+    }
+
+    /// Moves every value for which `keep` returns true to the end of the `target` heap, and drops
+    /// every other value, leaving this heap empty. Returns the old and new pointers of every
+    /// moved value; new pointers copy the metadata of old pointers.
+    ///
+    /// If both heaps have headers, moved values keep theirs, with their age incremented and their
+    /// mark bits cleared.
+    ///
+    /// Values are visited in reverse order. Large evacuations copy values in parallel, on the
+    /// given number of threads or executor; values' bytes are copied without running any of their
+    /// code.
+    ///
+    /// Panics if the values to keep don't fit in the target heap, before moving or dropping any.
+    pub fn evacuate_into<'a>(&mut self, target: &mut Heap<T, Ptr>, mut keep: impl FnMut(&Ptr) -> bool, parallelism: impl Into<Parallelism<'a>>) -> Vec<(Ptr, Ptr)>{
+        let kept: Vec<bool> = self.indexes_mut().iter().map(&mut keep).collect();
+        let sequence = (0..kept.len()).rev().filter(|idx| kept[*idx]).collect();
+        return self.evacuate(target, kept, sequence, parallelism.into(), true).unwrap_or_else(|_| no_space());
+    }
+
+    /// Moves values to the end of the `target` heap as with [Heap::evacuate_into], placing the
+    /// values at the pointers in `first` that are kept before any others, in the given order.
+    /// Pointers in `first` are compared ignoring metadata; pointers outside of this heap, and
+    /// repeated pointers, are ignored.
+    ///
+    /// Panics if the values to keep don't fit in the target heap, before moving or dropping any.
+    pub fn evacuate_into_ordered<'a>(&mut self, target: &mut Heap<T, Ptr>, first: &[Ptr], keep: impl FnMut(&Ptr) -> bool, parallelism: impl Into<Parallelism<'a>>) -> Vec<(Ptr, Ptr)>{
+        return self.try_evacuate_into_ordered(target, first, keep, parallelism).unwrap_or_else(|_| no_space());
+    }
+
+    /// Moves values to the end of the `target` heap as with [Heap::evacuate_into_ordered], or
+    /// returns the capacity a target heap would need for them to fit if they don't, before moving
+    /// or dropping any. For heaps using size classes, that capacity may be more than needed.
+    pub fn try_evacuate_into_ordered<'a>(&mut self, target: &mut Heap<T, Ptr>, first: &[Ptr], keep: impl FnMut(&Ptr) -> bool, parallelism: impl Into<Parallelism<'a>>) -> Result<Vec<(Ptr, Ptr)>, usize>{
+        let (kept, sequence) = self.ordered_sequence(first, keep);
+        return self.evacuate(target, kept, sequence, parallelism.into(), true);
+    }
+
+    /// Moves values to the end of the `target` heap as with [Heap::evacuate_into_ordered], but
+    /// leaves every value that isn't kept in this heap rather than dropping it, so that they can
+    /// be dropped later, e.g. by dropping this heap on another thread. The space of moved values
+    /// isn't reused until this heap is [reset](Heap::reset).
+    ///
+    /// Panics if the values to keep don't fit in the target heap, before moving any.
+    pub fn evacuate_kept_into_ordered<'a>(&mut self, target: &mut Heap<T, Ptr>, first: &[Ptr], keep: impl FnMut(&Ptr) -> bool, parallelism: impl Into<Parallelism<'a>>) -> Vec<(Ptr, Ptr)>{
+        return self.try_evacuate_kept_into_ordered(target, first, keep, parallelism).unwrap_or_else(|_| no_space());
+    }
+
+    /// Moves values to the end of the `target` heap as with [Heap::evacuate_kept_into_ordered],
+    /// or returns the capacity a target heap would need for them to fit as with
+    /// [Heap::try_evacuate_into_ordered].
+    pub fn try_evacuate_kept_into_ordered<'a>(&mut self, target: &mut Heap<T, Ptr>, first: &[Ptr], keep: impl FnMut(&Ptr) -> bool, parallelism: impl Into<Parallelism<'a>>) -> Result<Vec<(Ptr, Ptr)>, usize>{
+        let (kept, sequence) = self.ordered_sequence(first, keep);
+        return self.evacuate(target, kept, sequence, parallelism.into(), false);
+    }
+
+    // returns whether each value is kept, and the indexes of kept values in the order they should
+    // be moved in, starting with those in `first`
+    fn ordered_sequence(&mut self, first: &[Ptr], mut keep: impl FnMut(&Ptr) -> bool) -> (Vec<bool>, Vec<usize>){
+        let kept: Vec<bool> = self.indexes_mut().iter().map(&mut keep).collect();
+        let mut placed = vec![false; kept.len()];
+        let mut sequence = Vec::with_capacity(kept.len());
+        for ptr in first{
+            if let Some(idx) = self.position(ptr, |p| p.eq_ignoring_meta(ptr)){
+                if kept[idx] && !placed[idx]{
+                    placed[idx] = true;
+                    sequence.push(idx);
+                }
+            }
         }
+        sequence.extend((0..kept.len()).rev().filter(|idx| kept[*idx] && !placed[*idx]));
+        return (kept, sequence);
+    }
+
+    // moves the values at the indexes in `sequence` to the target heap, in that order, and either
+    // drops every value not `kept`, or leaves them in this heap; or returns the capacity the
+    // target would need if they don't fit
+    fn evacuate(&mut self, target: &mut Heap<T, Ptr>, kept: Vec<bool>, sequence: Vec<usize>, parallelism: Parallelism<'_>, drop_rest: bool) -> Result<Vec<(Ptr, Ptr)>, usize>{
+        // plan where every kept value will go, so that space can be checked up-front
+        let indexes = mem::take(self.indexes_mut());
+        let mut end = *target.used.get_mut();
+        let mut classes = target.classes.clone();
+        let mut starts = Vec::with_capacity(sequence.len());
+        // the capacity needed past the target's, once a value doesn't fit
+        let mut needed: Option<usize> = None;
+        for idx in &sequence{
+            let value = unsafe{ &*indexes[*idx].to_raw_ptr() };
+            let (size, align) = (footprint(mem::size_of_val(value)), align_of(value));
+            let start = match &mut classes{
+                // values that don't fit in a block of their own in the extra space
+                Some(_) if needed.is_some() => None,
+                Some(classes) => {
+                    let offset = target.place(0, align);
+                    classes.alloc(offset + size, align).map(|slot| slot + offset)
+                }
+                None => Some(target.place(end, align))
+            };
+            match start{
+                Some(start) if needed.is_none() && start <= target.cap && target.cap - start >= size => {
+                    end = end.max(start + size);
+                    starts.push(start);
+                }
+                // keep planning as if the target were large enough, to find how large it must be
+                Some(start) => {
+                    end = end.max(start + size);
+                    needed = Some(end);
+                }
+                None => {
+                    let blocks = (target.place(0, align) + size).saturating_add(classes::BLOCK_SIZE - 1) / classes::BLOCK_SIZE;
+                    needed = Some(needed.unwrap_or(target.cap) + blocks * classes::BLOCK_SIZE);
+                }
+            }
+        }
+        if let Some(needed) = needed{
+            *self.indexes_mut() = indexes;
+            return Err(needed.max(target.cap + 1));
+        }
+        if let Some(classes) = classes{
+            end = classes.end();
+            target.classes = Some(classes);
+        }
+        // (source address, target address, size) of each value to copy
+        let mut copies: Vec<(usize, usize, usize)> = Vec::new();
+        let mut moved = Vec::with_capacity(sequence.len());
+        self.order.clear();
+        for (idx, start) in sequence.into_iter().zip(starts){
+            let ptr = indexes[idx].clone();
+            let raw = ptr.to_raw_ptr();
+            unsafe{
+                let size = layout_of_raw(raw).size();
+                if target.headers{
+                    let header = match self.headers{
+                        true => (*header_of(&ptr)).survived(),
+                        false => ObjectHeader::new(size)
+                    };
+                    target.restore_header(start, header);
+                }
+                sanitize::make_accessible(target.head.as_ptr().add(start), size);
+                let dest: *mut T = with_metadata_of(target.head.as_ptr().add(start), raw);
+                let mut new_ptr = Ptr::from_raw_ptr(dest);
+                new_ptr.copy_meta(&ptr);
+                copies.push((raw as *const u8 as usize, dest as *mut u8 as usize, size));
+                target.record_index(new_ptr.clone());
+                moved.push((ptr, new_ptr));
+            }
+        }
+        if !drop_rest{
+            unsafe{
+                copy_all(&copies, parallelism);
+            }
+            *target.used.get_mut() = end;
+            for (ptr, _) in indexes.into_iter().zip(kept).filter(|(_, keep)| !keep){
+                self.record_index(ptr);
+            }
+            return Ok(moved);
+        }
+        if !self.skip_drop{
+            for (ptr, keep) in indexes.into_iter().zip(kept).rev(){
+                if !keep{
+                    unsafe{
+                        (ptr.to_raw_ptr() as *mut T).drop_in_place();
+                    }
+                }
+            }
+        }
+        unsafe{
+            copy_all(&copies, parallelism);
+            self.poison(0, self.used.load(Ordering::Relaxed));
+        }
+        *target.used.get_mut() = end;
+        *self.used.get_mut() = 0;
+        self.free.clear();
+        if let Some(classes) = &mut self.classes{
+            classes.clear();
+        }
+        return Ok(moved);
+    }
+
+    /// Drops every value for which `keep` returns false, without moving the remaining values.
+    ///
+    /// Space used by dropped values is reused by later pushes, unlike with [Heap::retain]; values
+    /// pushed with [Heap::push_concurrent] are only placed at the end of this heap. Heaps using
+    /// size classes free the slots of dropped values, and then any blocks left empty. If this heap
+    /// has headers, remaining values' headers are updated as by [Heap::evacuate_into].
+    /// Remaining values must not point to dropped values.
+    pub fn sweep(&mut self, mut keep: impl FnMut(&Ptr) -> bool){
+        let head = self.head.as_ptr() as usize;
+        let mut freed = mem::take(&mut self.free);
+        let indexes = mem::take(self.indexes_mut());
+        let mut kept = Vec::with_capacity(indexes.len());
+        let mut end = 0;
+        for ptr in indexes{
+            let raw = ptr.to_raw_ptr() as *mut T;
+            unsafe{
+                let start = raw as *const u8 as usize - head;
+                let size = layout_of_raw(raw).size();
+                if keep(&ptr){
+                    if self.headers{
+                        *header_of(&ptr) = (*header_of(&ptr)).survived();
+                    }
+                    end = end.max(start + footprint(size));
+                    kept.push(ptr);
+                }else{
+                    if !self.skip_drop{
+                        raw.drop_in_place();
+                    }
+                    self.poison_value(&ptr, size);
+                    match &mut self.classes{
+                        Some(classes) => classes.release(start),
+                        None => freed.push((if self.headers { start - HEADER_SIZE } else { start }, start + footprint(size)))
+                    }
+                }
+            }
+        }
+        // merge adjacent free space, and give back space after the last remaining value
+        freed.sort_unstable();
+        for (start, free_end) in freed{
+            if start >= end || start == free_end{
+                continue;
+            }
+            match self.free.last_mut(){
+                Some((_, last_end)) if *last_end >= start => *last_end = (*last_end).max(free_end),
+                _ => self.free.push((start, free_end))
+            }
+        }
+        if let Some(classes) = &mut self.classes{
+            classes.reclaim_empty();
+            end = classes.end();
+        }
+        *self.indexes_mut() = kept;
+        self.rebuild_order();
+        *self.used.get_mut() = end;
+    }
+
+    /// Returns the number of bytes freed by [Heap::sweep] that can be reused by later pushes.
+    ///
+    /// For heaps using size classes, this is the size of every free slot and free block before
+    /// the last block in use.
+    pub fn free_space(&self) -> usize{
+        let classes = self.classes.as_ref().map_or(0, SizeClasses::free_bytes);
+        return classes + self.free.iter().map(|(start, end)| end - start).sum::<usize>();
+    }
+
+    /// Touches every page of the next `n` bytes of unused space at the end of this heap, up to its
+    /// capacity, so that the operating system backs them with memory now rather than when values
+    /// are first pushed there, e.g. before a known burst of allocations.
+    pub fn reserve_bytes(&mut self, n: usize){
+        let used = *self.used.get_mut();
+        let end = used.saturating_add(n).min(self.cap);
+        let head = self.head.as_ptr();
+        let mut at = used;
+        while at < end{
+            unsafe{
+                let byte = head.add(at);
+                sanitize::make_accessible(byte, 1);
+                // unused space may hold anything, or `POISON` in debug builds
+                ptr::write_volatile(byte, if cfg!(debug_assertions) { POISON } else { 0 });
+                sanitize::make_noaccess(byte, 1);
+            }
+            // the start of the next page
+            at = (head as usize + at) / PAGE_SIZE * PAGE_SIZE + PAGE_SIZE - head as usize;
+        }
+    }
+
+    /// Reserves space in this heap's indexes for at least `n` more values, so that pushing them
+    /// doesn't grow the indexes.
+    pub fn reserve_objects(&mut self, n: usize){
+        self.indexes_mut().reserve(n);
+        self.order.reserve(n);
+    }
+
+    /// Drops every value for which the given predicate returns false.
+    ///
+    /// Space used by dropped values at the end of this heap can be reused immediately; other
+    /// space is only reclaimed when the remaining values are moved, e.g. by a collector, unless
+    /// this heap uses size classes, in which case it's freed as by [Heap::sweep].
+    /// Remaining values must not point to dropped values.
+    pub fn retain(&mut self, mut f: impl FnMut(&Ptr, &T) -> bool){
+        let indexes = mem::take(self.indexes_mut());
+        let mut kept = Vec::with_capacity(indexes.len());
+        // values pushed concurrently may be out of order, so find the furthest end of any remaining value
+        let mut end = 0;
+        for ptr in indexes{
+            let raw = ptr.to_raw_ptr() as *mut T;
+            unsafe{
+                if f(&ptr, &*raw){
+                    end = end.max((raw as *const u8).offset_from(self.head.as_ptr()) as usize + footprint(layout_of_raw(raw).size()));
+                    kept.push(ptr);
+                }else{
+                    let size = layout_of_raw(raw).size();
+                    if !self.skip_drop{
+                        raw.drop_in_place();
+                    }
+                    self.poison_value(&ptr, size);
+                    if let Some(classes) = &mut self.classes{
+                        classes.release((raw as *const u8).offset_from(self.head.as_ptr()) as usize);
+                    }
+                }
+            }
+        }
+        if let Some(classes) = &mut self.classes{
+            classes.reclaim_empty();
+            end = classes.end();
+        }
+        *self.indexes_mut() = kept;
+        self.rebuild_order();
+        *self.used.get_mut() = end;
+        self.free.retain(|(start, _)| *start < end);
     }
 
     /// Empties this heap, dropping all values and allowing new ones to be pushed in their place.
     pub fn reset(&mut self){
-        for i in 0..self.len(){
-            let ptr = &self.indexes[i];
+        self.record_pending();
+        self.order.clear();
+        let skip_drop = self.skip_drop;
+        for ptr in self.indexes_mut().drain(..){
             let raw = ptr.to_raw_ptr() as *mut T;
-            unsafe{
-                raw.drop_in_place();
+            if !skip_drop{
+                unsafe{
+                    raw.drop_in_place();
+                }
             }
         }
-        self.used = 0;
+        unsafe{
+            self.poison(0, self.used.load(Ordering::Relaxed));
+        }
+        *self.used.get_mut() = 0;
+        self.free.clear();
+        if let Some(classes) = &mut self.classes{
+            classes.clear();
+        }
     }
 
     /// Returns the capacity of this heap, in bytes.
     pub fn capacity(&self) -> usize{
         return self.cap;
     }
+
+    /// Returns the number of bytes used by values in this heap, including any padding between
+    /// them and space not yet reclaimed from dropped values, or freed by [Heap::sweep].
+    pub fn used(&self) -> usize{
+        return self.used.load(Ordering::Relaxed);
+    }
+
+    /// Splits this heap into ranges of `bucket_size` bytes by address, returning how the bytes in
+    /// each range are used, e.g. to show fragmentation.
+    ///
+    /// Bytes are live if they hold a value or its header, free if they were freed by
+    /// [Heap::sweep] and can be reused, and unused if they're past the end of the used space.
+    /// Everything else within the used space is dead, such as padding and space not yet reclaimed
+    /// from dropped values.
+    pub fn occupancy(&self, bucket_size: usize) -> Vec<Occupancy>{
+        assert!(bucket_size > 0, "Heap: bucket size must not be 0");
+        let used = self.used.load(Ordering::Relaxed);
+        let mut buckets: Vec<Occupancy> = (0..self.cap.saturating_add(bucket_size - 1) / bucket_size)
+            .map(|idx| Occupancy{ start: idx * bucket_size, ..Occupancy::default() })
+            .collect();
+        let head = self.head.as_ptr() as usize;
+        for ptr in self.ptrs().iter(){
+            let start = address_of(ptr) - head;
+            let size = unsafe{ layout_of_raw(ptr.to_raw_ptr()) }.size();
+            let from = if self.headers { start - HEADER_SIZE } else { start };
+            Occupancy::add(&mut buckets, bucket_size, from, start + size, |o| &mut o.live);
+        }
+        let classes = self.classes.as_ref().map_or_else(Vec::new, SizeClasses::free_ranges);
+        for &(start, end) in self.free.iter().chain(&classes){
+            Occupancy::add(&mut buckets, bucket_size, start, end, |o| &mut o.free);
+        }
+        Occupancy::add(&mut buckets, bucket_size, used, self.cap, |o| &mut o.unused);
+        for bucket in &mut buckets{
+            let len = bucket_size.min(self.cap - bucket.start);
+            bucket.dead = len.saturating_sub(bucket.live + bucket.free + bucket.unused);
+        }
+        return buckets;
+    }
+
+    /// Copies every value in this heap, along with their headers and where they're stored, so that
+    /// the heap can be restored to its current state with [Heap::rollback].
+    pub fn checkpoint(&self) -> HeapCheckpoint<T, Ptr> where T: Clone{
+        return self.checkpoint_with(|v| Box::new(v.clone()));
+    }
+
+    /// Makes a checkpoint of this heap, as with [Heap::checkpoint], using the given function to
+    /// copy each value.
+    pub fn checkpoint_with(&self, mut copy: impl FnMut(&T) -> Box<T>) -> HeapCheckpoint<T, Ptr>{
+        let values = self.ptrs().iter().map(|ptr| unsafe{
+            let header = self.headers.then(|| *header_of(ptr));
+            (ptr.clone(), header, copy(&*ptr.to_raw_ptr()))
+        }).collect();
+        return HeapCheckpoint{
+            head: self.head.as_ptr() as usize,
+            cap: self.cap,
+            values,
+            used: self.used.load(Ordering::Relaxed),
+            free: self.free.clone(),
+            classes: self.classes.clone()
+        };
+    }
+
+    /// Drops every value in this heap, and restores the values, headers, and free space saved by
+    /// the given checkpoint, so that pointers to values in the heap when it was made are valid
+    /// again and point to the same values.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the checkpoint was made by a different heap.
+    pub fn rollback(&mut self, checkpoint: HeapCheckpoint<T, Ptr>){
+        assert!(checkpoint.head == self.head.as_ptr() as usize && checkpoint.cap == self.cap,
+                "Heap::rollback: checkpoint was made by a different heap");
+        self.reset();
+        for (ptr, header, value) in checkpoint.values{
+            let start = address_of(&ptr) - checkpoint.head;
+            unsafe{
+                if let Some(header) = header{
+                    self.restore_header(start, header);
+                }
+                self.move_into(value, start);
+            }
+            self.indexes_mut().push(ptr);
+        }
+        *self.used.get_mut() = checkpoint.used;
+        self.free = checkpoint.free;
+        self.classes = checkpoint.classes;
+        self.rebuild_order();
+    }
+
+    /// Creates a new heap with the same capacity and headers as this one, containing copies of
+    /// every value made by the given function, at the same offsets. Pointers in the copied values
+    /// still point into this heap; see [Heap::ptr_in_fork].
+    ///
+    /// Values are copied eagerly; heaps of [Copy] values can instead share memory with their forks
+    /// until either is written to, see [Heap::fork].
+    pub fn fork_with(&self, mut copy: impl FnMut(&T) -> Box<T>) -> Heap<T, Ptr>{
+        let mut fork = Heap::create(self.cap, self.headers);
+        fork.skip_drop = self.skip_drop;
+        let head = self.head.as_ptr() as usize;
+        for ptr in self.ptrs().iter(){
+            let start = address_of(ptr) - head;
+            let mut raw: Ptr = unsafe{
+                if self.headers{
+                    fork.restore_header(start, *header_of(ptr));
+                }
+                fork.move_into(copy(&*ptr.to_raw_ptr()), start)
+            };
+            raw.copy_meta(ptr);
+            fork.indexes_mut().push(raw);
+        }
+        *fork.used.get_mut() = self.used.load(Ordering::Relaxed);
+        fork.free = self.free.clone();
+        fork.classes = self.classes.clone();
+        fork.rebuild_order();
+        return fork;
+    }
+
+    /// Returns a pointer to the same offset in the given fork of this heap as the given pointer,
+    /// keeping its metadata, or `None` if it doesn't point into the used space of this heap.
+    pub fn ptr_in_fork(&self, ptr: &Ptr, fork: &Heap<T, Ptr>) -> Option<Ptr>{
+        return self.rebase(ptr, fork.start());
+    }
+
+    // moves a pointer into the used space of this heap to the same offset from `head`
+    pub(crate) fn rebase(&self, ptr: &Ptr, head: *const u8) -> Option<Ptr>{
+        let (addr, start) = (address_of(ptr), self.head.as_ptr() as usize);
+        if addr < start || addr - start > self.used.load(Ordering::Relaxed){
+            return None;
+        }
+        let raw = with_metadata_of(head.wrapping_add(addr - start) as *mut u8, ptr.to_raw_ptr());
+        let mut rebased = Ptr::from_raw_ptr(raw);
+        rebased.copy_meta(ptr);
+        return Some(rebased);
+    }
+
+    /// Checks that every value is within the used space of this heap, properly aligned, and
+    /// doesn't overlap any other value (or its header), and that headers record values' sizes,
+    /// returning the first broken invariant found.
+    ///
+    /// Takes time proportional to `n log n` for `n` values; intended for debugging.
+    pub fn verify(&self) -> Result<(), VerifyError>{
+        let head = self.head.as_ptr() as usize;
+        let used = self.used.load(Ordering::Relaxed);
+        // (start, end, index) of each value
+        let mut ranges: Vec<(usize, usize, usize)> = Vec::with_capacity(self.len());
+        for (index, ptr) in self.ptrs().iter().enumerate(){
+            let raw = ptr.to_raw_ptr();
+            let layout = unsafe{ layout_of_raw(raw) };
+            let (size, align) = (layout.size(), layout.align());
+            let start = raw.cast::<u8>() as usize;
+            if start < head || start - head > used || used - (start - head) < footprint(size){
+                return Err(VerifyError::OutOfBounds{ index });
+            }
+            if start & (align - 1) != 0{
+                return Err(VerifyError::Misaligned{ index });
+            }
+            if self.headers{
+                if start - head < HEADER_SIZE{
+                    return Err(VerifyError::OutOfBounds{ index });
+                }
+                if unsafe{ (*header_of(ptr)).size } != size{
+                    return Err(VerifyError::HeaderMismatch{ index });
+                }
+                // the header is part of the value's space
+                ranges.push((start - HEADER_SIZE, start + footprint(size), index));
+                continue;
+            }
+            ranges.push((start, start + footprint(size), index));
+        }
+        ranges.sort_unstable();
+        // the furthest end of any value so far, and its index
+        let mut furthest: Option<(usize, usize)> = None;
+        for (start, end, index) in ranges{
+            if let Some((prev_end, prev)) = furthest{
+                if start < prev_end{
+                    return Err(VerifyError::Overlap{ first: prev, second: index });
+                }
+            }
+            match furthest{
+                Some((prev_end, _)) if prev_end >= end => {}
+                _ => furthest = Some((end, index))
+            }
+        }
+        return Ok(());
+    }
+
+    // the pointers to every value in index order, copied only if values were pushed concurrently
+    // since the last mutable method, so that no lock is held while they're used
+    fn ptrs(&self) -> Cow<'_, [Ptr]>{
+        if self.pending_len.load(Ordering::Acquire) == 0{
+            return Cow::Borrowed(&self.indexes);
+        }
+        let pending = self.pending.lock().expect("Heap: pending lock poisoned");
+        return Cow::Owned(self.indexes.iter().chain(pending.iter()).cloned().collect());
+    }
+
+    fn indexes_mut(&mut self) -> &mut Vec<Ptr>{
+        self.record_pending();
+        return &mut self.indexes;
+    }
 }
 
+impl<T, Ptr: HeapPtr<[T]>> Heap<[T], Ptr>{
+    /// Pushes a slice containing clones of the given values onto the end of this heap, returning
+    /// a pointer to it, or the reason it can't be allocated.
+    pub fn push_slice(&mut self, data: &[T]) -> Result<Ptr, AllocErrorKind> where T: Clone{
+        return self.push_slice_with_len(data.len(), |i| data[i].clone());
+    }
+
+    /// Pushes a slice of the given length onto the end of this heap, with each element
+    /// initialized by calling `fill` with its index, returning a pointer to it, or the reason
+    /// it can't be allocated.
+    ///
+    /// The slice is built directly in this heap, without an intermediate box.
+    pub fn push_slice_with_len(&mut self, len: usize, mut fill: impl FnMut(usize) -> T) -> Result<Ptr, AllocErrorKind>{
+        let size = mem::size_of::<T>().checked_mul(len).ok_or(AllocErrorKind::TooLarge)?;
+        let start = match self.reserve(size, mem::align_of::<T>()){
+            Ok(start) => start,
+            Err(kind) => {
+                gc_event!(DEBUG, ?kind, size, capacity = self.cap, "allocation failed");
+                return Err(kind);
+            }
+        };
+        unsafe{
+            self.write_header(start, size);
+            sanitize::make_accessible(self.head.as_ptr().add(start), size);
+            let dest: *mut T = self.head.as_ptr().add(start) as *mut T;
+            for i in 0..len{
+                // if `fill` panics, the elements so far are leaked, along with the space reserved for them
+                dest.add(i).write(fill(i));
+            }
+            let new_ptr = Ptr::from_raw_ptr(ptr::slice_from_raw_parts(dest, len));
+            self.record_index(new_ptr.clone());
+            return Ok(new_ptr);
+        }
+    }
+}
+
+impl<T: Copy + DynSized, Ptr: HeapPtr<T>> Heap<T, Ptr>{
+    /// Creates a copy of this heap, as with [Heap::fork_with], copying the bytes of each value.
+    ///
+    /// With the `persist` feature on 64-bit Linux, heaps created by `Heap::forkable` share their
+    /// memory with the fork until either is written to; other heaps are copied eagerly. Returns an
+    /// error if memory can't be mapped for the fork.
+    pub fn fork(&mut self) -> io::Result<Heap<T, Ptr>>{
+        #[cfg(all(feature = "persist", target_os = "linux", target_pointer_width = "64"))]
+        if let Backing::Cow(_) = self.backing{
+            return self.fork_cow();
+        }
+        return Ok(self.fork_with(|v| Box::new(*v)));
+    }
+}
+
+const HEADER_SIZE: usize = mem::size_of::<ObjectHeader>();
+
+// the size of the pages touched by `Heap::reserve_bytes`; larger pages are just touched more than
+// once
+const PAGE_SIZE: usize = 4096;
+
+// the alignment a value is placed at, as given by `DynSized::dyn_align_of_val`, but at least the
+// alignment it requires
+fn align_of<T: ?Sized + DynSized>(v: &T) -> usize{
+    return v.dyn_align_of_val().max(mem::align_of_val(v));
+}
+
+// the space taken by a value of the given size; zero-sized values take a byte, so that every
+// value has its own address
+fn footprint(size: usize) -> usize{
+    return size.max(1);
+}
+
+/// The byte written over memory freed by a [Heap] in debug builds.
+pub const POISON: u8 = 0xDE;
+
+impl ObjectHeader{
+    fn new(size: usize) -> Self{
+        return ObjectHeader{ size, user: 0, marks: 0, age: 0 };
+    }
+
+    // the header of a value after surviving a collection
+    fn survived(&self) -> Self{
+        return ObjectHeader{ marks: 0, age: self.age.saturating_add(1), ..*self };
+    }
+
+    /// Returns the size of the value, in bytes.
+    pub fn size(&self) -> usize{
+        return self.size;
+    }
+
+    /// Returns the number of times the value has been moved by [Heap::evacuate_into], e.g. the
+    /// number of collections it survived.
+    pub fn age(&self) -> u32{
+        return self.age;
+    }
+
+    /// Returns the value's mark bits, which are cleared when it is moved.
+    pub fn marks(&self) -> u32{
+        return self.marks;
+    }
+
+    /// Replaces the value's mark bits.
+    pub fn set_marks(&mut self, marks: u32){
+        self.marks = marks;
+    }
+
+    /// Returns whether the lowest mark bit is set, as used by collectors to mark reachable values.
+    pub fn is_marked(&self) -> bool{
+        return self.marks & 1 != 0;
+    }
+
+    /// Sets or clears the lowest mark bit.
+    pub fn set_marked(&mut self, marked: bool){
+        self.marks = (self.marks & !1) | marked as u32;
+    }
+
+    /// Returns the word reserved for the embedder, which is 0 for new values.
+    pub fn user(&self) -> usize{
+        return self.user;
+    }
+
+    /// Replaces the word reserved for the embedder.
+    pub fn set_user(&mut self, user: usize){
+        self.user = user;
+    }
+}
+
+impl Occupancy{
+    // adds the bytes of the given range to the given count of every bucket it overlaps
+    fn add(buckets: &mut [Occupancy], bucket_size: usize, start: usize, end: usize, count: impl Fn(&mut Occupancy) -> &mut usize){
+        let mut at = start;
+        while at < end{
+            let bucket_end = (at / bucket_size + 1) * bucket_size;
+            let next = bucket_end.min(end);
+            *count(&mut buckets[at / bucket_size]) += next - at;
+            at = next;
+        }
+    }
+
+    /// Returns the offset of the start of the range.
+    pub fn start(&self) -> usize{
+        return self.start;
+    }
+
+    /// Returns the size of the range, in bytes.
+    pub fn size(&self) -> usize{
+        return self.live + self.dead + self.free + self.unused;
+    }
+
+    /// Returns the number of bytes holding values or their headers.
+    pub fn live(&self) -> usize{
+        return self.live;
+    }
+
+    /// Returns the number of used bytes that hold no value and can't be reused yet.
+    pub fn dead(&self) -> usize{
+        return self.dead;
+    }
+
+    /// Returns the number of bytes freed by a sweep, which can be reused.
+    pub fn free(&self) -> usize{
+        return self.free;
+    }
+
+    /// Returns the number of bytes past the end of the used space.
+    pub fn unused(&self) -> usize{
+        return self.unused;
+    }
+}
+
+// inserts the given index into a list of indexes sorted by the addresses of their values
+fn insert_ordered<T: ?Sized, Ptr: HeapPtr<T>>(order: &mut Vec<usize>, indexes: &[Ptr], idx: usize){
+    let addr = address_of(&indexes[idx]);
+    // values are usually pushed at increasing addresses
+    match order.last(){
+        Some(last) if address_of(&indexes[*last]) > addr => {
+            let at = order.partition_point(|other| address_of(&indexes[*other]) <= addr);
+            order.insert(at, idx);
+        }
+        _ => order.push(idx)
+    }
+}
+
+fn address_of<T: ?Sized, Ptr: HeapPtr<T>>(ptr: &Ptr) -> usize{
+    return ptr.to_raw_ptr().cast::<u8>() as usize;
+}
+
+// finds the header of a value in a heap with headers
+pub(crate) fn header_of<T: ?Sized, Ptr: HeapPtr<T>>(ptr: &Ptr) -> *mut ObjectHeader{
+    return ptr.to_raw_ptr().cast::<u8>().wrapping_sub(HEADER_SIZE) as *mut ObjectHeader;
+}
+
+impl<T: ?Sized> AllocError<T>{
+    /// Creates a new `AllocError` returning the given value for the given reason.
+    pub fn new(value: Box<T>, kind: AllocErrorKind) -> Self{
+        return AllocError{ value, kind };
+    }
+
+    /// Returns the reason the value could not be allocated.
+    pub fn kind(&self) -> AllocErrorKind{
+        return self.kind;
+    }
+
+    /// Returns a reference to the value that could not be allocated.
+    pub fn value(&self) -> &T{
+        return &self.value;
+    }
+
+    /// Returns the value that could not be allocated, e.g. to retry after a collection.
+    pub fn into_value(self) -> Box<T>{
+        return self.value;
+    }
+}
+
+impl<T: ?Sized> Debug for AllocError<T>{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result{
+        return f.debug_struct("AllocError")
+            .field("kind", &self.kind)
+            .field("size", &mem::size_of_val(self.value.as_ref()))
+            .finish();
+    }
+}
+
+impl<T: ?Sized + DynSized> Display for AllocError<T>{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result{
+        let size = mem::size_of_val(self.value.as_ref());
+        return match self.kind{
+            AllocErrorKind::OutOfSpace => write!(f, "not enough free space in heap for value of {size} bytes"),
+            AllocErrorKind::TooLarge => write!(f, "value of {size} bytes is larger than the heap's capacity"),
+            AllocErrorKind::Alignment => write!(f, "value requires alignment of {} bytes, greater than the heap's", align_of(self.value.as_ref())),
+            AllocErrorKind::LimitExceeded => write!(f, "value of {size} bytes would exceed the memory's hard limit")
+        };
+    }
+}
+
+impl<T: ?Sized + DynSized> Error for AllocError<T>{}
+
+impl Display for VerifyError{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result{
+        return match self{
+            VerifyError::OutOfBounds{ index } => write!(f, "value {index} is outside of the heap's used space"),
+            VerifyError::Misaligned{ index } => write!(f, "value {index} is not properly aligned"),
+            VerifyError::Overlap{ first, second } => write!(f, "values {first} and {second} overlap"),
+            VerifyError::DanglingEdge{ index, target } => write!(f, "value {index} points to {target:?}, which is not a value in the same memory"),
+            VerifyError::HeaderMismatch{ index } => write!(f, "value {index} has a header recording the wrong size")
+        };
+    }
+}
+
+impl Error for VerifyError{}
+
+// the panic of evacuations that don't fit in their target heap
+fn no_space() -> !{
+    panic!("Heap::evacuate_into: not enough space in target heap for kept values");
+}
+
+// evacuations copying less than this many bytes are always done on one thread
+const PARALLEL_COPY_THRESHOLD: usize = 1 << 16;
+
+// the most bytes copied by each work item of a parallel evacuation, so that threads that finish
+// early can steal the rest
+const PARALLEL_COPY_CHUNK: usize = 1 << 14;
+
+// copies each (source, target, size) range, splitting the work between threads by size
+unsafe fn copy_all(copies: &[(usize, usize, usize)], parallelism: Parallelism<'_>){
+    let total: usize = copies.iter().map(|c| c.2).sum();
+    let threads = parallelism.workers();
+    if threads <= 1 || total < PARALLEL_COPY_THRESHOLD{
+        for &(src, dest, size) in copies{
+            (dest as *mut u8).copy_from_nonoverlapping(src as *const u8, size);
+        }
+        return;
+    }
+    let mut pool = WorkPool::new(threads);
+    let mut rest = copies;
+    let mut chunks = 0;
+    while !rest.is_empty(){
+        let mut bytes = 0;
+        let split = rest.iter().position(|c| { bytes += c.2; bytes >= PARALLEL_COPY_CHUNK }).map_or(rest.len(), |i| i + 1);
+        let (chunk, next) = rest.split_at(split);
+        pool.push(chunks, chunk);
+        chunks += 1;
+        rest = next;
+    }
+    let copy = |_: &mut (), _: &Worker<'_, _>, chunk: &[(usize, usize, usize)]| {
+        for &(src, dest, size) in chunk{
+            unsafe{
+                (dest as *mut u8).copy_from_nonoverlapping(src as *const u8, size);
+            }
+        }
+    };
+    match parallelism{
+        Parallelism::Threads(_) => pool.run_with(|_| (), copy),
+        Parallelism::Executor(executor) => pool.run_on(executor, |_| (), copy)
+    };
+}
+
+// stand-ins for the unstable `layout_for_ptr` and `set_ptr_value` APIs, so that the crate can be
+// built on stable with the `stable` feature; the pointers given must point to initialized values
+
+#[cfg(not(feature = "stable"))]
+unsafe fn layout_of_raw<T: ?Sized>(raw: *const T) -> alloc::Layout{
+    return alloc::Layout::for_value_raw(raw);
+}
+
+#[cfg(feature = "stable")]
+unsafe fn layout_of_raw<T: ?Sized>(raw: *const T) -> alloc::Layout{
+    return alloc::Layout::for_value(&*raw);
+}
+
+// returns a pointer to the given address with the metadata (e.g. slice length) of `meta`
+#[cfg(not(feature = "stable"))]
+fn with_metadata_of<T: ?Sized>(addr: *mut u8, meta: *const T) -> *mut T{
+    return addr.with_metadata_of(meta as *mut T);
+}
+
+#[cfg(feature = "stable")]
+fn with_metadata_of<T: ?Sized>(addr: *mut u8, meta: *const T) -> *mut T{
+    let mut ptr = meta as *mut T;
+    // the address is stored first in both thin and fat pointers
+    unsafe{
+        *(&mut ptr as *mut *mut T as *mut *mut u8) = addr;
+    }
+    return ptr;
+}
+
+// safety: a heap owns its values, and the pointers it stores only address its own memory
+unsafe impl<T: ?Sized + DynSized + Send, Ptr: HeapPtr<T> + ThreadSafePtr> Send for Heap<T, Ptr>{}
+
+unsafe impl<T: ?Sized + DynSized + Sync, Ptr: HeapPtr<T> + ThreadSafePtr> Sync for Heap<T, Ptr>{}
+
 impl<T: ?Sized + DynSized, Ptr: HeapPtr<T>> Drop for Heap<T, Ptr>{
     fn drop(&mut self){
-        // drop each object
-        self.reset();
-        unsafe{
-            // then deallocate the whole thing
-            alloc::dealloc(self.head.as_ptr(), alloc::Layout::array::<()>(self.cap).unwrap());
+        match self.backing{
+            Backing::Alloc => {
+                // drop each object
+                self.reset();
+                unsafe{
+                    // then deallocate the whole thing, which the allocator may hand out again
+                    sanitize::make_accessible(self.head.as_ptr(), self.cap);
+                    alloc::dealloc(self.head.as_ptr(), Heap::<T, Ptr>::layout(self.cap));
+                }
+            }
+            // values are kept in the file, which is unmapped when the backing is dropped
+            #[cfg(all(feature = "persist", unix, target_pointer_width = "64"))]
+            Backing::File(_) => {
+                let _ = self.sync();
+                unsafe{
+                    sanitize::make_accessible(self.head.as_ptr(), self.cap);
+                }
+            }
+            // values are dropped, and the memory is unmapped when the backing is dropped
+            #[cfg(all(feature = "persist", target_os = "linux", target_pointer_width = "64"))]
+            Backing::Cow(_) => {
+                self.reset();
+                unsafe{
+                    sanitize::make_accessible(self.head.as_ptr(), self.cap);
+                }
+            }
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,158 @@
+//! Placing values in blocks of equally-sized slots, grouped by size class, for heaps created with
+//! [Heap::with_size_classes](crate::heap::Heap::with_size_classes).
+//!
+//! The heap is split into blocks of [BLOCK_SIZE] bytes. Each block holds slots of a single size
+//! class, or is part of a run of blocks holding a single large value. Freed slots are reused by
+//! values of the same class, and blocks left empty by a sweep can be reused by any class.
+
+/// The size of each block of a heap with size classes, in bytes.
+pub const BLOCK_SIZE: usize = 4096;
+
+// the slot size of each class, in bytes; larger values take whole blocks
+const CLASSES: [usize; 12] = [16, 32, 48, 64, 96, 128, 192, 256, 384, 512, 768, 1024];
+// the most that a slot's start is aligned to
+const SLOT_ALIGN: usize = 16;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum Block{
+    Free,
+    // slots of the size class at the given index
+    Class(usize),
+    // the first of the given number of blocks holding a single large value
+    Large(usize),
+    // a later block holding a large value
+    Continued
+}
+
+// the blocks of a heap, and the free slots of each class
+#[derive(Clone, Debug)]
+pub(super) struct SizeClasses{
+    blocks: Vec<Block>,
+    // the number of values in each block
+    live: Vec<usize>,
+    // offsets of free slots in each class, popped from the end
+    free: Vec<Vec<usize>>
+}
+
+impl SizeClasses{
+    pub(super) fn new(capacity: usize) -> Self{
+        let blocks = capacity / BLOCK_SIZE;
+        return SizeClasses{
+            blocks: vec![Block::Free; blocks],
+            live: vec![0; blocks],
+            free: vec![Vec::new(); CLASSES.len()]
+        };
+    }
+
+    // reserves a slot for a value that needs `size` bytes from the start of its slot, with the
+    // given alignment, returning the slot's offset, or `None` if there's no space
+    pub(super) fn alloc(&mut self, size: usize, align: usize) -> Option<usize>{
+        if align <= SLOT_ALIGN{
+            if let Some(class) = CLASSES.iter().position(|slot| *slot >= size){
+                return self.alloc_slot(class);
+            }
+        }
+        // large values, and those with larger alignments, take whole blocks
+        let count = size.max(1).saturating_add(BLOCK_SIZE - 1) / BLOCK_SIZE;
+        let first = self.find_free_run(count)?;
+        self.blocks[first] = Block::Large(count);
+        for block in &mut self.blocks[first + 1..first + count]{
+            *block = Block::Continued;
+        }
+        self.live[first] = 1;
+        return Some(first * BLOCK_SIZE);
+    }
+
+    fn alloc_slot(&mut self, class: usize) -> Option<usize>{
+        if self.free[class].is_empty(){
+            // split a free block into slots, to be handed out from the lowest address
+            let block = self.find_free_run(1)?;
+            self.blocks[block] = Block::Class(class);
+            let start = block * BLOCK_SIZE;
+            let slots = BLOCK_SIZE / CLASSES[class];
+            self.free[class].extend((0..slots).rev().map(|slot| start + slot * CLASSES[class]));
+        }
+        let slot = self.free[class].pop()?;
+        self.live[slot / BLOCK_SIZE] += 1;
+        return Some(slot);
+    }
+
+    fn find_free_run(&self, count: usize) -> Option<usize>{
+        let mut run = 0;
+        for (idx, block) in self.blocks.iter().enumerate(){
+            run = if *block == Block::Free { run + 1 } else { 0 };
+            if run == count{
+                return Some(idx + 1 - count);
+            }
+        }
+        return None;
+    }
+
+    // frees the slot of the value starting at the given offset
+    pub(super) fn release(&mut self, offset: usize){
+        let block = offset / BLOCK_SIZE;
+        match self.blocks[block]{
+            Block::Class(class) => {
+                let start = block * BLOCK_SIZE;
+                self.free[class].push(start + (offset - start) / CLASSES[class] * CLASSES[class]);
+                self.live[block] -= 1;
+            }
+            Block::Large(count) => {
+                self.blocks[block..block + count].fill(Block::Free);
+                self.live[block] = 0;
+            }
+            Block::Free | Block::Continued => panic!("Heap: released value is not in a slot")
+        }
+    }
+
+    // frees every block with no values left, so that it can be used by any class
+    pub(super) fn reclaim_empty(&mut self){
+        let mut reclaimed = false;
+        for (block, live) in self.blocks.iter_mut().zip(&self.live){
+            if matches!(block, Block::Class(_)) && *live == 0{
+                *block = Block::Free;
+                reclaimed = true;
+            }
+        }
+        if reclaimed{
+            let blocks = &self.blocks;
+            for free in &mut self.free{
+                free.retain(|slot| blocks[slot / BLOCK_SIZE] != Block::Free);
+            }
+        }
+    }
+
+    // frees every block
+    pub(super) fn clear(&mut self){
+        self.blocks.fill(Block::Free);
+        self.live.fill(0);
+        for free in &mut self.free{
+            free.clear();
+        }
+    }
+
+    // the offset of the end of the last block in use
+    pub(super) fn end(&self) -> usize{
+        return self.blocks.iter().rposition(|block| *block != Block::Free).map_or(0, |last| (last + 1) * BLOCK_SIZE);
+    }
+
+    // the number of bytes in free slots and free blocks before the end of the last block in use
+    pub(super) fn free_bytes(&self) -> usize{
+        let slots: usize = self.free.iter().zip(CLASSES).map(|(free, size)| free.len() * size).sum();
+        let blocks = self.blocks[..self.end() / BLOCK_SIZE].iter().filter(|block| **block == Block::Free).count();
+        return slots + blocks * BLOCK_SIZE;
+    }
+
+    // the (start, end) offsets of every free slot and free block before the end of the last block in use
+    pub(super) fn free_ranges(&self) -> Vec<(usize, usize)>{
+        let mut ranges: Vec<(usize, usize)> = self.free.iter().zip(CLASSES)
+            .flat_map(|(free, size)| free.iter().map(move |slot| (*slot, slot + size)))
+            .collect();
+        for (idx, block) in self.blocks[..self.end() / BLOCK_SIZE].iter().enumerate(){
+            if *block == Block::Free{
+                ranges.push((idx * BLOCK_SIZE, (idx + 1) * BLOCK_SIZE));
+            }
+        }
+        return ranges;
+    }
+}
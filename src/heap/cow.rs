@@ -0,0 +1,171 @@
+//! Heaps that share their memory with their forks until either is written to.
+//!
+//! A heap created with [Heap::forkable] keeps its memory in an anonymous file, mapped so that
+//! writes reach the file. [Heap::fork] maps that file privately for the fork, and maps it privately
+//! again over the heap's own memory, so that pages are only copied once either writes to them.
+//! Later forks of the same heap first copy its used space into a new file, which takes time
+//! proportional to the space used, but still avoids copying the pages neither heap writes to.
+//!
+//! Only available with the `persist` feature, on 64-bit Linux.
+
+use std::ffi::{c_char, c_int, c_uint, c_void};
+use std::fs::File;
+use std::io;
+use std::io::ErrorKind;
+use std::marker::PhantomData;
+use std::os::unix::fs::FileExt;
+use std::os::unix::io::{AsRawFd, FromRawFd};
+use std::ptr::NonNull;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::{ptr, slice};
+use crate::heap::{sanitize, Backing, DynSized, Heap, HeapPtr, PAGE_SIZE};
+use crate::heap::mapped::{mmap, munmap, MAP_SHARED, PROT_READ, PROT_WRITE};
+
+extern "C"{
+    fn memfd_create(name: *const c_char, flags: c_uint) -> c_int;
+}
+
+const MAP_PRIVATE: c_int = 2;
+const MAP_FIXED: c_int = 0x10;
+const MFD_CLOEXEC: c_uint = 1;
+
+// memory mapped from an anonymous file
+pub(super) struct CowMap{
+    base: NonNull<u8>,
+    len: usize,
+    // the file, while the memory is mapped so that writes reach it
+    file: Option<File>
+}
+
+impl<T: ?Sized + DynSized, Ptr: HeapPtr<T>> Heap<T, Ptr>{
+    /// Creates a new heap with the given capacity in bytes, and with or without headers, whose
+    /// memory can be shared with its forks; see [Heap::fork].
+    ///
+    /// Returns an error if the memory can't be mapped, or if values must be aligned to more than a
+    /// page.
+    pub fn forkable(capacity: usize, headers: bool) -> io::Result<Self>{
+        if Self::align() > PAGE_SIZE{
+            return Err(io::Error::new(ErrorKind::InvalidInput, "values are aligned to more than a page"));
+        }
+        let len = capacity.max(1);
+        let file = anonymous_file(len)?;
+        let base = map(ptr::null_mut(), len, MAP_SHARED, &file)?;
+        unsafe{
+            sanitize::make_noaccess(base.as_ptr(), capacity);
+        }
+        return Ok(Heap{
+            head: base,
+            cap: capacity,
+            used: AtomicUsize::new(0),
+            indexes: vec![],
+            order: vec![],
+            pending: Mutex::new(vec![]),
+            pending_len: AtomicUsize::new(0),
+            headers,
+            free: Vec::new(),
+            classes: None,
+            skip_drop: false,
+            backing: Backing::Cow(CowMap{ base, len, file: Some(file) }),
+            _phantom: PhantomData
+        });
+    }
+}
+
+impl<T: Copy + DynSized, Ptr: HeapPtr<T>> Heap<T, Ptr>{
+    // forks a heap created by `Heap::forkable`, mapping the same file privately for both heaps
+    pub(super) fn fork_cow(&mut self) -> io::Result<Heap<T, Ptr>>{
+        self.record_pending();
+        let cow = match &self.backing{
+            Backing::Cow(cow) => cow,
+            _ => unreachable!()
+        };
+        let len = cow.len;
+        // once mapped privately, writes no longer reach the file, so a new one must be written
+        let copy = match cow.file{
+            Some(_) => None,
+            None => {
+                let file = anonymous_file(len)?;
+                self.write_used(&file)?;
+                Some(file)
+            }
+        };
+        let file = match (&copy, &cow.file){
+            (Some(file), _) | (None, Some(file)) => file,
+            (None, None) => unreachable!()
+        };
+        let base = map(ptr::null_mut(), len, MAP_PRIVATE, file)?;
+        let fork_map = CowMap{ base, len, file: None };
+        map(self.head.as_ptr(), len, MAP_PRIVATE | MAP_FIXED, file)?;
+        // only now that both heaps map it privately can the file be closed
+        if let Backing::Cow(cow) = &mut self.backing{
+            cow.file = None;
+        }
+        return Ok(Heap{
+            head: base,
+            cap: self.cap,
+            used: AtomicUsize::new(self.used.load(Ordering::Relaxed)),
+            indexes: self.indexes.iter().map(|ptr| self.rebase(ptr, base.as_ptr()).expect("value outside of the used space")).collect(),
+            order: self.order.clone(),
+            pending: Mutex::new(vec![]),
+            pending_len: AtomicUsize::new(0),
+            headers: self.headers,
+            free: self.free.clone(),
+            classes: self.classes.clone(),
+            skip_drop: self.skip_drop,
+            backing: Backing::Cow(fork_map),
+            _phantom: PhantomData
+        });
+    }
+
+    // writes the used space of this heap to the start of the given file
+    fn write_used(&self, file: &File) -> io::Result<()>{
+        let used = self.used.load(Ordering::Relaxed);
+        unsafe{
+            // free space and padding may be marked inaccessible, so mark it all accessible while
+            // copying, then mark everything but values and their headers inaccessible again
+            sanitize::make_accessible(self.head.as_ptr(), used);
+            let written = file.write_all_at(slice::from_raw_parts(self.head.as_ptr(), used), 0);
+            #[cfg(feature = "sanitize")]
+            {
+                sanitize::make_noaccess(self.head.as_ptr(), used);
+                let header = if self.headers{ super::HEADER_SIZE } else { 0 };
+                for ptr in self.ptrs().iter(){
+                    let at = super::address_of(ptr) as *const u8;
+                    sanitize::make_accessible(at.sub(header), header + super::footprint(std::mem::size_of::<T>()));
+                }
+            }
+            return written;
+        }
+    }
+}
+
+impl Drop for CowMap{
+    fn drop(&mut self){
+        unsafe{
+            munmap(self.base.as_ptr() as *mut c_void, self.len);
+        }
+    }
+}
+
+// creates an anonymous file of the given length
+fn anonymous_file(len: usize) -> io::Result<File>{
+    // `c""` literals need a newer compiler than this crate supports
+    #[allow(clippy::manual_c_str_literals)]
+    let fd = unsafe{ memfd_create(b"swifer-heap\0".as_ptr() as *const c_char, MFD_CLOEXEC) };
+    if fd == -1{
+        return Err(io::Error::last_os_error());
+    }
+    let file = unsafe{ File::from_raw_fd(fd) };
+    file.set_len(len as u64)?;
+    return Ok(file);
+}
+
+// maps the given file readably and writably, at the given address if `flags` has `MAP_FIXED`
+fn map(addr: *mut u8, len: usize, flags: c_int, file: &File) -> io::Result<NonNull<u8>>{
+    let base = unsafe{ mmap(addr as *mut c_void, len, PROT_READ | PROT_WRITE, flags, file.as_raw_fd(), 0) };
+    if base as isize == -1{
+        return Err(io::Error::last_os_error());
+    }
+    return NonNull::new(base as *mut u8).ok_or_else(|| io::Error::from(ErrorKind::AddrNotAvailable));
+}
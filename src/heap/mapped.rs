@@ -0,0 +1,253 @@
+//! Heaps stored in memory-mapped files, which keep their values after the process exits.
+//!
+//! A file opened with [Heap::open_file] starts with a page of metadata, followed by the memory of
+//! the heap, and then the offset of every value in it and the free space within it, as written by
+//! [Heap::sync]. When the file is opened again, values are found from those offsets and checked
+//! with [Heap::verify] before the heap can be used.
+//!
+//! Values are stored as their raw bytes, so they must not own memory outside of the heap or need
+//! dropping; only [Copy] values are supported. Pointers to values are stored without metadata.
+//!
+//! Only available with the `persist` feature, on 64-bit Unix platforms.
+
+use std::ffi::{c_int, c_void};
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::io::ErrorKind;
+use std::marker::PhantomData;
+use std::os::unix::fs::FileExt;
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+use std::ptr::NonNull;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use crate::heap::{address_of, Backing, DynSized, Heap, HeapPtr};
+
+const MAGIC: &[u8; 8] = b"swiferhp";
+const VERSION: u64 = 1;
+// the space reserved for metadata before the heap's memory, which keeps it page-aligned
+const META_SIZE: usize = 4096;
+// magic, version, headers, capacity, used, start address, value count, free range count
+const META_WORDS: usize = 8;
+
+extern "C"{
+    pub(super) fn mmap(addr: *mut c_void, len: usize, prot: c_int, flags: c_int, fd: c_int, offset: i64) -> *mut c_void;
+    pub(super) fn munmap(addr: *mut c_void, len: usize) -> c_int;
+}
+
+pub(super) const PROT_READ: c_int = 1;
+pub(super) const PROT_WRITE: c_int = 2;
+pub(super) const MAP_SHARED: c_int = 1;
+
+// a file mapped into memory, which is unmapped when dropped
+pub(super) struct MappedFile{
+    file: File,
+    base: NonNull<u8>,
+    len: usize,
+    // where the heap's memory was when the file was last synced, if it was mapped somewhere else
+    moved_from: Option<usize>,
+    // whether the heap was recovered, and can be written back to the file
+    valid: bool
+}
+
+// the metadata at the start of a file
+#[derive(Default)]
+struct Meta{
+    headers: bool,
+    cap: usize,
+    used: usize,
+    start: usize,
+    count: usize,
+    free: usize
+}
+
+impl<T: ?Sized + DynSized, Ptr: HeapPtr<T>> Heap<T, Ptr>{
+    /// Writes the offset of every value in this heap, and the space freed within it, to the file it
+    /// was opened from, and waits for them and the values to reach the disk. Does nothing for heaps
+    /// not opened with [Heap::open_file].
+    ///
+    /// Heaps are also synced when dropped, but errors are then ignored.
+    pub fn sync(&self) -> io::Result<()>{
+        let mapped = match &self.backing{
+            Backing::File(mapped) if mapped.valid => mapped,
+            _ => return Ok(())
+        };
+        let head = self.head.as_ptr() as usize;
+        let indexes = self.ptrs();
+        let mut table = Vec::with_capacity((indexes.len() + 2 * self.free.len()) * 8);
+        for ptr in indexes.iter(){
+            table.extend_from_slice(&((address_of(ptr) - head) as u64).to_ne_bytes());
+        }
+        for (start, end) in &self.free{
+            table.extend_from_slice(&(*start as u64).to_ne_bytes());
+            table.extend_from_slice(&(*end as u64).to_ne_bytes());
+        }
+        // write the table before the metadata that says how long it is
+        let file = &mapped.file;
+        file.write_all_at(&table, mapped.len as u64)?;
+        file.set_len((mapped.len + table.len()) as u64)?;
+        file.sync_all()?;
+        let meta = Meta{
+            headers: self.headers,
+            cap: self.cap,
+            used: self.used.load(Ordering::Relaxed),
+            start: head,
+            count: indexes.len(),
+            free: self.free.len()
+        };
+        file.write_all_at(&meta.to_bytes(), 0)?;
+        return file.sync_all();
+    }
+
+    /// Returns the address the memory of this heap started at when its file was last synced, if it
+    /// has since been mapped at a different address. Pointers between values must then be updated
+    /// by the same offset.
+    pub fn moved_from(&self) -> Option<*const u8>{
+        return match &self.backing{
+            Backing::File(mapped) => mapped.moved_from.map(|start| start as *const u8),
+            _ => None
+        };
+    }
+}
+
+impl<T: Copy + DynSized, Ptr: HeapPtr<T>> Heap<T, Ptr>{
+    /// Opens a heap stored in the given file, creating the file with the given capacity and headers
+    /// if it doesn't exist or is empty. Values pushed into the heap are written to the file, and
+    /// kept when the heap is dropped and opened again.
+    ///
+    /// The file is mapped at the same address as when it was last synced if possible; otherwise,
+    /// [Heap::moved_from] gives the old address. Values left in an existing file are found using
+    /// the offsets recorded by the last [Heap::sync], and checked with [Heap::verify].
+    ///
+    /// Returns an error with the kind [ErrorKind::InvalidData] if the file is not a heap, or its
+    /// values are not valid.
+    pub fn open_file(path: impl AsRef<Path>, capacity: usize, headers: bool) -> io::Result<Self>{
+        let file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(path)?;
+        let file_len = file.metadata()?.len() as usize;
+        let existing = file_len > 0;
+        let meta = match existing{
+            true => {
+                let mut bytes = [0; META_WORDS * 8];
+                file.read_exact_at(&mut bytes, 0)?;
+                Meta::from_bytes(&bytes)?
+            }
+            false => {
+                file.set_len((META_SIZE + capacity) as u64)?;
+                Meta{ headers, cap: capacity, ..Meta::default() }
+            }
+        };
+        let len = META_SIZE.checked_add(meta.cap).ok_or_else(|| invalid("capacity is too large"))?;
+        let end = meta.free.checked_mul(2)
+            .and_then(|words| words.checked_add(meta.count))
+            .and_then(|words| words.checked_mul(8))
+            .and_then(|table| table.checked_add(len));
+        if meta.used > meta.cap || (existing && !matches!(end, Some(end) if end <= file_len)){
+            return Err(invalid("file is truncated"));
+        }
+        // ask for the same address as before, so that pointers between values stay valid
+        let hint = meta.start.saturating_sub(META_SIZE) as *mut c_void;
+        let base = unsafe{ mmap(hint, len, PROT_READ | PROT_WRITE, MAP_SHARED, file.as_raw_fd(), 0) };
+        if base as isize == -1{
+            return Err(io::Error::last_os_error());
+        }
+        let base = NonNull::new(base as *mut u8).ok_or_else(|| invalid("file was mapped at null"))?;
+        let head = unsafe{ NonNull::new_unchecked(base.as_ptr().add(META_SIZE)) };
+        let mut heap = Heap{
+            head,
+            cap: meta.cap,
+            used: AtomicUsize::new(meta.used),
+            indexes: vec![],
+            order: vec![],
+            pending: Mutex::new(vec![]),
+            pending_len: AtomicUsize::new(0),
+            headers: meta.headers,
+            free: Vec::new(),
+            classes: None,
+            skip_drop: false,
+            backing: Backing::File(MappedFile{
+                file,
+                base,
+                len,
+                moved_from: (existing && meta.start != head.as_ptr() as usize).then_some(meta.start),
+                valid: !existing
+            }),
+            _phantom: PhantomData
+        };
+        match existing{
+            true => heap.recover(&meta)?,
+            false => heap.sync()?
+        }
+        return Ok(heap);
+    }
+
+    // reads the offsets of values and free space saved by `sync`, and checks them
+    fn recover(&mut self, meta: &Meta) -> io::Result<()>{
+        let mapped = match &self.backing{
+            Backing::File(mapped) => mapped,
+            _ => unreachable!()
+        };
+        let mut table = vec![0; (meta.count + 2 * meta.free) * 8];
+        mapped.file.read_exact_at(&mut table, mapped.len as u64)?;
+        let word = |idx: usize| u64::from_ne_bytes(table[idx * 8..idx * 8 + 8].try_into().unwrap()) as usize;
+        let head = self.head.as_ptr() as usize;
+        let indexes: Vec<Ptr> = (0..meta.count).map(|idx| Ptr::from_raw_ptr(head.wrapping_add(word(idx)) as *const T)).collect();
+        let free: Vec<(usize, usize)> = (0..meta.free).map(|idx| (word(meta.count + 2 * idx), word(meta.count + 2 * idx + 1))).collect();
+        let mut prev_end = 0;
+        for (start, end) in &free{
+            if start < &prev_end || start >= end || *end > meta.used{
+                return Err(invalid("free space is out of order"));
+            }
+            prev_end = *end;
+        }
+        *self.indexes_mut() = indexes;
+        self.free = free;
+        self.rebuild_order();
+        self.verify().map_err(|err| invalid(&err.to_string()))?;
+        if let Backing::File(mapped) = &mut self.backing{
+            mapped.valid = true;
+        }
+        return Ok(());
+    }
+}
+
+impl Meta{
+    fn from_bytes(bytes: &[u8; META_WORDS * 8]) -> io::Result<Self>{
+        let word = |idx: usize| u64::from_ne_bytes(bytes[idx * 8..idx * 8 + 8].try_into().unwrap());
+        if &bytes[..8] != MAGIC || word(1) != VERSION{
+            return Err(invalid("not a heap file"));
+        }
+        let size = |idx: usize| usize::try_from(word(idx)).map_err(|_| invalid("size is too large"));
+        return Ok(Meta{
+            headers: word(2) != 0,
+            cap: size(3)?,
+            used: size(4)?,
+            start: size(5)?,
+            count: size(6)?,
+            free: size(7)?
+        });
+    }
+
+    fn to_bytes(&self) -> [u8; META_WORDS * 8]{
+        let mut bytes = [0; META_WORDS * 8];
+        bytes[..8].copy_from_slice(MAGIC);
+        let words = [VERSION, self.headers as u64, self.cap as u64, self.used as u64, self.start as u64, self.count as u64, self.free as u64];
+        for (idx, word) in words.iter().enumerate(){
+            bytes[(idx + 1) * 8..(idx + 2) * 8].copy_from_slice(&word.to_ne_bytes());
+        }
+        return bytes;
+    }
+}
+
+fn invalid(reason: &str) -> io::Error{
+    return io::Error::new(ErrorKind::InvalidData, format!("invalid heap file: {reason}"));
+}
+
+//////////////// impls
+
+impl Drop for MappedFile{
+    fn drop(&mut self){
+        unsafe{
+            munmap(self.base.as_ptr() as *mut c_void, self.len);
+        }
+    }
+}
@@ -0,0 +1,93 @@
+//! Annotations telling memory checkers which parts of a heap hold live values, so that accesses
+//! through stale managed pointers are reported as errors.
+//!
+//! Space in a heap is marked inaccessible when the heap is created, and whenever the value stored
+//! there is dropped or moved; it's marked accessible again just before a value or header is
+//! written to it. With the `sanitize` feature, this is done through AddressSanitizer's manual
+//! poisoning when built with `-Zsanitizer=address`, and through Valgrind client requests on
+//! `x86_64` and `aarch64`, which do nothing when not running under Valgrind. Without the feature,
+//! these functions do nothing.
+//!
+//! AddressSanitizer tracks memory in 8-byte granules, so a few bytes next to a live value may be
+//! considered accessible, and values smaller than a granule may share one.
+
+/// Marks the given range as inaccessible.
+#[inline]
+pub(super) unsafe fn make_noaccess(addr: *const u8, len: usize){
+    #[cfg(feature = "sanitize")]
+    {
+        #[cfg(sanitize = "address")]
+        asan::__asan_poison_memory_region(addr.cast(), len);
+        valgrind::client_request(valgrind::MAKE_MEM_NOACCESS, addr as usize, len);
+    }
+    #[cfg(not(feature = "sanitize"))]
+    let _ = (addr, len);
+}
+
+/// Marks the given range as accessible, but not yet initialized.
+#[inline]
+pub(super) unsafe fn make_accessible(addr: *const u8, len: usize){
+    #[cfg(feature = "sanitize")]
+    {
+        #[cfg(sanitize = "address")]
+        asan::__asan_unpoison_memory_region(addr.cast(), len);
+        valgrind::client_request(valgrind::MAKE_MEM_UNDEFINED, addr as usize, len);
+    }
+    #[cfg(not(feature = "sanitize"))]
+    let _ = (addr, len);
+}
+
+#[cfg(feature = "sanitize")]
+mod asan{
+    #[cfg(sanitize = "address")]
+    use std::ffi::c_void;
+
+    // only linked when built with AddressSanitizer, which provides them
+    #[cfg(sanitize = "address")]
+    extern "C"{
+        pub(super) fn __asan_poison_memory_region(addr: *const c_void, size: usize);
+        pub(super) fn __asan_unpoison_memory_region(addr: *const c_void, size: usize);
+    }
+}
+
+#[cfg(feature = "sanitize")]
+mod valgrind{
+    // memcheck's requests, from valgrind/memcheck.h
+    const MEMCHECK_BASE: usize = ((b'M' as usize) << 24) | ((b'C' as usize) << 16);
+    pub(super) const MAKE_MEM_NOACCESS: usize = MEMCHECK_BASE;
+    pub(super) const MAKE_MEM_UNDEFINED: usize = MEMCHECK_BASE + 1;
+
+    // runs the special instruction sequence Valgrind recognises as a client request, which leaves
+    // the registers unchanged when running natively
+    #[cfg(target_arch = "x86_64")]
+    #[inline(always)]
+    pub(super) unsafe fn client_request(request: usize, arg1: usize, arg2: usize){
+        let args: [usize; 6] = [request, arg1, arg2, 0, 0, 0];
+        std::arch::asm!(
+            "rol rdi, 3", "rol rdi, 13", "rol rdi, 61", "rol rdi, 51",
+            "xchg rbx, rbx",
+            in("rax") args.as_ptr(),
+            inout("rdx") 0usize => _,
+            inout("rdi") 0usize => _,
+            options(nostack)
+        );
+    }
+
+    #[cfg(target_arch = "aarch64")]
+    #[inline(always)]
+    pub(super) unsafe fn client_request(request: usize, arg1: usize, arg2: usize){
+        let args: [usize; 6] = [request, arg1, arg2, 0, 0, 0];
+        std::arch::asm!(
+            "ror x12, x12, #3", "ror x12, x12, #13", "ror x12, x12, #51", "ror x12, x12, #61",
+            "orr x10, x10, x10",
+            in("x4") args.as_ptr(),
+            inout("x3") 0usize => _,
+            inout("x12") 0usize => _,
+            options(nostack, preserves_flags)
+        );
+    }
+
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+    #[inline(always)]
+    pub(super) unsafe fn client_request(_request: usize, _arg1: usize, _arg2: usize){}
+}
@@ -1,14 +1,47 @@
-#![feature(layout_for_ptr)]
-#![feature(set_ptr_value)]
+#![cfg_attr(not(feature = "stable"), feature(layout_for_ptr))]
+#![cfg_attr(not(feature = "stable"), feature(set_ptr_value))]
+#![cfg_attr(feature = "sanitize", feature(cfg_sanitize))]
+#![allow(clippy::needless_return, clippy::len_without_is_empty)]
 
 //! # Swifer!
 //!
 //! Swifer is a garbage collection library, providing both garbage collectors for use by
 //! language runtimes, and tools for the GC implementations themselves, while providing
 //! a uniform interface.
+//!
+//! ## Features
+//!
+//! - `debug`: tools for inspecting managed memory, such as [Graphviz export](gc::debug) and heap layout maps.
+//! - `trace`: emits events about allocation failures and collections through the
+//!   [`tracing`](https://docs.rs/tracing) crate, with the target `swifer`.
+//! - `ffi`: a [C interface](ffi) for embedding in runtimes not written in Rust.
+//! - `serde`: saving the contents of managed memory as [images](gc::image) with `serde`.
+//! - `persist`: heaps [stored in memory-mapped files](heap::mapped), which keep their values
+//!   across process restarts, on 64-bit Unix platforms, and heaps
+//!   [forked copy-on-write](heap::cow) on 64-bit Linux.
+//! - `metrics`: exporting [collection statistics](gc::metrics) through the
+//!   [`metrics`](https://docs.rs/metrics) facade.
+//! - `random`: generating [random object graphs](testing::graph) for property testing collectors.
+//! - `sanitize`: marks memory freed by heaps as inaccessible to AddressSanitizer (when built with
+//!   `-Zsanitizer=address`) and Valgrind, so that uses of stale managed pointers are reported.
+//!   Requires a nightly compiler.
+//! - `stable`: avoids the nightly-only features used by default, so that the crate can be built
+//!   with a stable compiler. This relies on the address being stored first in fat pointers, which
+//!   holds for every current Rust target but is not guaranteed.
+
+// emits a `tracing` event at the given level when the `trace` feature is enabled
+macro_rules! gc_event{
+    ($level:ident, $($args:tt)*) => {
+        #[cfg(feature = "trace")]
+        tracing::event!(target: "swifer", tracing::Level::$level, $($args)*);
+    }
+}
 
 pub mod heap;
 pub mod gc;
+pub mod testing;
+#[cfg(feature = "ffi")]
+pub mod ffi;
 
 #[cfg(test)]
 mod tests;
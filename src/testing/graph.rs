@@ -0,0 +1,169 @@
+//! Generating random object graphs, for property testing collectors and [GcCandidate] implementations.
+//!
+//! A [GraphConfig] describes the shape of the graphs to generate, and [GraphConfig::generate]
+//! allocates one in a [ManagedMem] using a node type supplied by the caller. The returned
+//! [RandomGraph] records the edges and roots chosen, so that e.g. the values a collection
+//! should keep can be checked against [RandomGraph::reachable].
+//!
+//! Generation is deterministic for a given configuration and seed.
+
+use crate::gc::{GcCandidate, ManagedMem};
+use crate::heap::{AllocError, HeapPtr};
+
+/// The shape of random object graphs to generate.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct GraphConfig{
+    nodes: usize,
+    fan_out: usize,
+    cycle_density: f64,
+    roots: usize,
+    seed: u64
+}
+
+/// An object graph allocated by [GraphConfig::generate]. Nodes are identified by the order they
+/// were allocated in.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RandomGraph<Ptr>{
+    nodes: Vec<Ptr>,
+    edges: Vec<(usize, usize)>,
+    roots: Vec<usize>
+}
+
+impl GraphConfig{
+    /// Creates a configuration for graphs with the given number of nodes, each with up to 2 edges,
+    /// a cycle density of 0.1, and a single root.
+    pub fn new(nodes: usize) -> Self{
+        return GraphConfig{
+            nodes,
+            fan_out: 2,
+            cycle_density: 0.1,
+            roots: 1,
+            seed: 0
+        };
+    }
+
+    /// Sets the maximum number of edges from each node. Each node gets a random number of
+    /// edges up to this.
+    pub fn with_fan_out(mut self, fan_out: usize) -> Self{
+        self.fan_out = fan_out;
+        return self;
+    }
+
+    /// Sets the probability, between 0 and 1, of each edge pointing back to the same or an earlier
+    /// node. Other edges point to later nodes, so a density of 0 produces acyclic graphs.
+    pub fn with_cycle_density(mut self, density: f64) -> Self{
+        self.cycle_density = density.clamp(0.0, 1.0);
+        return self;
+    }
+
+    /// Sets the number of nodes chosen as roots. Roots are distinct, and the first node is always a root.
+    pub fn with_roots(mut self, roots: usize) -> Self{
+        self.roots = roots;
+        return self;
+    }
+
+    /// Sets the seed used to choose edges and roots.
+    pub fn with_seed(mut self, seed: u64) -> Self{
+        self.seed = seed;
+        return self;
+    }
+
+    /// Allocates a random graph in the given memory.
+    ///
+    /// Nodes are created without edges by `make`, given their index, and then edges are added by
+    /// calling `link` with the source node and a pointer to the target.
+    pub fn generate<T, Ptr, M>(&self, mem: &mut M, mut make: impl FnMut(usize) -> Box<T>, mut link: impl FnMut(&mut T, Ptr))
+        -> Result<RandomGraph<Ptr>, AllocError<T>>
+        where T: ?Sized + GcCandidate<Ptr>, Ptr: HeapPtr<T>, M: ManagedMem<T, Ptr> + ?Sized
+    {
+        let mut rng = Rng(self.seed);
+        let mut nodes = Vec::with_capacity(self.nodes);
+        for idx in 0..self.nodes{
+            nodes.push(mem.push(make(idx))?);
+        }
+        let mut edges = Vec::new();
+        for from in 0..self.nodes{
+            for _ in 0..rng.below(self.fan_out + 1){
+                let later = self.nodes - from - 1;
+                let to = match later == 0 || rng.chance(self.cycle_density){
+                    true => rng.below(from + 1),
+                    false => from + 1 + rng.below(later)
+                };
+                edges.push((from, to));
+                if let Some(source) = mem.get_by(&nodes[from]){
+                    link(source, nodes[to].clone());
+                }
+            }
+        }
+        // the first node, and a random choice of the others
+        let count = self.roots.min(self.nodes);
+        let mut roots: Vec<usize> = (0..self.nodes).collect();
+        for idx in 1..count{
+            let chosen = idx + rng.below(self.nodes - idx);
+            roots.swap(idx, chosen);
+        }
+        roots.truncate(count);
+        roots.sort_unstable();
+        return Ok(RandomGraph{ nodes, edges, roots });
+    }
+}
+
+impl<Ptr: Clone> RandomGraph<Ptr>{
+    /// Returns pointers to every node, in the order they were allocated.
+    pub fn nodes(&self) -> &[Ptr]{
+        return &self.nodes;
+    }
+
+    /// Returns every edge as a pair of source and target node indexes, in the order they were added.
+    pub fn edges(&self) -> &[(usize, usize)]{
+        return &self.edges;
+    }
+
+    /// Returns the indexes of the nodes chosen as roots, in ascending order.
+    pub fn roots(&self) -> &[usize]{
+        return &self.roots;
+    }
+
+    /// Returns pointers to the nodes chosen as roots, for passing to [ManagedMem::gc].
+    pub fn root_pointers(&self) -> Vec<Ptr>{
+        return self.roots.iter().map(|idx| self.nodes[*idx].clone()).collect();
+    }
+
+    /// Returns the indexes of every node reachable from the roots, in ascending order.
+    pub fn reachable(&self) -> Vec<usize>{
+        let mut reached = vec![false; self.nodes.len()];
+        let mut pending = self.roots.clone();
+        while let Some(idx) = pending.pop(){
+            if !reached[idx]{
+                reached[idx] = true;
+                pending.extend(self.edges.iter().filter(|(from, _)| *from == idx).map(|(_, to)| *to));
+            }
+        }
+        return (0..self.nodes.len()).filter(|idx| reached[*idx]).collect();
+    }
+}
+
+// splitmix64, which is enough for choosing edges
+struct Rng(u64);
+
+impl Rng{
+    fn next(&mut self) -> u64{
+        self.0 = self.0.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        return z ^ (z >> 31);
+    }
+
+    // a number in 0..n, or 0 if n is 0
+    fn below(&mut self, n: usize) -> usize{
+        if n == 0{
+            return 0;
+        }
+        return (self.next() % n as u64) as usize;
+    }
+
+    fn chance(&mut self, p: f64) -> bool{
+        return ((self.next() >> 11) as f64 / (1u64 << 53) as f64) < p;
+    }
+}
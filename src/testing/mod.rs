@@ -0,0 +1,336 @@
+//! Tools for testing code that uses managed memory.
+//!
+//! [MockMem] is a [ManagedMem] that records how it's used, for testing how a runtime integrates
+//! with a collector.
+//!
+//! With the `random` feature, [graph] generates random object graphs for property testing.
+//!
+//! Collectors also rely on the methods of [GcCandidate] agreeing with each other: every pointer
+//! reported by [GcCandidate::trace] must be passed to the function given to
+//! [GcCandidate::adjust_ptrs], and the pointers it returns must be stored. [check_candidate]
+//! checks this for a sample value, and [check_all] for every value in a memory, so that bugs in
+//! implementations can be found in tests instead of as corrupted heaps.
+
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::fmt;
+use crate::gc::{GcCandidate, ManagedMem};
+use crate::heap::{AllocError, AllocErrorKind, HeapPtr};
+
+#[cfg(feature = "random")]
+pub mod graph;
+
+/// A [ManagedMem] that records every push, access and collection, for testing how language
+/// runtimes use managed memory without depending on a real collector.
+///
+/// Values are kept in separate boxes, and never move. Collections drop values unreachable from
+/// the given roots and clear weak pointers to them, as a non-moving collector would. Zero-sized
+/// values all have the same address, and so can't be told apart by pointer.
+pub struct MockMem<T: ?Sized, Ptr = *const T>{
+    values: Vec<(Box<T>, Ptr)>,
+    limit: Option<usize>,
+    calls: RefCell<Vec<MemCall<Ptr>>>
+}
+
+/// A call made to a [MockMem].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum MemCall<Ptr>{
+    /// A value was pushed, and given the pointer.
+    Push(Ptr),
+    /// A value couldn't be pushed, because the memory was at its limit.
+    PushFailed,
+    /// A value was accessed by index, through [ManagedMem::get] or [ManagedMem::get_mut].
+    Get(usize),
+    /// A value was accessed by pointer, through [ManagedMem::get_by] or [ManagedMem::get_by_ref].
+    /// The access may have failed.
+    GetBy(Ptr),
+    /// A collection ran, given the pointers in the root and weak locations when it started.
+    Gc{ roots: Vec<Ptr>, weaks: Vec<Ptr> }
+}
+
+impl<T: ?Sized + GcCandidate<Ptr>, Ptr: HeapPtr<T>> MockMem<T, Ptr>{
+    /// Creates an empty memory with no limit on the number of values.
+    pub fn new() -> Self{
+        return MockMem{
+            values: Vec::new(),
+            limit: None,
+            calls: RefCell::new(Vec::new())
+        };
+    }
+
+    /// Makes pushes fail with [AllocErrorKind::OutOfSpace] when the given number of values are
+    /// stored, for testing how allocation failures are handled.
+    pub fn with_limit(mut self, values: usize) -> Self{
+        self.limit = Some(values);
+        return self;
+    }
+
+    /// Returns every call made so far, in order.
+    pub fn calls(&self) -> Vec<MemCall<Ptr>>{
+        return self.calls.borrow().clone();
+    }
+
+    /// Returns and forgets every call made so far.
+    pub fn take_calls(&mut self) -> Vec<MemCall<Ptr>>{
+        return self.calls.take();
+    }
+
+    /// Returns the number of successful pushes made so far.
+    pub fn pushes(&self) -> usize{
+        return self.count(|call| matches!(call, MemCall::Push(_)));
+    }
+
+    /// Returns the number of accesses made so far, by index or pointer.
+    pub fn gets(&self) -> usize{
+        return self.count(|call| matches!(call, MemCall::Get(_) | MemCall::GetBy(_)));
+    }
+
+    /// Returns the number of collections run so far.
+    pub fn collections(&self) -> usize{
+        return self.count(|call| matches!(call, MemCall::Gc{ .. }));
+    }
+
+    fn count(&self, f: impl Fn(&MemCall<Ptr>) -> bool) -> usize{
+        return self.calls.borrow().iter().filter(|call| f(call)).count();
+    }
+
+    fn record(&self, call: MemCall<Ptr>){
+        self.calls.borrow_mut().push(call);
+    }
+
+    fn index_of(&self, ptr: &Ptr) -> Option<usize>{
+        return self.values.iter().position(|(_, p)| p.eq_ignoring_meta(ptr));
+    }
+}
+
+/// A way in which a [GcCandidate] implementation is inconsistent.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ConformanceError{
+    /// [GcCandidate::trace] and [GcCandidate::collect_managed_pointers] reported different pointers.
+    TraceMismatch,
+    /// [GcCandidate::adjust_ptrs] didn't call the given function with exactly the pointers
+    /// reported by [GcCandidate::trace].
+    AdjustMismatch,
+    /// Adjusting every pointer to itself changed the pointers reported by [GcCandidate::trace].
+    IdentityChanged,
+    /// Pointers returned by the function given to [GcCandidate::adjust_ptrs] weren't all stored.
+    AdjustNotStored,
+    /// [GcCandidate::visit_edges] didn't visit exactly the pointers reported by [GcCandidate::trace],
+    /// or didn't store the pointers it was given.
+    VisitMismatch
+}
+
+/// Checks that the methods of the given value's [GcCandidate] implementation agree with each
+/// other, returning the first inconsistency found. `this` is passed to every method, and should
+/// point to the value.
+///
+/// Pointers in the value are temporarily replaced, but the value is left with its original
+/// pointers if the check succeeds.
+pub fn check_candidate<T, Ptr>(value: &mut T, this: &Ptr) -> Result<(), ConformanceError>
+    where T: ?Sized + GcCandidate<Ptr>, Ptr: HeapPtr<T>
+{
+    let edges = traced(value, this);
+    if !same_pointers(&edges, &value.collect_managed_pointers(this)){
+        return Err(ConformanceError::TraceMismatch);
+    }
+    // adjusting with the identity must see every edge, and change nothing
+    let seen = RefCell::new(Vec::new());
+    value.adjust_ptrs(|p| {
+        seen.borrow_mut().push(p.clone());
+        p.clone()
+    }, this);
+    if !same_pointers(&edges, &seen.into_inner()){
+        return Err(ConformanceError::AdjustMismatch);
+    }
+    if !same_pointers(&edges, &traced(value, this)){
+        return Err(ConformanceError::IdentityChanged);
+    }
+    // replacing every edge must store the replacements, and replacing them back must restore them
+    let (from, to) = rotation(&edges, this);
+    value.adjust_ptrs(|p| replace(p, &from, &to), this);
+    let rotated: Vec<Ptr> = edges.iter().map(|p| replace(p, &from, &to)).collect();
+    let stored = same_pointers(&rotated, &traced(value, this));
+    value.adjust_ptrs(|p| replace(p, &to, &from), this);
+    if !stored || !same_pointers(&edges, &traced(value, this)){
+        return Err(ConformanceError::AdjustNotStored);
+    }
+    // visiting edges must see and store the same pointers
+    let mut visited = Vec::new();
+    value.visit_edges(&mut |p: &mut Ptr| {
+        visited.push(p.clone());
+        *p = replace(p, &from, &to);
+    }, this);
+    let stored = same_pointers(&rotated, &traced(value, this));
+    value.adjust_ptrs(|p| replace(p, &to, &from), this);
+    if !same_pointers(&edges, &visited) || !stored{
+        return Err(ConformanceError::VisitMismatch);
+    }
+    return Ok(());
+}
+
+/// Runs [check_candidate] on every value in the given memory, returning the index of the first
+/// inconsistent value along with the inconsistency.
+pub fn check_all<T, Ptr, M>(mem: &mut M) -> Result<(), (usize, ConformanceError)>
+    where T: ?Sized + GcCandidate<Ptr>, Ptr: HeapPtr<T>, M: ManagedMem<T, Ptr> + ?Sized
+{
+    let mut ptrs = Vec::with_capacity(mem.len());
+    mem.for_each(|_, ptr| ptrs.push(ptr.clone()));
+    for (index, ptr) in ptrs.iter().enumerate(){
+        if let Some(value) = mem.get_by(ptr){
+            check_candidate(value, ptr).map_err(|err| (index, err))?;
+        }
+    }
+    return Ok(());
+}
+
+fn traced<T: ?Sized + GcCandidate<Ptr>, Ptr: HeapPtr<T>>(value: &T, this: &Ptr) -> Vec<Ptr>{
+    let mut edges = Vec::new();
+    value.trace(&mut |p: &Ptr| edges.push(p.clone()), this);
+    return edges;
+}
+
+// whether both lists contain the same pointers the same number of times, in any order
+fn same_pointers<Ptr: Eq>(left: &[Ptr], right: &[Ptr]) -> bool{
+    if left.len() != right.len(){
+        return false;
+    }
+    let mut unmatched: Vec<&Ptr> = right.iter().collect();
+    for ptr in left{
+        match unmatched.iter().position(|p| *p == ptr){
+            Some(idx) => unmatched.swap_remove(idx),
+            None => return false
+        };
+    }
+    return true;
+}
+
+// a replacement for every distinct edge, which is different from it if possible, as a pair of
+// lists where each pointer in the first is replaced by the pointer at the same index in the second
+fn rotation<Ptr: Clone + Eq>(edges: &[Ptr], this: &Ptr) -> (Vec<Ptr>, Vec<Ptr>){
+    let mut from: Vec<Ptr> = Vec::new();
+    for edge in edges{
+        if !from.contains(edge){
+            from.push(edge.clone());
+        }
+    }
+    // with one distinct edge, swap it with the value's own pointer instead
+    if from.len() == 1 && from[0] != *this{
+        from.push(this.clone());
+    }
+    let mut to = from.clone();
+    if !to.is_empty(){
+        to.rotate_left(1);
+    }
+    return (from, to);
+}
+
+fn replace<Ptr: Clone + Eq>(ptr: &Ptr, from: &[Ptr], to: &[Ptr]) -> Ptr{
+    return match from.iter().position(|p| p == ptr){
+        Some(idx) => to[idx].clone(),
+        None => ptr.clone()
+    };
+}
+
+//////////////// impls
+
+impl<T: ?Sized + GcCandidate<Ptr>, Ptr: HeapPtr<T>> ManagedMem<T, Ptr> for MockMem<T, Ptr>{
+    fn push(&mut self, v: Box<T>) -> Result<Ptr, AllocError<T>>{
+        return self.push_with(v, |x| x);
+    }
+
+    fn push_with(&mut self, v: Box<T>, with: impl FnOnce(Ptr) -> Ptr) -> Result<Ptr, AllocError<T>>{
+        if matches!(self.limit, Some(limit) if self.values.len() >= limit){
+            self.record(MemCall::PushFailed);
+            return Err(AllocError::new(v, AllocErrorKind::OutOfSpace));
+        }
+        let ptr = with(Ptr::from_raw_ptr(v.as_ref()));
+        self.values.push((v, ptr.clone()));
+        self.record(MemCall::Push(ptr.clone()));
+        return Ok(ptr);
+    }
+
+    fn get(&self, idx: usize) -> &T{
+        self.record(MemCall::Get(idx));
+        return &self.values[idx].0;
+    }
+
+    fn get_mut(&mut self, idx: usize) -> &mut T{
+        self.record(MemCall::Get(idx));
+        return &mut self.values[idx].0;
+    }
+
+    fn get_by(&mut self, ptr: &Ptr) -> Option<&mut T>{
+        self.record(MemCall::GetBy(ptr.clone()));
+        let idx = self.index_of(ptr)?;
+        return Some(&mut self.values[idx].0);
+    }
+
+    fn get_by_ref(&self, ptr: &Ptr) -> Option<&T>{
+        self.record(MemCall::GetBy(ptr.clone()));
+        let idx = self.index_of(ptr)?;
+        return Some(&self.values[idx].0);
+    }
+
+    fn len(&self) -> usize{
+        return self.values.len();
+    }
+
+    fn contains_ptr(&self, ptr: &Ptr) -> bool{
+        return self.index_of(ptr).is_some();
+    }
+
+    fn for_each(&self, mut cb: impl FnMut(&T, &Ptr)){
+        for (value, ptr) in &self.values{
+            cb(value, ptr);
+        }
+    }
+
+    fn retain(&mut self, mut f: impl FnMut(&Ptr, &T) -> bool){
+        self.values.retain(|(value, ptr)| f(ptr, value));
+    }
+
+    unsafe fn gc(&mut self, roots: Vec<*mut Ptr>, weaks: Vec<*mut Ptr>){
+        let root_ptrs: Vec<Ptr> = roots.iter().map(|root| (**root).clone()).collect();
+        let weak_ptrs: Vec<Ptr> = weaks.iter().map(|weak| (**weak).clone()).collect();
+        self.record(MemCall::Gc{ roots: root_ptrs.clone(), weaks: weak_ptrs });
+        // mark everything reachable from the roots, by address
+        let mut marked: HashSet<*const ()> = HashSet::new();
+        let mut pending = root_ptrs;
+        while let Some(ptr) = pending.pop(){
+            if !ptr.is_cleared() && marked.insert(ptr.to_raw_ptr().cast::<()>()){
+                if let Some(idx) = self.index_of(&ptr){
+                    self.values[idx].0.trace(&mut |edge: &Ptr| pending.push(edge.clone()), &ptr);
+                }
+            }
+        }
+        for weak in weaks{
+            let weak = &mut *weak;
+            if !weak.is_cleared() && self.contains_ptr(weak) && !marked.contains(&weak.to_raw_ptr().cast::<()>()){
+                weak.set_cleared();
+            }
+        }
+        self.values.retain(|(_, ptr)| marked.contains(&ptr.to_raw_ptr().cast::<()>()));
+    }
+}
+
+impl<T: ?Sized + GcCandidate<Ptr>, Ptr: HeapPtr<T>> Default for MockMem<T, Ptr>{
+    fn default() -> Self{
+        return MockMem::new();
+    }
+}
+
+impl Display for ConformanceError{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result{
+        return f.write_str(match self{
+            ConformanceError::TraceMismatch => "trace and collect_managed_pointers report different pointers",
+            ConformanceError::AdjustMismatch => "adjust_ptrs doesn't adjust exactly the traced pointers",
+            ConformanceError::IdentityChanged => "adjusting pointers to themselves changes them",
+            ConformanceError::AdjustNotStored => "adjust_ptrs doesn't store adjusted pointers",
+            ConformanceError::VisitMismatch => "visit_edges doesn't visit and store exactly the traced pointers"
+        });
+    }
+}
+
+impl Error for ConformanceError{}
@@ -0,0 +1,81 @@
+use crate::gc::{GcCandidate, ManagedMem};
+use crate::gc::actors::ActorHeaps;
+use crate::gc::config::MemConfig;
+use crate::gc::copy::clone_graph;
+use crate::gc::impls::{adjust_slice, collect_all, trace_slice};
+use crate::gc::mas::MarkAndSweepMem;
+
+#[derive(Clone)]
+struct Node{
+    id: u32,
+    edges: Vec<*const Node>
+}
+
+impl GcCandidate for Node{
+    fn collect_managed_pointers(&self, _this: &*const Node) -> Vec<*const Node>{
+        return collect_all(&self.edges);
+    }
+
+    fn trace(&self, visitor: &mut impl FnMut(&*const Node), _this: &*const Node){
+        trace_slice(&self.edges, visitor);
+    }
+
+    fn adjust_ptrs(&mut self, adjust: impl Fn(&*const Node) -> *const Node, _this: &*const Node){
+        adjust_slice(&mut self.edges, adjust);
+    }
+}
+
+#[test]
+fn test_send(){
+    let mut actors = ActorHeaps::new();
+    let a = actors.spawn(MarkAndSweepMem::<Node>::new(MemConfig::new(1024)));
+    let b = actors.spawn(MarkAndSweepMem::<Node>::new(MemConfig::new(1024)));
+    // a cycle, and a value not reachable from it
+    let heap = actors.heap_mut(a).unwrap();
+    let first = heap.push(Box::new(Node{ id: 1, edges: vec![] })).unwrap();
+    let second = heap.push(Box::new(Node{ id: 2, edges: vec![first] })).unwrap();
+    heap.push(Box::new(Node{ id: 3, edges: vec![] })).unwrap();
+    heap.write_field(&first, |n| n.edges.push(second));
+
+    let copy = actors.send(&first, a, b).unwrap();
+    let received = actors.heap(b).unwrap();
+    assert_eq!(received.len(), 2);
+    let copy_second = received.get_by_ref(&copy).unwrap().edges[0];
+    assert_eq!(received.get_by_ref(&copy_second).unwrap().id, 2);
+    assert_eq!(received.get_by_ref(&copy_second).unwrap().edges[0], copy);
+    received.verify().unwrap();
+
+    // the copy is independent of the original, which can be collected on its own
+    unsafe{ actors.heap_mut(a).unwrap().gc(vec![], vec![]) };
+    assert_eq!(actors.heap(a).unwrap().len(), 0);
+    assert_eq!(actors.heap(b).unwrap().get_by_ref(&copy).unwrap().id, 1);
+
+    assert!(actors.remove(a).is_some());
+    assert_eq!(actors.ids().collect::<Vec<_>>(), vec![b]);
+}
+
+#[test]
+fn test_clone_graph(){
+    let mut scratch = MarkAndSweepMem::<Node>::new(MemConfig::new(1024));
+    let mut kept = MarkAndSweepMem::<Node>::new(MemConfig::new(1024));
+    // a diamond, with a cycle back to the top
+    let bottom = scratch.push(Box::new(Node{ id: 4, edges: vec![] })).unwrap();
+    let left = scratch.push(Box::new(Node{ id: 2, edges: vec![bottom] })).unwrap();
+    let right = scratch.push(Box::new(Node{ id: 3, edges: vec![bottom] })).unwrap();
+    let top = scratch.push(Box::new(Node{ id: 1, edges: vec![left, right] })).unwrap();
+    scratch.write_field(&bottom, |n| n.edges.push(top));
+
+    let copy = clone_graph(&scratch, &top, &mut kept).unwrap();
+    assert_eq!(kept.len(), 4);
+    kept.verify().unwrap();
+    let edges = kept.get_by_ref(&copy).unwrap().edges.clone();
+    let (left, right) = (kept.get_by_ref(&edges[0]).unwrap(), kept.get_by_ref(&edges[1]).unwrap());
+    assert_eq!((left.id, right.id), (2, 3));
+    assert_eq!(left.edges[0], right.edges[0]);
+    assert_eq!(kept.get_by_ref(&left.edges[0]).unwrap().edges[0], copy);
+
+    // a failed copy leaves nothing behind
+    let mut small = MarkAndSweepMem::<Node>::new(MemConfig::new(100));
+    assert!(clone_graph(&scratch, &top, &mut small).is_err());
+    assert_eq!(small.len(), 0);
+}
@@ -0,0 +1,153 @@
+use std::alloc::Layout;
+use std::io::ErrorKind;
+use std::rc::Rc;
+use crate::gc::ManagedMem;
+use crate::gc::any::{AnyCandidate, AnyMem, AnyObject, AnyPtr, TypeInfo};
+use crate::gc::dump::HeapDump;
+use crate::gc::impls::{adjust_option, trace_option};
+use crate::gc::mas::MarkAndSweepMem;
+
+// a few unrelated types sharing one heap
+
+struct Pair{
+    left: Option<AnyPtr>,
+    right: Option<AnyPtr>
+}
+
+struct Num(i64);
+
+struct Str(Rc<String>);
+
+impl AnyCandidate for Pair{
+    fn trace(&self, mut visitor: &mut dyn FnMut(&AnyPtr)){
+        trace_option(&self.left, &mut visitor);
+        trace_option(&self.right, &mut visitor);
+    }
+
+    fn adjust_ptrs(&mut self, adjust: &dyn Fn(&AnyPtr) -> AnyPtr){
+        adjust_option(&mut self.left, adjust);
+        adjust_option(&mut self.right, adjust);
+    }
+}
+
+impl AnyCandidate for Num{
+    fn trace(&self, _visitor: &mut dyn FnMut(&AnyPtr)){}
+
+    fn adjust_ptrs(&mut self, _adjust: &dyn Fn(&AnyPtr) -> AnyPtr){}
+}
+
+impl AnyCandidate for Str{
+    fn trace(&self, _visitor: &mut dyn FnMut(&AnyPtr)){}
+
+    fn adjust_ptrs(&mut self, _adjust: &dyn Fn(&AnyPtr) -> AnyPtr){}
+}
+
+#[test]
+fn test_heterogeneous_mem(){
+    let mut mem = AnyMem::new(MarkAndSweepMem::<AnyObject>::new(1000));
+    let pair_tag = mem.register_type::<Pair>();
+    let num_tag = mem.register_type::<Num>();
+    mem.register_type::<Str>();
+    assert_eq!(mem.register_type::<Pair>(), pair_tag);
+
+    let text = Rc::new("hello".to_string());
+    let num = mem.push(Num(5)).unwrap();
+    let str = mem.push(Str(text.clone())).unwrap();
+    let mut root = mem.push(Pair{ left: Some(num), right: Some(str) }).unwrap();
+    mem.push(Str(text.clone())).unwrap();
+    assert_eq!(Rc::strong_count(&text), 3);
+
+    unsafe{
+        mem.gc(vec![&mut root], vec![]);
+    }
+    // the unreachable string was dropped
+    assert_eq!(Rc::strong_count(&text), 2);
+    assert_eq!(mem.tag_at(&root), Some(pair_tag));
+    assert!(mem.get::<Num>(&root).is_none());
+
+    let pair = mem.get_ref::<Pair>(&root).unwrap();
+    let (left, right) = (pair.left.unwrap(), pair.right.unwrap());
+    assert_eq!(mem.tag_at(&left), Some(num_tag));
+    // shared lookups can coexist
+    assert_eq!(mem.get_ref::<Num>(&left).unwrap().0 + mem.get_ref::<Num>(&left).unwrap().0, 10);
+    assert_eq!(mem.get::<Str>(&right).unwrap().0.as_str(), "hello");
+
+    drop(mem);
+    assert_eq!(Rc::strong_count(&text), 1);
+}
+
+#[test]
+fn test_values_outlive_registry(){
+    let mut mem = AnyMem::new(MarkAndSweepMem::<AnyObject>::new(100));
+    mem.register_type::<Str>();
+    let text = Rc::new("hello".to_string());
+    let err = loop{
+        if let Err(err) = mem.push(Str(text.clone())){
+            break err;
+        }
+    };
+    let inner = std::mem::replace(mem.mem_mut(), MarkAndSweepMem::new(100));
+    drop(mem);
+    // stored values and failed pushes keep their type information after the registry is gone
+    assert!(inner.get(0).type_info().name().ends_with("Str"));
+    assert!(err.value().downcast_ref::<Str>().is_some());
+    drop(inner);
+    drop(err);
+    assert_eq!(Rc::strong_count(&text), 1);
+}
+
+#[test]
+fn test_heap_dump(){
+    let mut mem = AnyMem::new(MarkAndSweepMem::<AnyObject>::new(1000));
+    let num_tag = mem.register_type::<Num>();
+    let pair_tag = mem.register_type::<Pair>();
+    let num = mem.push(Num(5)).unwrap();
+    let pair = mem.push(Pair{ left: Some(num), right: Some(num) }).unwrap();
+
+    let mut out = Vec::new();
+    mem.dump(&mut out).unwrap();
+    let dump = HeapDump::read(out.as_slice()).unwrap();
+    assert_eq!(dump.objects().len(), 2);
+    let num_addr = num.cast::<()>() as usize as u64;
+    let pair_obj = dump.get(pair.cast::<()>() as usize as u64).unwrap();
+    assert_eq!(pair_obj.type_id(), Some(pair_tag.index() as u32));
+    assert_eq!(pair_obj.edges(), &[num_addr, num_addr]);
+    assert_eq!(dump.get(num_addr).unwrap().type_id(), Some(num_tag.index() as u32));
+    assert_eq!(dump.referrers(num_addr).count(), 1);
+    assert_eq!(dump.total_size(), mem.mem().total_size() as u64);
+
+    // dumps without type information
+    let mut out = Vec::new();
+    mem.mem().dump(&mut out).unwrap();
+    assert_eq!(HeapDump::read(out.as_slice()).unwrap().get(num_addr).unwrap().type_id(), None);
+    assert_eq!(HeapDump::read(&b"nope"[..]).unwrap_err().kind(), ErrorKind::InvalidData);
+}
+
+#[test]
+fn test_variably_sized_type(){
+    // length-prefixed byte strings, registered without a Rust type
+    unsafe fn no_trace(_value: *const u8, _visitor: &mut dyn FnMut(&AnyPtr)){}
+    unsafe fn no_adjust(_value: *mut u8, _adjust: &dyn Fn(&AnyPtr) -> AnyPtr){}
+    unsafe fn bytes_size(value: *const u8) -> usize{
+        return 8 + (value as *const u64).read() as usize;
+    }
+    let mut mem = AnyMem::new(MarkAndSweepMem::<AnyObject>::new(1000));
+    let info = TypeInfo::new("bytes", Layout::new::<u64>(), no_trace, no_adjust, None).with_size(bytes_size);
+    assert!(info.is_variably_sized());
+    let tag = mem.register(info);
+
+    let encode = |s: &[u8]| [&(s.len() as u64).to_ne_bytes()[..], s].concat();
+    let mut short = unsafe{ mem.push_raw(tag, &encode(b"hi")).unwrap() };
+    let long = unsafe{ mem.push_raw(tag, &encode(&[7; 40])).unwrap() };
+    let obj = mem.mem().get_by_ref(&long).unwrap();
+    assert_eq!(obj.size(), 48);
+    assert_eq!(obj.tag(), tag);
+
+    unsafe{
+        mem.gc(vec![&mut short], vec![]);
+    }
+    let obj = mem.mem().get_by_ref(&short).unwrap();
+    assert_eq!(obj.size(), 10);
+    assert_eq!(unsafe{ std::slice::from_raw_parts(obj.payload().add(8), 2) }, b"hi");
+    assert_eq!(mem.mem().len(), 1);
+}
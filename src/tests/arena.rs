@@ -0,0 +1,64 @@
+use std::ptr;
+use crate::gc::{GcCandidate, ManagedMem};
+use crate::gc::arena::Arena;
+use crate::gc::impls::{adjust_option, collect_all};
+use crate::gc::incremental::{Budget, GcPhaseProgress};
+use crate::gc::mas::MarkAndSweepMem;
+use crate::heap::AllocError;
+
+struct Node{
+    id: u32,
+    next: Option<*const Node>
+}
+
+impl GcCandidate for Node{
+    fn collect_managed_pointers(&self, _this: &*const Node) -> Vec<*const Node>{
+        return collect_all(&self.next);
+    }
+
+    fn adjust_ptrs(&mut self, adjust: impl Fn(&*const Node) -> *const Node, _this: &*const Node){
+        adjust_option(&mut self.next, adjust);
+    }
+}
+
+#[test]
+fn test_arena(){
+    let mem = MarkAndSweepMem::<Node>::new(4096);
+    let mut arena = Arena::new(mem, |mc| -> Result<_, AllocError<Node>> {
+        let tail = mc.alloc(Box::new(Node{ id: 1, next: None }))?;
+        return mc.alloc(Box::new(Node{ id: 0, next: Some(*tail.as_ptr()) }));
+    }).unwrap();
+
+    // a value that isn't linked from the root is collected after the mutation
+    let len = arena.mutate(|mc, root| {
+        let extra = mc.alloc(Box::new(Node{ id: 2, next: None })).unwrap();
+        assert_eq!(mc.get(&extra).id, 2);
+        let mut len = 0;
+        let mut at = Some(root);
+        while let Some(node) = at{
+            len += 1;
+            at = mc.load(&node, |n| n.next.unwrap_or(ptr::null()));
+        }
+        return len;
+    });
+    assert_eq!(len, 2);
+    assert_eq!(arena.collect_all().objects_reclaimed(), 1);
+
+    // replace the root with a new head linking to the old one
+    arena.mutate_root(|mc, root| {
+        let head = mc.alloc(Box::new(Node{ id: 5, next: None })).unwrap();
+        mc.write(&head, |n| n.next = Some(*root.as_ptr()));
+        *root = head;
+    });
+    let result = loop{
+        if let GcPhaseProgress::Complete(report) = arena.collect_step(Budget::Objects(1)){
+            break report;
+        }
+    };
+    assert_eq!(result.objects_reclaimed(), 0);
+    assert_eq!(arena.mem().len(), 3);
+    arena.mutate(|mc, root| assert_eq!(mc.get(&root).id, 5));
+
+    let (mem, root) = arena.into_inner();
+    assert_eq!(mem.get_by_ref(&root).map(|n| n.id), Some(5));
+}
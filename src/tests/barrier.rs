@@ -0,0 +1,188 @@
+use crate::gc::{GcCandidate, ManagedMem, NoGcMem};
+use crate::gc::barriers::{Barriered, CardTable, ForwardingTable, RememberedSet, SatbQueue};
+use crate::gc::impls::{adjust_option, collect_all, trace_option};
+use crate::heap::{AllocError, Heap};
+
+struct Node{
+    next: Option<*const Node>
+}
+
+impl GcCandidate for Node{
+    fn collect_managed_pointers(&self, _this: &*const Node) -> Vec<*const Node>{
+        return collect_all(&self.next);
+    }
+
+    fn trace(&self, visitor: &mut impl FnMut(&*const Node), _this: &*const Node){
+        trace_option(&self.next, visitor);
+    }
+
+    fn adjust_ptrs(&mut self, adjust: impl Fn(&*const Node) -> *const Node, _this: &*const Node){
+        adjust_option(&mut self.next, adjust);
+    }
+}
+
+// a memory that records every barriered write
+struct BarrierMem{
+    mem: NoGcMem<Node>,
+    writes: Vec<(*const Node, *const Node)>,
+    forwards: ForwardingTable<Node, *const Node>
+}
+
+impl ManagedMem<Node> for BarrierMem{
+    fn push(&mut self, v: Box<Node>) -> Result<*const Node, AllocError<Node>>{
+        return self.mem.push(v);
+    }
+
+    fn push_with(&mut self, v: Box<Node>, with: impl FnOnce(*const Node) -> *const Node) -> Result<*const Node, AllocError<Node>>{
+        return self.mem.push_with(v, with);
+    }
+
+    fn get(&self, idx: usize) -> &Node{
+        return self.mem.get(idx);
+    }
+
+    fn get_mut(&mut self, idx: usize) -> &mut Node{
+        return self.mem.get_mut(idx);
+    }
+
+    fn get_by(&mut self, ptr: &*const Node) -> Option<&mut Node>{
+        return self.mem.get_by(ptr);
+    }
+
+    fn get_by_ref(&self, ptr: &*const Node) -> Option<&Node>{
+        return self.mem.get_by_ref(ptr);
+    }
+
+    fn len(&self) -> usize{
+        return self.mem.len();
+    }
+
+    fn contains_ptr(&self, ptr: &*const Node) -> bool{
+        return self.mem.contains_ptr(ptr);
+    }
+
+    fn for_each(&self, cb: impl FnMut(&Node, &*const Node)){
+        self.mem.for_each(cb);
+    }
+
+    fn retain(&mut self, f: impl FnMut(&*const Node, &Node) -> bool){
+        self.mem.retain(f);
+    }
+
+    unsafe fn gc(&mut self, roots: Vec<*mut *const Node>, weaks: Vec<*mut *const Node>){
+        self.mem.gc(roots, weaks);
+    }
+
+    fn record_write(&mut self, src: &*const Node, dst: &*const Node){
+        self.writes.push((*src, *dst));
+    }
+
+    fn has_write_barrier(&self) -> bool{
+        return true;
+    }
+
+    fn load_barrier(&self, ptr: &*const Node) -> *const Node{
+        return self.forwards.forward(ptr);
+    }
+
+    fn has_read_barrier(&self) -> bool{
+        return true;
+    }
+}
+
+#[test]
+fn test_write_barrier(){
+    let mut mem = BarrierMem{ mem: NoGcMem::new(100), writes: vec![], forwards: ForwardingTable::new() };
+    let a = mem.push(Box::new(Node{ next: None })).unwrap();
+    let b = mem.push(Box::new(Node{ next: None })).unwrap();
+
+    mem.get_by(&a).unwrap().next = Some(b);
+    assert!(mem.writes.is_empty());
+
+    assert_eq!(mem.write_field(&b, |n| n.next = Some(a)), Some(()));
+    assert_eq!(mem.writes, vec![(b, a)]);
+    assert_eq!(mem.write_field(&std::ptr::null(), |n| n.next = None), None);
+}
+
+#[test]
+fn test_read_barrier(){
+    let mut mem = BarrierMem{ mem: NoGcMem::new(100), writes: vec![], forwards: ForwardingTable::new() };
+    let a = mem.push(Box::new(Node{ next: None })).unwrap();
+    let a_copy = mem.push(Box::new(Node{ next: None })).unwrap();
+    let b = mem.push(Box::new(Node{ next: Some(a) })).unwrap();
+
+    // pretend a concurrent collector copied `a`
+    mem.forwards.insert(&a, a_copy);
+    assert_eq!(mem.load_field(&b, |n| n.next.unwrap()), Some(a_copy));
+    assert_eq!(Barriered::new(a).load(&mem), a_copy);
+    assert_eq!(Barriered::new(b).load(&mem), b);
+    assert!(mem.forwards.is_forwarded(&a));
+}
+
+#[test]
+fn test_card_table(){
+    let mut heap = Heap::<[u8]>::new(256);
+    let a = heap.push_slice(&[0; 100]).unwrap();
+    let b = heap.push_slice(&[0; 100]).unwrap();
+    let c = heap.push_slice(&[0; 50]).unwrap();
+    let mut cards = CardTable::for_heap(&heap, 64);
+    assert_eq!((cards.len(), cards.card_size()), (5, 64));
+
+    assert!(cards.mark(&b));
+    assert!(cards.mark(&c));
+    assert!(!cards.mark(&std::ptr::slice_from_raw_parts(&0u8 as *const u8, 0)));
+    assert!(!cards.is_dirty(&a));
+    assert!(cards.is_dirty(&b) && cards.is_dirty(&c));
+    // b starts at offset 100, and c at offset 200
+    assert_eq!(cards.dirty_cards().collect::<Vec<_>>(), vec![1, 3]);
+    let (start, end) = cards.card_bounds(3);
+    assert_eq!((start as usize - heap.start() as usize, end as usize - start as usize), (192, 64));
+
+    cards.clear_card(1);
+    assert_eq!(cards.dirty_cards().count(), 1);
+    cards.clear();
+    assert!(!cards.is_dirty(&c));
+}
+
+#[test]
+fn test_remembered_set(){
+    let mut mem = BarrierMem{ mem: NoGcMem::new(100), writes: vec![], forwards: ForwardingTable::new() };
+    let a = mem.push(Box::new(Node{ next: None })).unwrap();
+    let b = mem.push(Box::new(Node{ next: None })).unwrap();
+    let mut set = RememberedSet::<Node, *const Node>::new();
+    assert!(set.insert(&a));
+    assert!(!set.insert(&a));
+    assert!(set.insert(&b));
+    assert_eq!(set.len(), 2);
+
+    // pretend a collector moved `a` and dropped `b`
+    set.update(|p| if *p == a { Some(b) } else { None });
+    assert_eq!(set.iter().collect::<Vec<_>>(), vec![&b]);
+    assert!(set.remove(&b));
+    assert!(set.is_empty());
+}
+
+#[test]
+fn test_satb_queue(){
+    let mut mem = BarrierMem{ mem: NoGcMem::new(100), writes: vec![], forwards: ForwardingTable::new() };
+    let a = mem.push(Box::new(Node{ next: None })).unwrap();
+    let b = mem.push(Box::new(Node{ next: Some(a) })).unwrap();
+    let queue = SatbQueue::<Node, *const Node>::new(2);
+    let mut buffer = queue.buffer();
+    // nothing is recorded until marking starts
+    buffer.enqueue(&a);
+    assert!(buffer.is_empty());
+
+    queue.activate();
+    buffer.enqueue(&a);
+    buffer.enqueue(&std::ptr::null());
+    assert_eq!((buffer.len(), queue.len()), (1, 0));
+    // full buffers are handed to the queue
+    buffer.enqueue_edges(mem.get_by_ref(&b).unwrap(), &b);
+    assert_eq!((buffer.len(), queue.len()), (0, 2));
+    buffer.enqueue(&b);
+    drop(buffer);
+    queue.deactivate();
+    assert_eq!(queue.drain(), vec![a, a, b]);
+    assert!(queue.is_empty());
+}
@@ -0,0 +1,69 @@
+use std::ptr;
+use crate::gc::{GcCandidate, ManagedMem};
+use crate::gc::cell::{GcCell, GcRefCell};
+use crate::gc::incremental::{Budget, GcPhaseProgress, IncrementalMem};
+use crate::gc::mas::MarkAndSweepMem;
+
+struct Node{
+    id: u32,
+    next: GcCell<*const Node>,
+    edges: GcRefCell<Vec<*const Node>>
+}
+
+impl Node{
+    fn new(id: u32) -> Box<Node>{
+        return Box::new(Node{ id, next: GcCell::new(ptr::null()), edges: GcRefCell::new(vec![]) });
+    }
+}
+
+impl GcCandidate for Node{
+    fn collect_managed_pointers(&self, this: &*const Node) -> Vec<*const Node>{
+        let mut ptrs = Vec::new();
+        self.trace(&mut |p| ptrs.push(*p), this);
+        return ptrs;
+    }
+
+    fn trace(&self, visitor: &mut impl FnMut(&*const Node), _this: &*const Node){
+        self.next.trace(&mut |p| if !p.is_null(){ visitor(p) });
+        self.edges.borrow().iter().for_each(visitor);
+    }
+
+    fn adjust_ptrs(&mut self, adjust: impl Fn(&*const Node) -> *const Node, _this: &*const Node){
+        let next = self.next.get_mut();
+        if !next.is_null(){
+            *next = adjust(next);
+        }
+        for edge in self.edges.get_mut(){
+            *edge = adjust(edge);
+        }
+    }
+}
+
+#[test]
+fn test_cells_record_writes(){
+    let mut mem = MarkAndSweepMem::<Node>::new(4096);
+    // root -> a -> b -> {x, y}
+    let x = mem.push(Node::new(4)).unwrap();
+    let y = mem.push(Node::new(5)).unwrap();
+    let b = mem.push(Node::new(2)).unwrap();
+    GcRefCell::update(&mut mem, &b, |n: &Node| &n.edges, |edges| edges.extend([x, y])).unwrap();
+    let a = mem.push(Node::new(1)).unwrap();
+    GcCell::set(&mut mem, &a, |n: &Node| &n.next, b).unwrap();
+    let mut root = mem.push(Node::new(0)).unwrap();
+    GcCell::set(&mut mem, &root, |n: &Node| &n.next, a).unwrap();
+
+    let progress = unsafe{ mem.gc_step(vec![&mut root], vec![], Budget::Objects(2)) };
+    assert!(matches!(progress, GcPhaseProgress::Marking{ .. }));
+    // move x and y from the unscanned b to the already scanned root
+    let old = GcCell::set(&mut mem, &root, |n: &Node| &n.next, x).unwrap();
+    assert_eq!(old, a);
+    GcRefCell::update(&mut mem, &root, |n: &Node| &n.edges, |edges| edges.push(y)).unwrap();
+    GcRefCell::update(&mut mem, &b, |n: &Node| &n.edges, |edges| edges.clear()).unwrap();
+
+    while let GcPhaseProgress::Marking{ .. } = unsafe{ mem.gc_step(vec![&mut root], vec![], Budget::Objects(1)) }{}
+    let root_node = mem.get_by_ref(&root).unwrap();
+    assert_eq!(mem.get_by_ref(&root_node.next.get()).unwrap().id, 4);
+    assert_eq!(mem.get_by_ref(&root_node.edges.borrow()[0]).unwrap().id, 5);
+    // a and b survive, as they were reached before root was unlinked from a
+    assert_eq!(mem.len(), 5);
+}
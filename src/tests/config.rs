@@ -0,0 +1,41 @@
+use crate::gc::config::{GrowthPolicy, MemConfig};
+use crate::gc::ManagedMem;
+use crate::gc::mas::MarkAndSweepMem;
+
+#[test]
+fn test_config_builder(){
+    let config = MemConfig::new(100)
+        .with_growth(GrowthPolicy::Multiply(2))
+        .with_occupancy_trigger(0.75)
+        .with_threads(4);
+    assert_eq!(config.heap_size(), 100);
+    assert_eq!(config.allocation_trigger(), None);
+    assert_eq!(config.growth().grow(100, 350), Some(400));
+    assert_eq!(GrowthPolicy::Add(64).grow(100, 150), Some(164));
+    assert_eq!(GrowthPolicy::Fixed.grow(100, 150), None);
+
+    let mut mem = MarkAndSweepMem::<u32>::new(config.clone());
+    assert_eq!(mem.config(), &config);
+    assert!(mem.push(Box::new(1)).is_ok());
+    assert_eq!(MarkAndSweepMem::<u32>::new(16).config(), &MemConfig::new(16));
+}
+
+#[test]
+fn test_allocation_profiling(){
+    let mut mem = MarkAndSweepMem::<u32>::new(MemConfig::new(100).with_allocation_profiling(true));
+    let loop_line = line!() + 2;
+    for i in 0..3{
+        mem.push(Box::new(i)).unwrap();
+    }
+    // call sites are still found through the object-safe facade
+    let mut boxed: Box<dyn crate::gc::dyn_mem::DynManagedMem<u32>> = Box::new(mem);
+    let boxed_line = line!() + 1;
+    boxed.push(Box::new(4)).unwrap();
+
+    let report = boxed.allocation_profile().unwrap().report();
+    assert_eq!(report.len(), 2);
+    assert_eq!((report[0].0.file(), report[0].0.line()), (file!(), loop_line));
+    assert_eq!((report[0].1.count(), report[0].1.bytes()), (3, 12));
+    assert_eq!((report[1].0.line(), report[1].1.count()), (boxed_line, 1));
+    assert!(MarkAndSweepMem::<u32>::new(100).allocation_profile().is_none());
+}
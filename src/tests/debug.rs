@@ -0,0 +1,62 @@
+use crate::gc::{GcCandidate, ManagedMem, NoGcMem};
+use crate::gc::debug::{write_dot, write_layout_map, write_layout_svg};
+use crate::gc::impls::{adjust_option, collect_all};
+use crate::heap::Heap;
+
+struct Node{
+    next: Option<*const Node>
+}
+
+impl GcCandidate for Node{
+    fn collect_managed_pointers(&self, _this: &*const Node) -> Vec<*const Node>{
+        return collect_all(&self.next);
+    }
+
+    fn adjust_ptrs(&mut self, adjust: impl Fn(&*const Node) -> *const Node, _this: &*const Node){
+        adjust_option(&mut self.next, adjust);
+    }
+}
+
+#[test]
+fn test_write_dot(){
+    let mut mem = NoGcMem::<Node>::new(100);
+    let a = mem.push(Box::new(Node{ next: None })).unwrap();
+    let b = mem.push(Box::new(Node{ next: Some(a) })).unwrap();
+    mem.push(Box::new(Node{ next: Some(std::ptr::null()) })).unwrap();
+
+    let mut out = Vec::new();
+    write_dot(&mem, &[b], &mut out).unwrap();
+    let dot = String::from_utf8(out).unwrap();
+    assert!(dot.starts_with("digraph heap {"));
+    assert!(dot.contains("n1 -> n0;"));
+    assert!(dot.contains("n2 -> external [style=dashed];"));
+    assert_eq!(dot.matches("fillcolor").count(), 1);
+    assert!(dot.lines().any(|l| l.starts_with("    n1 [") && l.contains("fillcolor")));
+}
+
+#[test]
+fn test_write_layout(){
+    let mut heap = Heap::<u64>::new(128);
+    let ptrs: Vec<*const u64> = (0..6).map(|i| heap.push(Box::new(i)).unwrap()).collect();
+    // the second value's space is freed, and the fourth's is left dead
+    heap.sweep(|p| *p != ptrs[1]);
+    heap.retain(|p, _| *p != ptrs[3]);
+
+    let buckets = heap.occupancy(16);
+    assert_eq!(buckets.len(), 8);
+    assert_eq!((buckets[0].live(), buckets[0].free()), (8, 8));
+    assert_eq!((buckets[1].live(), buckets[1].dead()), (8, 8));
+    assert_eq!(buckets[7].unused(), 16);
+    assert!(buckets.iter().all(|b| b.size() == 16));
+
+    let mut out = Vec::new();
+    write_layout_map(&heap, 8, false, &mut out).unwrap();
+    let map = String::from_utf8(out).unwrap();
+    assert_eq!(map, "0x00000000 |#.#x##          |\nlive 32B, dead 8B, free 8B, unused 80B\n");
+
+    let mut out = Vec::new();
+    write_layout_svg(&heap, 8, &mut out).unwrap();
+    let svg = String::from_utf8(out).unwrap();
+    assert!(svg.starts_with("<svg"));
+    assert_eq!(svg.matches("<rect").count(), 16);
+}
@@ -0,0 +1,132 @@
+use crate::gc::{EdgeVisitor, GcCandidate, ManagedMem};
+use crate::gc::impls::{adjust_option, collect_all, trace_option};
+use crate::gc::mas::MarkAndSweepMem;
+use crate::heap::AllocError;
+
+// a value implementing only the methods used by collectors
+struct Link{
+    id: u32,
+    next: Option<*const Link>
+}
+
+// a value implementing only the methods that collectors used to use
+struct OldLink{
+    next: Option<*const OldLink>
+}
+
+impl GcCandidate for Link{
+    fn trace(&self, visitor: &mut impl FnMut(&*const Link), _this: &*const Link){
+        trace_option(&self.next, visitor);
+    }
+
+    fn visit_edges(&mut self, v: &mut impl EdgeVisitor<*const Link>, _this: &*const Link){
+        if let Some(next) = &mut self.next{
+            v.visit_edge(next);
+        }
+    }
+}
+
+impl GcCandidate for OldLink{
+    fn collect_managed_pointers(&self, _this: &*const OldLink) -> Vec<*const OldLink>{
+        return collect_all(&self.next);
+    }
+
+    fn adjust_ptrs(&mut self, adjust: impl Fn(&*const OldLink) -> *const OldLink, _this: &*const OldLink){
+        adjust_option(&mut self.next, adjust);
+    }
+}
+
+// a memory implementing only the methods that have no default
+struct MinimalMem{
+    mem: MarkAndSweepMem<u64>
+}
+
+impl ManagedMem<u64> for MinimalMem{
+    fn push(&mut self, v: Box<u64>) -> Result<*const u64, AllocError<u64>>{
+        return self.mem.push(v);
+    }
+
+    fn push_with(&mut self, v: Box<u64>, with: impl FnOnce(*const u64) -> *const u64) -> Result<*const u64, AllocError<u64>>{
+        return self.mem.push_with(v, with);
+    }
+
+    fn get(&self, idx: usize) -> &u64{
+        return self.mem.get(idx);
+    }
+
+    fn get_mut(&mut self, idx: usize) -> &mut u64{
+        return self.mem.get_mut(idx);
+    }
+
+    fn get_by(&mut self, ptr: &*const u64) -> Option<&mut u64>{
+        return self.mem.get_by(ptr);
+    }
+
+    fn len(&self) -> usize{
+        return self.mem.len();
+    }
+
+    fn contains_ptr(&self, ptr: &*const u64) -> bool{
+        return self.mem.contains_ptr(ptr);
+    }
+
+    fn for_each(&self, cb: impl FnMut(&u64, &*const u64)){
+        self.mem.for_each(cb);
+    }
+
+    fn retain(&mut self, f: impl FnMut(&*const u64, &u64) -> bool){
+        self.mem.retain(f);
+    }
+
+    unsafe fn gc(&mut self, roots: Vec<*mut *const u64>, weaks: Vec<*mut *const u64>){
+        self.mem.gc(roots, weaks);
+    }
+}
+
+#[test]
+fn test_default_get_by_ref(){
+    let mut mem = MinimalMem{ mem: MarkAndSweepMem::new(64) };
+    let ptrs: Vec<_> = (0..4).map(|i| mem.push(Box::new(i * 10)).unwrap()).collect();
+    for (i, ptr) in ptrs.iter().enumerate(){
+        assert_eq!(mem.get_by_ref(ptr), Some(&(i as u64 * 10)));
+    }
+    assert_eq!(mem.get_by_ref(&std::ptr::null()), None);
+    // a pointer into the middle of a value isn't found
+    assert_eq!(mem.get_by_ref(&ptrs[1].cast::<u8>().wrapping_add(1).cast()), None);
+}
+
+#[test]
+fn test_visit_edges_only(){
+    let mut mem = MarkAndSweepMem::<Link>::new(256);
+    mem.push(Box::new(Link{ id: 0, next: None })).unwrap();
+    let tail = mem.push(Box::new(Link{ id: 2, next: None })).unwrap();
+    let mut head = mem.push(Box::new(Link{ id: 1, next: Some(tail) })).unwrap();
+    // the garbage before them is dropped, so both values move, and the head's edge is relocated
+    let report = unsafe{ mem.gc_with_report(vec![&mut head], vec![]) };
+    assert_eq!(report.objects_reclaimed(), 1);
+    let next = mem.get_by_ref(&head).unwrap().next.unwrap();
+    assert_ne!(next, tail);
+    assert_eq!(mem.get_by_ref(&next).unwrap().id, 2);
+
+    // the other methods are derived from them
+    let mut link = Link{ id: 3, next: Some(next) };
+    assert_eq!(link.collect_managed_pointers(&head), vec![next]);
+    link.adjust_ptrs(|_| head, &head);
+    assert_eq!(link.next, Some(head));
+}
+
+#[test]
+fn test_default_visit_edges(){
+    let (x, y) = (OldLink{ next: None }, OldLink{ next: None });
+    let (a, b) = (&x as *const OldLink, &y as *const OldLink);
+    let mut link = OldLink{ next: Some(a) };
+    let mut visited = Vec::new();
+    link.visit_edges(&mut |edge: &mut *const OldLink| {
+        visited.push(*edge);
+        *edge = b;
+    }, &a);
+    assert_eq!((visited, link.next), (vec![a], Some(b)));
+    let mut traced = Vec::new();
+    link.trace(&mut |edge: &*const OldLink| traced.push(*edge), &a);
+    assert_eq!(traced, vec![b]);
+}
@@ -0,0 +1,24 @@
+use crate::gc::{ManagedMem, NoGcMem};
+use crate::gc::dyn_mem::DynManagedMem;
+use crate::gc::mas::MarkAndSweepMem;
+
+#[test]
+fn test_dyn_managed_mem(){
+    let mems: Vec<Box<dyn DynManagedMem<u32>>> = vec![
+        Box::new(NoGcMem::new(64)),
+        Box::new(MarkAndSweepMem::new(64))
+    ];
+    let mut lens = Vec::new();
+    for mut mem in mems{
+        let mut root = ManagedMem::push(&mut mem, Box::new(1)).unwrap();
+        ManagedMem::push_with(&mut mem, Box::new(2), |p| p).unwrap();
+        let mut sum = 0;
+        ManagedMem::for_each(&mem, |v, _| sum += v);
+        assert_eq!(sum, 3);
+        unsafe{
+            ManagedMem::gc(&mut mem, vec![&mut root], vec![]);
+        }
+        lens.push(ManagedMem::len(&mem));
+    }
+    assert_eq!(lens, vec![2, 1]);
+}
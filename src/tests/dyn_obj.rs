@@ -0,0 +1,205 @@
+// Test storing trait objects of different types and alignments in the same heap
+
+use std::mem;
+use crate::gc::{GcCandidate, ManagedMem};
+use crate::gc::mas::MarkAndSweepMem;
+use crate::heap::{AllocErrorKind, DynSized, Heap};
+
+// spell out the lifetime, as `*const dyn Shape` in argument position has an elided lifetime
+type ShapePtr = *const (dyn Shape + 'static);
+
+trait Shape{
+    fn area(&self) -> u64;
+    fn children(&self) -> &[ShapePtr];
+    fn children_mut(&mut self) -> &mut [ShapePtr];
+}
+
+struct Square(u8);
+
+struct Group{
+    scale: u64,
+    members: [ShapePtr; 2]
+}
+
+impl Shape for Square{
+    fn area(&self) -> u64{
+        return (self.0 as u64) * (self.0 as u64);
+    }
+
+    fn children(&self) -> &[ShapePtr]{
+        return &[];
+    }
+
+    fn children_mut(&mut self) -> &mut [ShapePtr]{
+        return &mut [];
+    }
+}
+
+impl Shape for Group{
+    fn area(&self) -> u64{
+        return self.scale * self.members.iter().map(|m| unsafe{ (**m).area() }).sum::<u64>();
+    }
+
+    fn children(&self) -> &[ShapePtr]{
+        return &self.members;
+    }
+
+    fn children_mut(&mut self) -> &mut [ShapePtr]{
+        return &mut self.members;
+    }
+}
+
+unsafe impl DynSized for dyn Shape{
+    fn dyn_align() -> usize{
+        return mem::align_of::<Group>();
+    }
+}
+
+impl GcCandidate for dyn Shape{
+    fn collect_managed_pointers(&self, _this: &ShapePtr) -> Vec<ShapePtr>{
+        return self.children().to_vec();
+    }
+
+    fn adjust_ptrs(&mut self, adjust: impl Fn(&ShapePtr) -> ShapePtr, _this: &ShapePtr){
+        for child in self.children_mut(){
+            *child = adjust(child);
+        }
+    }
+}
+
+// a trait whose objects use the default alignment
+trait Label{
+    fn text(&self) -> String;
+}
+
+#[repr(align(32))]
+struct Wide(u8);
+
+#[repr(align(8192))]
+struct Huge(u8);
+
+impl Label for u8{
+    fn text(&self) -> String{
+        return self.to_string();
+    }
+}
+
+impl Label for u64{
+    fn text(&self) -> String{
+        return self.to_string();
+    }
+}
+
+impl Label for Wide{
+    fn text(&self) -> String{
+        return self.0.to_string();
+    }
+}
+
+impl Label for Huge{
+    fn text(&self) -> String{
+        return self.0.to_string();
+    }
+}
+
+unsafe impl DynSized for dyn Label{}
+
+// a trait whose objects are placed at a greater alignment than they require
+trait Slot{
+    fn id(&self) -> u8;
+}
+
+impl Slot for u8{
+    fn id(&self) -> u8{
+        return *self;
+    }
+}
+
+unsafe impl DynSized for dyn Slot{
+    fn dyn_align_of_val(&self) -> usize{
+        return 64;
+    }
+}
+
+#[test]
+fn test_trait_objects(){
+    let mut heap = MarkAndSweepMem::<dyn Shape>::new(500);
+    let a = heap.push(Box::new(Square(2))).unwrap();
+    heap.push(Box::new(Square(7))).unwrap();
+    let b = heap.push(Box::new(Square(3))).unwrap();
+    // squares are placed by their own alignment
+    assert_eq!(b.cast::<u8>() as usize - a.cast::<u8>() as usize, 2);
+    let mut group = heap.push(Box::new(Group{ scale: 2, members: [a, b] })).unwrap();
+    assert_eq!(group.cast::<u8>() as usize % mem::align_of::<Group>(), 0);
+
+    unsafe{
+        heap.gc(vec![&mut group], vec![]);
+        assert_eq!(heap.len(), 3);
+        assert_eq!((*group).area(), 26);
+    }
+}
+
+#[test]
+fn test_bitmap_marking(){
+    // squares a byte apart are marked separately from each other and the groups between them
+    let mut heap = MarkAndSweepMem::<dyn Shape>::new(500);
+    let squares: Vec<ShapePtr> = (1..=6).map(|i| heap.push(Box::new(Square(i))).unwrap()).collect();
+    let mut group = heap.push(Box::new(Group{ scale: 1, members: [squares[1], squares[4]] })).unwrap();
+    heap.push(Box::new(Square(7))).unwrap();
+    let mut root = squares[2];
+    let report = unsafe{ heap.gc_with_report(vec![&mut group, &mut root], vec![]) };
+    assert_eq!((report.objects_reclaimed(), heap.len()), (4, 4));
+    // marks don't carry over to the next collection
+    let report = unsafe{ heap.gc_with_report(vec![&mut group, &mut root], vec![]) };
+    assert_eq!((report.objects_reclaimed(), heap.len()), (0, 4));
+    unsafe{
+        assert_eq!((*group).area(), 29);
+        assert_eq!((*root).area(), 9);
+    }
+}
+
+#[test]
+fn test_growing_to_fit_survivors(){
+    // squares packed before the group take padding after it once evacuated in reverse order
+    let mut heap = MarkAndSweepMem::<dyn Shape>::new(49);
+    let mut squares: Vec<ShapePtr> = (1..=8).map(|i| heap.push(Box::new(Square(i))).unwrap()).collect();
+    let mut group = heap.push(Box::new(Group{ scale: 1, members: [squares[0], squares[7]] })).unwrap();
+    squares.push(heap.push(Box::new(Square(9))).unwrap());
+    assert_eq!(heap.used_bytes(), 49);
+
+    let mut roots: Vec<*mut ShapePtr> = squares.iter_mut().map(|p| p as *mut _).collect();
+    roots.push(&mut group);
+    let report = unsafe{ heap.gc_with_report(roots, vec![]) };
+    assert_eq!(report.resize(), Some((49, 56)));
+    assert_eq!(heap.capacity(), Some(56));
+    assert_eq!(heap.len(), 10);
+    unsafe{
+        assert_eq!((*group).area(), 65);
+        assert_eq!(squares.iter().map(|p| (**p).area()).sum::<u64>(), 285);
+    }
+}
+
+#[test]
+fn test_default_alignment(){
+    let mut heap = Heap::<dyn Label>::new(64);
+    let a = heap.push(Box::new(1u8)).unwrap();
+    let b = heap.push(Box::new(Wide(2))).unwrap();
+    // values are placed by their own alignment, so only padded up to the next 32 bytes
+    assert_eq!(b.cast::<u8>() as usize - a.cast::<u8>() as usize, 32);
+    assert_eq!(heap.get_by_ref(&b).unwrap().text(), "2");
+    let err = heap.push(Box::new(Huge(3))).unwrap_err();
+    assert_eq!((err.kind(), err.value().text()), (AllocErrorKind::Alignment, "3".to_string()));
+    assert_eq!(err.to_string(), "value requires alignment of 8192 bytes, greater than the heap's");
+    assert_eq!(heap.len(), 2);
+}
+
+#[test]
+fn test_overridden_alignment(){
+    let mut heap = Heap::<dyn Slot>::new(256);
+    let ptrs: Vec<*const dyn Slot> = (1..=3u8).map(|i| heap.push(Box::new(i)).unwrap()).collect();
+    // each value is placed at the alignment its type gives, rather than the byte it requires
+    for (i, ptr) in ptrs.iter().enumerate(){
+        assert_eq!(ptr.cast::<u8>() as usize % 64, 0);
+        assert_eq!(heap.get_by_ref(ptr).unwrap().id(), i as u8 + 1);
+    }
+}
@@ -0,0 +1,61 @@
+use std::ffi::c_void;
+use std::ptr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use crate::ffi::*;
+
+#[repr(C)]
+struct Cons{
+    value: i64,
+    next: *mut c_void
+}
+
+unsafe extern "C" fn trace_cons(object: *mut c_void, visit: SwiferVisitFn, ctx: *mut c_void){
+    visit(&mut (*(object as *mut Cons)).next, ctx);
+}
+
+static FINALIZED: AtomicUsize = AtomicUsize::new(0);
+
+unsafe extern "C" fn finalize_cons(_object: *mut c_void){
+    FINALIZED.fetch_add(1, Ordering::Relaxed);
+}
+
+unsafe fn cons(heap: *mut SwiferHeap, value: i64, next: *mut c_void) -> *mut c_void{
+    let object = swifer_alloc(heap, std::mem::size_of::<Cons>(), Some(trace_cons), Some(finalize_cons));
+    *(object as *mut Cons) = Cons{ value, next };
+    return object;
+}
+
+#[test]
+fn test_ffi_heap(){
+    unsafe{
+        let heap = swifer_heap_new(1024);
+        let mut list = ptr::null_mut();
+        for i in 0..3{
+            list = cons(heap, i, list);
+        }
+        cons(heap, 10, ptr::null_mut());
+        assert_eq!(swifer_heap_len(heap), 4);
+        assert_eq!(swifer_heap_used(heap), 4 * 48);
+
+        swifer_add_root(heap, &mut list);
+        let before = list;
+        swifer_gc(heap);
+        assert_eq!(swifer_heap_len(heap), 3);
+        assert_eq!(FINALIZED.load(Ordering::Relaxed), 1);
+        assert_ne!(list, before);
+        let mut values = Vec::new();
+        let mut current = list;
+        while !current.is_null(){
+            let cell = &*(current as *const Cons);
+            values.push(cell.value);
+            current = cell.next;
+        }
+        assert_eq!(values, vec![2, 1, 0]);
+
+        // allocation fails without collecting when the heap is full
+        assert!(swifer_alloc(heap, 1024, None, None).is_null());
+        swifer_remove_root(heap, &mut list);
+        swifer_heap_free(heap);
+        assert_eq!(FINALIZED.load(Ordering::Relaxed), 4);
+    }
+}
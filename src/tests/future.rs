@@ -0,0 +1,39 @@
+use std::future::Future;
+use std::pin::pin;
+use std::ptr;
+use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+use crate::gc::ManagedMem;
+use crate::gc::future::AsyncCollector;
+use crate::gc::incremental::Budget;
+use crate::gc::mas::MarkAndSweepMem;
+
+// a waker that does nothing, as the test polls in a loop anyway
+fn noop_waker() -> Waker{
+    const VTABLE: RawWakerVTable = RawWakerVTable::new(|_| RawWaker::new(ptr::null(), &VTABLE), |_| {}, |_| {}, |_| {});
+    // safety: every function in the vtable ignores the data pointer
+    return unsafe{ Waker::from_raw(RawWaker::new(ptr::null(), &VTABLE)) };
+}
+
+#[test]
+fn test_run_gc(){
+    let mut mem = MarkAndSweepMem::<u64>::new(4096);
+    let roots: Vec<*const u64> = (0..5).map(|i| mem.push(Box::new(i)).unwrap()).collect();
+    mem.push(Box::new(9)).unwrap();
+    let mut collector = AsyncCollector::new(mem, roots, Budget::Objects(2));
+
+    let waker = noop_waker();
+    let mut cx = Context::from_waker(&waker);
+    let mut future = pin!(collector.run_gc());
+    let report = loop{
+        if let Poll::Ready(report) = future.as_mut().poll(&mut cx){
+            break report;
+        }
+    };
+    // five roots marked two at a time, and a final step to sweep
+    assert_eq!(future.steps(), 3);
+    assert_eq!(report.objects_reclaimed(), 1);
+    assert_eq!(collector.mem().len(), 5);
+    for (i, root) in collector.roots().iter().enumerate(){
+        assert_eq!(collector.mem().get_by_ref(root), Some(&(i as u64)));
+    }
+}
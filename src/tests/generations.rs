@@ -0,0 +1,86 @@
+use crate::gc::ManagedMem;
+use crate::gc::mas::MarkAndSweepMem;
+use crate::heap::AllocError;
+
+// a memory that pretends minor collections can't free anything, recording every collection
+struct GenMem{
+    mem: MarkAndSweepMem<u64>,
+    collections: Vec<&'static str>
+}
+
+impl ManagedMem<u64> for GenMem{
+    fn push(&mut self, v: Box<u64>) -> Result<*const u64, AllocError<u64>>{
+        return self.mem.push(v);
+    }
+
+    fn push_with(&mut self, v: Box<u64>, with: impl FnOnce(*const u64) -> *const u64) -> Result<*const u64, AllocError<u64>>{
+        return self.mem.push_with(v, with);
+    }
+
+    fn get(&self, idx: usize) -> &u64{
+        return self.mem.get(idx);
+    }
+
+    fn get_mut(&mut self, idx: usize) -> &mut u64{
+        return self.mem.get_mut(idx);
+    }
+
+    fn get_by(&mut self, ptr: &*const u64) -> Option<&mut u64>{
+        return self.mem.get_by(ptr);
+    }
+
+    fn get_by_ref(&self, ptr: &*const u64) -> Option<&u64>{
+        return self.mem.get_by_ref(ptr);
+    }
+
+    fn len(&self) -> usize{
+        return self.mem.len();
+    }
+
+    fn contains_ptr(&self, ptr: &*const u64) -> bool{
+        return self.mem.contains_ptr(ptr);
+    }
+
+    fn for_each(&self, cb: impl FnMut(&u64, &*const u64)){
+        self.mem.for_each(cb);
+    }
+
+    fn retain(&mut self, f: impl FnMut(&*const u64, &u64) -> bool){
+        self.mem.retain(f);
+    }
+
+    unsafe fn gc(&mut self, roots: Vec<*mut *const u64>, weaks: Vec<*mut *const u64>){
+        self.gc_minor(roots, weaks);
+    }
+
+    unsafe fn gc_minor(&mut self, _roots: Vec<*mut *const u64>, _weaks: Vec<*mut *const u64>){
+        self.collections.push("minor");
+    }
+
+    unsafe fn gc_full(&mut self, roots: Vec<*mut *const u64>, weaks: Vec<*mut *const u64>){
+        self.collections.push("full");
+        self.mem.gc(roots, weaks);
+    }
+
+    fn is_generational(&self) -> bool{
+        return true;
+    }
+}
+
+#[test]
+fn test_minor_before_full(){
+    let mut mem = GenMem{ mem: MarkAndSweepMem::new(16), collections: vec![] };
+    let mut roots = vec![mem.push(Box::new(1)).unwrap()];
+    mem.push(Box::new(2)).unwrap();
+    mem.push_or_collect(Box::new(3), &mut roots).unwrap();
+    assert_eq!(mem.collections, vec!["minor", "full"]);
+    assert_eq!(mem.len(), 2);
+    assert_eq!(mem.get_by_ref(&roots[0]), Some(&1));
+
+    // non-generational collectors skip straight to a full collection
+    let mut plain = MarkAndSweepMem::<u64>::new(8);
+    plain.push(Box::new(1)).unwrap();
+    assert!(!plain.is_generational());
+    plain.push_or_collect(Box::new(2), &mut Vec::new()).unwrap();
+    assert_eq!(plain.len(), 1);
+}
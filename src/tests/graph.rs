@@ -0,0 +1,44 @@
+use crate::gc::{GcCandidate, ManagedMem};
+use crate::gc::config::MemConfig;
+use crate::gc::impls::{adjust_slice, collect_all};
+use crate::gc::mas::MarkAndSweepMem;
+use crate::testing::check_all;
+use crate::testing::graph::GraphConfig;
+
+struct Node{
+    id: usize,
+    edges: Vec<*const Node>
+}
+
+impl GcCandidate for Node{
+    fn collect_managed_pointers(&self, _this: &*const Node) -> Vec<*const Node>{
+        return collect_all(&self.edges);
+    }
+
+    fn adjust_ptrs(&mut self, adjust: impl Fn(&*const Node) -> *const Node, _this: &*const Node){
+        adjust_slice(&mut self.edges, adjust);
+    }
+}
+
+#[test]
+fn test_random_graph(){
+    for seed in 0..20{
+        let config = GraphConfig::new(40).with_fan_out(3).with_cycle_density(0.3).with_roots(3).with_seed(seed);
+        let mut mem = MarkAndSweepMem::<Node>::new(MemConfig::new(4096).with_heap_verification(true));
+        let graph = config.generate(&mut mem, |id| Box::new(Node{ id, edges: Vec::new() }), |node, to| node.edges.push(to)).unwrap();
+        let same = config.generate(&mut MarkAndSweepMem::<Node>::new(4096), |id| Box::new(Node{ id, edges: Vec::new() }), |node, to| node.edges.push(to)).unwrap();
+        assert_eq!((graph.edges(), graph.roots()), (same.edges(), same.roots()));
+        assert_eq!(graph.roots().len(), 3);
+        assert_eq!(graph.roots()[0], 0);
+        assert_eq!(check_all(&mut mem), Ok(()));
+
+        let mut roots = graph.root_pointers();
+        unsafe{
+            mem.gc(roots.iter_mut().map(|r| r as *mut _).collect(), vec![]);
+        }
+        let mut kept = Vec::new();
+        mem.for_each(|node, _| kept.push(node.id));
+        kept.sort_unstable();
+        assert_eq!(kept, graph.reachable());
+    }
+}
@@ -0,0 +1,64 @@
+use crate::gc::{GcCandidate, ManagedMem};
+use crate::gc::config::{ForeignPtrPolicy, MemConfig};
+use crate::gc::group::HeapGroup;
+use crate::gc::impls::{adjust_slice, collect_all, trace_slice};
+use crate::gc::mas::MarkAndSweepMem;
+
+struct Node{
+    id: u32,
+    edges: Vec<*const Node>
+}
+
+impl GcCandidate for Node{
+    fn collect_managed_pointers(&self, _this: &*const Node) -> Vec<*const Node>{
+        return collect_all(&self.edges);
+    }
+
+    fn trace(&self, visitor: &mut impl FnMut(&*const Node), _this: &*const Node){
+        trace_slice(&self.edges, visitor);
+    }
+
+    fn adjust_ptrs(&mut self, adjust: impl Fn(&*const Node) -> *const Node, _this: &*const Node){
+        adjust_slice(&mut self.edges, adjust);
+    }
+}
+
+fn space() -> MarkAndSweepMem<Node>{
+    return MarkAndSweepMem::new(MemConfig::new(1024).with_foreign_pointers(ForeignPtrPolicy::Skip));
+}
+
+fn node(id: u32, edges: Vec<*const Node>) -> Box<Node>{
+    return Box::new(Node{ id, edges });
+}
+
+#[test]
+fn test_heap_group(){
+    let mut group = HeapGroup::new(Vec::<*const Node>::new());
+    let (a, b) = (group.add(space()), group.add(space()));
+    // a cycle between the two memories, reachable from a root
+    let first = group.member_mut(a).push(node(1, vec![])).unwrap();
+    let second = group.member_mut(b).push(node(2, vec![first])).unwrap();
+    group.member_mut(a).write_field(&first, |n| n.edges.push(second));
+    // and an unreachable one
+    let third = group.member_mut(a).push(node(3, vec![])).unwrap();
+    let fourth = group.member_mut(b).push(node(4, vec![third])).unwrap();
+    group.member_mut(a).write_field(&third, |n| n.edges.push(fourth));
+    group.roots_mut().push(first);
+
+    let reports = group.gc();
+    assert_eq!(reports.len(), 2);
+    assert_eq!((reports[0].objects_reclaimed(), reports[1].objects_reclaimed()), (1, 1));
+    assert_eq!((group.member(a).len(), group.member(b).len()), (1, 1));
+    // both values moved, and pointers across are kept up to date
+    let first = group.roots()[0];
+    let second = group.get_by_ref(&first).unwrap().edges[0];
+    assert_eq!(group.member_of(&second), Some(b));
+    assert_eq!(group.get_by_ref(&second).unwrap().id, 2);
+    assert_eq!(group.get_by_ref(&second).unwrap().edges[0], first);
+    assert_eq!(group.get_by_ref(&first).unwrap().id, 1);
+
+    // without the root, the cycle is reclaimed
+    group.roots_mut().clear();
+    group.gc();
+    assert!(group.members().iter().all(|m| m.len() == 0));
+}
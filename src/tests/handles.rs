@@ -0,0 +1,40 @@
+use crate::gc::ManagedMem;
+use crate::gc::handles::HandleTable;
+use crate::gc::mas::MarkAndSweepMem;
+use crate::gc::roots::RootProvider;
+
+#[test]
+fn test_handle_table(){
+    let mut heap = MarkAndSweepMem::<u64>::new(4096);
+    let mut handles = HandleTable::new();
+    handles.open_scope();
+    let one = handles.new_local(heap.push(Box::new(1)).unwrap());
+    let kept = handles.persist(heap.push(Box::new(2)).unwrap());
+
+    handles.with_scope(|handles| handles.new_local(heap.push(Box::new(3)).unwrap()));
+    handles.open_scope();
+    let four = handles.new_local(heap.push(Box::new(4)).unwrap());
+    handles.new_local(heap.push(Box::new(5)).unwrap());
+    let escaped = handles.escape(four).unwrap();
+    assert!(handles.local(four).is_none());
+    assert_eq!(handles.scope_depth(), 1);
+    assert_eq!(handles.local_count(), 2);
+
+    // safety: guaranteed by RootProvider
+    unsafe{ heap.gc(handles.roots(), handles.weaks()) };
+    assert_eq!(heap.len(), 3);
+    assert_eq!(heap.get_by_ref(handles.local(one).unwrap()), Some(&1));
+    assert_eq!(heap.get_by_ref(handles.local(escaped).unwrap()), Some(&4));
+    assert_eq!(heap.get_by_ref(handles.persistent(&kept).unwrap()), Some(&2));
+
+    // persistent handles outlive every scope, until released
+    handles.close_scope();
+    assert!(handles.local(one).is_none());
+    unsafe{ heap.gc(handles.roots(), handles.weaks()) };
+    assert_eq!(heap.len(), 1);
+    assert_eq!(heap.get_by_ref(handles.persistent(&kept).unwrap()), Some(&2));
+    assert!(handles.release(kept).is_some());
+    assert_eq!(handles.persistent_count(), 0);
+    unsafe{ heap.gc(handles.roots(), handles.weaks()) };
+    assert_eq!(heap.len(), 0);
+}
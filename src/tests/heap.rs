@@ -1,10 +1,12 @@
-use std::mem;
+use std::{mem, ptr};
 use std::sync::atomic::{AtomicU8, Ordering};
-use crate::heap::{DynSized, Heap};
+use crate::heap::{AllocErrorKind, DynSized, Heap, POISON};
+use crate::heap::classes::BLOCK_SIZE;
 
 use dyn_struct2::dyn_arg;
 use dyn_struct_derive2::DynStruct;
-use crate::gc::GcCandidate;
+use crate::gc::{GcCandidate, ManagedMem, NoGcMem};
+use crate::gc::mas::MarkAndSweepMem;
 
 // setup the heap allocated struct
 
@@ -53,4 +55,328 @@ fn test_basic_push_drop(){
     drop(heap2);
 
     assert_eq!(DROP_COUNTER.load(Ordering::Relaxed), 3);
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_alloc_errors(){
+    let mut heap = Heap::<[u8]>::new(4);
+    heap.push(Box::new([1, 2, 3])).unwrap();
+
+    let err = heap.push(Box::new([4, 5])).unwrap_err();
+    assert_eq!(err.kind(), AllocErrorKind::OutOfSpace);
+    assert_eq!(err.into_value().as_ref(), &[4, 5]);
+
+    let err = heap.push(Box::new([0; 5])).unwrap_err();
+    assert_eq!(err.kind(), AllocErrorKind::TooLarge);
+    assert_eq!(heap.len(), 1);
+}
+
+#[test]
+fn test_retain(){
+    let mut heap = Heap::<u32>::new(16);
+    for i in 0..4{
+        heap.push(Box::new(i)).unwrap();
+    }
+    heap.retain(|_, v| *v % 2 == 0);
+    assert_eq!(heap.len(), 2);
+    assert_eq!(*heap.get(1), 2);
+    // the last value was dropped, so its space can be reused
+    heap.push(Box::new(5)).unwrap();
+    assert!(heap.push(Box::new(6)).is_err());
+}
+
+#[test]
+fn test_take_where(){
+    let mut heap = Heap::<u32>::new(32);
+    let ptrs: Vec<_> = (0..6).map(|i| heap.push(Box::new(i)).unwrap()).collect();
+    let taken = heap.take_where(|_, v| *v % 3 == 0);
+    assert_eq!(taken.iter().map(|(v, p)| (**v, *p)).collect::<Vec<_>>(), vec![(0, ptrs[0]), (3, ptrs[3])]);
+    assert_eq!(heap.len(), 4);
+    assert_eq!((*heap.get(0), *heap.get(3)), (1, 5));
+    assert!(!heap.contains_ptr(&ptrs[3]));
+    let (value, ptr) = heap.take(1);
+    assert_eq!((*value, ptr), (2, ptrs[2]));
+}
+
+#[test]
+fn test_push_slice(){
+    let mut heap = Heap::<[u16]>::new(16);
+    let a = heap.push_slice(&[1, 2, 3]).unwrap();
+    let b = heap.push_slice_with_len(4, |i| i as u16 * 10).unwrap();
+    assert_eq!(heap.get_by_ref(&a).unwrap(), &[1, 2, 3]);
+    assert_eq!(heap.get_by_ref(&b).unwrap(), &[0, 10, 20, 30]);
+    assert_eq!(heap.push_slice(&[0; 2]), Err(AllocErrorKind::OutOfSpace));
+    assert_eq!(heap.push_slice_with_len(9, |_| 0), Err(AllocErrorKind::TooLarge));
+    assert_eq!(heap.len(), 2);
+}
+
+#[test]
+fn test_alloc_uninit(){
+    let mut heap = Heap::<[u16]>::with_headers(64);
+    let (ptr, bytes) = heap.alloc_uninit(6, 2, ptr::slice_from_raw_parts(ptr::null(), 3)).unwrap();
+    // built field by field, then committed
+    for (i, value) in [5u16, 6, 7].into_iter().enumerate(){
+        bytes[i * 2].write(value.to_ne_bytes()[0]);
+        bytes[i * 2 + 1].write(value.to_ne_bytes()[1]);
+    }
+    assert!(!heap.contains_ptr(&ptr));
+    assert_eq!(heap.len(), 0);
+    unsafe{
+        heap.commit_uninit(ptr);
+    }
+    assert_eq!(heap.get_by_ref(&ptr).unwrap(), &[5, 6, 7]);
+    assert_eq!(heap.header(&ptr).unwrap().size(), 6);
+    assert!(heap.alloc_uninit(2, 3, ptr::slice_from_raw_parts(ptr::null(), 1)).is_none());
+    assert!(heap.alloc_uninit(128, 2, ptr::slice_from_raw_parts(ptr::null(), 64)).is_none());
+}
+
+#[test]
+fn test_zero_sized_values(){
+    let mut heap = Heap::<()>::new(8);
+    let a = heap.push(Box::new(())).unwrap();
+    let b = heap.push(Box::new(())).unwrap();
+    assert_ne!(a, b);
+    assert!(heap.contains_ptr(&a) && heap.contains_ptr(&b));
+    heap.sweep(|p| *p == b);
+    assert!(!heap.contains_ptr(&a) && heap.contains_ptr(&b));
+    // the dropped value's space is reused without sharing an address with the kept one
+    let c = heap.push(Box::new(())).unwrap();
+    assert_eq!(c, a);
+    assert_eq!(heap.len(), 2);
+    assert!(heap.verify().is_ok());
+    let (taken, ptr) = heap.take(0);
+    assert_eq!((*taken, ptr), ((), b));
+
+    // zero-length slices are told apart too, even when pushed concurrently
+    let slices = Heap::<[u32]>::new(8);
+    let empty: Vec<_> = (0..2).map(|_| slices.push_concurrent(Vec::new().into_boxed_slice()).unwrap()).collect();
+    assert_ne!(empty[0].cast::<u32>(), empty[1].cast::<u32>());
+    assert!(slices.verify().is_ok());
+}
+
+#[test]
+fn test_reserve(){
+    let mut heap = Heap::<u64>::new(3 * 4096 + 8);
+    heap.push(Box::new(1)).unwrap();
+    heap.reserve_objects(100);
+    heap.reserve_bytes(2 * 4096);
+    // reserving past the capacity only touches what's there
+    heap.reserve_bytes(usize::MAX);
+    let ptrs: Vec<_> = (0..100).map(|i| heap.push(Box::new(i)).unwrap()).collect();
+    assert!(ptrs.iter().enumerate().all(|(i, p)| heap.get_by_ref(p) == Some(&(i as u64))));
+    assert_eq!(heap.len(), 101);
+    assert!(heap.verify().is_ok());
+}
+
+#[test]
+fn test_push_concurrent(){
+    let heap: Heap<u64> = Heap::new(400 * mem::size_of::<u64>());
+    std::thread::scope(|scope| {
+        for t in 0..4{
+            let heap = &heap;
+            scope.spawn(move || {
+                for i in 0..100{
+                    let ptr = heap.push_concurrent(Box::new(t * 100 + i)).unwrap();
+                    assert_eq!(heap.get_by_ref(&ptr), Some(&(t * 100 + i)));
+                }
+            });
+        }
+    });
+    assert_eq!(heap.len(), 400);
+    let mut values = Vec::new();
+    heap.for_each(|v, _| values.push(*v));
+    values.sort();
+    assert!(values.into_iter().eq(0..400));
+    assert_eq!(heap.push_concurrent(Box::new(0)).unwrap_err().kind(), AllocErrorKind::OutOfSpace);
+}
+
+#[test]
+fn test_lookup_in_for_each(){
+    let mut heap: Heap<u64> = Heap::new(64 * mem::size_of::<u64>());
+    heap.push(Box::new(1)).unwrap();
+    heap.push_concurrent(Box::new(2)).unwrap();
+    let mut found = 0;
+    heap.for_each(|v, p| {
+        assert!(heap.contains_ptr(p));
+        assert_eq!(heap.get_by_ref(p), Some(v));
+        heap.push_concurrent(Box::new(*v + 10)).unwrap();
+        found += 1;
+    });
+    assert_eq!((found, heap.len()), (2, 4));
+
+    let mut no_gc = NoGcMem::<u64>::new(64);
+    let mut mas = MarkAndSweepMem::<u64>::new(64);
+    no_gc.push(Box::new(3)).unwrap();
+    mas.push(Box::new(3)).unwrap();
+    ManagedMem::for_each(&no_gc, |v, p| assert_eq!(no_gc.get_by_ref(p), Some(v)));
+    ManagedMem::for_each(&mas, |v, p| assert_eq!(mas.get_by_ref(p), Some(v)));
+}
+
+#[test]
+fn test_push_paths(){
+    // bumping fills the heap exactly, then fails without recording anything
+    let mut heap: Heap<u64> = Heap::new(4 * 8);
+    let ptrs: Vec<_> = (0..4).map(|i| heap.push(Box::new(i)).unwrap()).collect();
+    assert_eq!((heap.used(), heap.capacity()), (32, 32));
+    let err = heap.push(Box::new(4)).unwrap_err();
+    assert_eq!((err.kind(), *err.value()), (AllocErrorKind::OutOfSpace, 4));
+    assert_eq!(heap.len(), 4);
+
+    // freed space is filled first, then pushes go back to bumping the end
+    heap.sweep(|p| *p != ptrs[1] && *p != ptrs[3]);
+    assert_eq!((heap.used(), heap.free_space()), (24, 8));
+    assert_eq!(heap.push(Box::new(5)).unwrap(), ptrs[1]);
+    assert_eq!(heap.free_space(), 0);
+    assert_eq!(heap.push(Box::new(6)).unwrap(), ptrs[3]);
+    assert!(heap.push(Box::new(7)).is_err());
+    assert_eq!(heap.get_by_ref(&ptrs[1]), Some(&5));
+    assert!(heap.verify().is_ok());
+
+    // the indexes grow past the space reserved for them
+    let mut heap: Heap<u8> = Heap::new(256);
+    heap.reserve_objects(1);
+    let ptrs: Vec<_> = (0..=255).map(|i| heap.push(Box::new(i)).unwrap()).collect();
+    assert!(ptrs.iter().enumerate().all(|(i, p)| heap.get_by_ref(p) == Some(&(i as u8))));
+    assert_eq!(heap.push(Box::new(0)).unwrap_err().kind(), AllocErrorKind::OutOfSpace);
+
+    // values with headers or in size classes never bump directly, but fail at the same point
+    let mut heap: Heap<u64> = Heap::with_headers(64);
+    let mut pushed = 0;
+    while heap.push(Box::new(pushed)).is_ok(){
+        pushed += 1;
+    }
+    assert_eq!(heap.used(), heap.capacity());
+    assert!(pushed > 0 && (0..pushed as usize).all(|i| *heap.get(i) == i as u64));
+    let mut heap: Heap<u64> = Heap::new(BLOCK_SIZE).with_size_classes();
+    let mut pushed = 0;
+    while heap.push(Box::new(pushed)).is_ok(){
+        pushed += 1;
+    }
+    assert!(pushed > 0 && (0..pushed as usize).all(|i| *heap.get(i) == i as u64));
+    assert!(heap.verify().is_ok());
+}
+
+#[test]
+fn test_object_headers(){
+    let mut heap = Heap::<[u16]>::with_headers(128);
+    assert!(heap.has_headers());
+    let a = heap.push_slice(&[1, 2, 3]).unwrap();
+    let b = heap.push_slice(&[4]).unwrap();
+    assert_eq!(heap.header(&a).unwrap().size(), 6);
+    assert_eq!(heap.header(&b).unwrap().age(), 0);
+    heap.header_mut(&a).unwrap().set_user(7);
+    heap.header_mut(&a).unwrap().set_marked(true);
+    assert!(heap.verify().is_ok());
+
+    let mut next = Heap::<[u16]>::with_headers(128);
+    let moved = heap.evacuate_into(&mut next, |p| p.cast::<u16>() == a.cast(), 1);
+    let new = moved[0].1;
+    let header = next.header(&new).unwrap();
+    assert_eq!((header.size(), header.user(), header.age()), (6, 7, 1));
+    assert!(!header.is_marked());
+    assert_eq!(next.get_by_ref(&new).unwrap(), &[1, 2, 3]);
+    assert!(Heap::<[u16]>::new(16).header(&new).is_none());
+}
+
+#[test]
+fn test_sweep(){
+    let mut heap = Heap::<[u16]>::new(32);
+    let a = heap.push_slice(&[1, 2]).unwrap();
+    let b = heap.push_slice(&[3, 4, 5, 6]).unwrap();
+    let c = heap.push_slice(&[7, 8]).unwrap();
+    let d = heap.push_slice(&[9]).unwrap();
+    heap.sweep(|p| p.cast::<u16>() == a.cast() || p.cast::<u16>() == c.cast());
+    assert_eq!(heap.len(), 2);
+    assert_eq!(heap.get_by_ref(&c).unwrap(), &[7, 8]);
+    // b's space is free, and d's is given back to the end of the heap
+    assert_eq!((heap.free_space(), heap.used()), (8, 16));
+
+    let e = heap.push_slice(&[10, 11, 12]).unwrap();
+    assert_eq!(e.cast::<u16>(), b.cast::<u16>());
+    assert_eq!(heap.free_space(), 2);
+    let f = heap.push_slice(&[13, 14]).unwrap();
+    assert_eq!(f.cast::<u16>(), d.cast::<u16>());
+    assert!(heap.verify().is_ok());
+}
+
+#[test]
+fn test_poison(){
+    let mut heap = Heap::<[u32]>::with_headers(128);
+    let a = heap.push_slice(&[1, 2]).unwrap();
+    let b = heap.push_slice(&[3]).unwrap();
+    let bytes = |p: *const [u32]| unsafe{ std::slice::from_raw_parts(p as *const u8, 4) };
+    heap.retain(|p, _| p.cast::<u32>() != a.cast());
+    // tests are built with debug assertions
+    assert_eq!(bytes(a), &[POISON; 4]);
+    assert_eq!(bytes(b), &3u32.to_ne_bytes());
+    heap.reset();
+    assert_eq!(bytes(b), &[POISON; 4]);
+}
+
+#[test]
+fn test_contains_ptr(){
+    let mut heap = Heap::<[u16]>::new(32);
+    let a = heap.push_slice(&[1, 2]).unwrap();
+    // an empty slice takes no space, so shares its address with the next value
+    let empty = heap.push_slice(&[]).unwrap();
+    let b = heap.push_slice(&[3]).unwrap();
+    assert!(heap.contains_ptr(&a) && heap.contains_ptr(&empty) && heap.contains_ptr(&b));
+    assert_eq!(heap.get_by_ref(&empty).unwrap(), &[]);
+    assert_eq!(heap.get_by_ref(&b).unwrap(), &[3]);
+    // pointers into the middle of values, and outside of the heap, are rejected
+    assert!(!heap.contains_ptr(&std::ptr::slice_from_raw_parts(a.cast::<u16>().wrapping_add(1), 1)));
+    assert!(!heap.contains_ptr(&(&[1u16, 2] as *const [u16])));
+
+    // values placed in free space before others are still found
+    heap.sweep(|p| p.cast::<u16>() != a.cast());
+    let c = heap.push_slice(&[4, 5]).unwrap();
+    assert_eq!(c.cast::<u16>(), a.cast::<u16>());
+    assert_eq!(heap.get_by_ref(&c).unwrap(), &[4, 5]);
+    assert_eq!(heap.get_by_ref(&b).unwrap(), &[3]);
+}
+
+#[test]
+fn test_size_classes(){
+    let mut heap = Heap::<[u64]>::with_headers(4 * BLOCK_SIZE).with_size_classes();
+    // values of the same size class share a block, after their headers
+    let a = heap.push_slice(&[1, 2]).unwrap();
+    let b = heap.push_slice(&[3, 4]).unwrap();
+    assert_eq!(b.cast::<u8>() as usize - a.cast::<u8>() as usize, 48);
+    // large values take whole blocks
+    let c = heap.push_slice_with_len(600, |i| i as u64).unwrap();
+    assert_eq!(heap.used(), 3 * BLOCK_SIZE);
+
+    // freed slots are reused by the same class
+    heap.sweep(|p| p.cast::<u64>() != a.cast());
+    let d = heap.push_slice(&[5, 6]).unwrap();
+    assert_eq!(d.cast::<u64>(), a.cast::<u64>());
+    assert_eq!(heap.get_by_ref(&c).unwrap()[599], 599);
+
+    // empty blocks can be reused by any class
+    heap.sweep(|_| false);
+    assert_eq!((heap.used(), heap.free_space()), (0, 0));
+    let e = heap.push_slice_with_len(1500, |i| i as u64).unwrap();
+    assert_eq!(e.cast::<u64>(), a.cast::<u64>());
+    assert!(heap.push_slice(&[6; 200]).is_ok());
+    assert!(heap.verify().is_ok());
+}
+
+#[test]
+fn test_fork_keeps_metadata(){
+    // pointers moved between heaps keep their lengths, with or without the `stable` feature
+    let mut heap = Heap::<[u16]>::new(64);
+    let slices: [&[u16]; 3] = [&[], &[1, 2, 3], &[4, 5]];
+    let ptrs: Vec<_> = slices.iter().map(|s| heap.push_slice(s).unwrap()).collect();
+    let fork = heap.fork_with(|v| v.into());
+    for (ptr, slice) in ptrs.iter().zip(slices){
+        let forked = heap.ptr_in_fork(ptr, &fork).unwrap();
+        assert_ne!(forked.cast::<u16>(), ptr.cast::<u16>());
+        assert_eq!(fork.get_by_ref(&forked).unwrap(), slice);
+    }
+
+    let mut strs = Heap::<str>::new(16);
+    let ptr = strs.push(Box::from("héllo")).unwrap();
+    let fork = strs.fork_with(|v| v.into());
+    assert_eq!(fork.get_by_ref(&strs.ptr_in_fork(&ptr, &fork).unwrap()), Some("héllo"));
+    assert_eq!(fork.used(), 6);
+}
@@ -0,0 +1,52 @@
+use crate::gc::{GcCandidate, ManagedMem};
+use crate::gc::image::{address, HeapImage, RestoreError};
+use crate::gc::impls::{adjust_option, collect_all, trace_option};
+use crate::gc::mas::MarkAndSweepMem;
+
+struct Node{
+    value: i32,
+    next: Option<*const Node>
+}
+
+impl GcCandidate for Node{
+    fn collect_managed_pointers(&self, _this: &*const Node) -> Vec<*const Node>{
+        return collect_all(&self.next);
+    }
+
+    fn trace(&self, visitor: &mut impl FnMut(&*const Node), _this: &*const Node){
+        trace_option(&self.next, visitor);
+    }
+
+    fn adjust_ptrs(&mut self, adjust: impl Fn(&*const Node) -> *const Node, _this: &*const Node){
+        adjust_option(&mut self.next, adjust);
+    }
+}
+
+#[derive(Clone)]
+struct SavedNode{
+    value: i32,
+    next: Option<u64>
+}
+
+#[test]
+fn test_heap_image(){
+    let mut mem = MarkAndSweepMem::<Node>::new(1024);
+    let tail = mem.push(Box::new(Node{ value: 2, next: None })).unwrap();
+    let head = mem.push(Box::new(Node{ value: 1, next: Some(tail) })).unwrap();
+    let image = HeapImage::capture(&mem, &[head], |n: &Node, _| SavedNode{ value: n.value, next: n.next.as_ref().map(address) });
+    assert_eq!(image.objects().len(), 2);
+    assert_eq!(image.roots(), &[address(&head)]);
+
+    let mut restored = MarkAndSweepMem::<Node>::new(1024);
+    restored.push(Box::new(Node{ value: 0, next: None })).unwrap();
+    let roots = image.clone().restore(&mut restored, |s| Box::new(Node{ value: s.value, next: s.next.map(|a| a as usize as *const Node) })).unwrap();
+    assert_eq!(restored.len(), 3);
+    let new_head = restored.get_by_ref(&roots[0]).unwrap();
+    assert_eq!(new_head.value, 1);
+    assert_ne!(roots[0], head);
+    assert_eq!(restored.get_by_ref(&new_head.next.unwrap()).unwrap().value, 2);
+
+    // pointers to values that weren't captured can't be restored
+    let err = image.restore(&mut restored, |s| Box::new(Node{ value: s.value, next: Some(16 as *const Node) })).unwrap_err();
+    assert!(matches!(err, RestoreError::Dangling{ address: 16 }));
+}
@@ -0,0 +1,79 @@
+use crate::gc::{GcCandidate, ManagedMem};
+use crate::gc::impls::{adjust_option, adjust_slice, collect_all, trace_option, trace_slice};
+use crate::gc::mas::MarkAndSweepMem;
+use crate::pointer_free_candidate;
+
+// a node using the helper functions for its pointer fields
+
+struct Node{
+    parent: Option<*const Node>,
+    children: Vec<*const Node>
+}
+
+impl GcCandidate for Node{
+    fn collect_managed_pointers(&self, _this: &*const Node) -> Vec<*const Node>{
+        return collect_all(self.parent.iter().chain(self.children.iter()));
+    }
+
+    fn trace(&self, visitor: &mut impl FnMut(&*const Node), _this: &*const Node){
+        trace_option(&self.parent, visitor);
+        trace_slice(&self.children, visitor);
+    }
+
+    fn adjust_ptrs(&mut self, adjust: impl Fn(&*const Node) -> *const Node, _this: &*const Node){
+        adjust_option(&mut self.parent, &adjust);
+        adjust_slice(&mut self.children, &adjust);
+    }
+}
+
+// a type holding no pointers, given the same impl as leaf types
+struct Label(String);
+
+pointer_free_candidate!(Label);
+
+#[test]
+fn test_leaf_impls(){
+    let mut ints = MarkAndSweepMem::<u64>::new(64);
+    let mut a = ints.push(Box::new(1)).unwrap();
+    ints.push(Box::new(2)).unwrap();
+    unsafe{
+        ints.gc(vec![&mut a], vec![]);
+    }
+    assert_eq!(ints.len(), 1);
+    assert_eq!(*ints.get(0), 1);
+
+    let mut bytes = MarkAndSweepMem::<[u8]>::new(64);
+    let mut b = bytes.push_slice(&[1, 2, 3]).unwrap();
+    unsafe{
+        bytes.gc(vec![&mut b], vec![]);
+    }
+    assert_eq!(bytes.get(0), &[1, 2, 3]);
+}
+
+#[test]
+fn test_container_helpers(){
+    let mut heap = MarkAndSweepMem::<Node>::new(500);
+    let mut root = heap.push(Box::new(Node{ parent: None, children: vec![] })).unwrap();
+    let child = heap.push(Box::new(Node{ parent: Some(root), children: vec![] })).unwrap();
+    heap.push(Box::new(Node{ parent: None, children: vec![] })).unwrap();
+    heap.get_by(&root).unwrap().children.push(child);
+    unsafe{
+        heap.gc(vec![&mut root], vec![]);
+    }
+    assert_eq!(heap.len(), 2);
+    let child = heap.get_by(&root).unwrap().children[0];
+    assert_eq!(heap.get_by(&child).unwrap().parent, Some(root));
+}
+
+#[test]
+fn test_pointer_free_candidate(){
+    let mut heap = MarkAndSweepMem::<Label>::new(256);
+    heap.push(Box::new(Label("dropped".to_string()))).unwrap();
+    let mut kept = heap.push(Box::new(Label("kept".to_string()))).unwrap();
+    assert!(heap.get_by_ref(&kept).unwrap().collect_managed_pointers(&kept).is_empty());
+    unsafe{
+        heap.gc(vec![&mut kept], vec![]);
+    }
+    assert_eq!(heap.len(), 1);
+    assert_eq!(heap.get_by_ref(&kept).unwrap().0, "kept");
+}
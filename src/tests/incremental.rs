@@ -0,0 +1,139 @@
+use crate::gc::{GcCandidate, ManagedMem};
+use crate::gc::config::{MemConfig, WriteBarrier};
+use crate::gc::impls::{adjust_slice, collect_all, trace_slice};
+use crate::gc::incremental::{Budget, GcPhaseProgress, IncrementalMem};
+use std::time::Duration;
+use crate::gc::mas::MarkAndSweepMem;
+use crate::gc::pauses::PauseStats;
+
+struct Node{
+    id: u32,
+    edges: Vec<*const Node>
+}
+
+impl GcCandidate for Node{
+    fn collect_managed_pointers(&self, _this: &*const Node) -> Vec<*const Node>{
+        return collect_all(&self.edges);
+    }
+
+    fn trace(&self, visitor: &mut impl FnMut(&*const Node), _this: &*const Node){
+        trace_slice(&self.edges, visitor);
+    }
+
+    fn adjust_ptrs(&mut self, adjust: impl Fn(&*const Node) -> *const Node, _this: &*const Node){
+        adjust_slice(&mut self.edges, adjust);
+    }
+}
+
+#[test]
+fn test_gc_step(){
+    let mut mem = MarkAndSweepMem::<Node>::new(4096);
+    // root -> a -> b -> c -> y, and some garbage
+    let y = mem.push(Box::new(Node{ id: 4, edges: vec![] })).unwrap();
+    let c = mem.push(Box::new(Node{ id: 3, edges: vec![y] })).unwrap();
+    let b = mem.push(Box::new(Node{ id: 2, edges: vec![c] })).unwrap();
+    let a = mem.push(Box::new(Node{ id: 1, edges: vec![b] })).unwrap();
+    let mut root = mem.push(Box::new(Node{ id: 0, edges: vec![a] })).unwrap();
+    mem.push(Box::new(Node{ id: 9, edges: vec![] })).unwrap();
+
+    let progress = unsafe{ mem.gc_step(vec![&mut root], vec![], Budget::Objects(2)) };
+    assert_eq!(progress, GcPhaseProgress::Marking{ marked: 2, pending: 1 });
+    assert!(mem.is_collecting());
+    assert!(mem.has_write_barrier());
+    // move y from the unscanned c to the already scanned root
+    mem.write_field(&root, |n| n.edges.push(y));
+    mem.write_field(&c, |n| n.edges.clear());
+    // values pushed while marking survive
+    mem.push(Box::new(Node{ id: 5, edges: vec![] })).unwrap();
+
+    let report = loop{
+        match unsafe{ mem.gc_step(vec![&mut root], vec![], Budget::Objects(1)) }{
+            GcPhaseProgress::Marking{ .. } => continue,
+            GcPhaseProgress::Complete(report) => break report
+        }
+    };
+    assert!(!mem.is_collecting());
+    assert_eq!(report.objects_reclaimed(), 1);
+    assert_eq!(mem.len(), 6);
+    let root_node = mem.get_by_ref(&root).unwrap();
+    assert_eq!(root_node.id, 0);
+    assert_eq!(mem.get_by_ref(&root_node.edges[1]).unwrap().id, 4);
+
+    // each step is a marking pause, but the whole collection counts once
+    let pauses = mem.pause_times().unwrap();
+    let steps = pauses.phase("mark").unwrap().count();
+    assert!(steps > 1);
+    assert_eq!(pauses.phase("sweep").unwrap().count(), 1);
+    assert_eq!(pauses.collections().count(), 1);
+    assert_eq!(pauses.collections().total(), report.pause());
+}
+
+#[test]
+fn test_write_barriers(){
+    // root -> a -> b -> c -> y, and some garbage; b's edge is cut after root and a are marked
+    let collect = |barrier: WriteBarrier| {
+        let mut mem = MarkAndSweepMem::<Node>::new(MemConfig::new(4096).with_write_barrier(barrier));
+        let y = mem.push(Box::new(Node{ id: 4, edges: vec![] })).unwrap();
+        let c = mem.push(Box::new(Node{ id: 3, edges: vec![y] })).unwrap();
+        let b = mem.push(Box::new(Node{ id: 2, edges: vec![c] })).unwrap();
+        let a = mem.push(Box::new(Node{ id: 1, edges: vec![b] })).unwrap();
+        let mut root = mem.push(Box::new(Node{ id: 0, edges: vec![a] })).unwrap();
+        mem.push(Box::new(Node{ id: 9, edges: vec![] })).unwrap();
+        unsafe{ mem.gc_step(vec![&mut root], vec![], Budget::Objects(2)) };
+        assert_eq!(mem.has_write_barrier(), barrier == WriteBarrier::IncrementalUpdate);
+        assert_eq!(mem.has_pre_write_barrier(), barrier == WriteBarrier::SnapshotAtBeginning);
+        mem.write_field(&b, |n| n.edges.clear());
+        loop{
+            if let GcPhaseProgress::Complete(report) = unsafe{ mem.gc_step(vec![&mut root], vec![], Budget::Objects(1)) }{
+                return report.objects_reclaimed();
+            }
+        }
+    };
+    assert_eq!(collect(WriteBarrier::IncrementalUpdate), 3);
+    // everything reachable when marking started survives
+    assert_eq!(collect(WriteBarrier::SnapshotAtBeginning), 1);
+}
+
+#[test]
+fn test_push_slice_while_marking(){
+    for barrier in [WriteBarrier::IncrementalUpdate, WriteBarrier::SnapshotAtBeginning]{
+        let mut mem = MarkAndSweepMem::<[u32]>::new(MemConfig::new(4096).with_write_barrier(barrier));
+        let mut root = mem.push_slice(&[1, 2]).unwrap();
+        let mut other = mem.push_slice(&[3]).unwrap();
+        mem.push_slice(&[4]).unwrap();
+        unsafe{ mem.gc_step(vec![&mut root, &mut other], vec![], Budget::Objects(1)) };
+        assert!(mem.is_collecting());
+        // slices pushed while marking survive, and are counted as if present from the start
+        mem.push_slice(&[5, 6, 7]).unwrap();
+        let report = loop{
+            if let GcPhaseProgress::Complete(report) = unsafe{ mem.gc_step(vec![&mut root, &mut other], vec![], Budget::Objects(1)) }{
+                break report;
+            }
+        };
+        assert_eq!(report.objects_reclaimed(), 1);
+        let mut kept = Vec::new();
+        ManagedMem::for_each(&mem, |slice, _| kept.push(slice.to_vec()));
+        kept.sort();
+        assert_eq!(kept, vec![vec![1, 2], vec![3], vec![5, 6, 7]]);
+    }
+}
+
+#[test]
+fn test_pause_stats(){
+    let mut stats = PauseStats::new();
+    assert_eq!(stats.p99(), Duration::ZERO);
+    for micros in 1..=100{
+        stats.record(Duration::from_micros(micros));
+    }
+    assert_eq!(stats.count(), 100);
+    assert_eq!(stats.total(), Duration::from_micros(5050));
+    assert_eq!(stats.max(), Duration::from_micros(100));
+    assert_eq!(stats.mean(), Duration::from_nanos(50500));
+    for (p, exact) in [(0.5, 50), (0.99, 99), (1.0, 100)]{
+        let estimate = stats.percentile(p).as_nanos() as f64;
+        let exact = Duration::from_micros(exact).as_nanos() as f64;
+        assert!(estimate >= exact && estimate <= exact * 1.125, "p{p}: {estimate} vs {exact}");
+    }
+    stats.clear();
+    assert_eq!(stats.count(), 0);
+}
@@ -1,8 +1,15 @@
-use std::mem;
-use std::sync::Mutex;
+use std::alloc::Layout;
+use std::{mem, panic, ptr};
+use std::panic::AssertUnwindSafe;
+use std::sync::{Arc, Mutex};
 use dyn_struct2::dyn_arg;
 use dyn_struct_derive2::DynStruct;
-use crate::gc::{GcCandidate, ManagedMem};
+use crate::gc::{GcCandidate, ManagedMem, Placement};
+use crate::gc::config::{EvacuationOrder, ForeignPtrPolicy, MemConfig};
+use crate::gc::hooks::GcPhase;
+use crate::gc::impls::{adjust_option, trace_option};
+use crate::gc::incremental::{Budget, GcPhaseProgress, IncrementalMem};
+use crate::gc::log::{GcLogLevel, GcLogRecord, ObjectFate, WriterSink};
 use crate::gc::mas::MarkAndSweepMem;
 use crate::heap::{DynSized, HeapPtr};
 use crate::tests::mas::MyDataValue::{Int, Nothing, Pointer};
@@ -22,6 +29,15 @@ struct MyUnsized{
     values: [MyDataValue]
 }
 
+impl MyDataValue{
+    fn pointer(&self) -> Option<MyPointer>{
+        return match self{
+            Pointer(p) => Some(*p),
+            _ => None
+        };
+    }
+}
+
 impl MyUnsized{
     pub fn new_u<const N: usize>(values: [MyDataValue; N]) -> Box<MyUnsized>{
         return MyUnsized::new(dyn_arg!(values));
@@ -45,6 +61,14 @@ impl GcCandidate<MyPointer> for MyUnsized{
         }).collect();
     }
 
+    fn trace(&self, visitor: &mut impl FnMut(&MyPointer), _this: &MyPointer){
+        for value in &self.values{
+            if let Pointer(p) = value{
+                visitor(p);
+            }
+        }
+    }
+
     fn adjust_ptrs(&mut self, adjust: impl Fn(&MyPointer) -> MyPointer, _this: &MyPointer){
         for i in 0..self.values.len(){
             if let Pointer(p) = &self.values[i]{
@@ -77,6 +101,25 @@ impl Drop for MyUnsized{
     }
 }
 
+// a value whose pointers can only be traced, to check that marking doesn't collect them into a Vec
+struct TraceOnly{
+    next: Option<*const TraceOnly>
+}
+
+impl GcCandidate for TraceOnly{
+    fn collect_managed_pointers(&self, _this: &*const TraceOnly) -> Vec<*const TraceOnly>{
+        panic!("TraceOnly: pointers collected instead of traced");
+    }
+
+    fn trace(&self, visitor: &mut impl FnMut(&*const TraceOnly), _this: &*const TraceOnly){
+        trace_option(&self.next, visitor);
+    }
+
+    fn adjust_ptrs(&mut self, adjust: impl Fn(&*const TraceOnly) -> *const TraceOnly, _this: &*const TraceOnly){
+        adjust_option(&mut self.next, adjust);
+    }
+}
+
 #[test]
 fn test_mark_and_sweep(){
     // set up a heap with cycles
@@ -87,6 +130,7 @@ fn test_mark_and_sweep(){
     let mut r = heap.push(MyUnsized::new_u([Int(3), Nothing])).unwrap();
     let mut s = heap.push(MyUnsized::new_u([Int(8), Nothing])).unwrap();
     let mut n = heap.push(MyUnsized::new_u([Int(14)])).unwrap();
+    assert_eq!(heap.get_by_ref(&n).unwrap().values.len(), 1);
 
     // root -> l
     { heap.get_by(&root).unwrap().values[1] = Pointer(l.clone()); }
@@ -127,4 +171,448 @@ fn test_mark_and_sweep(){
             assert_eq!(heap.len(), 0);
         }
     }
-}
\ No newline at end of file
+}
+#[test]
+fn test_parallel_evacuation(){
+    // enough data to be copied between multiple threads
+    let mut heap = MarkAndSweepMem::<[u64]>::new(MemConfig::new(1 << 18).with_threads(4));
+    let ptrs: Vec<*const [u64]> = (0..300)
+        .map(|i| heap.push_slice_with_len(64, |j| (i * 64 + j) as u64).unwrap())
+        .collect();
+    let mut kept: Vec<*const [u64]> = ptrs.iter().copied().step_by(2).collect();
+    unsafe{
+        heap.gc(kept.iter_mut().map(|p| p as *mut _).collect(), vec![]);
+    }
+    assert_eq!(heap.len(), 150);
+    for (k, ptr) in kept.iter().enumerate(){
+        let values = heap.get_by_ref(ptr).unwrap();
+        assert_eq!(values.len(), 64);
+        assert!(values.iter().enumerate().all(|(j, &v)| v == (k * 128 + j) as u64));
+    }
+}
+
+#[test]
+fn test_gc_report(){
+    let mut heap = MarkAndSweepMem::<[u64]>::new(1024);
+    let mut kept = heap.push_slice(&[1, 2, 3]).unwrap();
+    let mut weak = heap.push_slice(&[4]).unwrap();
+    heap.push_slice(&[5, 6]).unwrap();
+    let report = unsafe{ heap.gc_with_report(vec![&mut kept], vec![&mut weak]) };
+    assert_eq!(report.objects_scanned(), 1);
+    assert_eq!(report.objects_reclaimed(), 2);
+    assert_eq!(report.bytes_reclaimed(), 3 * 8);
+    assert_eq!(report.bytes_surviving(), 3 * 8);
+    assert_eq!(heap.total_size(), 3 * 8);
+    // the weak pointer's value was dropped
+    assert_eq!(report.weaks_cleared(), 1);
+    assert!(weak.is_cleared());
+    assert_eq!(weak.len(), 1);
+}
+
+#[test]
+fn test_gc_hooks(){
+    let mut heap = MarkAndSweepMem::<[u64]>::new(1024);
+    let mut root = heap.push_slice(&[1, 2]).unwrap();
+    heap.push_slice(&[3]).unwrap();
+    let phases = Arc::new(Mutex::new(Vec::new()));
+    let seen = phases.clone();
+    heap.gc_hooks().unwrap().add(move |phase| seen.lock().unwrap().push(*phase));
+    let report = unsafe{ heap.gc_with_report(vec![&mut root], vec![]) };
+    assert!(phases.lock().unwrap().eq(&vec![GcPhase::Before, GcPhase::Marked{ reachable: 1 }, GcPhase::After(report)]));
+}
+
+#[test]
+fn test_mark_stack_limit(){
+    for headers in [false, true]{
+        let config = MemConfig::new(8192).with_mark_stack_limit(1).with_object_headers(headers);
+        let mut heap = MarkAndSweepMem::<MyUnsized, MyPointer>::new(config);
+        // a chain of nodes, each pointing to two leaves and the next node, overflows the stack at every node
+        let mut next = Nothing;
+        for i in 0..8{
+            let left = heap.push(MyUnsized::new_u([Int(i)])).unwrap();
+            let right = heap.push(MyUnsized::new_u([Int(i)])).unwrap();
+            next = Pointer(heap.push(MyUnsized::new_u([Pointer(left), Pointer(right), next])).unwrap());
+        }
+        heap.push(MyUnsized::new_u([Int(-1)])).unwrap();
+        let mut root = next.pointer().unwrap();
+        let report = unsafe{ heap.gc_with_report(vec![&mut root], vec![]) };
+        assert_eq!(report.objects_reclaimed(), 1);
+        assert_eq!(heap.len(), 24);
+        assert!(heap.verify().is_ok());
+    }
+}
+
+#[test]
+fn test_relocation_hooks(){
+    let mut heap = MarkAndSweepMem::<[u64]>::new(1024);
+    heap.push_slice(&[3]).unwrap();
+    let mut root = heap.push_slice(&[1, 2]).unwrap();
+    let old_root = root;
+    let moves = Arc::new(Mutex::new(Vec::new()));
+    let seen = moves.clone();
+    heap.relocation_hooks().unwrap().add(move |old: &*const [u64], new: &*const [u64]| {
+        seen.lock().unwrap().push((old.cast::<u64>() as usize, new.cast::<u64>() as usize));
+    });
+    unsafe{ heap.gc(vec![&mut root], vec![]) };
+    assert_ne!(root.cast::<u64>(), old_root.cast::<u64>());
+    assert_eq!(*moves.lock().unwrap(), vec![(old_root.cast::<u64>() as usize, root.cast::<u64>() as usize)]);
+}
+
+#[test]
+fn test_gc_log(){
+    let mut heap = MarkAndSweepMem::<[u64]>::new(1024);
+    let mut root = heap.push_slice(&[1, 2]).unwrap();
+    let dropped = heap.push_slice(&[3]).unwrap();
+    let records = Arc::new(Mutex::new(Vec::new()));
+    let seen = records.clone();
+    let log = heap.gc_log().unwrap();
+    log.set_level(GcLogLevel::Objects);
+    log.add_sink(move |record: &GcLogRecord| seen.lock().unwrap().push(record.clone()));
+    log.add_sink(WriterSink::new(Vec::new()));
+    let old_root = root;
+    let report = unsafe{ heap.gc_with_report(vec![&mut root], vec![]) };
+
+    let records = records.lock().unwrap();
+    assert_eq!(records.len(), 1);
+    let record = &records[0];
+    assert_eq!(record.collection(), 1);
+    assert_eq!(record.report(), &report);
+    assert_eq!(record.phases().iter().map(|(name, _)| *name).collect::<Vec<_>>(), ["mark", "sweep"]);
+    let fates: Vec<_> = record.objects().iter().map(|o| (o.address(), o.size(), o.fate())).collect();
+    assert_eq!(fates, [
+        (old_root.cast::<u64>() as usize, 16, ObjectFate::Moved(root.cast::<u64>() as usize)),
+        (dropped.cast::<u64>() as usize, 8, ObjectFate::Reclaimed)
+    ]);
+    assert!(record.to_string().starts_with("[gc] GC(1) mark-and-sweep: 1 scanned, 1 reclaimed (8B)"));
+}
+
+#[test]
+fn test_header_marking(){
+    let mut heap = MarkAndSweepMem::<MyUnsized, MyPointer>::new(MemConfig::new(500).with_object_headers(true));
+    // values without an int first aren't recorded in DROPPED
+    let mut root = heap.push(MyUnsized::new_u([Nothing, Nothing])).unwrap();
+    let l = heap.push(MyUnsized::new_u([Nothing, Nothing])).unwrap();
+    heap.push(MyUnsized::new_u([Nothing])).unwrap();
+    // root -> l -> l
+    { heap.get_by(&root).unwrap().values[1] = Pointer(l); }
+    { heap.get_by(&l).unwrap().values[0] = Pointer(l); }
+    let report = unsafe{ heap.gc_with_report(vec![&mut root], vec![]) };
+    assert_eq!(report.objects_scanned(), 2);
+    assert_eq!(heap.len(), 2);
+    let header = heap.header(&root).unwrap();
+    assert_eq!(header.age(), 1);
+    assert!(!header.is_marked());
+    assert!(heap.verify().is_ok());
+}
+
+#[test]
+fn test_header_marks_after_panic(){
+    let mut other = MarkAndSweepMem::<MyUnsized, MyPointer>::new(500);
+    let foreign = other.push(MyUnsized::new_u([Nothing])).unwrap();
+    let mut heap = MarkAndSweepMem::<MyUnsized, MyPointer>::new(MemConfig::new(500).with_object_headers(true));
+    let child = heap.push(MyUnsized::new_u([Nothing])).unwrap();
+    // the foreign pointer is found before the child, after the root is marked
+    let mut root = heap.push(MyUnsized::new_u([Pointer(child), Pointer(foreign)])).unwrap();
+    let result = panic::catch_unwind(AssertUnwindSafe(|| unsafe{ heap.gc(vec![&mut root], vec![]) }));
+    assert!(result.is_err());
+    heap.get_by(&root).unwrap().values[1] = Nothing;
+    // the root is traced again, so its child survives
+    unsafe{ heap.gc(vec![&mut root], vec![]) };
+    assert_eq!(heap.len(), 2);
+    let child = heap.get_by_ref(&root).unwrap().values[0].pointer().unwrap();
+    assert!(heap.contains_ptr(&child));
+    assert!(heap.verify().is_ok());
+}
+
+#[test]
+fn test_sweep_in_place(){
+    let mut heap = MarkAndSweepMem::<[u64]>::new(MemConfig::new(128).with_sweep_in_place(true).with_object_headers(true));
+    let mut kept = heap.push_slice(&[1, 2]).unwrap();
+    let dropped = heap.push_slice(&[3]).unwrap();
+    let before = kept;
+    let report = unsafe{ heap.gc_with_report(vec![&mut kept], vec![]) };
+    assert_eq!(kept, before);
+    assert_eq!(report.objects_reclaimed(), 1);
+    assert_eq!(heap.header(&kept).unwrap().age(), 1);
+    // the dropped value's space is reused
+    assert_eq!(heap.push_slice(&[4]).unwrap().cast::<u64>(), dropped.cast::<u64>());
+    assert!(heap.verify().is_ok());
+}
+
+#[test]
+fn test_push_zeroed(){
+    let mut heap = MarkAndSweepMem::<[u64]>::new(MemConfig::new(128).with_sweep_in_place(true));
+    let mut kept = heap.push_slice(&[1]).unwrap();
+    let dropped = heap.push_slice(&[u64::MAX; 4]).unwrap();
+    unsafe{
+        heap.gc(vec![&mut kept], vec![]);
+    }
+    // the dropped value's space is reused, and zeroed before the initializer sees it
+    let zeroed = unsafe{
+        heap.push_zeroed(Layout::array::<u64>(4).unwrap(), |dest| {
+            let values = ptr::slice_from_raw_parts_mut(dest as *mut u64, 4);
+            assert_eq!(*values, [0; 4]);
+            (*values)[1] = 7;
+            values
+        })
+    }.unwrap();
+    assert_eq!(zeroed.cast::<u64>(), dropped.cast::<u64>());
+    assert_eq!(heap.get_by_ref(&zeroed).unwrap(), &[0, 7, 0, 0]);
+
+    let mut ints = MarkAndSweepMem::<u64>::new(64);
+    let int = unsafe{ ints.alloc_zeroed(|v| *v += 3) }.unwrap();
+    assert_eq!(ints.get_by_ref(&int), Some(&3));
+}
+
+#[test]
+fn test_zero_sized_values(){
+    let mut heap = MarkAndSweepMem::<[u64]>::new(64);
+    let mut kept: Vec<*const [u64]> = (0..4).map(|_| heap.push_slice(&[]).unwrap()).collect();
+    kept.remove(1);
+    let report = unsafe{ heap.gc_with_report(kept.iter_mut().map(|p| p as *mut _).collect(), vec![]) };
+    // every empty slice keeps its own identity through relocation
+    assert_eq!(report.objects_reclaimed(), 1);
+    assert_eq!(heap.len(), 3);
+    for (k, ptr) in kept.iter().enumerate(){
+        assert!(heap.contains_ptr(ptr));
+        assert!(kept[k + 1..].iter().all(|other| other.cast::<u64>() != ptr.cast::<u64>()));
+    }
+    assert!(heap.verify().is_ok());
+}
+
+#[test]
+fn test_foreign_pointers(){
+    let mut other = MarkAndSweepMem::<MyUnsized, MyPointer>::new(500);
+    let mut foreign = other.push(MyUnsized::new_u([Nothing])).unwrap();
+    let config = MemConfig::new(500).with_foreign_pointers(ForeignPtrPolicy::Report);
+    let mut heap = MarkAndSweepMem::<MyUnsized, MyPointer>::new(config);
+    let mut root = heap.push(MyUnsized::new_u([Nothing, Pointer(foreign)])).unwrap();
+    let report = unsafe{ heap.gc_with_report(vec![&mut root, &mut foreign], vec![]) };
+    assert_eq!(report.foreign_pointers(), 2);
+    assert_eq!(heap.len(), 1);
+    // foreign pointers are left unchanged
+    assert_eq!(heap.get_by_ref(&root).unwrap().values[1].pointer(), Some(foreign));
+    assert!(other.contains_ptr(&foreign));
+}
+
+#[test]
+fn test_repeated_roots(){
+    let mut heap = MarkAndSweepMem::<[u64]>::new(1024);
+    heap.push_slice(&[1]).unwrap();
+    let mut root = heap.push_slice(&[2, 3]).unwrap();
+    let mut copy = root;
+    // the same location as a root twice and a weak, and another location with the same value
+    let report = unsafe{ heap.gc_with_report(vec![&mut root, &mut root, &mut copy], vec![&mut root]) };
+    assert_eq!(report.objects_reclaimed(), 1);
+    assert_eq!(report.weaks_cleared(), 0);
+    assert_eq!(root, copy);
+    assert_eq!(heap.get_by_ref(&root).unwrap(), &[2, 3]);
+}
+
+#[test]
+fn test_quarantine(){
+    let mut heap = MarkAndSweepMem::<[u64]>::new(MemConfig::new(1024).with_quarantine(1));
+    let mut kept = heap.push_slice(&[1]).unwrap();
+    let dropped = heap.push_slice(&[2]).unwrap();
+    let (old, line) = (kept, line!() - 2);
+    unsafe{
+        heap.gc(vec![&mut kept], vec![]);
+    }
+    let quarantine = heap.quarantine().unwrap();
+    let moved = quarantine.get(old.cast::<u8>() as usize).unwrap();
+    assert_eq!(moved.moved_to(), Some(kept.cast::<u8>() as usize));
+    assert_eq!(moved.allocated_at().line(), line);
+    assert_eq!(quarantine.get(dropped.cast::<u8>() as usize).unwrap().moved_to(), None);
+    let err = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| heap.get_by_ref(&dropped))).unwrap_err();
+    assert!(err.downcast_ref::<String>().unwrap().contains("dropped by collection 1"));
+
+    // freed values are forgotten after the given number of collections
+    unsafe{
+        heap.gc(vec![&mut kept], vec![]);
+    }
+    assert!(heap.quarantine().unwrap().get(dropped.cast::<u8>() as usize).is_none());
+    assert_eq!(heap.get_by_ref(&kept).unwrap(), &[1]);
+}
+
+#[test]
+fn test_identity_hash(){
+    let mut heap = MarkAndSweepMem::<[u64]>::new(MemConfig::new(1024).with_identity_hashes(true));
+    let mut a = heap.push_slice(&[1]).unwrap();
+    let dropped = heap.push_slice(&[2]).unwrap();
+    let mut b = heap.push_slice(&[3]).unwrap();
+    let (hash_a, hash_b) = (heap.identity_hash(&a).unwrap(), heap.identity_hash(&b).unwrap());
+    assert_ne!(hash_a, hash_b);
+    let old = a;
+    unsafe{
+        heap.gc(vec![&mut a, &mut b], vec![]);
+    }
+    assert_ne!(a, old);
+    assert_eq!(heap.identity_hash(&a), Some(hash_a));
+    assert_eq!(heap.identity_hash(&b), Some(hash_b));
+    assert_eq!(heap.identity_hash(&dropped), None);
+
+    // without a table, only values that never move have identity hashes
+    let mut moving = MarkAndSweepMem::<[u64]>::new(1024);
+    let c = moving.push_slice(&[4]).unwrap();
+    assert_eq!(moving.identity_hash(&c), None);
+}
+
+#[test]
+fn test_survival(){
+    let mut heap = MarkAndSweepMem::<[u64]>::new(MemConfig::new(1024).with_object_headers(true));
+    let mut a = heap.push_slice(&[1]).unwrap();
+    heap.push_slice(&[2]).unwrap();
+    unsafe{
+        heap.gc(vec![&mut a], vec![]);
+    }
+    assert_eq!(heap.age(&a), Some(1));
+    let c = heap.push_slice(&[3]).unwrap();
+    assert_eq!(heap.age(&c), Some(0));
+    unsafe{
+        heap.gc(vec![&mut a], vec![]);
+    }
+    assert_eq!(heap.age(&a), Some(2));
+
+    let survival = heap.survival().unwrap();
+    assert_eq!((survival.at(0).survived(), survival.at(0).dropped()), (1, 2));
+    assert_eq!((survival.at(1).survived(), survival.at(1).dropped()), (1, 0));
+    assert_eq!(survival.survival_rate(2), None);
+    assert!((survival.survival_curve(1) - 1.0 / 3.0).abs() < 1e-9);
+
+    // ages are kept in headers
+    assert!(MarkAndSweepMem::<[u64]>::new(1024).survival().is_none());
+}
+
+#[test]
+fn test_checkpoint(){
+    let mut heap = MarkAndSweepMem::<[u64]>::new(MemConfig::new(256).with_sweep_in_place(true).with_object_headers(true));
+    let mut kept = heap.push_slice(&[1, 2]).unwrap();
+    let dropped = heap.push_slice(&[3]).unwrap();
+    let checkpoint = heap.checkpoint_with(|v| v.into());
+
+    // speculatively change a value, collect another, and reuse its space
+    heap.get_by(&kept).unwrap()[0] = 5;
+    unsafe{ heap.gc(vec![&mut kept], vec![]) };
+    heap.push_slice(&[6, 7, 8]).unwrap();
+    assert_eq!(heap.len(), 2);
+
+    heap.rollback(checkpoint);
+    assert_eq!(heap.len(), 2);
+    assert_eq!(heap.get_by_ref(&kept).unwrap(), &[1, 2]);
+    assert_eq!(heap.get_by_ref(&dropped).unwrap(), &[3]);
+    assert_eq!(heap.header(&kept).unwrap().age(), 0);
+    assert!(heap.verify().is_ok());
+}
+
+#[test]
+fn test_fork(){
+    let mut heap = MarkAndSweepMem::<MyUnsized, MyPointer>::new(MemConfig::new(512).with_identity_hashes(true));
+    let first = heap.push(MyUnsized::new_u([Nothing])).unwrap();
+    let second = heap.push(MyUnsized::new_u([Nothing, Pointer(first)])).unwrap();
+    let hash = heap.identity_hash(&second);
+
+    let fork = heap.fork_with(|v| match v.values[..]{
+        [Nothing] => MyUnsized::new_u([Nothing]),
+        [Nothing, Pointer(p)] => MyUnsized::new_u([Nothing, Pointer(p)]),
+        _ => unreachable!()
+    });
+    let forked = heap.ptr_in_fork(&second, &fork).unwrap();
+    assert_ne!(forked, second);
+    assert_eq!(fork.identity_hash(&forked), hash);
+    // pointers in the copies point to other copies
+    let target = fork.get_by_ref(&forked).unwrap().values[1].pointer().unwrap();
+    assert_eq!(Some(target), heap.ptr_in_fork(&first, &fork));
+    assert!(fork.verify().is_ok());
+
+    // changes to the original don't affect the fork
+    heap.get_by(&second).unwrap().values[0] = Pointer(second);
+    assert!(matches!(fork.get_by_ref(&forked).unwrap().values[0], Nothing));
+}
+
+#[test]
+fn test_depth_first_evacuation(){
+    let mut heap = MarkAndSweepMem::<MyUnsized, MyPointer>::new(MemConfig::new(512).with_evacuation_order(EvacuationOrder::DepthFirst));
+    let child = heap.push(MyUnsized::new_u([Nothing])).unwrap();
+    let mut other = heap.push(MyUnsized::new_u([Nothing])).unwrap();
+    let mut parent = heap.push(MyUnsized::new_u([Nothing, Pointer(child)])).unwrap();
+    unsafe{
+        heap.gc(vec![&mut parent, &mut other], vec![]);
+    }
+    // the child is placed right after its parent, rather than after the value allocated between them
+    let child = heap.get_by_ref(&parent).unwrap().values[1].pointer().unwrap();
+    let parent_end = parent.0 as *const u8 as usize + mem::size_of_val(heap.get_by_ref(&parent).unwrap());
+    assert_eq!(child.0 as *const u8 as usize, parent_end);
+    assert_eq!(heap.len(), 3);
+    assert!(heap.verify().is_ok());
+}
+
+#[test]
+fn test_placement(){
+    let mut heap = MarkAndSweepMem::<[u64]>::new(128);
+    let mut cold = heap.push_placed(Box::new([1]), Placement::Cold, |p| p).unwrap();
+    let mut hot = heap.push_slice(&[2]).unwrap();
+    heap.push_placed(Box::new([3]), Placement::Immortal, |p| p).unwrap();
+    heap.push_slice(&[4]).unwrap();
+    unsafe{
+        heap.gc(vec![&mut cold, &mut hot], vec![]);
+    }
+    // the immortal value survives without a root, and cold values are placed last
+    let mut values = Vec::new();
+    heap.for_each(|v, p| values.push((p.cast::<u64>(), v[0])));
+    values.sort();
+    assert_eq!(values.iter().map(|(_, v)| *v).collect::<Vec<_>>(), [3, 2, 1]);
+    assert_eq!(heap.get_by_ref(&cold).unwrap(), &[1]);
+}
+
+#[test]
+fn test_cleared_weaks(){
+    let mut heap = MarkAndSweepMem::<[u64]>::new(1024);
+    // garbage before the kept value makes it move
+    heap.push_slice(&[0]).unwrap();
+    let mut kept = heap.push_slice(&[1]).unwrap();
+    let mut dead = heap.push_slice(&[2]).unwrap();
+    let before = kept;
+    let mut alive = kept;
+    let mut already_cleared = heap.push_slice(&[3]).unwrap();
+    already_cleared.set_cleared();
+    let (report, cleared) = unsafe{ heap.gc_reporting_cleared(vec![&mut kept], vec![&mut alive, &mut dead, &mut already_cleared]) };
+    // only weaks cleared by this collection are listed
+    assert_eq!(cleared, [1]);
+    assert_eq!(report.weaks_cleared(), 1);
+    assert!(dead.is_cleared() && already_cleared.is_cleared());
+    // weaks to surviving values are updated like roots
+    assert_ne!(kept, before);
+    assert_eq!(alive, kept);
+    assert!(!alive.is_cleared());
+    assert_eq!(heap.get_by_ref(&alive).unwrap(), &[1]);
+}
+
+#[test]
+fn test_walk(){
+    let mut heap = MarkAndSweepMem::<MyUnsized, MyPointer>::new(512);
+    let first = heap.push(MyUnsized::new_u([Nothing])).unwrap();
+    let second = heap.push(MyUnsized::new_u([Nothing, Pointer(first), Pointer(first)])).unwrap();
+    let mut walked = Vec::new();
+    heap.walk(&mut |_: &MyUnsized, ptr: &MyPointer, size: usize, edges: &[MyPointer]| walked.push((*ptr, size, edges.to_vec())));
+    let size = mem::size_of::<MyDataValue>();
+    assert_eq!(walked, [(first, size, vec![]), (second, 3 * size, vec![first, first])]);
+}
+
+#[test]
+fn test_marking_traces(){
+    let mut mem = MarkAndSweepMem::<TraceOnly>::new(256);
+    let tail = mem.push(Box::new(TraceOnly{ next: None })).unwrap();
+    mem.push(Box::new(TraceOnly{ next: None })).unwrap();
+    let mut head = mem.push(Box::new(TraceOnly{ next: Some(tail) })).unwrap();
+    let report = unsafe{ mem.gc_with_report(vec![&mut head], vec![]) };
+    assert_eq!((report.objects_reclaimed(), mem.len()), (1, 2));
+
+    // marking in steps traces too
+    mem.push(Box::new(TraceOnly{ next: None })).unwrap();
+    let report = loop{
+        if let GcPhaseProgress::Complete(report) = unsafe{ mem.gc_step(vec![&mut head], vec![], Budget::Objects(1)) }{
+            break report;
+        }
+    };
+    assert_eq!((report.objects_reclaimed(), mem.len()), (1, 2));
+}
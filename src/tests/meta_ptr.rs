@@ -2,7 +2,7 @@
 // Test a data type that stores type information in the heap's pointers, not inline
 
 use std::ptr::null;
-use crate::gc::{GcCandidate, ManagedMem};
+use crate::gc::{GcCandidate, ManagedMem, NoGcMem};
 use crate::gc::mas::MarkAndSweepMem;
 use crate::heap::HeapPtr;
 
@@ -78,6 +78,10 @@ impl HeapPtr<PolyData> for PolyPtr{
     fn eq_ignoring_meta(&self, other: &Self) -> bool {
         return self.ptr == other.ptr;
     }
+
+    fn type_tag(&self) -> Option<u32>{
+        return Some(self.tag as u32);
+    }
 }
 
 #[test]
@@ -107,4 +111,76 @@ fn test_ptr_with_meta(){
         heap.gc(vec![&mut n], vec![]);
         assert_eq!(heap.len(), 1);
     }
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_lookup_ignoring_meta(){
+    let mut heap = MarkAndSweepMem::<PolyData, PolyPtr>::new(500);
+    let int = heap.push_with(Box::new(PolyData{ i_val: 1 }), |mut p| { p.tag = PolyTag::Int; p }).unwrap();
+    let untyped = PolyPtr{ ptr: int.ptr, tag: PolyTag::Untyped };
+    assert!(heap.get_by(&untyped).is_none());
+    assert!(heap.to_full_ptr(&untyped) == Some(int));
+    heap.get_by_ignoring_meta(&untyped).unwrap().i_val = 2;
+    assert_eq!(unsafe{ heap.get_by_ref(&int).unwrap().i_val }, 2);
+
+    let missing = PolyPtr{ ptr: null(), tag: PolyTag::Untyped };
+    assert!(heap.to_full_ptr(&missing).is_none());
+    assert!(heap.get_by_ignoring_meta(&missing).is_none());
+
+    // collectors find values through pointers without their metadata
+    assert!(!heap.contains_ptr(&untyped) && heap.contains_ptr_ignoring_meta(&untyped));
+    let mut weak = untyped;
+    unsafe{ heap.gc(vec![], vec![&mut weak]); }
+    assert!(weak.is_cleared());
+}
+
+#[test]
+fn test_untyped_edges(){
+    let mut heap = MarkAndSweepMem::<PolyData, PolyPtr>::new(500);
+    // garbage before every value makes them all move
+    heap.push_with(Box::new(PolyData{ i_val: 0 }), |mut p| { p.tag = PolyTag::Int; p }).unwrap();
+    let int = heap.push_with(Box::new(PolyData{ i_val: 7 }), |mut p| { p.tag = PolyTag::Int; p }).unwrap();
+    let mid = heap.push_with(Box::new(PolyData{ ptr_val: int.ptr }), |mut p| { p.tag = PolyTag::Ptr; p }).unwrap();
+    let mut root = heap.push_with(Box::new(PolyData{ ptr_val: mid.ptr }), |mut p| { p.tag = PolyTag::Ptr; p }).unwrap();
+
+    // values only reachable through untyped edges survive, and the edges follow them
+    let report = unsafe{ heap.gc_with_report(vec![&mut root], vec![]) };
+    assert_eq!((report.objects_reclaimed(), heap.len()), (1, 3));
+    let mid_edge = PolyPtr{ ptr: unsafe{ heap.get_by_ref(&root).unwrap().ptr_val }, tag: PolyTag::Untyped };
+    assert!(mid_edge.ptr != mid.ptr);
+    let new_mid = heap.to_full_ptr(&mid_edge).unwrap();
+    assert_eq!(new_mid.tag, PolyTag::Ptr);
+    let int_edge = PolyPtr{ ptr: unsafe{ heap.get_by_ref(&new_mid).unwrap().ptr_val }, tag: PolyTag::Untyped };
+    assert!(int_edge.ptr != int.ptr);
+    let new_int = heap.to_full_ptr(&int_edge).unwrap();
+    assert_eq!(new_int.tag, PolyTag::Int);
+    assert_eq!(unsafe{ heap.get_by_ref(&new_int).unwrap().i_val }, 7);
+}
+
+#[test]
+fn test_report_unreachable_ignoring_meta(){
+    let mut mem = NoGcMem::<PolyData, PolyPtr>::new(500);
+    let n = mem.push_with(Box::new(PolyData{ nothing_val: () }), |mut p| { p.tag = PolyTag::Nothing; p }).unwrap();
+    // the edge to `n` is untyped, unlike the pointer it was pushed with
+    let root = mem.push_with(Box::new(PolyData{ ptr_val: n.ptr }), |mut p| { p.tag = PolyTag::Ptr; p }).unwrap();
+    mem.push_with(Box::new(PolyData{ i_val: 1 }), |mut p| { p.tag = PolyTag::Int; p }).unwrap();
+
+    let report = mem.report_unreachable(&[root]);
+    assert_eq!(report.objects().len(), 1);
+    assert!(report.objects()[0].tag == PolyTag::Int);
+}
+
+#[test]
+fn test_census_by_type(){
+    let mut heap = MarkAndSweepMem::<PolyData, PolyPtr>::new(500);
+    for i in 0..3{
+        heap.push_with(Box::new(PolyData{ i_val: i }), |mut p| { p.tag = PolyTag::Int; p }).unwrap();
+    }
+    heap.push_with(Box::new(PolyData{ nothing_val: () }), |mut p| { p.tag = PolyTag::Nothing; p }).unwrap();
+
+    let census = heap.census();
+    assert_eq!((census.total().count(), census.total().bytes()), (4, 32));
+    assert_eq!(census.by_size().keys().copied().collect::<Vec<_>>(), vec![8]);
+    assert_eq!(census.by_type()[&(PolyTag::Int as u32)].count(), 3);
+    assert_eq!(census.by_type()[&(PolyTag::Nothing as u32)].bytes(), 8);
+}
@@ -0,0 +1,79 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU64, Ordering};
+use metrics::{Counter, Gauge, Histogram, HistogramFn, Key, KeyName, Recorder, SharedString, Unit};
+use crate::gc::ManagedMem;
+use crate::gc::mas::MarkAndSweepMem;
+use crate::gc::metrics::GcMetrics;
+
+// a recorder keeping the last value of every counter and gauge, and every histogram sample
+#[derive(Default)]
+struct TestRecorder{
+    values: Mutex<HashMap<String, Arc<AtomicU64>>>,
+    samples: Mutex<HashMap<String, Arc<Samples>>>
+}
+
+#[derive(Default)]
+struct Samples(Mutex<Vec<f64>>);
+
+impl HistogramFn for Samples{
+    fn record(&self, value: f64){
+        self.0.lock().unwrap().push(value);
+    }
+}
+
+impl TestRecorder{
+    fn value(&self, name: &str) -> Arc<AtomicU64>{
+        return self.values.lock().unwrap().entry(name.to_string()).or_default().clone();
+    }
+}
+
+impl Recorder for TestRecorder{
+    fn describe_counter(&self, _key: KeyName, _unit: Option<Unit>, _description: SharedString){}
+
+    fn describe_gauge(&self, _key: KeyName, _unit: Option<Unit>, _description: SharedString){}
+
+    fn describe_histogram(&self, _key: KeyName, _unit: Option<Unit>, _description: SharedString){}
+
+    fn register_counter(&self, key: &Key) -> Counter{
+        assert_eq!(key.labels().next().unwrap().value(), "test");
+        return Counter::from_arc(self.value(key.name()));
+    }
+
+    fn register_gauge(&self, key: &Key) -> Gauge{
+        return Gauge::from_arc(self.value(key.name()));
+    }
+
+    fn register_histogram(&self, key: &Key) -> Histogram{
+        let samples = self.samples.lock().unwrap().entry(key.name().to_string()).or_default().clone();
+        return Histogram::from_arc(samples);
+    }
+}
+
+#[test]
+fn test_gc_metrics(){
+    // the recorder is global, so only this test may record metrics
+    let recorder: &'static TestRecorder = Box::leak(Box::default());
+    metrics::set_recorder(recorder).unwrap();
+    let mut heap = MarkAndSweepMem::<u64>::new(1024);
+    GcMetrics::new("test").install(heap.gc_hooks().unwrap());
+    let mut root = heap.push(Box::new(1)).unwrap();
+    heap.push(Box::new(2)).unwrap();
+    unsafe{ heap.gc(vec![&mut root], vec![]) };
+    heap.push(Box::new(3)).unwrap();
+    heap.push(Box::new(4)).unwrap();
+    unsafe{ heap.gc(vec![&mut root], vec![]) };
+    GcMetrics::new("test").record_heap(&heap);
+
+    let counter = |name: &str| recorder.value(name).load(Ordering::Relaxed);
+    let gauge = |name: &str| f64::from_bits(counter(name));
+    assert_eq!(counter("swifer_gc_collections_total"), 2);
+    assert_eq!(counter("swifer_gc_objects_reclaimed_total"), 3);
+    assert_eq!(counter("swifer_gc_bytes_reclaimed_total"), 24);
+    // only the two values pushed between collections are counted
+    assert_eq!(counter("swifer_gc_allocated_bytes_total"), 16);
+    assert_eq!(gauge("swifer_heap_live_bytes"), 8.0);
+    assert_eq!(gauge("swifer_heap_objects"), 1.0);
+    assert_eq!(gauge("swifer_heap_capacity_bytes"), 1024.0);
+    assert_eq!(recorder.samples.lock().unwrap()["swifer_gc_pause_seconds"].0.lock().unwrap().len(), 2);
+}
@@ -1,3 +1,47 @@
+mod any;
+mod arena;
+mod barrier;
+mod cell;
+mod config;
+#[cfg(feature = "debug")]
+mod debug;
+mod defaults;
+mod dyn_mem;
+mod dyn_obj;
+#[cfg(feature = "ffi")]
+mod ffi;
+mod generations;
+mod future;
+#[cfg(feature = "random")]
+mod graph;
 mod heap;
+#[cfg(feature = "serde")]
+mod image;
+mod impls;
+mod incremental;
+#[allow(clippy::clone_on_copy, clippy::forget_non_drop)] // pointers are cloned explicitly, and dyn_arg! forgets its arguments
 mod mas;
-mod meta_ptr;
\ No newline at end of file
+mod meta_ptr;
+#[cfg(feature = "metrics")]
+mod metrics;
+mod parallel;
+mod policy;
+mod roots;
+mod safepoint;
+#[cfg(feature = "sanitize")]
+mod sanitize;
+mod shared;
+mod spaces;
+mod sweep;
+mod group;
+mod handles;
+mod actors;
+mod raw_image;
+mod regions;
+mod scoped;
+#[cfg(all(feature = "persist", unix, target_pointer_width = "64"))]
+mod persist;
+mod testing;
+#[cfg(feature = "trace")]
+mod trace;
+mod verify;
\ No newline at end of file
@@ -0,0 +1,89 @@
+use std::panic;
+use std::panic::AssertUnwindSafe;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use crate::gc::config::MemConfig;
+use crate::gc::ManagedMem;
+use crate::gc::mas::MarkAndSweepMem;
+use crate::gc::parallel::WorkPool;
+use crate::gc::threads::{GcExecutor, GcThreadPool, ThreadPoolConfig};
+
+#[test]
+fn test_work_pool(){
+    // every item n > 0 queues the two items n - 1, so a tree of 2^(n+1) - 1 items is processed in total
+    let mut pool = WorkPool::new(4);
+    pool.push(0, 10u32);
+    let processed = AtomicUsize::new(0);
+    let counts = pool.run_with(|_| 0usize, |count, worker, n| {
+        processed.fetch_add(1, Ordering::Relaxed);
+        *count += 1;
+        if n > 0{
+            worker.push(n - 1);
+            worker.push(n - 1);
+        }
+    });
+    assert_eq!(processed.load(Ordering::Relaxed), (1 << 11) - 1);
+    assert_eq!(counts.len(), 4);
+    assert_eq!(counts.iter().sum::<usize>(), (1 << 11) - 1);
+    assert!(pool.is_empty());
+
+    // pools can be reused
+    for i in 0..8{
+        pool.push(i, 0);
+    }
+    pool.run(|_, _| { processed.fetch_add(1, Ordering::Relaxed); });
+    assert_eq!(processed.load(Ordering::Relaxed), (1 << 11) + 7);
+}
+
+
+#[test]
+fn test_thread_pool(){
+    let started = Arc::new(Mutex::new(Vec::new()));
+    let recorded = started.clone();
+    let pool = Arc::new(GcThreadPool::new(ThreadPoolConfig::new(3)
+        .with_name("test-gc")
+        .with_stack_size(1 << 20)
+        .with_start_handler(move |index| recorded.lock().unwrap().push((index, thread::current().name().unwrap().to_string())))));
+    assert_eq!(pool.threads(), 3);
+
+    // work pools run on the pool's threads rather than spawning their own
+    let mut work = WorkPool::new(3);
+    work.push(0, 8u32);
+    let names = Mutex::new(Vec::new());
+    let counts = work.run_on(&*pool, |_| 0usize, |count, worker, n| {
+        *count += 1;
+        names.lock().unwrap().push(thread::current().name().unwrap().to_string());
+        if n > 0{
+            worker.push(n - 1);
+            worker.push(n - 1);
+        }
+    });
+    assert_eq!(counts.iter().sum::<usize>(), (1 << 9) - 1);
+    assert!(names.into_inner().unwrap().iter().all(|name| name.starts_with("test-gc-")));
+    let mut started = started.lock().unwrap().clone();
+    started.sort();
+    assert_eq!(started, vec![(0, "test-gc-0".to_string()), (1, "test-gc-1".to_string()), (2, "test-gc-2".to_string())]);
+
+    // panics are resumed on the calling thread, and the pool stays usable
+    let result = panic::catch_unwind(AssertUnwindSafe(|| pool.run(2, &|index| assert_ne!(index, 1))));
+    assert!(result.is_err());
+    let ran = AtomicUsize::new(0);
+    pool.run(3, &|_| { ran.fetch_add(1, Ordering::Relaxed); });
+    assert_eq!(ran.load(Ordering::Relaxed), 3);
+
+    // collectors evacuate on a configured executor
+    let mut heap = MarkAndSweepMem::<[u64]>::new(MemConfig::new(1 << 18).with_executor(pool.clone()));
+    assert_eq!(heap.config().threads(), 3);
+    let ptrs: Vec<*const [u64]> = (0..300)
+        .map(|i| heap.push_slice_with_len(64, |j| (i * 64 + j) as u64).unwrap())
+        .collect();
+    let mut kept: Vec<*const [u64]> = ptrs.iter().copied().step_by(2).collect();
+    unsafe{
+        heap.gc(kept.iter_mut().map(|p| p as *mut _).collect(), vec![]);
+    }
+    assert_eq!(heap.len(), 150);
+    for (k, ptr) in kept.iter().enumerate(){
+        assert!(heap.get_by_ref(ptr).unwrap().iter().enumerate().all(|(j, &v)| v == (k * 128 + j) as u64));
+    }
+}
@@ -0,0 +1,117 @@
+use std::fs;
+use std::io::ErrorKind;
+use std::os::unix::fs::FileExt;
+use crate::gc::{GcCandidate, ManagedMem};
+use crate::gc::config::MemConfig;
+use crate::gc::mas::MarkAndSweepMem;
+use crate::heap::Heap;
+
+#[derive(Copy, Clone)]
+struct Cell{
+    value: u64,
+    next: *const Cell
+}
+
+impl GcCandidate for Cell{
+    fn collect_managed_pointers(&self, _this: &*const Cell) -> Vec<*const Cell>{
+        return match self.next.is_null(){
+            true => vec![],
+            false => vec![self.next]
+        };
+    }
+
+    fn adjust_ptrs(&mut self, adjust: impl Fn(&*const Cell) -> *const Cell, _this: &*const Cell){
+        if !self.next.is_null(){
+            self.next = adjust(&self.next);
+        }
+    }
+}
+
+#[test]
+fn test_persistent_heap(){
+    let path = std::env::temp_dir().join(format!("swifer-test-{}.heap", std::process::id()));
+    let _ = fs::remove_file(&path);
+    {
+        let mut mem = MarkAndSweepMem::<Cell>::open_file(MemConfig::new(4096), &path).unwrap();
+        let tail = mem.push(Box::new(Cell{ value: 2, next: std::ptr::null() })).unwrap();
+        let mut head = mem.push(Box::new(Cell{ value: 1, next: tail })).unwrap();
+        mem.push(Box::new(Cell{ value: 3, next: std::ptr::null() })).unwrap();
+        // collections keep values in the file
+        unsafe{ mem.gc(vec![&mut head], vec![]) };
+        assert_eq!(mem.len(), 2);
+    }
+    {
+        let mem = MarkAndSweepMem::<Cell>::open_file(MemConfig::new(4096), &path).unwrap();
+        assert_eq!(mem.len(), 2);
+        let head = mem.get(1);
+        assert_eq!(head.value, 1);
+        assert_eq!(mem.get_by_ref(&head.next).unwrap().value, 2);
+        assert!(mem.verify().is_ok());
+    }
+    // corrupt the offset of the first value
+    let file = fs::OpenOptions::new().write(true).open(&path).unwrap();
+    file.write_all_at(&u64::MAX.to_ne_bytes(), 4096 + 4096).unwrap();
+    let err = Heap::<Cell>::open_file(&path, 4096, false).err().unwrap();
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn test_cow_fork(){
+    let mut heap = Heap::<Cell>::forkable(4096, true).unwrap();
+    let tail = heap.push(Box::new(Cell{ value: 2, next: std::ptr::null() })).unwrap();
+    let head = heap.push(Box::new(Cell{ value: 1, next: tail })).unwrap();
+    let mut fork = heap.fork().unwrap();
+    assert_eq!(fork.len(), 2);
+    assert!(fork.verify().is_ok());
+    // writes to either heap aren't seen by the other
+    let forked = heap.ptr_in_fork(&head, &fork).unwrap();
+    heap.get_by(&head).unwrap().value = 10;
+    assert_eq!(fork.get_by_ref(&forked).unwrap().value, 1);
+    fork.get_by(&forked).unwrap().value = 20;
+    assert_eq!(heap.get_by_ref(&head).unwrap().value, 10);
+    // pointers in forked values still point into the original heap
+    assert_eq!(fork.get_by_ref(&forked).unwrap().next, tail);
+    // later forks copy the heap as it is
+    heap.push(Box::new(Cell{ value: 3, next: std::ptr::null() })).unwrap();
+    let second = heap.fork().unwrap();
+    assert_eq!(second.len(), 3);
+    assert_eq!(second.get_by_ref(&heap.ptr_in_fork(&head, &second).unwrap()).unwrap().value, 10);
+    assert!(second.verify().is_ok());
+    drop(heap);
+    assert_eq!(fork.get_by_ref(&forked).unwrap().value, 20);
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn test_cow_fork_mem(){
+    let mut mem = MarkAndSweepMem::<Cell>::new(MemConfig::new(4096).with_forkable_heaps(true));
+    let tail = mem.push(Box::new(Cell{ value: 2, next: std::ptr::null() })).unwrap();
+    let mut head = mem.push(Box::new(Cell{ value: 1, next: tail })).unwrap();
+    let fork = mem.fork_copy_on_write().unwrap();
+    // pointers between values point into the fork
+    let forked = mem.ptr_in_fork(&head, &fork).unwrap();
+    let forked_tail = fork.get_by_ref(&forked).unwrap().next;
+    assert_eq!(mem.ptr_in_fork(&tail, &fork), Some(forked_tail));
+    mem.get_by(&tail).unwrap().value = 20;
+    assert_eq!(fork.get_by_ref(&forked_tail).unwrap().value, 2);
+
+    // heaps created by collections can be forked too
+    mem.push(Box::new(Cell{ value: 3, next: std::ptr::null() })).unwrap();
+    unsafe{ mem.gc(vec![&mut head], vec![]) };
+    let second = mem.fork_copy_on_write().unwrap();
+    assert_eq!(second.len(), 2);
+    let tail = mem.get_by_ref(&head).unwrap().next;
+    assert_eq!(second.get_by_ref(&mem.ptr_in_fork(&tail, &second).unwrap()).unwrap().value, 20);
+    assert!(fork.verify().is_ok() && second.verify().is_ok());
+}
+
+#[test]
+fn test_eager_fork(){
+    let mut heap = Heap::<Cell>::new(4096);
+    let value = heap.push(Box::new(Cell{ value: 1, next: std::ptr::null() })).unwrap();
+    let fork = heap.fork().unwrap();
+    heap.get_by(&value).unwrap().value = 2;
+    assert_eq!(fork.get_by_ref(&heap.ptr_in_fork(&value, &fork).unwrap()).unwrap().value, 1);
+}
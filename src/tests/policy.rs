@@ -0,0 +1,72 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use crate::gc::ManagedMem;
+use crate::gc::config::MemConfig;
+use crate::gc::mas::MarkAndSweepMem;
+use crate::gc::policy::{AutoGcMem, GcPolicy};
+use crate::heap::AllocErrorKind;
+
+#[test]
+fn test_allocation_trigger(){
+    let config = MemConfig::new(256).with_allocation_trigger(32);
+    let mut mem = AutoGcMem::new(MarkAndSweepMem::<u64>::new(config.clone()), Vec::new(), GcPolicy::from_config(&config));
+    let kept = mem.push(Box::new(1)).unwrap();
+    mem.roots_mut().push(kept);
+    for i in 0..3{
+        mem.push(Box::new(i)).unwrap();
+    }
+    assert_eq!(mem.len(), 4);
+    assert_eq!(mem.policy().allocated(), 32);
+    // the next push collects first, keeping only the rooted value
+    mem.push(Box::new(5)).unwrap();
+    assert_eq!(mem.len(), 2);
+    assert_eq!(mem.policy().allocated(), 8);
+    assert_eq!(mem.get_by_ref(&mem.roots()[0]), Some(&1));
+}
+
+#[test]
+fn test_occupancy_trigger(){
+    let config = MemConfig::new(64).with_occupancy_trigger(0.5);
+    let mut mem = AutoGcMem::new(MarkAndSweepMem::<u64>::new(config.clone()), Vec::new(), GcPolicy::from_config(&config));
+    for i in 0..4{
+        mem.push(Box::new(i)).unwrap();
+    }
+    assert_eq!(mem.used_bytes(), 32);
+    mem.push(Box::new(4)).unwrap();
+    assert_eq!(mem.len(), 1);
+
+    // without any triggers, collections only happen when out of space
+    let mut manual = AutoGcMem::new(MarkAndSweepMem::<u64>::new(16), Vec::new(), GcPolicy::new());
+    for i in 0..3{
+        manual.push(Box::new(i)).unwrap();
+    }
+    assert_eq!(manual.len(), 1);
+}
+
+#[test]
+fn test_limits(){
+    let config = MemConfig::new(256).with_soft_limit(16).with_hard_limit(32);
+    let mut mem = AutoGcMem::new(MarkAndSweepMem::<u64>::new(config.clone()), Vec::new(), GcPolicy::from_config(&config));
+    let soft = Arc::new(AtomicUsize::new(0));
+    let hard = Arc::new(AtomicUsize::new(0));
+    let (s, h) = (soft.clone(), hard.clone());
+    mem.on_soft_limit(move |_| { s.fetch_add(1, Ordering::Relaxed); });
+    mem.on_hard_limit(move |_| { h.fetch_add(1, Ordering::Relaxed); });
+    for i in 0..2{
+        let ptr = mem.push(Box::new(i)).unwrap();
+        mem.roots_mut().push(ptr);
+    }
+    assert_eq!(soft.load(Ordering::Relaxed), 0);
+    // passing the soft limit notifies and collects, but everything is rooted
+    let ptr = mem.push(Box::new(2)).unwrap();
+    mem.roots_mut().push(ptr);
+    assert_eq!(soft.load(Ordering::Relaxed), 1);
+    assert_eq!(mem.len(), 3);
+    let ptr = mem.push(Box::new(3)).unwrap();
+    mem.roots_mut().push(ptr);
+    // the heap has space, but the hard limit doesn't
+    let err = mem.push(Box::new(4)).unwrap_err();
+    assert_eq!(err.kind(), AllocErrorKind::LimitExceeded);
+    assert_eq!(hard.load(Ordering::Relaxed), 1);
+    assert_eq!(mem.len(), 4);
+}
@@ -0,0 +1,55 @@
+use std::io::ErrorKind;
+use crate::gc::{GcCandidate, ManagedMem};
+use crate::gc::config::MemConfig;
+use crate::gc::mas::MarkAndSweepMem;
+use crate::gc::raw_image::{load_image, save_image};
+
+#[derive(Copy, Clone)]
+struct Pair{
+    value: u64,
+    left: *const Pair,
+    right: *const Pair
+}
+
+impl GcCandidate for Pair{
+    fn collect_managed_pointers(&self, _this: &*const Pair) -> Vec<*const Pair>{
+        return [self.left, self.right].into_iter().filter(|p| !p.is_null()).collect();
+    }
+
+    fn adjust_ptrs(&mut self, adjust: impl Fn(&*const Pair) -> *const Pair, _this: &*const Pair){
+        for ptr in [&mut self.left, &mut self.right]{
+            if !ptr.is_null(){
+                *ptr = adjust(ptr);
+            }
+        }
+    }
+}
+
+fn pair(value: u64, left: *const Pair, right: *const Pair) -> Box<Pair>{
+    return Box::new(Pair{ value, left, right });
+}
+
+#[test]
+fn test_raw_image(){
+    let mut mem = MarkAndSweepMem::<Pair>::new(MemConfig::new(1024));
+    let leaf = mem.push(pair(1, std::ptr::null(), std::ptr::null())).unwrap();
+    let root = mem.push(pair(2, leaf, leaf)).unwrap();
+    mem.write_field(&leaf, |p| p.left = root);
+    let mut image = Vec::new();
+    save_image(&mem, &[root], &mut image).unwrap();
+
+    let mut restored = MarkAndSweepMem::<Pair>::new(MemConfig::new(1024));
+    let roots = unsafe{ load_image(&mut restored, image.as_slice()) }.unwrap();
+    assert_eq!(restored.len(), 2);
+    let new_root = restored.get_by_ref(&roots[0]).unwrap();
+    assert_eq!(new_root.value, 2);
+    assert_eq!(new_root.left, new_root.right);
+    assert_eq!(restored.get_by_ref(&new_root.left).unwrap().left, roots[0]);
+    assert!(restored.verify().is_ok());
+
+    // truncated images are rejected, leaving nothing behind
+    let mut restored = MarkAndSweepMem::<Pair>::new(MemConfig::new(1024));
+    let err = unsafe{ load_image(&mut restored, &image[..image.len() - 1]) }.unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+    assert_eq!(restored.len(), 0);
+}
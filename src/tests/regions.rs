@@ -0,0 +1,66 @@
+use crate::gc::{GcCandidate, ManagedMem};
+use crate::gc::config::{ForeignPtrPolicy, MemConfig};
+use crate::gc::impls::{adjust_slice, collect_all, trace_slice};
+use crate::gc::mas::MarkAndSweepMem;
+use crate::gc::regions::TypedRegions;
+
+const STRING: u32 = 1;
+const LIST: u32 = 2;
+
+enum Value{
+    Str(&'static str),
+    List(Vec<*const Value>)
+}
+
+impl GcCandidate for Value{
+    fn collect_managed_pointers(&self, _this: &*const Value) -> Vec<*const Value>{
+        return match self{
+            Value::Str(_) => Vec::new(),
+            Value::List(items) => collect_all(items)
+        };
+    }
+
+    fn trace(&self, visitor: &mut impl FnMut(&*const Value), _this: &*const Value){
+        if let Value::List(items) = self{
+            trace_slice(items, visitor);
+        }
+    }
+
+    fn adjust_ptrs(&mut self, adjust: impl Fn(&*const Value) -> *const Value, _this: &*const Value){
+        if let Value::List(items) = self{
+            adjust_slice(items, adjust);
+        }
+    }
+}
+
+fn space() -> MarkAndSweepMem<Value>{
+    return MarkAndSweepMem::new(MemConfig::new(1024).with_foreign_pointers(ForeignPtrPolicy::Skip));
+}
+
+#[test]
+fn test_typed_regions(){
+    let mut regions = TypedRegions::new(space(), Vec::<*const Value>::new());
+    regions.add_region(STRING, space());
+    let a = regions.push(STRING, Box::new(Value::Str("a"))).unwrap();
+    let b = regions.push(STRING, Box::new(Value::Str("b"))).unwrap();
+    let list = regions.push(LIST, Box::new(Value::List(vec![a, b]))).unwrap();
+    // lists have no region of their own, so go in the default region
+    assert!(!regions.has_region(LIST));
+    assert_eq!((regions.region(STRING).len(), regions.default_region().len()), (2, 1));
+
+    // only strings are visited
+    let mut strings = Vec::new();
+    regions.for_each_of_type(STRING, |v: &Value, _| if let Value::Str(s) = v { strings.push(*s) });
+    assert_eq!(strings, ["a", "b"]);
+
+    // strings reachable from other regions survive collection
+    regions.default_region_mut().write_field(&list, |v| if let Value::List(items) = v { items.pop(); });
+    regions.roots_mut().push(list);
+    regions.gc();
+    assert_eq!(regions.region(STRING).len(), 1);
+    let list = regions.roots()[0];
+    match regions.get_by_ref(&list).unwrap(){
+        Value::List(items) => assert!(matches!(regions.get_by_ref(&items[0]), Some(Value::Str("a")))),
+        Value::Str(_) => panic!("expected a list")
+    }
+}
@@ -0,0 +1,89 @@
+use crate::gc::ManagedMem;
+use crate::gc::mas::MarkAndSweepMem;
+use crate::gc::roots::{RootArrays, RootProvider};
+use crate::heap::HeapPtr;
+use crate::gc::stack::{MutatorStack, StackRoots};
+use crate::heap::AllocErrorKind;
+
+#[test]
+fn test_push_or_collect(){
+    let mut heap = MarkAndSweepMem::<u64>::new(16);
+    let mut roots = vec![heap.push(Box::new(1)).unwrap()];
+    heap.push(Box::new(2)).unwrap();
+
+    // the heap is full, so the unreachable `2` is collected to make space
+    let three = heap.push_or_collect(Box::new(3), &mut roots).unwrap();
+    assert_eq!(heap.len(), 2);
+    assert_eq!(*heap.get_by(&roots[0]).unwrap(), 1);
+    assert_eq!(*heap.get_by(&three).unwrap(), 3);
+
+    // everything is reachable, so collecting doesn't help
+    roots.push(three);
+    let err = heap.push_or_collect(Box::new(4), &mut roots).unwrap_err();
+    assert_eq!(err.kind(), AllocErrorKind::OutOfSpace);
+    assert_eq!(heap.len(), 2);
+}
+
+// a toy interpreter stack, where each frame holds a list of local values
+enum Value{
+    Nil,
+    Ref(*const u64)
+}
+
+struct Frames(Vec<Vec<Value>>);
+
+unsafe impl MutatorStack<*const u64> for Frames{
+    fn depth(&self) -> usize{
+        return self.0.len();
+    }
+
+    fn visit_frame(&mut self, depth: usize, visitor: &mut dyn FnMut(*mut *const u64)){
+        let idx = self.0.len() - 1 - depth;
+        for value in &mut self.0[idx]{
+            if let Value::Ref(ptr) = value{
+                visitor(ptr);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_stack_roots(){
+    let mut heap = MarkAndSweepMem::<u64>::new(24);
+    let outer = heap.push(Box::new(1)).unwrap();
+    let inner = heap.push(Box::new(2)).unwrap();
+    heap.push(Box::new(3)).unwrap();
+    let mut roots = StackRoots::new(Frames(vec![vec![Value::Ref(outer), Value::Nil], vec![Value::Ref(inner)]]));
+    assert_eq!(roots.roots().len(), 2);
+
+    heap.push_or_collect(Box::new(4), &mut roots).unwrap();
+    assert_eq!(heap.len(), 3);
+    let frames = &roots.stack().0;
+    assert!(matches!(frames[0][0], Value::Ref(p) if heap.get_by_ref(&p) == Some(&1)));
+    assert!(matches!(frames[1][0], Value::Ref(p) if heap.get_by_ref(&p) == Some(&2)));
+}
+
+#[test]
+fn test_root_arrays(){
+    let mut heap = MarkAndSweepMem::<u64>::new(64);
+    let values: Vec<*const u64> = (0..6).map(|i| heap.push(Box::new(i)).unwrap()).collect();
+    // an operand stack with two live slots, a register file, and a weak cache
+    let mut stack = [values[0], values[1], values[2]];
+    let mut registers = [values[3]];
+    let mut cache = [values[4], values[3]];
+    let mut roots = RootArrays::new()
+        .with_array(&mut stack[..2])
+        .with_array(&mut registers)
+        .with_weak_array(&mut cache);
+    assert_eq!(roots.len(), 5);
+    let report = heap.collect_from(&mut roots);
+    assert_eq!(report.objects_reclaimed(), 3);
+    assert_eq!(report.weaks_cleared(), 1);
+    drop(roots);
+
+    // every slot is updated in place
+    assert_eq!(stack[..2].iter().map(|p| *heap.get_by_ref(p).unwrap()).collect::<Vec<_>>(), vec![0, 1]);
+    assert_eq!(heap.get_by_ref(&registers[0]), Some(&3));
+    assert!(cache[0].is_cleared());
+    assert_eq!(cache[1], registers[0]);
+}
@@ -0,0 +1,33 @@
+use crate::gc::ManagedMem;
+use crate::gc::mas::MarkAndSweepMem;
+use crate::gc::safepoint::Safepoint;
+use crate::poll_safepoint;
+
+#[test]
+fn test_poll_safepoint(){
+    let mut mem = MarkAndSweepMem::<u64>::new(4096);
+    let safepoint = Safepoint::new();
+    let mut roots = vec![];
+    let mut collections = 0;
+    // a toy dispatch loop, which keeps every 25th value and requests a collection every 10 ops
+    for op in 0..100u64{
+        poll_safepoint!(safepoint, {
+            unsafe{ mem.gc(roots.iter_mut().map(|p| p as *mut _).collect(), vec![]) };
+            collections += 1;
+            safepoint.clear();
+        });
+        let value = mem.push(Box::new(op)).unwrap();
+        if op % 25 == 0{
+            roots.push(value);
+        }
+        if op % 10 == 9{
+            safepoint.request();
+        }
+    }
+    assert_eq!(collections, 9);
+    assert!(safepoint.is_requested());
+    // four roots, and the values pushed since the last collection
+    assert_eq!(safepoint.poll(|| mem.len()), Some(14));
+    assert!(!safepoint.is_requested());
+    assert_eq!(safepoint.poll(|| ()), None);
+}
@@ -0,0 +1,28 @@
+use crate::heap::Heap;
+
+// only linked when built with AddressSanitizer, e.g. with `RUSTFLAGS=-Zsanitizer=address`
+#[cfg(sanitize = "address")]
+extern "C"{
+    fn __asan_address_is_poisoned(addr: *const std::ffi::c_void) -> i32;
+}
+
+#[cfg(sanitize = "address")]
+fn is_poisoned(ptr: *const u64) -> bool{
+    return unsafe{ __asan_address_is_poisoned(ptr.cast()) != 0 };
+}
+
+#[test]
+fn test_freed_memory_inaccessible(){
+    let mut heap = Heap::<u64>::new(64);
+    let kept = heap.push(Box::new(1)).unwrap();
+    let dropped = heap.push(Box::new(2)).unwrap();
+    heap.retain(|p, _| *p == kept);
+    #[cfg(sanitize = "address")]
+    assert!(is_poisoned(dropped) && !is_poisoned(kept));
+    assert_eq!(heap.get_by_ref(&kept), Some(&1));
+
+    // the space is made accessible again when reused
+    let reused = heap.push(Box::new(3)).unwrap();
+    assert_eq!(reused, dropped);
+    assert_eq!(heap.get_by_ref(&reused), Some(&3));
+}
@@ -0,0 +1,55 @@
+use crate::gc::{GcCandidate, ManagedMem};
+use crate::gc::config::{ForeignPtrPolicy, MemConfig};
+use crate::gc::impls::{adjust_slice, collect_all, trace_slice};
+use crate::gc::mas::MarkAndSweepMem;
+use crate::gc::scoped::{RegionError, ScopedRegions};
+
+struct Node{
+    id: u32,
+    edges: Vec<*const Node>
+}
+
+impl GcCandidate for Node{
+    fn collect_managed_pointers(&self, _this: &*const Node) -> Vec<*const Node>{
+        return collect_all(&self.edges);
+    }
+
+    fn trace(&self, visitor: &mut impl FnMut(&*const Node), _this: &*const Node){
+        trace_slice(&self.edges, visitor);
+    }
+
+    fn adjust_ptrs(&mut self, adjust: impl Fn(&*const Node) -> *const Node, _this: &*const Node){
+        adjust_slice(&mut self.edges, adjust);
+    }
+}
+
+fn node(id: u32, edges: Vec<*const Node>) -> Box<Node>{
+    return Box::new(Node{ id, edges });
+}
+
+#[test]
+fn test_scoped_regions(){
+    let main = MarkAndSweepMem::new(MemConfig::new(1024).with_foreign_pointers(ForeignPtrPolicy::Skip));
+    let mut regions = ScopedRegions::new(main, Vec::<*const Node>::new());
+    regions.open("request", 1024);
+    // a value in the main memory only kept alive by the region
+    let shared = regions.push(node(1, vec![])).unwrap();
+    let scoped = regions.push_in("request", node(2, vec![shared])).unwrap();
+    let holder = regions.push(node(3, vec![scoped])).unwrap();
+    regions.roots_mut().push(holder);
+
+    regions.gc();
+    assert_eq!(regions.main().len(), 2);
+    let shared = regions.get_by_ref(&scoped).unwrap().edges[0];
+    assert_eq!(regions.get_by_ref(&shared).unwrap().id, 1);
+
+    // the region can't be closed while a reachable value points into it
+    assert_eq!(regions.close("request"), Err(RegionError::InUse));
+    let holder = regions.roots()[0];
+    regions.main_mut().write_field(&holder, |n| n.edges.clear());
+    assert_eq!(regions.close("request"), Ok(()));
+    assert!(regions.region("request").is_none());
+    assert_eq!(regions.close("request"), Err(RegionError::NotOpen));
+    regions.gc();
+    assert_eq!(regions.main().len(), 1);
+}
@@ -0,0 +1,209 @@
+use std::sync::mpsc;
+use std::thread;
+use crate::gc::impls::{adjust_option, collect_all, trace_option};
+use crate::gc::{GcCandidate, ManagedMem};
+use crate::gc::controller::GcController;
+use crate::gc::mas::MarkAndSweepMem;
+use crate::gc::shared::{Handle, SharedMem};
+use crate::heap::Heap;
+use crate::poll_safepoint;
+
+#[derive(Debug)]
+struct Cell{
+    value: u64,
+    next: Option<*const Cell>
+}
+
+// values only contain pointers into the shared memory, which are synchronized by it
+unsafe impl Send for Cell{}
+unsafe impl Sync for Cell{}
+
+impl GcCandidate for Cell{
+    fn collect_managed_pointers(&self, _this: &*const Cell) -> Vec<*const Cell>{
+        return collect_all(&self.next);
+    }
+
+    fn trace(&self, visitor: &mut impl FnMut(&*const Cell), _this: &*const Cell){
+        trace_option(&self.next, visitor);
+    }
+
+    fn adjust_ptrs(&mut self, adjust: impl Fn(&*const Cell) -> *const Cell, _this: &*const Cell){
+        adjust_option(&mut self.next, adjust);
+    }
+}
+
+#[test]
+fn test_shared_mem(){
+    let mem = SharedMem::new(MarkAndSweepMem::<Cell>::new(10_000));
+    let tail = mem.push(Box::new(Cell{ value: 0, next: None })).unwrap();
+    let head = mem.push(Box::new(Cell{ value: 1, next: Some(tail.ptr()) })).unwrap();
+    drop(tail);
+
+    let workers: Vec<_> = (0..4).map(|i| {
+        let mem = mem.clone();
+        let head = head.clone();
+        thread::spawn(move || {
+            for j in 0..20{
+                // garbage, and a value that stays alive for this thread
+                mem.push_or_collect(Box::new(Cell{ value: j, next: None })).unwrap();
+                let mine = mem.push_or_collect(Box::new(Cell{ value: i, next: None })).unwrap();
+                if j % 5 == 0{
+                    mem.gc();
+                }
+                assert_eq!(mine.read(|c| c.value), i);
+                assert_eq!(head.read(|c| c.value), 1);
+            }
+        })
+    }).collect();
+    for worker in workers{
+        worker.join().unwrap();
+    }
+
+    mem.gc();
+    assert_eq!(mem.len(), 2);
+    let tail = mem.root(&head.read(|c| c.next.unwrap())).unwrap();
+    tail.write(|c| c.value = 5);
+    assert_eq!(tail.read(|c| c.value), 5);
+}
+
+#[test]
+fn test_gc_controller(){
+    let mem = SharedMem::new(MarkAndSweepMem::<Cell>::new(1000));
+    let controller = GcController::new(mem.clone());
+    let (sender, receiver) = mpsc::channel();
+    controller.on_complete(move |n| sender.send(n).unwrap());
+
+    let kept = mem.push(Box::new(Cell{ value: 1, next: None })).unwrap();
+    mem.push(Box::new(Cell{ value: 2, next: None })).unwrap();
+    controller.wait_for_gc();
+    assert_eq!(mem.len(), 1);
+    assert_eq!(receiver.recv().unwrap(), 1);
+
+    let ticket = controller.request_gc();
+    controller.wait_for(ticket);
+    assert!(controller.collections() >= ticket);
+    assert!(!controller.safepoint().is_requested());
+    assert_eq!(kept.read(|c| c.value), 1);
+    drop(controller);
+}
+
+#[test]
+fn test_mutators(){
+    let mem = SharedMem::new(MarkAndSweepMem::<Cell>::new(10_000));
+    let workers: Vec<_> = (0..3).map(|i| {
+        let mem = mem.clone();
+        thread::spawn(move || {
+            let mutator = mem.register_mutator();
+            let handle = mutator.with_mem(|mem, state| {
+                let ptr = mem.push(Box::new(Cell{ value: i, next: None })).unwrap();
+                state.handles_mut().persist(ptr)
+            });
+            mem.push(Box::new(Cell{ value: 10, next: None })).unwrap();
+            mem.gc();
+            // the value was kept, and the handle updated, by the mutator's handles
+            mutator.with_mem(|mem, state| {
+                let ptr = state.handles().persistent(&handle).unwrap();
+                assert_eq!(mem.get_by_ref(ptr).unwrap().value, i);
+            });
+            mutator
+        })
+    }).collect();
+    let mutators: Vec<_> = workers.into_iter().map(|worker| worker.join().unwrap()).collect();
+    assert_eq!(mem.mutators(), 3);
+    mem.gc();
+    assert_eq!(mem.len(), 3);
+
+    for mutator in mutators{
+        mem.unregister_mutator(mutator);
+    }
+    assert_eq!(mem.mutators(), 0);
+    mem.gc();
+    assert_eq!(mem.len(), 0);
+    assert_eq!(mem.register_mutator().id(), 0);
+}
+
+#[test]
+fn test_handshakes(){
+    let mem = SharedMem::new(MarkAndSweepMem::<Cell>::new(10_000));
+    let idle = mem.register_mutator();
+    let (sender, receiver) = mpsc::channel();
+    let worker = {
+        let mem = mem.clone();
+        thread::spawn(move || {
+            let mutator = mem.register_mutator();
+            sender.send(()).unwrap();
+            let mut handled = 0;
+            while handled == 0{
+                poll_safepoint!(mutator.safepoint(), {
+                    handled = mutator.process_handshakes();
+                });
+                thread::yield_now();
+            }
+            mutator.with_mem(|_, state| state.handles().persistent_count())
+        })
+    };
+    receiver.recv().unwrap();
+    // each mutator persists a new value at its next safepoint
+    let handshake = mem.handshake(|mem, state| {
+        let ptr = mem.push(Box::new(Cell{ value: 1, next: None })).unwrap();
+        state.handles_mut().persist(ptr);
+    });
+    assert_eq!(worker.join().unwrap(), 1);
+    assert_eq!(handshake.remaining(), 1);
+    assert!(idle.safepoint().is_requested());
+    assert_eq!(idle.process_handshakes(), 1);
+    assert!(!idle.safepoint().is_requested());
+    handshake.wait();
+    assert!(handshake.is_complete());
+    mem.gc();
+    assert_eq!(mem.len(), 1);
+
+    // unregistered mutators never run handshakes
+    mem.handshake(|_, _| panic!("handshake run by unregistered mutator"));
+    let handshake = mem.handshake(|_, _| {});
+    drop(idle);
+    assert!(handshake.is_complete());
+}
+
+#[test]
+fn test_local_handles(){
+    let mem = SharedMem::new(MarkAndSweepMem::<Cell>::new(10_000));
+    let workers: Vec<_> = (0..3).map(|i| {
+        let mem = mem.clone();
+        thread::spawn(move || {
+            let mutator = mem.register_mutator();
+            mutator.with_locals(|locals| locals.open_scope());
+            for j in 0..10{
+                // rooted before another thread can collect
+                let local = mem.with_mem(|mem| {
+                    let ptr = mem.push(Box::new(Cell{ value: i * 100 + j, next: None })).unwrap();
+                    mutator.with_locals(|locals| locals.new_local(ptr))
+                });
+                mem.push(Box::new(Cell{ value: 0, next: None })).unwrap();
+                mem.gc();
+                // the value survived through the local handle, which was updated
+                let ptr = mutator.with_locals(|locals| *locals.local(local).unwrap());
+                assert_eq!(mem.with_mem(|mem| mem.get_by_ref(&ptr).unwrap().value), i * 100 + j);
+            }
+            mutator.with_locals(|locals| locals.close_scope());
+            mem.gc();
+            mutator
+        })
+    }).collect();
+    let mutators: Vec<_> = workers.into_iter().map(|worker| worker.join().unwrap()).collect();
+    mem.gc();
+    assert_eq!(mem.len(), 0);
+    drop(mutators);
+}
+
+fn assert_thread_safe<S: Send + Sync>(){}
+
+#[test]
+fn test_thread_safety(){
+    // raw pointers are thread-safe pointers, so memories of thread-safe values are too, and can be
+    // shared; hooks and log sinks don't stop them being `Sync`, as they're only called through `&mut`
+    assert_thread_safe::<Heap<Cell>>();
+    assert_thread_safe::<MarkAndSweepMem<Cell>>();
+    assert_thread_safe::<SharedMem<Cell, *const Cell, MarkAndSweepMem<Cell>>>();
+    assert_thread_safe::<Handle<Cell, *const Cell, MarkAndSweepMem<Cell>>>();
+}
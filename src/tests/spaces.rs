@@ -0,0 +1,63 @@
+use crate::gc::{GcCandidate, ManagedMem};
+use crate::gc::config::{ForeignPtrPolicy, MemConfig};
+use crate::gc::impls::{adjust_slice, collect_all, trace_slice};
+use crate::gc::mas::MarkAndSweepMem;
+use crate::gc::spaces::LinkedSpaces;
+
+struct Node{
+    id: u32,
+    edges: Vec<*const Node>
+}
+
+impl GcCandidate for Node{
+    fn collect_managed_pointers(&self, _this: &*const Node) -> Vec<*const Node>{
+        return collect_all(&self.edges);
+    }
+
+    fn trace(&self, visitor: &mut impl FnMut(&*const Node), _this: &*const Node){
+        trace_slice(&self.edges, visitor);
+    }
+
+    fn adjust_ptrs(&mut self, adjust: impl Fn(&*const Node) -> *const Node, _this: &*const Node){
+        adjust_slice(&mut self.edges, adjust);
+    }
+}
+
+fn space() -> MarkAndSweepMem<Node>{
+    return MarkAndSweepMem::new(MemConfig::new(1024).with_foreign_pointers(ForeignPtrPolicy::Skip));
+}
+
+#[test]
+fn test_linked_spaces(){
+    let mut spaces = LinkedSpaces::new(space(), space());
+    // code -> data -> code
+    let data = spaces.first_mut().push(Box::new(Node{ id: 1, edges: vec![] })).unwrap();
+    spaces.first_mut().push(Box::new(Node{ id: 2, edges: vec![] })).unwrap();
+    let mut code = spaces.second_mut().push(Box::new(Node{ id: 3, edges: vec![data] })).unwrap();
+    spaces.record_value(&code);
+    spaces.first_mut().write_field(&data, |n| n.edges.push(code));
+    spaces.record_write(&data, &code);
+    assert_eq!((spaces.refs_into_first().len(), spaces.refs_into_second().len()), (1, 1));
+
+    // data is only reachable from the other space, and moves
+    let report = unsafe{ spaces.gc_first(vec![], vec![]) };
+    assert_eq!(report.objects_reclaimed(), 1);
+    let new_data = spaces.second().get_by_ref(&code).unwrap().edges[0];
+    assert_ne!(new_data, data);
+    assert_eq!(spaces.first().get_by_ref(&new_data).unwrap().id, 1);
+    assert!(spaces.refs_into_second().contains(&new_data));
+
+    // code is kept alive by data's pointer back to it only if data is a root
+    let mut root = new_data;
+    unsafe{ spaces.gc_second(vec![&mut root], vec![]) };
+    let moved_code = spaces.first().get_by_ref(&new_data).unwrap().edges[0];
+    assert_ne!(moved_code, code);
+    code = moved_code;
+    assert_eq!(spaces.second().get_by_ref(&code).unwrap().id, 3);
+
+    // once nothing points across, remembered values are forgotten
+    spaces.second_mut().write_field(&code, |n| n.edges.clear());
+    unsafe{ spaces.gc_first(vec![], vec![]) };
+    assert_eq!(spaces.first().len(), 0);
+    assert!(spaces.refs_into_first().is_empty() && spaces.refs_into_second().is_empty());
+}
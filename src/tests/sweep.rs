@@ -0,0 +1,75 @@
+use std::sync::Mutex;
+use std::thread;
+use crate::gc::{GcCandidate, ManagedMem};
+use crate::gc::config::MemConfig;
+use crate::gc::mas::MarkAndSweepMem;
+
+struct Noisy{
+    id: u32
+}
+
+// the id of every value dropped, and the thread it was dropped on
+static DROPPED: Mutex<Vec<(u32, Option<String>)>> = Mutex::new(Vec::new());
+
+impl GcCandidate for Noisy{
+    fn collect_managed_pointers(&self, _this: &*const Noisy) -> Vec<*const Noisy>{
+        return vec![];
+    }
+
+    fn adjust_ptrs(&mut self, _adjust: impl Fn(&*const Noisy) -> *const Noisy, _this: &*const Noisy){}
+}
+
+impl Drop for Noisy{
+    fn drop(&mut self){
+        DROPPED.lock().unwrap().push((self.id, thread::current().name().map(str::to_string)));
+    }
+}
+
+#[test]
+fn test_background_sweep(){
+    let mut mem = MarkAndSweepMem::<Noisy>::new(1024);
+    assert!(!mem.background_sweep());
+    mem.set_background_sweep(true);
+    assert!(mem.background_sweep());
+    mem.push(Box::new(Noisy{ id: 1 })).unwrap();
+    let mut root = mem.push(Box::new(Noisy{ id: 2 })).unwrap();
+    mem.push(Box::new(Noisy{ id: 3 })).unwrap();
+
+    let report = unsafe{ mem.gc_with_report(vec![&mut root], vec![]) };
+    assert_eq!(report.objects_reclaimed(), 2);
+    assert_eq!(mem.len(), 1);
+    assert_eq!(mem.get_by_ref(&root).unwrap().id, 2);
+    mem.wait_for_sweep();
+    let mut dropped = DROPPED.lock().unwrap().clone();
+    dropped.sort();
+    assert_eq!(dropped, [(1, Some("swifer-sweep".to_string())), (3, Some("swifer-sweep".to_string()))]);
+
+    // the next collection waits for the last sweep, and the survivor is still reachable
+    mem.push(Box::new(Noisy{ id: 4 })).unwrap();
+    unsafe{ mem.gc(vec![&mut root], vec![]) };
+    assert_eq!(mem.get_by_ref(&root).unwrap().id, 2);
+    drop(mem);
+    assert_eq!(DROPPED.lock().unwrap().len(), 4);
+}
+
+#[test]
+fn test_skip_drop(){
+    for sweep_in_place in [false, true]{
+        let config = MemConfig::new(1024).with_skip_drop(true).with_sweep_in_place(sweep_in_place);
+        assert!(config.skip_drop());
+        let mut mem = MarkAndSweepMem::<Noisy>::new(config);
+        let dropped = mem.push(Box::new(Noisy{ id: 10 })).unwrap();
+        let mut root = mem.push(Box::new(Noisy{ id: 11 })).unwrap();
+
+        let report = unsafe{ mem.gc_with_report(vec![&mut root], vec![]) };
+        assert_eq!(report.objects_reclaimed(), 1);
+        assert_eq!(mem.get_by_ref(&root).unwrap().id, 11);
+        // the reclaimed value's space is reused
+        if sweep_in_place{
+            assert_eq!(mem.push(Box::new(Noisy{ id: 12 })).unwrap(), dropped);
+        }
+        drop(mem);
+    }
+    // nothing is dropped, not even the values left when the memory is
+    assert!(DROPPED.lock().unwrap().iter().all(|(id, _)| *id < 10));
+}
@@ -0,0 +1,69 @@
+use crate::gc::{GcCandidate, ManagedMem, NoGcMem};
+use crate::gc::impls::{adjust_option, collect_all, trace_option};
+use crate::testing::{check_all, check_candidate, ConformanceError, MemCall, MockMem};
+
+struct Pair{
+    left: Option<*const Pair>,
+    right: Option<*const Pair>,
+    // whether `adjust_ptrs` forgets to update `right`
+    buggy: bool
+}
+
+impl GcCandidate for Pair{
+    fn collect_managed_pointers(&self, _this: &*const Pair) -> Vec<*const Pair>{
+        let mut ptrs = collect_all(&self.left);
+        ptrs.extend(collect_all(&self.right));
+        return ptrs;
+    }
+
+    fn trace(&self, visitor: &mut impl FnMut(&*const Pair), _this: &*const Pair){
+        trace_option(&self.left, visitor);
+        trace_option(&self.right, visitor);
+    }
+
+    fn adjust_ptrs(&mut self, adjust: impl Fn(&*const Pair) -> *const Pair, _this: &*const Pair){
+        adjust_option(&mut self.left, &adjust);
+        match self.buggy{
+            true => { self.right.as_ref().map(&adjust); }
+            false => adjust_option(&mut self.right, adjust)
+        }
+    }
+}
+
+#[test]
+fn test_check_candidate(){
+    let mut mem = NoGcMem::<Pair>::new(200);
+    let a = mem.push(Box::new(Pair{ left: None, right: None, buggy: false })).unwrap();
+    let b = mem.push(Box::new(Pair{ left: Some(a), right: Some(a), buggy: false })).unwrap();
+    mem.push(Box::new(Pair{ left: Some(b), right: Some(a), buggy: false })).unwrap();
+    assert_eq!(check_all(&mut mem), Ok(()));
+    // values are left unchanged
+    assert_eq!(mem.get_by_ref(&b).unwrap().right, Some(a));
+
+    let bad = mem.push(Box::new(Pair{ left: Some(a), right: Some(b), buggy: true })).unwrap();
+    assert_eq!(check_all(&mut mem), Err((3, ConformanceError::AdjustNotStored)));
+    assert_eq!(check_candidate(mem.get_by(&bad).unwrap(), &bad), Err(ConformanceError::AdjustNotStored));
+}
+
+#[test]
+fn test_mock_mem(){
+    let mut mem = MockMem::<Pair>::new().with_limit(3);
+    let a = mem.push(Box::new(Pair{ left: None, right: None, buggy: false })).unwrap();
+    let mut b = mem.push(Box::new(Pair{ left: Some(a), right: None, buggy: false })).unwrap();
+    let mut c = mem.push(Box::new(Pair{ left: None, right: None, buggy: false })).unwrap();
+    assert!(mem.push(Box::new(Pair{ left: None, right: None, buggy: false })).is_err());
+    assert_eq!(mem.get_by_ref(&b).unwrap().left, Some(a));
+    let old_c = c;
+    unsafe{ mem.gc(vec![&mut b], vec![&mut c]); }
+    assert_eq!(mem.len(), 2);
+    assert!(c.is_null());
+    assert_eq!(mem.pushes(), 3);
+    assert_eq!(mem.gets(), 1);
+    assert_eq!(mem.collections(), 1);
+    assert_eq!(mem.take_calls()[3..], [
+        MemCall::PushFailed,
+        MemCall::GetBy(b),
+        MemCall::Gc{ roots: vec![b], weaks: vec![old_c] }
+    ]);
+    assert!(mem.calls().is_empty());
+}
@@ -0,0 +1,79 @@
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+use tracing::{Event, Metadata, Subscriber};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use crate::gc::ManagedMem;
+use crate::gc::mas::MarkAndSweepMem;
+
+type Fields = HashMap<String, String>;
+
+// a subscriber keeping the fields of every event, with its message under `message`
+#[derive(Clone, Default)]
+struct TestSubscriber{
+    events: Arc<Mutex<Vec<Fields>>>
+}
+
+impl TestSubscriber{
+    fn find(&self, message: &str) -> Vec<Fields>{
+        let events = self.events.lock().unwrap();
+        return events.iter().filter(|e| e["message"] == message).cloned().collect();
+    }
+}
+
+// collects the fields of an event
+struct FieldVisitor<'a>(&'a mut Fields);
+
+impl Visit for FieldVisitor<'_>{
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug){
+        self.0.insert(field.name().to_string(), format!("{value:?}"));
+    }
+}
+
+impl Subscriber for TestSubscriber{
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool{
+        return metadata.target() == "swifer";
+    }
+
+    fn new_span(&self, _span: &Attributes<'_>) -> Id{
+        return Id::from_u64(1);
+    }
+
+    fn record(&self, _span: &Id, _values: &Record<'_>){}
+
+    fn record_follows_from(&self, _span: &Id, _follows: &Id){}
+
+    fn event(&self, event: &Event<'_>){
+        let mut fields = Fields::new();
+        event.record(&mut FieldVisitor(&mut fields));
+        self.events.lock().unwrap().push(fields);
+    }
+
+    fn enter(&self, _span: &Id){}
+
+    fn exit(&self, _span: &Id){}
+}
+
+#[test]
+fn test_gc_events(){
+    let subscriber = TestSubscriber::default();
+    tracing::subscriber::with_default(subscriber.clone(), || {
+        let mut heap = MarkAndSweepMem::<u64>::new(16);
+        heap.push(Box::new(1)).unwrap();
+        let mut root = heap.push(Box::new(2)).unwrap();
+        assert!(heap.push(Box::new(3)).is_err());
+        unsafe{ heap.gc(vec![&mut root], vec![]) };
+    });
+
+    let failed = subscriber.find("allocation failed");
+    assert_eq!(failed.len(), 1);
+    assert_eq!((failed[0]["kind"].as_str(), failed[0]["size"].as_str()), ("OutOfSpace", "8"));
+    assert_eq!(subscriber.find("collection started").len(), 1);
+    let finished = subscriber.find("collection finished");
+    assert_eq!(finished.len(), 1);
+    assert_eq!(finished[0]["objects_reclaimed"], "1");
+    assert_eq!(finished[0]["bytes_reclaimed"], "8");
+    // the surviving value moves down over the dropped one
+    assert_eq!(finished[0]["relocated"], "1");
+}
@@ -0,0 +1,58 @@
+use crate::gc::{GcCandidate, ManagedMem, NoGcMem};
+use crate::gc::config::MemConfig;
+use crate::gc::impls::{adjust_option, collect_all};
+use crate::gc::mas::MarkAndSweepMem;
+use crate::heap::{Heap, VerifyError};
+
+struct Node{
+    next: Option<*const Node>
+}
+
+impl GcCandidate for Node{
+    fn collect_managed_pointers(&self, _this: &*const Node) -> Vec<*const Node>{
+        return collect_all(&self.next);
+    }
+
+    fn adjust_ptrs(&mut self, adjust: impl Fn(&*const Node) -> *const Node, _this: &*const Node){
+        adjust_option(&mut self.next, adjust);
+    }
+}
+
+#[test]
+fn test_verify(){
+    let mut heap: Heap<[u16]> = Heap::new(64);
+    heap.push_slice(&[1, 2, 3]).unwrap();
+    heap.push(Box::new([4u16])).unwrap();
+    heap.push_slice(&[]).unwrap();
+    assert_eq!(heap.verify(), Ok(()));
+
+    let mut mem = MarkAndSweepMem::<Node>::new(MemConfig::new(100).with_heap_verification(true));
+    let mut a = mem.push(Box::new(Node{ next: None })).unwrap();
+    mem.push(Box::new(Node{ next: Some(a) })).unwrap();
+    assert_eq!(mem.verify(), Ok(()));
+    unsafe{
+        mem.gc(vec![&mut a], vec![]);
+    }
+    assert_eq!(mem.len(), 1);
+
+    let mut broken = NoGcMem::<Node>::new(100);
+    broken.push(Box::new(Node{ next: None })).unwrap();
+    broken.push(Box::new(Node{ next: Some(std::ptr::null()) })).unwrap();
+    assert_eq!(broken.verify(), Err(VerifyError::DanglingEdge{ index: 1, target: std::ptr::null() }));
+}
+
+#[test]
+fn test_report_unreachable(){
+    let mut mem = NoGcMem::<Node>::new(100);
+    let a = mem.push(Box::new(Node{ next: None })).unwrap();
+    let b = mem.push(Box::new(Node{ next: Some(a) })).unwrap();
+    let c = mem.push(Box::new(Node{ next: Some(b) })).unwrap();
+    let d = mem.push(Box::new(Node{ next: Some(std::ptr::null()) })).unwrap();
+
+    let report = mem.report_unreachable(&[b]);
+    assert_eq!(report.objects(), &[c, d]);
+    assert_eq!(report.bytes(), 2 * std::mem::size_of::<Node>());
+    assert!(mem.report_unreachable(&[c, d]).is_empty());
+    // nothing is dropped
+    assert_eq!(mem.len(), 4);
+}